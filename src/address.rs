@@ -0,0 +1,208 @@
+//! Typed addresses of a proxy. Using them instead of plain strings (see [`ProxyPack::from_addrs`])
+//! makes mixing up the listen and the upstream address a compile error, and rejects malformed
+//! addresses before anything reaches the server.
+//!
+//! [`ProxyPack::from_addrs`]: ../proxy/struct.ProxyPack.html#method.from_addrs
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Address a proxy listens on, eg. `127.0.0.1:2001`. Port `0` lets the server pick a free port.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Listen(String);
+
+/// Address of the service a proxy forwards to, eg. `db:5432`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Upstream(String);
+
+impl Listen {
+    /// Creates a listen address from a host (name or IP) and a port. An empty host listens on all
+    /// interfaces of the server, as Toxiproxy does for `:0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::address::Listen;
+    /// let listen = Listen::new("127.0.0.1", 2001).expect("address is valid");
+    /// assert_eq!("127.0.0.1:2001", listen.as_str());
+    ///
+    /// assert_eq!(":0", Listen::new("", 0).unwrap().as_str());
+    /// ```
+    pub fn new(host: &str, port: u16) -> Result<Self, String> {
+        if !host.is_empty() {
+            validate_host(host)?;
+        }
+        Ok(Self(join_host_port(host, port)))
+    }
+
+    /// The address as sent to the server, eg. `127.0.0.1:2001` or `[::1]:2001`.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Upstream {
+    /// Creates an upstream address from a host (name or IP) and a port.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::address::Upstream;
+    /// let upstream = Upstream::new("db", 5432).expect("address is valid");
+    /// assert_eq!("db:5432", upstream.as_str());
+    /// ```
+    pub fn new(host: &str, port: u16) -> Result<Self, String> {
+        validate_host(host)?;
+        if port == 0 {
+            return Err("upstream port cannot be 0".into());
+        }
+        Ok(Self(join_host_port(host, port)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Proxy name derived from the address, eg. `db_5432` for `db:5432`.
+    pub(crate) fn proxy_name(&self) -> String {
+        self.0.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    }
+}
+
+impl FromStr for Listen {
+    type Err = String;
+
+    /// Parses a `host:port` listen address.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::address::Listen;
+    /// let listen: Listen = "[::1]:2001".parse().expect("address is valid");
+    /// assert!(":0".parse::<Listen>().is_ok());
+    /// assert!("localhost".parse::<Listen>().is_err());
+    /// ```
+    fn from_str(addr: &str) -> Result<Self, String> {
+        let (host, port) = split_host_port(addr)?;
+        Self::new(&host, port)
+    }
+}
+
+impl FromStr for Upstream {
+    type Err = String;
+
+    /// Parses a `host:port` upstream address.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::address::Upstream;
+    /// let upstream: Upstream = "db:5432".parse().expect("address is valid");
+    /// assert!("db:0".parse::<Upstream>().is_err());
+    /// ```
+    fn from_str(addr: &str) -> Result<Self, String> {
+        let (host, port) = split_host_port(addr)?;
+        Self::new(&host, port)
+    }
+}
+
+impl fmt::Display for Listen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Display for Upstream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<Listen> for String {
+    fn from(listen: Listen) -> String {
+        listen.0
+    }
+}
+
+impl From<Upstream> for String {
+    fn from(upstream: Upstream) -> String {
+        upstream.0
+    }
+}
+
+/// Splits a `host:port` string, accepting bracketed IPv6 hosts (`[::1]:2001`).
+pub(crate) fn split_host_port(addr: &str) -> Result<(String, u16), String> {
+    let (host, port) = if let Some(rest) = addr.strip_prefix('[') {
+        let end = rest
+            .find("]:")
+            .ok_or_else(|| format!("invalid address (expected [host]:port): {}", addr))?;
+        (&rest[..end], &rest[end + 2..])
+    } else {
+        let idx = addr
+            .rfind(':')
+            .ok_or_else(|| format!("invalid address (expected host:port): {}", addr))?;
+        (&addr[..idx], &addr[idx + 1..])
+    };
+
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| format!("invalid port in address: {}", addr))?;
+
+    Ok((host.to_owned(), port))
+}
+
+/// Whether proxies listening on both addresses can't be bound together: same port (other than
+/// `0`) on the same host, or on any host if either one is a wildcard (eg. `0.0.0.0`). Addresses
+/// which can't be parsed don't conflict, the server reports them.
+pub(crate) fn listen_conflict(a: &str, b: &str) -> bool {
+    let normalize = |addr: &str| {
+        split_host_port(addr).ok().map(|(host, port)| {
+            let host = match host.as_str() {
+                "localhost" => "127.0.0.1".to_owned(),
+                "" | "0.0.0.0" | "::" => "*".to_owned(),
+                _ => host,
+            };
+            (host, port)
+        })
+    };
+
+    match (normalize(a), normalize(b)) {
+        (Some((host_a, port_a)), Some((host_b, port_b))) => {
+            port_a != 0 && port_a == port_b && (host_a == host_b || host_a == "*" || host_b == "*")
+        }
+        _ => false,
+    }
+}
+
+pub(crate) fn join_host_port(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+fn validate_host(host: &str) -> Result<(), String> {
+    if host.parse::<IpAddr>().is_ok() {
+        return Ok(());
+    }
+
+    let is_hostname = !host.is_empty()
+        && host.len() <= 253
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        });
+
+    if is_hostname {
+        Ok(())
+    } else {
+        Err(format!("invalid host: {:?}", host))
+    }
+}
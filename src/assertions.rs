@@ -0,0 +1,297 @@
+//! Assertions about the server state of proxies and toxics. They panic with a message including
+//! what the server actually reports, so a failing test shows why.
+//!
+//! ```
+//! # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new(
+//! #    "socket".into(),
+//! #    "localhost:2001".into(),
+//! #    "localhost:2000".into(),
+//! # )]);
+//! let proxy = toxiproxy_rust::TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+//!
+//! proxy
+//!     .with_latency("downstream".into(), 100, 0, 1.0)
+//!     .assert_has_toxic("latency_downstream")
+//!     .assert_enabled();
+//! # proxy.delete_all_toxics().unwrap();
+//! ```
+
+use super::client::*;
+use super::proxy::*;
+use super::toxic::*;
+use std::collections::BTreeMap;
+
+impl Proxy {
+    /// Asserts the proxy has a toxic with the given name.
+    #[track_caller]
+    pub fn assert_has_toxic(&self, name: &str) -> &Self {
+        let toxics = self.expect_toxics();
+        if !toxics.iter().any(|toxic| toxic.name == name) {
+            panic!(
+                "expected proxy {:?} to have toxic {:?}, found: {}",
+                self.proxy_pack.name,
+                name,
+                describe_toxics(&toxics)
+            );
+        }
+        self
+    }
+
+    /// Asserts the proxy has no toxic with the given name.
+    #[track_caller]
+    pub fn assert_no_toxic(&self, name: &str) -> &Self {
+        let toxics = self.expect_toxics();
+        if toxics.iter().any(|toxic| toxic.name == name) {
+            panic!(
+                "expected proxy {:?} not to have toxic {:?}, found: {}",
+                self.proxy_pack.name,
+                name,
+                describe_toxics(&toxics)
+            );
+        }
+        self
+    }
+
+    /// Asserts the proxy has no toxics at all.
+    #[track_caller]
+    pub fn assert_no_toxics(&self) -> &Self {
+        let toxics = self.expect_toxics();
+        if !toxics.is_empty() {
+            panic!(
+                "expected proxy {:?} to have no toxics, found: {}",
+                self.proxy_pack.name,
+                describe_toxics(&toxics)
+            );
+        }
+        self
+    }
+
+    /// Asserts the proxy is enabled on the server.
+    #[track_caller]
+    pub fn assert_enabled(&self) -> &Self {
+        if !self.expect_enabled() {
+            panic!("expected proxy {:?} to be enabled", self.proxy_pack.name);
+        }
+        self
+    }
+
+    /// Asserts the proxy is disabled on the server.
+    #[track_caller]
+    pub fn assert_disabled(&self) -> &Self {
+        if self.expect_enabled() {
+            panic!("expected proxy {:?} to be disabled", self.proxy_pack.name);
+        }
+        self
+    }
+
+    #[track_caller]
+    fn expect_toxics(&self) -> Vec<ToxicPack> {
+        self.toxics().unwrap_or_else(|err| {
+            panic!(
+                "cannot fetch toxics of proxy {:?}: {}",
+                self.proxy_pack.name, err
+            )
+        })
+    }
+
+    #[track_caller]
+    fn expect_enabled(&self) -> bool {
+        self.is_enabled().unwrap_or_else(|err| {
+            panic!(
+                "cannot fetch state of proxy {:?}: {}",
+                self.proxy_pack.name, err
+            )
+        })
+    }
+}
+
+impl Client {
+    /// Asserts a proxy with the given name exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new(
+    /// #    "socket".into(),
+    /// #    "localhost:2001".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// toxiproxy_rust::TOXIPROXY
+    ///     .assert_proxy_exists("socket")
+    ///     .assert_proxy_missing("no_such_proxy");
+    /// ```
+    #[track_caller]
+    pub fn assert_proxy_exists(&self, name: &str) -> &Self {
+        let proxies = self.expect_proxies();
+        if !proxies.contains_key(name) {
+            panic!(
+                "expected proxy {:?} to exist, found: {:?}",
+                name,
+                proxies.keys().collect::<Vec<&String>>()
+            );
+        }
+        self
+    }
+
+    /// Asserts no proxy with the given name exists.
+    #[track_caller]
+    pub fn assert_proxy_missing(&self, name: &str) -> &Self {
+        let proxies = self.expect_proxies();
+        if proxies.contains_key(name) {
+            panic!("expected proxy {:?} not to exist", name);
+        }
+        self
+    }
+
+    /// Asserts the proxy with the given name exists and is enabled.
+    #[track_caller]
+    pub fn assert_proxy_enabled(&self, name: &str) -> &Self {
+        if !self.expect_proxy(name).enabled {
+            panic!("expected proxy {:?} to be enabled", name);
+        }
+        self
+    }
+
+    /// Asserts the proxy with the given name exists and is disabled.
+    #[track_caller]
+    pub fn assert_proxy_disabled(&self, name: &str) -> &Self {
+        if self.expect_proxy(name).enabled {
+            panic!("expected proxy {:?} to be disabled", name);
+        }
+        self
+    }
+
+    #[track_caller]
+    fn expect_proxies(&self) -> BTreeMap<String, ProxyPack> {
+        self.all()
+            .unwrap_or_else(|err| panic!("cannot fetch proxies: {}", err))
+            .into_iter()
+            .map(|(name, proxy)| (name, proxy.proxy_pack))
+            .collect()
+    }
+
+    #[track_caller]
+    fn expect_proxy(&self, name: &str) -> ProxyPack {
+        let mut proxies = self.expect_proxies();
+        proxies.remove(name).unwrap_or_else(|| {
+            panic!(
+                "expected proxy {:?} to exist, found: {:?}",
+                name,
+                proxies.keys().collect::<Vec<&String>>()
+            )
+        })
+    }
+}
+
+/// State of a proxy at a point in time, see [`Proxy::snapshot`].
+#[derive(Debug, Clone)]
+pub struct ProxySnapshot {
+    pub name: String,
+    pub enabled: bool,
+    /// Toxics sorted by name.
+    pub toxics: Vec<ToxicPack>,
+}
+
+impl ProxySnapshot {
+    /// Differences from another snapshot, one line each: `-` for what is only in `self`, `+` for
+    /// what is only in `other`. Empty if they are identical.
+    pub fn diff(&self, other: &ProxySnapshot) -> Vec<String> {
+        let mut lines = vec![];
+
+        if self.enabled != other.enabled {
+            lines.push(format!("- enabled: {}", self.enabled));
+            lines.push(format!("+ enabled: {}", other.enabled));
+        }
+
+        let before = self
+            .toxics
+            .iter()
+            .map(describe_toxic)
+            .collect::<Vec<String>>();
+        let after = other
+            .toxics
+            .iter()
+            .map(describe_toxic)
+            .collect::<Vec<String>>();
+        for toxic in before.iter().filter(|toxic| !after.contains(toxic)) {
+            lines.push(format!("- toxic: {}", toxic));
+        }
+        for toxic in after.iter().filter(|toxic| !before.contains(toxic)) {
+            lines.push(format!("+ toxic: {}", toxic));
+        }
+
+        lines
+    }
+}
+
+impl Proxy {
+    /// Records the current state (enabled flag and toxics) of the proxy, eg. to check with
+    /// [`Proxy::assert_unchanged_since`] that a test leaves it as it found it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new_ephemeral(
+    /// #    "snapshot_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// let proxy = toxiproxy_rust::TOXIPROXY.find_and_reset_proxy("snapshot_doc").unwrap();
+    /// let snapshot = proxy.snapshot().unwrap();
+    ///
+    /// proxy.with_latency("downstream".into(), 100, 0, 1.0).apply(|| {
+    ///     /* test code */
+    /// });
+    ///
+    /// proxy.assert_unchanged_since(&snapshot);
+    /// # proxy.delete().unwrap();
+    /// ```
+    pub fn snapshot(&self) -> Result<ProxySnapshot, String> {
+        let enabled = self.is_enabled()?;
+        let mut toxics = self.toxics()?;
+        toxics.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(ProxySnapshot {
+            name: self.proxy_pack.name.clone(),
+            enabled,
+            toxics,
+        })
+    }
+
+    /// Asserts the proxy is in the same state as in `snapshot`, printing the differences otherwise.
+    #[track_caller]
+    pub fn assert_unchanged_since(&self, snapshot: &ProxySnapshot) -> &Self {
+        let current = self.snapshot().unwrap_or_else(|err| {
+            panic!(
+                "cannot fetch state of proxy {:?}: {}",
+                self.proxy_pack.name, err
+            )
+        });
+        let diff = snapshot.diff(&current);
+        if !diff.is_empty() {
+            panic!(
+                "proxy {:?} changed since the snapshot:\n{}",
+                self.proxy_pack.name,
+                diff.join("\n")
+            );
+        }
+        self
+    }
+}
+
+/// Readable summary of toxics, eg. `[latency_downstream (latency, downstream, 1, {"jitter": 0,
+/// "latency": 100})]`.
+pub(crate) fn describe_toxics(toxics: &[ToxicPack]) -> String {
+    let described = toxics.iter().map(describe_toxic).collect::<Vec<String>>();
+    format!("[{}]", described.join(", "))
+}
+
+pub(crate) fn describe_toxic(toxic: &ToxicPack) -> String {
+    format!(
+        "{} ({}, {}, {}, {:?})",
+        toxic.name,
+        toxic.r#type,
+        toxic.stream,
+        toxic.toxicity,
+        toxic.attributes.iter().collect::<BTreeMap<_, _>>()
+    )
+}
@@ -0,0 +1,155 @@
+//! Audit log of the requests changing the state of the server, appended to a file as lines of
+//! JSON - to find out who or what changed a shared environment. Logging starts with
+//! [`Client::audit_to`], or, for `TOXIPROXY` and clients made with [`Client::from_env`], when the
+//! `TOXIPROXY_AUDIT_LOG` environment variable names a file.
+//!
+//! Lines are appended whole, so several processes (eg. test binaries run in parallel) can share
+//! the same file.
+//!
+//! [`Client::audit_to`]: ../client/struct.Client.html#method.audit_to
+//! [`Client::from_env`]: ../client/struct.Client.html#method.from_env
+
+use super::client::*;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable naming the audit log of clients made with [`Client::from_env`].
+///
+/// [`Client::from_env`]: ../client/struct.Client.html#method.from_env
+pub const ENV_TOXIPROXY_AUDIT_LOG: &str = "TOXIPROXY_AUDIT_LOG";
+
+/// Request changing the server, with the process sending it and the answer. The timestamp is in
+/// milliseconds since the Unix epoch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub timestamp_ms: u64,
+    pub pid: u32,
+    pub program: String,
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<String>,
+    /// Status of the response, if the server answered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    pub(crate) fn new(method: &str, path: &str, payload: Option<String>) -> Self {
+        Self {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_millis() as u64),
+            pid: process::id(),
+            program: env::current_exe()
+                .ok()
+                .and_then(|exe| exe.file_name().map(|name| name.to_string_lossy().into()))
+                .unwrap_or_default(),
+            method: method.into(),
+            path: path.into(),
+            payload,
+            status: None,
+            error: None,
+        }
+    }
+}
+
+/// File the entries are appended to.
+#[derive(Debug)]
+pub(crate) struct AuditLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    pub(crate) fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| format!("cannot open audit log {}: {}", path.display(), err))?;
+
+        Ok(Self {
+            path: path.into(),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Appends the entry. Failures are reported on stderr, as they shouldn't fail the request.
+    pub(crate) fn append(&self, entry: &AuditEntry) {
+        let written = serde_json::to_string(entry)
+            .map_err(|err| err.to_string())
+            .and_then(|line| {
+                self.file
+                    .lock()
+                    .map_err(|err| err.to_string())?
+                    .write_all(format!("{}\n", line).as_bytes())
+                    .map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = written {
+            eprintln!("cannot write audit log {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+/// Reads the entries of an audit log.
+///
+/// # Examples
+///
+/// ```
+/// # use toxiproxy_rust::{audit, client::Client, proxy::ProxyPack};
+/// # let path = std::env::temp_dir().join("toxiproxy_audit_doc.jsonl");
+/// # let _ = std::fs::remove_file(&path);
+/// let client = Client::from_env();
+/// client.audit_to(&path).expect("audit log is opened");
+/// let proxy = client
+///     .create_proxy(ProxyPack::new_ephemeral("audit_doc".into(), "localhost:2000".into()))
+///     .unwrap();
+/// proxy.delete().unwrap();
+///
+/// let entries = audit::read(&path).expect("audit log is read");
+/// assert_eq!("POST", entries[0].method);
+/// assert_eq!("proxies/audit_doc", entries[1].path);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<AuditEntry>, String> {
+    let path = path.as_ref();
+    fs::read_to_string(path)
+        .map_err(|err| format!("cannot read audit log {}: {}", path.display(), err))?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str(line)
+                .map_err(|err| format!("invalid audit log {}: {}", path.display(), err))
+        })
+        .collect()
+}
+
+impl Client {
+    /// Appends every request changing the server, sent by the client, its clones and its
+    /// proxies, to the file (replacing an audit log set before).
+    pub fn audit_to<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let audit = AuditLog::open(path)?;
+        self.http_client()
+            .lock()
+            .map_err(|err| format!("lock error: {}", err))?
+            .set_audit(Some(Arc::new(audit)));
+        Ok(())
+    }
+
+    pub fn stop_audit(&self) {
+        if let Ok(mut client) = self.http_client().lock() {
+            client.set_audit(None);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! [Criterion] integration (requires the `criterion` feature), benchmarking code under toxics to
+//! track performance under degradation like any other benchmark.
+//!
+//! [Criterion]: https://docs.rs/criterion
+
+use super::proxy::*;
+use criterion::measurement::WallTime;
+use criterion::{black_box, Bencher};
+use std::time::{Duration, Instant};
+
+impl Proxy {
+    /// Measures `routine` with the toxics registered by `set_toxics` in place. The toxics are set
+    /// before and deleted after every sample, outside of the measured time (and even if `routine`
+    /// panics).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new_ephemeral(
+    /// #    "bench_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// use criterion::Criterion;
+    ///
+    /// fn bench_slow_network(c: &mut Criterion) {
+    ///     let proxy = toxiproxy_rust::TOXIPROXY.find_proxy("bench_doc").unwrap();
+    ///
+    ///     c.bench_function("call with 10ms latency", |b| {
+    ///         proxy.bench(
+    ///             b,
+    ///             |proxy| {
+    ///                 proxy.with_latency("downstream".into(), 10, 0, 1.0);
+    ///             },
+    ///             || {
+    ///                 /* Example benchmark:
+    ///                    MyService::Server::call(params)
+    ///                 */
+    ///             },
+    ///         )
+    ///     });
+    /// }
+    /// # toxiproxy_rust::TOXIPROXY.find_proxy("bench_doc").unwrap().delete().unwrap();
+    /// ```
+    pub fn bench<S, F, T>(&self, bencher: &mut Bencher<'_, WallTime>, set_toxics: S, mut routine: F)
+    where
+        S: Fn(&Self),
+        F: FnMut() -> T,
+    {
+        bencher.iter_custom(|iters| {
+            set_toxics(self);
+
+            let (elapsed, deleted): (Duration, _) = Self::run_guarded(
+                || {
+                    let started = Instant::now();
+                    for _ in 0..iters {
+                        black_box(routine());
+                    }
+                    started.elapsed()
+                },
+                || self.delete_all_toxics(),
+            );
+
+            deleted.unwrap_or_else(|err| panic!("toxics cannot be deleted: {}", err));
+            elapsed
+        });
+    }
+}
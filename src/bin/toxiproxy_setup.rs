@@ -0,0 +1,61 @@
+//! Populates proxies once before a test run and writes a manifest for the tests to attach to, eg.
+//! as a cargo-nextest setup script. With `--teardown`, deletes the proxies of the manifest once the
+//! run is over. See the `setup` module.
+//!
+//! Usage: `toxiproxy_setup <proxies.json> [manifest.json]` or
+//! `toxiproxy_setup --teardown [manifest.json]`
+
+use std::env;
+use std::fs;
+use std::process;
+use toxiproxy_rust::{proxy::ProxyPack, setup, TOXIPROXY};
+
+const DEFAULT_MANIFEST_PATH: &str = "target/toxiproxy-manifest.json";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let proxies_path = match args.next() {
+        Some(ref flag) if flag == "--teardown" => {
+            let manifest_path = args.next().unwrap_or_else(|| DEFAULT_MANIFEST_PATH.into());
+            teardown(&manifest_path);
+            return;
+        }
+        Some(path) => path,
+        None => {
+            eprintln!("usage: toxiproxy_setup <proxies.json> [manifest.json]");
+            eprintln!("       toxiproxy_setup --teardown [manifest.json]");
+            process::exit(2);
+        }
+    };
+    let manifest_path = args.next().unwrap_or_else(|| DEFAULT_MANIFEST_PATH.into());
+
+    let result = fs::read_to_string(&proxies_path)
+        .map_err(|err| format!("cannot read {}: {}", proxies_path, err))
+        .and_then(|json| {
+            serde_json::from_str::<Vec<ProxyPack>>(&json)
+                .map_err(|err| format!("json deserialize failed: {}", err))
+        })
+        .and_then(|proxies| setup::global_setup(&TOXIPROXY, proxies, &manifest_path));
+
+    match result {
+        Ok(manifest) => println!(
+            "{} proxies set up, manifest written to {}",
+            manifest.proxies.len(),
+            manifest_path
+        ),
+        Err(err) => {
+            eprintln!("toxiproxy setup failed: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+fn teardown(manifest_path: &str) {
+    match setup::global_teardown(manifest_path) {
+        Ok(()) => println!("proxies of {} torn down", manifest_path),
+        Err(err) => {
+            eprintln!("toxiproxy teardown failed: {}", err);
+            process::exit(1);
+        }
+    }
+}
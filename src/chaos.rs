@@ -0,0 +1,617 @@
+//! Background chaos: while a closure runs, toxics from a pool are randomly registered on and
+//! removed from a set of proxies, with intensities picked within configured bounds - eg. to keep
+//! a long integration test running under ever-changing network conditions.
+//!
+//! The random choices are reproducible: the seed of every run is printed (to stderr), and a run
+//! with the same seed - set with [`ChaosConfig::seed`] or the `TOXIPROXY_CHAOS_SEED` environment
+//! variable - makes the same choices.
+//!
+//! [`Downtime`] disables a proxy for random windows in a similar way.
+//!
+//! [`ChaosConfig::seed`]: struct.ChaosConfig.html#method.seed
+//! [`Downtime`]: struct.Downtime.html
+
+use super::client::*;
+use super::error::*;
+use super::proxy::*;
+use super::rng::*;
+use super::toxic::*;
+use std::env;
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Environment variable setting the seed of chaos runs which don't set one, to replay a run.
+pub const ENV_TOXIPROXY_CHAOS_SEED: &str = "TOXIPROXY_CHAOS_SEED";
+
+/// Toxic of a chaos pool, its attributes picked within bounds every time it's registered. It's
+/// named `chaos_<type>_<stream>`.
+#[derive(Debug, Clone)]
+pub struct ChaosToxic {
+    r#type: String,
+    stream: String,
+    toxicity: f32,
+    attributes: Vec<(String, RangeInclusive<ToxicValueType>)>,
+}
+
+impl ChaosToxic {
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::chaos::ChaosToxic;
+    /// let toxic = ChaosToxic::new("latency", "downstream")
+    ///     .attribute("latency", 100..=2000)
+    ///     .attribute("jitter", 0..=100);
+    /// ```
+    pub fn new(r#type: &str, stream: &str) -> Self {
+        Self {
+            r#type: r#type.into(),
+            stream: stream.into(),
+            toxicity: 1.0,
+            attributes: vec![],
+        }
+    }
+
+    /// Bounds of an attribute (both included).
+    pub fn attribute(mut self, name: &str, range: RangeInclusive<ToxicValueType>) -> Self {
+        self.attributes.push((name.into(), range));
+        self
+    }
+
+    /// Toxicity of the toxic, 1 by default.
+    pub fn toxicity(mut self, toxicity: f32) -> Self {
+        self.toxicity = toxicity;
+        self
+    }
+
+    fn name(&self) -> String {
+        format!("chaos_{}_{}", self.r#type, self.stream)
+    }
+
+    fn pick(&self, rng: &mut Rng) -> ToxicPack {
+        let mut toxic = ToxicPack::new(
+            self.r#type.clone(),
+            self.stream.clone(),
+            self.toxicity,
+            self.attributes
+                .iter()
+                .map(|(name, range)| (name.clone(), rng.in_range(range)))
+                .collect(),
+        );
+        toxic.name = self.name();
+        toxic
+    }
+}
+
+/// Proxies and toxics chaos plays with, see [`Chaos::run`].
+#[derive(Debug, Clone)]
+pub struct ChaosConfig {
+    proxies: Vec<String>,
+    pool: Vec<ChaosToxic>,
+    interval: Duration,
+    seed: Option<u64>,
+}
+
+impl ChaosConfig {
+    /// Chaos on the proxies with the given names, with a change every second by default.
+    pub fn new(proxies: &[&str]) -> Self {
+        Self {
+            proxies: proxies.iter().map(|name| (*name).to_owned()).collect(),
+            pool: vec![],
+            interval: Duration::from_secs(1),
+            seed: None,
+        }
+    }
+
+    /// Adds a toxic to the pool.
+    pub fn toxic(mut self, toxic: ChaosToxic) -> Self {
+        self.pool.push(toxic);
+        self
+    }
+
+    /// Time between two changes.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Seed of the random choices, to replay a run. By default it's taken from the
+    /// `TOXIPROXY_CHAOS_SEED` environment variable, or differs from run to run.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+/// Chaos engine: every interval, it picks one of the proxies and either removes one of the toxics
+/// it registered there, or registers (or replaces) a toxic of the pool.
+pub struct Chaos {
+    proxies: Vec<Proxy>,
+    pool: Vec<ChaosToxic>,
+    interval: Duration,
+    seed: u64,
+    rng: Rng,
+    /// Registered toxics, as proxy index and toxic name.
+    active: Vec<(usize, String)>,
+}
+
+impl Chaos {
+    fn new(client: &Client, config: ChaosConfig) -> Result<Self, String> {
+        if config.proxies.is_empty() || config.pool.is_empty() {
+            return Err("chaos needs proxies and toxics to pick from".into());
+        }
+
+        let proxies = config
+            .proxies
+            .iter()
+            .map(|name| client.find_proxy(name))
+            .collect::<Result<Vec<Proxy>, String>>()?;
+        let seed = resolve_seed(config.seed)?;
+
+        Ok(Self {
+            proxies,
+            pool: config.pool,
+            interval: config.interval,
+            seed,
+            rng: Rng::new(seed),
+            active: vec![],
+        })
+    }
+
+    /// Runs the closure while chaos plays with the proxies. The registered toxics are removed
+    /// afterwards (also when the closure panics). Fails if the toxics couldn't be changed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{chaos::*, TOXIPROXY};
+    /// # use std::time::Duration;
+    /// # TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new_ephemeral(
+    /// #    "chaos_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]).unwrap();
+    /// let config = ChaosConfig::new(&["chaos_doc"])
+    ///     .toxic(ChaosToxic::new("latency", "downstream").attribute("latency", 100..=2000))
+    ///     .toxic(ChaosToxic::new("bandwidth", "upstream").attribute("rate", 1..=100))
+    ///     .interval(Duration::from_millis(10));
+    ///
+    /// Chaos::run(&TOXIPROXY, config, || {
+    ///     /* Example test:
+    ///        for _ in 0..1000 {
+    ///            let service_result = MyService::Server::call(params);
+    ///        }
+    ///     */
+    ///     # std::thread::sleep(Duration::from_millis(50));
+    /// })
+    /// .expect("chaos has run");
+    /// # TOXIPROXY.find_proxy("chaos_doc").unwrap().delete().unwrap();
+    /// ```
+    pub fn run<F, T>(client: &Client, config: ChaosConfig, closure: F) -> Result<T, String>
+    where
+        F: FnOnce() -> T,
+    {
+        let handle = Self::start(client, config)?;
+        let (value, chaos_result) = Proxy::run_guarded(closure, || handle.stop());
+        chaos_result.map(|_| value)
+    }
+
+    /// Starts chaos on a background thread, independently of any closure - eg. for exploratory
+    /// testing or a long-lived staging environment. It goes on until the handle is stopped (or
+    /// dropped), which removes the registered toxics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{chaos::*, TOXIPROXY};
+    /// # use std::time::Duration;
+    /// # TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new_ephemeral(
+    /// #    "chaos_start_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]).unwrap();
+    /// let config = ChaosConfig::new(&["chaos_start_doc"])
+    ///     .toxic(ChaosToxic::new("timeout", "downstream").attribute("timeout", 0..=1000))
+    ///     .interval(Duration::from_millis(10));
+    ///
+    /// let handle = Chaos::start(&TOXIPROXY, config).expect("chaos is started");
+    /// println!("Chaos with seed {}, press enter to stop", handle.seed());
+    /// # /*
+    /// std::io::stdin().read_line(&mut String::new()).unwrap();
+    /// # */
+    /// handle.stop().expect("toxics are removed");
+    /// # TOXIPROXY.find_proxy("chaos_start_doc").unwrap().delete().unwrap();
+    /// ```
+    pub fn start(client: &Client, config: ChaosConfig) -> Result<ChaosHandle, String> {
+        let chaos = Self::new(client, config)?;
+        log_seed(chaos.seed);
+
+        let seed = chaos.seed;
+        let control = Arc::new(Control::default());
+        let worker = {
+            let control = control.clone();
+            thread::spawn(move || ApiError::capture(|| chaos.play(&control)))
+        };
+
+        Ok(ChaosHandle {
+            control,
+            worker: Some(worker),
+            seed,
+        })
+    }
+
+    fn play(mut self, control: &Control) -> Result<(), String> {
+        let mut result = Ok(());
+        loop {
+            result = match control.wait(self.interval) {
+                Signal::Stop => break,
+                Signal::Clear => {
+                    let result = self.cleanup();
+                    control.cleared();
+                    result
+                }
+                Signal::Step => self.step(),
+                Signal::Skip => continue,
+            };
+            if result.is_err() {
+                break;
+            }
+        }
+        result.and(self.cleanup())
+    }
+
+    fn step(&mut self) -> Result<(), String> {
+        let proxy_index = self.rng.below(self.proxies.len());
+        let proxy = &self.proxies[proxy_index];
+        let active = self
+            .active
+            .iter()
+            .enumerate()
+            .filter(|(_, (index, _))| *index == proxy_index)
+            .map(|(position, _)| position)
+            .collect::<Vec<usize>>();
+
+        // Toxics stay active until deleted, so that the cleanup retries the ones which weren't.
+        if !active.is_empty() && self.rng.chance(0.5) {
+            let position = active[self.rng.below(active.len())];
+            proxy.delete_toxic(&self.active[position].1)?;
+            self.active.remove(position);
+            return Ok(());
+        }
+
+        let toxic = self.pool[self.rng.below(self.pool.len())].pick(&mut self.rng);
+        if let Some(position) = self
+            .active
+            .iter()
+            .position(|(index, name)| *index == proxy_index && *name == toxic.name)
+        {
+            proxy.delete_toxic(&toxic.name)?;
+            self.active.remove(position);
+        }
+        let name = toxic.name.clone();
+        proxy.try_create_toxic(toxic)?;
+        self.active.push((proxy_index, name));
+        Ok(())
+    }
+
+    fn cleanup(&mut self) -> Result<(), String> {
+        let mut result = Ok(());
+        for (index, name) in self.active.drain(..) {
+            result = result.and(self.proxies[index].delete_toxic(&name));
+        }
+        result
+    }
+}
+
+/// Chaos running on a background thread, see [`Chaos::start`]. Dropping the handle stops it too,
+/// ignoring errors.
+///
+/// [`Chaos::start`]: struct.Chaos.html#method.start
+pub struct ChaosHandle {
+    control: Arc<Control>,
+    worker: Option<JoinHandle<Result<(), ThreadFailure>>>,
+    seed: u64,
+}
+
+impl ChaosHandle {
+    /// Seed of the random choices, see [`ChaosConfig::seed`].
+    ///
+    /// [`ChaosConfig::seed`]: struct.ChaosConfig.html#method.seed
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Whether the chaos thread has stopped, eg. because changing toxics failed.
+    pub fn is_finished(&self) -> bool {
+        self.worker
+            .as_ref()
+            .is_none_or(|worker| worker.is_finished())
+    }
+
+    /// Suspends registering and removing toxics until [`resume`], eg. while taking a measurement
+    /// baseline. The toxics registered so far stay, see [`pause_and_clear`] to remove them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{chaos::*, TOXIPROXY};
+    /// # use std::time::Duration;
+    /// # TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new_ephemeral(
+    /// #    "chaos_pause_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]).unwrap();
+    /// let config = ChaosConfig::new(&["chaos_pause_doc"])
+    ///     .toxic(ChaosToxic::new("latency", "downstream").attribute("latency", 100..=2000))
+    ///     .interval(Duration::from_millis(10));
+    /// let handle = Chaos::start(&TOXIPROXY, config).expect("chaos is started");
+    ///
+    /// handle.pause_and_clear();
+    /// /* Example baseline:
+    ///    let baseline = measure(MyService::Server::call);
+    /// */
+    /// handle.resume();
+    ///
+    /// handle.stop().expect("toxics are removed");
+    /// # TOXIPROXY.find_proxy("chaos_pause_doc").unwrap().delete().unwrap();
+    /// ```
+    ///
+    /// [`resume`]: #method.resume
+    /// [`pause_and_clear`]: #method.pause_and_clear
+    pub fn pause(&self) {
+        self.control.pause(false);
+    }
+
+    /// Pauses like [`pause`], and removes the registered toxics before returning. Errors removing
+    /// them stop the chaos, and are returned by [`stop`].
+    ///
+    /// [`pause`]: #method.pause
+    /// [`stop`]: #method.stop
+    pub fn pause_and_clear(&self) {
+        self.control.pause(true);
+        if let Some(worker) = self.worker.as_ref() {
+            self.control.wait_cleared(worker);
+        }
+    }
+
+    /// Goes on registering and removing toxics after a pause.
+    pub fn resume(&self) {
+        self.control.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.control.is_paused()
+    }
+
+    /// Stops the chaos and removes the registered toxics. Fails if changing the toxics failed
+    /// meanwhile, or they couldn't be removed.
+    pub fn stop(mut self) -> Result<(), String> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> Result<(), String> {
+        self.control.stop();
+        match self.worker.take() {
+            Some(worker) => ApiError::resume(
+                worker
+                    .join()
+                    .unwrap_or_else(|_| Err("chaos thread panicked".into())),
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ChaosHandle {
+    fn drop(&mut self) {
+        let _ = ApiError::keep(|| self.shutdown());
+    }
+}
+
+/// Random outages of a proxy: it's disabled for windows of random length, with random gaps in
+/// between - eg. to test retry budgets against realistic intermittent outages. The choices are
+/// seeded like chaos runs.
+#[derive(Debug, Clone)]
+pub struct Downtime {
+    length: RangeInclusive<Duration>,
+    gap: RangeInclusive<Duration>,
+    seed: Option<u64>,
+}
+
+impl Downtime {
+    /// Outages lasting within the given bounds, with gaps of 1 to 5 seconds by default.
+    pub fn new(length: RangeInclusive<Duration>) -> Self {
+        Self {
+            length,
+            gap: Duration::from_secs(1)..=Duration::from_secs(5),
+            seed: None,
+        }
+    }
+
+    /// Bounds of the time between two outages, setting how frequent they are.
+    pub fn gap(mut self, gap: RangeInclusive<Duration>) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    /// Seed of the random choices, see [`ChaosConfig::seed`].
+    ///
+    /// [`ChaosConfig::seed`]: struct.ChaosConfig.html#method.seed
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Runs outages on the proxy for the given duration, starting with a gap. Blocks like
+    /// [`Proxy::disable_for`], and leaves the proxy enabled, also when disabling it failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{chaos::Downtime, TOXIPROXY};
+    /// # use std::time::Duration;
+    /// # TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new_ephemeral(
+    /// #    "downtime_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]).unwrap();
+    /// let outages = std::thread::spawn(|| {
+    ///     Downtime::new(Duration::from_millis(10)..=Duration::from_millis(50))
+    ///         .gap(Duration::from_millis(20)..=Duration::from_millis(100))
+    ///         .run(&TOXIPROXY.find_proxy("downtime_doc").unwrap(), Duration::from_millis(200))
+    /// });
+    ///
+    /// /* Example test:
+    ///    let service_result = MyService::Server::call_with_retries(params);
+    ///    assert!(service_result.is_ok());
+    /// */
+    ///
+    /// outages.join().unwrap().expect("outages have run");
+    /// # TOXIPROXY.find_proxy("downtime_doc").unwrap().delete().unwrap();
+    /// ```
+    ///
+    /// [`Proxy::disable_for`]: ../proxy/struct.Proxy.html#method.disable_for
+    pub fn run(&self, proxy: &Proxy, duration: Duration) -> Result<(), String> {
+        if self.length.is_empty() || self.gap.is_empty() {
+            return Err("downtime needs non-empty length and gap bounds".into());
+        }
+
+        let seed = resolve_seed(self.seed)?;
+        log_seed(seed);
+        let mut rng = Rng::new(seed);
+
+        let deadline = Instant::now() + duration;
+        loop {
+            let gap = pick_duration(&mut rng, &self.gap);
+            thread::sleep(gap.min(deadline.saturating_duration_since(Instant::now())));
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left == Duration::from_secs(0) {
+                return Ok(());
+            }
+            proxy.disable_for(pick_duration(&mut rng, &self.length).min(left))?;
+        }
+    }
+}
+
+fn resolve_seed(seed: Option<u64>) -> Result<u64, String> {
+    if let Some(seed) = seed {
+        return Ok(seed);
+    }
+    match env::var(ENV_TOXIPROXY_CHAOS_SEED) {
+        Ok(seed) => seed
+            .trim()
+            .parse()
+            .map_err(|err| format!("invalid {}: {}", ENV_TOXIPROXY_CHAOS_SEED, err)),
+        Err(_) => Ok(Rng::random_seed()),
+    }
+}
+
+fn log_seed(seed: u64) {
+    eprintln!(
+        "Chaos seed: {} (replay with {}={})",
+        seed, ENV_TOXIPROXY_CHAOS_SEED, seed
+    );
+}
+
+/// Duration within the bounds, in milliseconds.
+fn pick_duration(rng: &mut Rng, range: &RangeInclusive<Duration>) -> Duration {
+    let millis = |duration: &Duration| duration.as_millis().min(u128::from(u32::MAX)) as u32;
+    Duration::from_millis(u64::from(
+        rng.in_range(&(millis(range.start())..=millis(range.end()))),
+    ))
+}
+
+/// What a chaos thread is asked to do next.
+enum Signal {
+    Stop,
+    Clear,
+    Step,
+    Skip,
+}
+
+#[derive(Default)]
+struct State {
+    stopped: bool,
+    paused: bool,
+    clearing: bool,
+}
+
+/// Stop, pause and clear signals of a chaos thread.
+#[derive(Default)]
+struct Control {
+    state: Mutex<State>,
+    wakeup: Condvar,
+}
+
+impl Control {
+    fn stop(&self) {
+        self.update(|state| state.stopped = true);
+    }
+
+    fn pause(&self, clear: bool) {
+        self.update(|state| {
+            state.paused = true;
+            state.clearing |= clear;
+        });
+    }
+
+    fn resume(&self) {
+        self.update(|state| state.paused = false);
+    }
+
+    fn cleared(&self) {
+        self.update(|state| state.clearing = false);
+    }
+
+    fn is_paused(&self) -> bool {
+        self.state.lock().is_ok_and(|state| state.paused)
+    }
+
+    fn update<F: FnOnce(&mut State)>(&self, change: F) {
+        if let Ok(mut state) = self.state.lock() {
+            change(&mut state);
+        }
+        self.wakeup.notify_all();
+    }
+
+    /// Waits for the given time unless stopped or asked to clear meanwhile.
+    fn wait(&self, timeout: Duration) -> Signal {
+        let deadline = Instant::now() + timeout;
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return Signal::Stop,
+        };
+
+        loop {
+            if state.stopped {
+                return Signal::Stop;
+            }
+            if state.clearing {
+                return Signal::Clear;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return if state.paused {
+                    Signal::Skip
+                } else {
+                    Signal::Step
+                };
+            }
+            state = match self.wakeup.wait_timeout(state, deadline - now) {
+                Ok((state, _)) => state,
+                Err(_) => return Signal::Stop,
+            };
+        }
+    }
+
+    /// Waits until the thread has cleared the toxics, unless it's finished.
+    fn wait_cleared(&self, worker: &JoinHandle<Result<(), ThreadFailure>>) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        while state.clearing && !worker.is_finished() {
+            state = match self.wakeup.wait_timeout(state, Duration::from_millis(10)) {
+                Ok((state, _)) => state,
+                Err(_) => return,
+            };
+        }
+    }
+}
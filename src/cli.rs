@@ -0,0 +1,93 @@
+//! [toxiproxy-cli] commands reproducing the state of a server, to replay the network conditions of
+//! a failing test by hand.
+//!
+//! [toxiproxy-cli]: https://github.com/Shopify/toxiproxy#cli-example
+
+use super::client::*;
+use super::proxy::*;
+use std::collections::BTreeMap;
+
+impl Client {
+    /// Shell script of `toxiproxy-cli` commands creating the current proxies (within the client's
+    /// namespace, under their full names) with their toxics on a server without them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{namespace::Namespace, proxy::ProxyPack, TOXIPROXY};
+    /// let client = TOXIPROXY.namespaced(Namespace::new("cli_doc"));
+    /// let proxies = client.populate(vec![ProxyPack::new_ephemeral(
+    ///     "socket".into(),
+    ///     "localhost:2000".into(),
+    /// )]).unwrap();
+    /// proxies[0].with_latency("downstream".into(), 100, 10, 1.0);
+    ///
+    /// let script = client.to_cli_script().expect("script is generated");
+    /// assert!(script.contains(
+    ///     "toxic add -n latency_downstream -t latency --downstream --tox 1 \
+    ///      -a jitter=10 -a latency=100 socket__cli_doc\n"
+    /// ));
+    /// # proxies[0].delete().unwrap();
+    /// ```
+    pub fn to_cli_script(&self) -> Result<String, String> {
+        let address = self.address()?;
+        let command = format!("toxiproxy-cli --host http://{}", address);
+
+        let mut proxies = self
+            .all()?
+            .into_values()
+            .map(|proxy| proxy.proxy_pack)
+            .collect::<Vec<ProxyPack>>();
+        proxies.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut script = format!(
+            "#!/bin/sh\n# Proxies and toxics of the Toxiproxy server at {}.\nset -e\n",
+            address
+        );
+        for proxy_pack in proxies {
+            let name = quote(&proxy_pack.name);
+            script += &format!(
+                "\n{} create -l {} -u {} {}\n",
+                command,
+                quote(&proxy_pack.listen),
+                quote(&proxy_pack.upstream),
+                name
+            );
+
+            for toxic in proxy_pack.toxics.iter() {
+                script += &format!(
+                    "{} toxic add -n {} -t {} --{} --tox {}",
+                    command,
+                    quote(&toxic.name),
+                    quote(&toxic.r#type),
+                    toxic.stream,
+                    toxic.toxicity
+                );
+                for (attribute, value) in toxic.attributes.iter().collect::<BTreeMap<_, _>>() {
+                    script += &format!(" -a {}={}", quote(attribute), value);
+                }
+                script += &format!(" {}\n", name);
+            }
+
+            if !proxy_pack.enabled {
+                script += &format!("{} toggle {}\n", command, name);
+            }
+        }
+
+        Ok(script)
+    }
+}
+
+/// Quotes a shell argument unless it's made of safe characters only.
+fn quote(arg: &str) -> String {
+    let is_safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.:/[]".contains(c));
+
+    if is_safe {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
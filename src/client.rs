@@ -1,22 +1,52 @@
 //! Main client for communicating with the Toxiproxy server.
 
 use serde_json;
-use std::net::ToSocketAddrs;
+use std::env;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use std::{collections::HashMap, io::Read};
 
+use super::address::*;
+use super::audit::*;
+use super::config::*;
+use super::consts::*;
+use super::guard::*;
 use super::http_client::*;
+use super::instrument::{self, Fields};
+use super::namespace::*;
 use super::proxy::*;
+use super::rng::Rng;
+
+/// Proxy wired in front of an upstream, see [`Client::proxy_for_upstream`].
+///
+/// [`Client::proxy_for_upstream`]: struct.Client.html#method.proxy_for_upstream
+#[derive(Debug)]
+pub struct UpstreamProxy {
+    pub proxy: Proxy,
+    /// Address (`host:port`) the application should connect to instead of the upstream.
+    pub addr: String,
+    /// Whether the proxy was created by the call - only then is it the caller's to delete, a reused
+    /// proxy may be shared with other tests.
+    pub created: bool,
+}
 
 /// Server client.
 #[derive(Clone)]
 pub struct Client {
     client: Arc<Mutex<HttpClient>>,
+    namespace: Option<Namespace>,
+    created_proxies: Option<Arc<Mutex<Vec<String>>>>,
+    /// Tags by (qualified) proxy name, shared by every clone of the client.
+    tags: TagRegistry,
 }
 
 impl Client {
     /// Creates a new client. There is also a prepopulated client, `toxiproxy_rust::TOXIPROXY`
-    /// connected to the server's default address.
+    /// connected to the server's default address. An address which can't be resolved fails the
+    /// requests of the client, until it's pointed to another one (see [`set_address`]).
     ///
     /// # Examples
     ///
@@ -24,13 +54,377 @@ impl Client {
     /// # use toxiproxy_rust::client::Client;
     /// let client = Client::new("127.0.0.1:8474");
     /// ```
+    ///
+    /// [`set_address`]: #method.set_address
     pub fn new<U: ToSocketAddrs>(toxiproxy_addr: U) -> Self {
         Self {
             client: Arc::new(Mutex::new(HttpClient::new(toxiproxy_addr))),
+            namespace: None,
+            created_proxies: None,
+            tags: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Creates a new client connected to the address in the `TOXIPROXY_URL` environment variable
+    /// (eg. `http://toxiproxy:8474`), or to the server's default address (`127.0.0.1:8474`) if it's
+    /// not set. If `TOXIPROXY_AUDIT_LOG` names a file, the client appends its requests changing the
+    /// server to it (see [`Client::audit_to`]). This is how `toxiproxy_rust::TOXIPROXY` is built.
+    ///
+    /// [`Client::audit_to`]: struct.Client.html#method.audit_to
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::client::Client;
+    /// let client = Client::from_env();
+    /// ```
+    pub fn from_env() -> Self {
+        let client = Self::from_url(env::var(ENV_TOXIPROXY_URL).ok().as_deref());
+        if let Ok(path) = env::var(ENV_TOXIPROXY_AUDIT_LOG) {
+            if let Err(err) = client.audit_to(&path) {
+                eprintln!("{}", err);
+            }
+        }
+        client
+    }
+
+    /// Creates a new client connected to the address of a `TOXIPROXY_URL` value, or to the server's
+    /// default address if it's not set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::client::Client;
+    /// let client = Client::from_url(Some("http://127.0.0.1:8474/"));
+    /// assert_eq!(Ok("127.0.0.1:8474".parse().unwrap()), client.address());
+    /// assert_eq!(client.address(), Client::from_url(None).address());
+    /// ```
+    pub fn from_url(url: Option<&str>) -> Self {
+        match url {
+            Some(url) => Self::new(addr_from_url(url)),
+            None => Self::new(DEFAULT_TOXIPROXY_ADDR),
+        }
+    }
+
+    /// Address of the Toxiproxy server. Fails if the address the client was created with couldn't
+    /// be resolved, as do its requests.
+    pub fn address(&self) -> Result<SocketAddr, String> {
+        self.client
+            .lock()
+            .map_err(|err| format!("lock error: {}", err))?
+            .toxiproxy_addr()
+    }
+
+    /// Points the client to another Toxiproxy server. As the connection is shared, this affects
+    /// every clone of the client and every proxy fetched through it - so it's best done before the
+    /// client is first used, eg. at the start of a test suite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// toxiproxy_rust::TOXIPROXY
+    ///     .set_address("127.0.0.1:8474")
+    ///     .expect("address is set");
+    /// ```
+    pub fn set_address<U: ToSocketAddrs>(&self, toxiproxy_addr: U) -> Result<(), String> {
+        self.client
+            .lock()
+            .map_err(|err| format!("lock error: {}", err))?
+            .set_toxiproxy_addr(toxiproxy_addr)
+    }
+
+    /// ID sent in the `X-Correlation-ID` header of every request, if any.
+    pub fn correlation_id(&self) -> Option<String> {
+        self.client
+            .lock()
+            .ok()
+            .and_then(|client| client.correlation_id().map(String::from))
+    }
+
+    /// Sends the ID in the `X-Correlation-ID` header of every request of the client, its clones and
+    /// its proxies, so the server's logs can be matched to a test run. Errors of failed requests
+    /// mention it too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::client::Client;
+    /// let client = Client::from_env();
+    /// client.set_correlation_id("checkout_test_run_42");
+    /// client.set_address("127.0.0.1:1").unwrap();
+    ///
+    /// let err = client.all().unwrap_err();
+    /// assert!(err.ends_with("(correlation id checkout_test_run_42)"));
+    /// ```
+    pub fn set_correlation_id<S: Into<String>>(&self, correlation_id: S) {
+        if let Ok(mut client) = self.client.lock() {
+            client.set_correlation_id(Some(correlation_id.into()));
+        }
+    }
+
+    /// Generates a new correlation ID (eg. `toxiproxy-3f2a9c41d07be815`) and sends it from now on,
+    /// see [`set_correlation_id`]. Called at the start of a test with a client of its own (see
+    /// [`from_env`]), the ID identifies the test.
+    ///
+    /// [`set_correlation_id`]: #method.set_correlation_id
+    /// [`from_env`]: #method.from_env
+    pub fn new_correlation_id(&self) -> String {
+        let correlation_id = format!("toxiproxy-{:016x}", Rng::random_seed());
+        self.set_correlation_id(correlation_id.clone());
+        correlation_id
+    }
+
+    pub fn clear_correlation_id(&self) {
+        if let Ok(mut client) = self.client.lock() {
+            client.set_correlation_id(None);
         }
     }
 
-    /// Establish a set of proxies to work with.
+    /// Debug mode: requests answered with an error status fail, the error containing the bodies
+    /// of the request and of the response (cut after `limit` characters, if any), and errors of
+    /// requests which couldn't be sent contain the request body. Affects the clones of the client
+    /// and its proxies too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::client::Client;
+    /// let client = Client::from_env();
+    /// client.debug_bodies(Some(512));
+    ///
+    /// let err = client.find_proxy("debug_bodies_doc_missing").unwrap_err();
+    /// assert!(err.starts_with("GET /proxies/debug_bodies_doc_missing answered 404"));
+    /// # client.stop_debug_bodies();
+    /// ```
+    pub fn debug_bodies(&self, limit: Option<usize>) {
+        if let Ok(mut client) = self.client.lock() {
+            client.set_debug_body_limit(Some(limit.unwrap_or(usize::MAX)));
+        }
+    }
+
+    pub fn stop_debug_bodies(&self) {
+        if let Ok(mut client) = self.client.lock() {
+            client.set_debug_body_limit(None);
+        }
+    }
+
+    /// Creates a client which doesn't talk to any server: every operation succeeds without effect.
+    /// Proxies created through it listen on their upstream address (also when fetched again), so
+    /// code under test connects to the upstream directly. Useful to let a suite run (without
+    /// network faults) where no Toxiproxy server is available, see [`or_noop`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{client::Client, proxy::ProxyPack};
+    /// let client = Client::noop();
+    /// let proxies = client.populate(vec![ProxyPack::new(
+    ///     "socket".into(),
+    ///     "localhost:2001".into(),
+    ///     "localhost:2000".into(),
+    /// )]).unwrap();
+    ///
+    /// assert_eq!("localhost:2000", proxies[0].proxy_pack.listen);
+    /// ```
+    ///
+    /// [`or_noop`]: #method.or_noop
+    pub fn noop() -> Self {
+        Self {
+            client: Arc::new(Mutex::new(HttpClient::new_noop())),
+            namespace: None,
+            created_proxies: None,
+            tags: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Returns this client if the server is available, or a no-op client (see [`noop`]) otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let client = toxiproxy_rust::TOXIPROXY.or_noop();
+    /// if client.is_noop() {
+    ///     eprintln!("Toxiproxy is not available, running without network faults");
+    /// }
+    /// ```
+    ///
+    /// [`noop`]: #method.noop
+    pub fn or_noop(&self) -> Self {
+        match self.health_check() {
+            Ok(_) => self.clone(),
+            Err(_) => Self::noop(),
+        }
+    }
+
+    /// Whether this is a no-op client, see [`noop`].
+    ///
+    /// [`noop`]: #method.noop
+    pub fn is_noop(&self) -> bool {
+        self.client
+            .lock()
+            .map(|client| client.is_noop())
+            .unwrap_or(false)
+    }
+
+    /// Returns a client working within the given namespace, sharing the connection of this one.
+    /// Proxy names passed to and returned by the namespaced client are plain names (eg.
+    /// `"socket"`), which are qualified with the namespace on the server, and only proxies of the
+    /// namespace are listed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{namespace::Namespace, proxy::ProxyPack};
+    /// let client = toxiproxy_rust::TOXIPROXY.namespaced(Namespace::unique());
+    /// client.populate(vec![ProxyPack::new_ephemeral(
+    ///     "socket".into(),
+    ///     "localhost:2000".into(),
+    /// )]).unwrap();
+    ///
+    /// let proxy = client.find_proxy("socket").expect("proxy returned");
+    /// assert!(proxy.proxy_pack.name.starts_with("socket__"));
+    /// # proxy.delete().unwrap();
+    /// ```
+    pub fn namespaced(&self, namespace: Namespace) -> Self {
+        Self {
+            client: self.client.clone(),
+            namespace: Some(namespace),
+            created_proxies: self.created_proxies.clone(),
+            tags: self.tags.clone(),
+        }
+    }
+
+    /// Returns a client for a single test: it works within a namespace of its own (see
+    /// [`namespaced`]) and deletes every proxy created through it when dropped (also when unwinding
+    /// from a panic).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::proxy::ProxyPack;
+    /// let client = toxiproxy_rust::TOXIPROXY.scoped();
+    /// client.populate(vec![ProxyPack::new_ephemeral(
+    ///     "socket".into(),
+    ///     "localhost:2000".into(),
+    /// )]).unwrap();
+    ///
+    /// client
+    ///     .find_proxy("socket")
+    ///     .unwrap()
+    ///     .with_latency("downstream".into(), 2000, 0, 1.0);
+    /// ```
+    ///
+    /// [`namespaced`]: #method.namespaced
+    pub fn scoped(&self) -> ScopedClient {
+        ScopedClient::new(Self {
+            client: self.client.clone(),
+            namespace: Some(Namespace::unique()),
+            created_proxies: Some(Arc::new(Mutex::new(vec![]))),
+            tags: self.tags.clone(),
+        })
+    }
+
+    /// Identity of the scoped client (shared by its clones), if it's one.
+    #[cfg(feature = "teardown")]
+    pub(crate) fn scope_key(&self) -> Option<usize> {
+        self.created_proxies
+            .as_ref()
+            .map(|created_proxies| Arc::as_ptr(created_proxies) as usize)
+    }
+
+    fn record_created(&self, name: &str) -> Result<(), String> {
+        if let Some(ref created_proxies) = self.created_proxies {
+            let mut created_proxies = created_proxies
+                .lock()
+                .map_err(|err| format!("lock error: {}", err))?;
+            if !created_proxies.iter().any(|created| created == name) {
+                created_proxies.push(name.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Same client, sending its requests through another HTTP client.
+    pub(crate) fn with_http_client(&self, client: HttpClient) -> Self {
+        Self {
+            client: Arc::new(Mutex::new(client)),
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn http_client(&self) -> &Arc<Mutex<HttpClient>> {
+        &self.client
+    }
+
+    pub(crate) fn record_tags(&self, name: &str, tags: &[String]) -> Result<(), String> {
+        let mut registry = self
+            .tags
+            .lock()
+            .map_err(|err| format!("lock error: {}", err))?;
+        if tags.is_empty() {
+            registry.remove(name);
+        } else {
+            registry.insert(name.into(), tags.to_vec());
+        }
+        Ok(())
+    }
+
+    /// Proxy handle with the tags of the registry.
+    fn proxy(&self, mut proxy_pack: ProxyPack) -> Proxy {
+        if let Ok(registry) = self.tags.lock() {
+            proxy_pack.tags = registry.get(&proxy_pack.name).cloned().unwrap_or_default();
+        }
+        Proxy::new(proxy_pack, self.client.clone()).with_tag_registry(self.tags.clone())
+    }
+
+    pub(crate) fn delete_created_proxies(&self) -> Result<(), String> {
+        let names = match self.created_proxies {
+            Some(ref created_proxies) => created_proxies
+                .lock()
+                .map_err(|err| format!("lock error: {}", err))?
+                .split_off(0),
+            None => vec![],
+        };
+
+        // Attempts every deletion, reporting the first failure.
+        let mut result = Ok(());
+        for name in names {
+            let path = format!("proxies/{}", name);
+            result = result.and(
+                self.client
+                    .lock()
+                    .map_err(|err| format!("lock error: {}", err))
+                    .and_then(|client| client.delete(&path))
+                    .and_then(|response| {
+                        if response.status().is_success() {
+                            self.record_tags(&name, &[])?;
+                        }
+                        Ok(())
+                    }),
+            );
+        }
+        result
+    }
+
+    /// Name of the proxy on the server, within the namespace of the client.
+    pub(crate) fn qualify(&self, name: &str) -> String {
+        match self.namespace {
+            Some(ref namespace) => namespace.qualify(name),
+            None => name.into(),
+        }
+    }
+
+    /// Plain name of a proxy on the server, if it's within the namespace of the client (the name
+    /// as it is otherwise).
+    pub(crate) fn plain_name<'a>(&self, name: &'a str) -> &'a str {
+        match self.namespace {
+            Some(ref namespace) => namespace.strip(name).unwrap_or(name),
+            None => name,
+        }
+    }
+
+    /// Establish a set of proxies to work with. Fails before anything is created if two proxies
+    /// would listen on the same address, or one on the address of another existing proxy.
     ///
     /// # Examples
     ///
@@ -52,24 +446,156 @@ impl Client {
     ///     "localhost:2000".into(),
     /// )]).expect("populate has completed");
     /// ```
-    pub fn populate(&self, proxies: Vec<ProxyPack>) -> Result<Vec<Proxy>, String> {
-        let proxies_json = serde_json::to_string(&proxies).unwrap();
+    pub fn populate(&self, mut proxies: Vec<ProxyPack>) -> Result<Vec<Proxy>, String> {
+        let names = proxies
+            .iter()
+            .map(|proxy| self.qualify(&proxy.name))
+            .collect::<Vec<String>>()
+            .join(",");
+        instrument::operation("populate", Fields::proxy(&names), || {
+            self.check_listen_conflicts(&proxies)?;
+
+            for proxy in proxies.iter_mut() {
+                proxy.name = self.qualify(&proxy.name);
+                self.record_created(&proxy.name)?;
+                self.record_tags(&proxy.name, &proxy.tags)?;
+            }
+
+            let proxies_json = serde_json::to_string(&proxies).unwrap();
+            let client = self
+                .client
+                .lock()
+                .map_err(|err| format!("lock error: {}", err))?;
+            client
+                .post_json::<HashMap<String, Vec<ProxyPack>>>("populate", proxies_json)
+                .map(|ref mut response_obj| response_obj.remove("proxies").unwrap_or(vec![]))
+                .map(|proxy_packs| {
+                    client.remember_populated(&proxy_packs);
+                    proxy_packs
+                        .into_iter()
+                        .map(|proxy_pack| self.proxy(proxy_pack))
+                        .collect::<Vec<Proxy>>()
+                })
+        })
+    }
+
+    /// Fails if two proxies of the batch would listen on the same address, or one would listen
+    /// on the address of an existing (enabled) proxy the batch doesn't replace - which the server
+    /// would only report halfway through populating. Existing proxies replaced by the batch are
+    /// checked as their batch entries.
+    fn check_listen_conflicts(&self, proxies: &[ProxyPack]) -> Result<(), String> {
+        for (index, proxy) in proxies.iter().enumerate() {
+            if let Some(other) = proxies[..index]
+                .iter()
+                .find(|other| listen_conflict(&other.listen, &proxy.listen))
+            {
+                return Err(format!(
+                    "listen address conflict: proxies {:?} ({}) and {:?} ({}) cannot listen \
+                     together",
+                    other.name, other.listen, proxy.name, proxy.listen
+                ));
+            }
+        }
+
+        // Proxies on ports picked by the server can't conflict.
+        if !proxies
+            .iter()
+            .any(|proxy| matches!(split_host_port(&proxy.listen), Ok((_, port)) if port != 0))
+        {
+            return Ok(());
+        }
+        let existing = self
+            .client
+            .lock()
+            .map_err(|err| format!("lock error: {}", err))?
+            .get_json::<HashMap<String, ProxyPack>>("proxies")?;
+
+        let names = proxies
+            .iter()
+            .map(|proxy| self.qualify(&proxy.name))
+            .collect::<Vec<String>>();
+        for proxy in proxies.iter() {
+            if let Some(other) = existing.values().find(|other| {
+                other.enabled
+                    && !names.contains(&other.name)
+                    && listen_conflict(&other.listen, &proxy.listen)
+            }) {
+                return Err(format!(
+                    "listen address conflict: proxy {:?} ({}) cannot listen with the existing \
+                     proxy {:?} ({})",
+                    proxy.name, proxy.listen, other.name, other.listen
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates a single proxy. Fails if a proxy with the same name already exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::proxy::ProxyPack;
+    /// let proxy = toxiproxy_rust::TOXIPROXY.create_proxy(ProxyPack::new(
+    ///     "socket_created".into(),
+    ///     "localhost:2002".into(),
+    ///     "localhost:2000".into(),
+    /// )).expect("proxy is created");
+    /// # proxy.delete().unwrap();
+    /// ```
+    pub fn create_proxy(&self, mut proxy: ProxyPack) -> Result<Proxy, String> {
+        proxy.name = self.qualify(&proxy.name);
+        self.record_created(&proxy.name)?;
+        self.record_tags(&proxy.name, &proxy.tags)?;
+        let proxy_json = serde_json::to_string(&proxy).unwrap();
         self.client
             .lock()
             .map_err(|err| format!("lock error: {}", err))?
-            .post_with_data("populate", proxies_json)
-            .and_then(|response| {
-                response
-                    .json::<HashMap<String, Vec<ProxyPack>>>()
-                    .map_err(|err| format!("json deserialize failed: {}", err))
-            })
-            .map(|ref mut response_obj| response_obj.remove("proxies").unwrap_or(vec![]))
-            .map(|proxy_packs| {
-                proxy_packs
-                    .into_iter()
-                    .map(|proxy_pack| Proxy::new(proxy_pack, self.client.clone()))
-                    .collect::<Vec<Proxy>>()
-            })
+            .post_json("proxies", proxy_json)
+            .map(|proxy_pack: ProxyPack| self.proxy(proxy_pack))
+    }
+
+    /// Returns a proxy in front of the given upstream, reusing an existing one if there is any,
+    /// or creating one listening on a port picked by the server. Only a created proxy (see
+    /// [`UpstreamProxy::created`]) should be deleted afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let wired = toxiproxy_rust::TOXIPROXY
+    ///     .proxy_for_upstream("localhost:2000")
+    ///     .expect("proxy is available");
+    ///
+    /// std::env::set_var("SERVICE_ADDR", &wired.addr);
+    /// if wired.created {
+    ///     wired.proxy.delete().unwrap();
+    /// }
+    /// ```
+    ///
+    /// [`UpstreamProxy::created`]: struct.UpstreamProxy.html#structfield.created
+    pub fn proxy_for_upstream(&self, upstream: &str) -> Result<UpstreamProxy, String> {
+        let upstream: Upstream = upstream.parse()?;
+
+        let existing = self
+            .all()?
+            .into_values()
+            .find(|proxy| proxy.proxy_pack.upstream == upstream.as_str());
+
+        let (proxy, created) = match existing {
+            Some(proxy) => (proxy, false),
+            None => {
+                let name = upstream.proxy_name();
+                let proxy_pack = ProxyPack::new_ephemeral(name, upstream.into());
+                (self.create_proxy(proxy_pack)?, true)
+            }
+        };
+
+        let addr = proxy.listen_addr()?.to_string();
+        Ok(UpstreamProxy {
+            proxy,
+            addr,
+            created,
+        })
     }
 
     /// Enable all proxies and remove all active toxics.
@@ -87,11 +613,13 @@ impl Client {
     /// toxiproxy_rust::TOXIPROXY.reset();
     /// ```
     pub fn reset(&self) -> Result<(), String> {
-        self.client
-            .lock()
-            .map_err(|err| format!("lock error: {}", err))?
-            .post("reset")
-            .map(|_| ())
+        instrument::operation("reset", Fields::default(), || {
+            self.client
+                .lock()
+                .map_err(|err| format!("lock error: {}", err))?
+                .post("reset")
+                .map(|_| ())
+        })
     }
 
     /// Returns all registered proxies and their toxics.
@@ -105,23 +633,91 @@ impl Client {
         self.client
             .lock()
             .map_err(|err| format!("lock error: {}", err))?
-            .get("proxies")
-            .and_then(|response| {
-                response
-                    .json()
-                    .map(|proxy_map: HashMap<String, ProxyPack>| {
-                        proxy_map
-                            .into_iter()
-                            .map(|(name, proxy_pack)| {
-                                (name, Proxy::new(proxy_pack, self.client.clone()))
-                            })
-                            .collect()
+            .get_json("proxies")
+            .map(|proxy_map: HashMap<String, ProxyPack>| {
+                proxy_map
+                    .into_iter()
+                    .filter_map(|(name, proxy_pack)| {
+                        let name = match self.namespace {
+                            Some(ref namespace) => namespace.strip(&name)?.to_owned(),
+                            None => name,
+                        };
+                        Some((name, self.proxy(proxy_pack)))
                     })
-                    .map_err(|err| format!("json deserialize failed: {}", err))
+                    .collect()
+            })
+    }
+
+    /// Proxies (in the client's namespace) created with the given tag, sorted by name - eg. to add
+    /// latency to every database. Tags are kept by the client (and its clones), not by the server.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{namespace::Namespace, proxy::ProxyPack, TOXIPROXY};
+    /// let client = TOXIPROXY.namespaced(Namespace::unique());
+    /// let proxies = client.populate(vec![
+    ///     ProxyPack::new_ephemeral("orders_db".into(), "localhost:2000".into()).tagged(&["db"]),
+    ///     ProxyPack::new_ephemeral("cache".into(), "localhost:2000".into()),
+    /// ]).unwrap();
+    ///
+    /// for proxy in client.proxies_with_tag("db").expect("proxies are fetched") {
+    ///     proxy.with_latency("downstream".into(), 500, 0, 1.0);
+    /// }
+    /// # for proxy in proxies { proxy.delete().unwrap(); }
+    /// ```
+    pub fn proxies_with_tag(&self, tag: &str) -> Result<Vec<Proxy>, String> {
+        let mut proxies = self
+            .all()?
+            .into_values()
+            .filter(|proxy| proxy.proxy_pack.tags.iter().any(|existing| existing == tag))
+            .collect::<Vec<Proxy>>();
+        proxies.sort_by(|a, b| a.proxy_pack.name.cmp(&b.proxy_pack.name));
+        Ok(proxies)
+    }
+
+    /// Current proxies (in the client's namespace) and their toxics, sorted by name.
+    pub fn config(&self) -> Result<Config, String> {
+        let mut proxies = self
+            .all()?
+            .into_iter()
+            .map(|(name, proxy)| {
+                let toxics = proxy.toxics()?;
+                Ok(ProxyPack {
+                    name,
+                    toxics,
+                    ..proxy.proxy_pack
+                })
             })
+            .collect::<Result<Vec<ProxyPack>, String>>()?;
+        proxies.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Config { proxies })
+    }
+
+    /// Writes the current proxies and their toxics to a config file, which [`Config::load`] and
+    /// [`Config::populate`] (or the server's populate endpoint for JSON) can set up again - eg. to
+    /// commit a chaos setup built interactively.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::config::{Config, ConfigFormat};
+    /// let path = std::env::temp_dir().join("toxiproxy_export_doc.json");
+    /// toxiproxy_rust::TOXIPROXY
+    ///     .export(&path, ConfigFormat::Json)
+    ///     .expect("proxies are exported");
+    ///
+    /// let config = Config::load(&path).unwrap();
+    /// ```
+    ///
+    /// [`Config::load`]: ../config/struct.Config.html#method.load
+    /// [`Config::populate`]: ../config/struct.Config.html#method.populate
+    pub fn export<P: AsRef<Path>>(&self, path: P, format: ConfigFormat) -> Result<(), String> {
+        self.config()?.save(path, format)
     }
 
-    /// Health check for the Toxiproxy server.
+    /// Health check for the Toxiproxy server. See [`health_check`] for the reason of a failure.
     ///
     /// # Examples
     ///
@@ -130,11 +726,80 @@ impl Client {
     ///     /* signal the problem */
     /// }
     /// ```
+    ///
+    /// [`health_check`]: #method.health_check
     pub fn is_running(&self) -> bool {
-        self.client.lock().expect("Client lock failed").is_alive()
+        self.health_check().is_ok()
     }
 
-    /// Version of the Toxiproxy server.
+    /// Checks that a Toxiproxy server answers on the address, by asking for its version - so a
+    /// stale container or another service listening on the port is detected too. Returns the
+    /// server version, or the reason of the failure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// match toxiproxy_rust::TOXIPROXY.health_check() {
+    ///     Ok(version) => println!("Toxiproxy {} is running", version),
+    ///     Err(reason) => panic!("Toxiproxy is not available: {}", reason),
+    /// }
+    /// ```
+    pub fn health_check(&self) -> Result<String, String> {
+        let response = self
+            .client
+            .lock()
+            .map_err(|err| format!("lock error: {}", err))?
+            .get("version")?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "unexpected status for version request: {}",
+                response.status()
+            ));
+        }
+
+        let body = response
+            .text()
+            .map_err(|err| format!("version response cannot be read: {}", err))?;
+
+        parse_version(&body).ok_or_else(|| {
+            format!(
+                "unexpected version response, is it a Toxiproxy server?: {:?}",
+                body.chars().take(64).collect::<String>()
+            )
+        })
+    }
+
+    /// Waits until the server passes the [`health_check`], eg. after starting it. Returns the
+    /// server version, or the reason of the last failure once the timeout has passed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// let version = toxiproxy_rust::TOXIPROXY
+    ///     .wait_until_running(Duration::from_secs(10))
+    ///     .expect("Toxiproxy is running");
+    /// ```
+    ///
+    /// [`health_check`]: #method.health_check
+    pub fn wait_until_running(&self, timeout: Duration) -> Result<String, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.health_check() {
+                Ok(version) => return Ok(version),
+                Err(reason) if Instant::now() >= deadline => {
+                    return Err(format!(
+                        "Toxiproxy is not running after {:?}: {}",
+                        timeout, reason
+                    ))
+                }
+                Err(_) => thread::sleep(READY_POLL_INTERVAL),
+            }
+        }
+    }
+
+    /// Version of the Toxiproxy server, eg. `2.1.4` (from the JSON response of newer servers too).
     ///
     /// # Examples
     ///
@@ -151,7 +816,7 @@ impl Client {
                 response
                     .read_to_string(&mut body)
                     .expect("HTTP response cannot be read");
-                body
+                parse_version(&body).unwrap_or(body)
             })
     }
 
@@ -190,17 +855,36 @@ impl Client {
     /// let proxy = toxiproxy_rust::TOXIPROXY.find_proxy("socket").expect("proxy returned");
     /// ```
     pub fn find_proxy(&self, name: &str) -> Result<Proxy, String> {
-        let path = format!("proxies/{}", name);
+        let path = format!("proxies/{}", self.qualify(name));
 
         self.client
             .lock()
             .map_err(|err| format!("lock error: {}", err))?
-            .get(&path)
-            .and_then(|response| {
-                response
-                    .json()
-                    .map_err(|err| format!("json deserialize failed: {}", err))
-            })
-            .and_then(|proxy_pack: ProxyPack| Ok(Proxy::new(proxy_pack, self.client.clone())))
+            .get_json(&path)
+            .map(|proxy_pack: ProxyPack| self.proxy(proxy_pack))
+    }
+}
+
+/// Extracts the version from a version response - plain text (`2.1.4`) for older servers, JSON
+/// (`{"version": "2.5.0"}`) for newer ones.
+fn parse_version(body: &str) -> Option<String> {
+    let body = body.trim();
+
+    if let Ok(json) = serde_json::from_str::<HashMap<String, serde_json::Value>>(body) {
+        return json
+            .get("version")
+            .and_then(|version| version.as_str())
+            .map(|version| version.to_owned());
+    }
+
+    let looks_like_version = body.chars().any(|c| c.is_ascii_digit())
+        && body
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ".-+_".contains(c));
+
+    if looks_like_version {
+        Some(body.to_owned())
+    } else {
+        None
     }
 }
@@ -0,0 +1,193 @@
+//! Several Toxiproxy servers driven as one, eg. one server per host where a chaos action has to
+//! hit all of them.
+
+use super::client::*;
+use super::error::*;
+use super::proxy::*;
+use super::toxic::*;
+use std::fmt;
+use std::thread;
+
+/// Clients of several servers. Operations run on every server at once, and report the result of
+/// each server.
+#[derive(Clone)]
+pub struct ClusterClient {
+    clients: Vec<Client>,
+}
+
+/// Result of an operation per server, labelled by the server address.
+#[derive(Debug)]
+pub struct ClusterOutcome<T> {
+    pub results: Vec<(String, Result<T, String>)>,
+}
+
+impl<T> ClusterOutcome<T> {
+    pub fn is_ok(&self) -> bool {
+        self.results.iter().all(|(_, result)| result.is_ok())
+    }
+
+    /// Servers the operation failed on, with the reason.
+    pub fn failures(&self) -> Vec<(&str, &str)> {
+        self.results
+            .iter()
+            .filter_map(|(endpoint, result)| {
+                result
+                    .as_ref()
+                    .err()
+                    .map(|err| (endpoint.as_str(), err.as_str()))
+            })
+            .collect()
+    }
+
+    /// Values of every server, or the failures.
+    pub fn into_result(self) -> Result<Vec<T>, String> {
+        if self.is_ok() {
+            Ok(self
+                .results
+                .into_iter()
+                .filter_map(|(_, result)| result.ok())
+                .collect())
+        } else {
+            Err(self.to_string())
+        }
+    }
+}
+
+impl<T> fmt::Display for ClusterOutcome<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return write!(
+                f,
+                "cluster operation succeeded on {} servers",
+                self.results.len()
+            );
+        }
+
+        let failures = self
+            .failures()
+            .into_iter()
+            .map(|(endpoint, err)| format!("{}: {}", endpoint, err))
+            .collect::<Vec<String>>();
+        write!(f, "cluster operation failed for {}", failures.join(", "))
+    }
+}
+
+impl ClusterClient {
+    pub fn new(clients: Vec<Client>) -> Self {
+        Self { clients }
+    }
+
+    /// Clients of the servers at the given addresses. An address which can't be resolved fails
+    /// the operations on its server only.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::cluster::ClusterClient;
+    /// let cluster = ClusterClient::from_addresses(&["10.0.0.1:8474", "10.0.0.2:8474"]);
+    /// assert_eq!(2, cluster.clients().len());
+    /// ```
+    pub fn from_addresses(addresses: &[&str]) -> Self {
+        Self::new(
+            addresses
+                .iter()
+                .map(|address| Client::new(*address))
+                .collect(),
+        )
+    }
+
+    pub fn clients(&self) -> &[Client] {
+        &self.clients
+    }
+
+    /// Creates (or updates) the proxies on every server.
+    pub fn populate(&self, proxies: Vec<ProxyPack>) -> ClusterOutcome<Vec<Proxy>> {
+        self.for_each(|client| client.populate(proxies.clone()))
+    }
+
+    /// Enables every proxy and removes their toxics, on every server.
+    pub fn reset(&self) -> ClusterOutcome<()> {
+        self.for_each(Client::reset)
+    }
+
+    /// Registers a toxic on the proxy of the given name on every server.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{cluster::ClusterClient, proxy::ProxyPack, toxic::ToxicPack};
+    /// # use toxiproxy_rust::TOXIPROXY;
+    /// let cluster = ClusterClient::new(vec![TOXIPROXY.clone()]);
+    /// cluster
+    ///     .populate(vec![ProxyPack::new_ephemeral("cluster_doc".into(), "localhost:2000".into())])
+    ///     .into_result()
+    ///     .expect("proxies are created");
+    ///
+    /// cluster
+    ///     .add_toxic("cluster_doc", ToxicPack::new(
+    ///         "latency".into(),
+    ///         "downstream".into(),
+    ///         1.0,
+    ///         vec![("latency".to_owned(), 100)].into_iter().collect(),
+    ///     ))
+    ///     .into_result()
+    ///     .expect("toxics are registered");
+    /// # cluster.for_each(|client| client.find_proxy("cluster_doc")?.delete());
+    /// ```
+    pub fn add_toxic(&self, proxy: &str, toxic: ToxicPack) -> ClusterOutcome<()> {
+        self.for_each(|client| {
+            client
+                .find_proxy(proxy)?
+                .try_create_toxic(toxic.clone())
+                .map(|_| ())
+        })
+    }
+
+    pub fn delete_all_toxics(&self, proxy: &str) -> ClusterOutcome<()> {
+        self.for_each(|client| client.find_proxy(proxy)?.delete_all_toxics())
+    }
+
+    pub fn disable(&self, proxy: &str) -> ClusterOutcome<()> {
+        self.for_each(|client| client.find_proxy(proxy)?.disable())
+    }
+
+    pub fn enable(&self, proxy: &str) -> ClusterOutcome<()> {
+        self.for_each(|client| client.find_proxy(proxy)?.enable())
+    }
+
+    /// Runs an operation on every server at once.
+    pub fn for_each<F, T>(&self, operation: F) -> ClusterOutcome<T>
+    where
+        F: Fn(&Client) -> Result<T, String> + Sync,
+        T: Send,
+    {
+        let operation = &operation;
+        let run = move |client| ApiError::capture(|| operation(client));
+        ApiError::clear();
+        let results = thread::scope(|scope| {
+            self.clients
+                .iter()
+                .map(|client| scope.spawn(move || run(client)))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|worker| {
+                    let result = worker
+                        .join()
+                        .unwrap_or_else(|_| Err("cluster operation panicked".into()));
+                    ApiError::resume(result)
+                })
+                .collect::<Vec<Result<T, String>>>()
+        });
+
+        ClusterOutcome {
+            results: self.clients.iter().map(endpoint).zip(results).collect(),
+        }
+    }
+}
+
+fn endpoint(client: &Client) -> String {
+    client.address().map_or_else(
+        |err| format!("unknown ({})", err),
+        |address| address.to_string(),
+    )
+}
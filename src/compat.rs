@@ -0,0 +1,193 @@
+//! Differences between the API of Toxiproxy server versions (2.1 to 2.9), so one test codebase
+//! works against all of them. Once the version of the server is known (see
+//! [`Client::detect_server_version`]), the client and its proxies adapt their requests to it:
+//! proxies and toxics are updated with `PATCH` where the server supports it (`POST` is deprecated
+//! there), and toxics the server doesn't know yet are refused with the version they need, instead
+//! of the server's bare `400`. Version responses are read in both formats regardless (plain text
+//! on older servers, JSON on newer ones). Without a known version, requests work as with every
+//! 2.x server.
+//!
+//! ```
+//! let version = toxiproxy_rust::TOXIPROXY
+//!     .detect_server_version()
+//!     .expect("version is detected");
+//! if !version.supports_toxic("reset_peer") {
+//!     /* skip the test */
+//! }
+//! ```
+//!
+//! [`Client::detect_server_version`]: ../client/struct.Client.html#method.detect_server_version
+
+use super::client::*;
+use reqwest::Method;
+use std::fmt;
+use std::str::FromStr;
+
+/// First version updating proxies and toxics with `PATCH`.
+pub const PATCH_UPDATES_SINCE: ServerVersion = ServerVersion::new(2, 4, 0);
+
+/// Toxics added after 2.0, with the first version supporting them.
+const TOXICS_SINCE: &[(&str, ServerVersion)] = &[
+    ("limit_data", ServerVersion::new(2, 1, 0)),
+    ("reset_peer", ServerVersion::new(2, 2, 0)),
+];
+
+/// Version of a Toxiproxy server, eg. `2.1.4`. Pre-release and build suffixes (`2.9.0-rc1`) are
+/// ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ServerVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl ServerVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Whether proxies and toxics are updated with `PATCH`.
+    pub fn supports_patch(&self) -> bool {
+        *self >= PATCH_UPDATES_SINCE
+    }
+
+    /// Whether the server knows the toxic type. Types the crate has no record of are assumed to be
+    /// supported, the server decides.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::compat::ServerVersion;
+    /// let version: ServerVersion = "2.1.4".parse().unwrap();
+    ///
+    /// assert!(version.supports_toxic("latency"));
+    /// assert!(!version.supports_toxic("reset_peer"));
+    /// ```
+    pub fn supports_toxic(&self, r#type: &str) -> bool {
+        self.toxic_since(r#type).is_none_or(|since| *self >= since)
+    }
+
+    /// Ok if the server knows the toxic type, or why the toxic can't be created.
+    pub(crate) fn check_toxic(&self, r#type: &str) -> Result<(), String> {
+        match self.toxic_since(r#type) {
+            Some(since) if *self < since => Err(format!(
+                "toxic type {:?} requires Toxiproxy {} or newer, the server is {}",
+                r#type, since, self
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Method updating a proxy or a toxic.
+    pub(crate) fn update_method(&self) -> Method {
+        if self.supports_patch() {
+            Method::PATCH
+        } else {
+            Method::POST
+        }
+    }
+
+    fn toxic_since(&self, r#type: &str) -> Option<ServerVersion> {
+        TOXICS_SINCE
+            .iter()
+            .find(|(name, _)| *name == r#type)
+            .map(|(_, since)| *since)
+    }
+}
+
+impl FromStr for ServerVersion {
+    type Err = String;
+
+    /// Parses a version like `2.1.4`, `v2.9.0` or `2.9.0-embedded`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::compat::ServerVersion;
+    /// let version: ServerVersion = "v2.9.0-rc1".parse().expect("version is valid");
+    ///
+    /// assert_eq!(ServerVersion::new(2, 9, 0), version);
+    /// assert!("git".parse::<ServerVersion>().is_err());
+    /// ```
+    fn from_str(version: &str) -> Result<Self, String> {
+        let trimmed = version.trim();
+        let core = trimmed
+            .strip_prefix('v')
+            .unwrap_or(trimmed)
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default();
+
+        let mut parts = core.split('.').map(|part| part.parse::<u32>());
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(major)), Some(Ok(minor)), patch, None) => {
+                let patch = match patch {
+                    Some(Ok(patch)) => patch,
+                    None => 0,
+                    Some(Err(_)) => return Err(format!("invalid server version: {:?}", version)),
+                };
+                Ok(Self::new(major, minor, patch))
+            }
+            _ => Err(format!("invalid server version: {:?}", version)),
+        }
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl Client {
+    /// Asks the server for its version, and adapts the requests of the client, its clones and its
+    /// proxies to it from now on - eg. at the start of a test suite running against servers of
+    /// several versions. Pointing the client to another server (see [`set_address`]) forgets it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let version = toxiproxy_rust::TOXIPROXY
+    ///     .detect_server_version()
+    ///     .expect("version is detected");
+    /// ```
+    ///
+    /// [`set_address`]: #method.set_address
+    pub fn detect_server_version(&self) -> Result<ServerVersion, String> {
+        let version = self.version()?.parse::<ServerVersion>()?;
+        self.set_server_version(Some(version))?;
+        Ok(version)
+    }
+
+    /// Adapts the requests to the given server version (without asking the server), or stops
+    /// adapting them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{client::Client, compat::ServerVersion};
+    /// let client = Client::new("127.0.0.1:8474");
+    /// client.set_server_version(Some(ServerVersion::new(2, 1, 4))).unwrap();
+    ///
+    /// assert_eq!(Some(ServerVersion::new(2, 1, 4)), client.server_version());
+    /// ```
+    pub fn set_server_version(&self, version: Option<ServerVersion>) -> Result<(), String> {
+        self.http_client()
+            .lock()
+            .map_err(|err| format!("lock error: {}", err))?
+            .set_server_version(version);
+        Ok(())
+    }
+
+    /// Version the requests are adapted to, if known.
+    pub fn server_version(&self) -> Option<ServerVersion> {
+        self.http_client()
+            .lock()
+            .ok()
+            .and_then(|client| client.server_version())
+    }
+}
@@ -0,0 +1,404 @@
+//! Declarative proxy setups loaded from config files, eg. to keep the proxies (and the toxics to
+//! start with) of a test suite next to it:
+//!
+//! ```toml
+//! [[proxies]]
+//! name = "db"
+//! listen = "127.0.0.1:5433"
+//! upstream = "db:5432"
+//!
+//! [[proxies.toxics]]
+//! type = "latency"
+//! attributes = { latency = 100 }
+//! ```
+//!
+//! JSON files are supported too (as an object with a `proxies` list, or the list itself as accepted
+//! by the [populate] endpoint); TOML files require the `toml` feature, YAML files the `yaml` one.
+//! Omitted fields default to: `enabled = true`, no toxics or tags, and for toxics
+//! `stream = "downstream"`, `toxicity = 1.0` and `name = "<type>_<stream>"`.
+//!
+//! Config files may refer to environment variables, as addresses tend to differ between local runs,
+//! docker-compose and CI: `${NAME}` is replaced by the value of `NAME` (an error if it's not set),
+//! `${NAME:-default}` falls back to `default`, and `$$` stands for a literal `$`. Per-environment
+//! differences can also live in overlay files merged onto a base config, see [`Config::load_all`].
+//!
+//! [populate]: https://github.com/Shopify/toxiproxy#populating-toxiproxy
+
+use super::client::*;
+use super::proxy::*;
+use super::toxic::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Format of a config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    /// Requires the `toml` feature.
+    #[cfg(feature = "toml")]
+    Toml,
+    /// Requires the `yaml` feature.
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Format of a config file picked by its extension (`.json`, `.toml`, `.yaml` or `.yml`).
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(ConfigFormat::Json),
+            #[cfg(feature = "toml")]
+            Some("toml") => Ok(ConfigFormat::Toml),
+            #[cfg(feature = "yaml")]
+            Some("yaml") | Some("yml") => Ok(ConfigFormat::Yaml),
+            _ => Err(format!("unsupported config format: {}", path.display())),
+        }
+    }
+}
+
+/// Proxies (and their toxics) to set up.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub proxies: Vec<ProxyPack>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonConfig {
+    Config(Config),
+    Proxies(Vec<ProxyPack>),
+}
+
+impl Config {
+    /// Loads a config file, its format picked by its extension (`.json`, `.toml`, `.yaml` or
+    /// `.yml`). Environment variables are substituted first, see [`expand_env`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        Self::load_all(&[path])
+    }
+
+    /// Loads a base config followed by overlays (eg. `base.toml` and `ci.toml`), each file merged
+    /// onto the previous ones:
+    ///
+    /// - proxies are matched by name: a new one is added, an existing one gets the fields set in
+    ///   the overlay replaced (eg. only `upstream`),
+    /// - toxics of a proxy are matched by name (`<type>_<stream>` by default) the same way, so an
+    ///   overlay can add or change toxics but not remove them,
+    /// - any other value is replaced.
+    ///
+    /// Only the merged result has to be a complete config: overlays may leave out required fields.
+    pub fn load_all<P: AsRef<Path>>(paths: &[P]) -> Result<Self, String> {
+        let mut merged = json!({ "proxies": [] });
+        for path in paths {
+            merge_values(&mut merged, read_value(path.as_ref())?);
+        }
+
+        serde_json::from_value::<Config>(merged)
+            .map(Self::with_default_names)
+            .map_err(|err| format!("invalid config: {}", err))
+    }
+
+    /// Parses a JSON config.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::config::Config;
+    /// let config = Config::from_json(r#"[
+    ///     {"name": "socket", "listen": "localhost:2001", "upstream": "localhost:2000"}
+    /// ]"#).expect("config is valid");
+    ///
+    /// assert!(config.proxies[0].enabled);
+    /// ```
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let config = match serde_json::from_str(json)
+            .map_err(|err| format!("invalid JSON config: {}", err))?
+        {
+            JsonConfig::Config(config) => config,
+            JsonConfig::Proxies(proxies) => Config { proxies },
+        };
+        Ok(config.with_default_names())
+    }
+
+    /// Parses a TOML config (requires the `toml` feature).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::config::Config;
+    /// let config = Config::from_toml(r#"
+    ///     [[proxies]]
+    ///     name = "socket"
+    ///     listen = "localhost:2001"
+    ///     upstream = "localhost:2000"
+    ///
+    ///     [[proxies.toxics]]
+    ///     type = "latency"
+    ///     attributes = { latency = 100 }
+    /// "#).expect("config is valid");
+    ///
+    /// assert_eq!("latency_downstream", config.proxies[0].toxics[0].name);
+    /// ```
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Self, String> {
+        toml::from_str::<Config>(toml)
+            .map(Self::with_default_names)
+            .map_err(|err| format!("invalid TOML config: {}", err))
+    }
+
+    /// Parses a YAML config (requires the `yaml` feature).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::config::Config;
+    /// let config = Config::from_yaml(r#"
+    /// proxies:
+    ///   - name: socket
+    ///     listen: localhost:2001
+    ///     upstream: localhost:2000
+    ///     toxics:
+    ///       - type: latency
+    ///         attributes: { latency: 100 }
+    /// "#).expect("config is valid");
+    ///
+    /// assert_eq!("latency_downstream", config.proxies[0].toxics[0].name);
+    /// ```
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(yaml: &str) -> Result<Self, String> {
+        serde_yaml::from_str::<Config>(yaml)
+            .map(Self::with_default_names)
+            .map_err(|err| format!("invalid YAML config: {}", err))
+    }
+
+    /// Creates (or updates) the proxies, then replaces their toxics with the configured ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{config::Config, TOXIPROXY};
+    /// let config = Config::from_json(r#"{"proxies": [{
+    ///     "name": "config_doc",
+    ///     "listen": "127.0.0.1:0",
+    ///     "upstream": "localhost:2000",
+    ///     "toxics": [{"type": "timeout", "attributes": {"timeout": 100}}]
+    /// }]}"#).unwrap();
+    ///
+    /// let proxies = config.populate(&TOXIPROXY).expect("proxies are set up");
+    /// assert_eq!(1, proxies[0].toxics().unwrap().len());
+    /// # proxies[0].delete().unwrap();
+    /// ```
+    pub fn populate(&self, client: &Client) -> Result<Vec<Proxy>, String> {
+        let proxies = client.populate(self.proxies.clone())?;
+
+        for proxy_pack in self.proxies.iter() {
+            let name = client.qualify(&proxy_pack.name);
+            let proxy = proxies
+                .iter()
+                .find(|proxy| proxy.proxy_pack.name == name)
+                .ok_or_else(|| format!("proxy {:?} was not populated", name))?;
+            proxy.delete_all_toxics()?;
+            for toxic in proxy_pack.toxics.iter() {
+                proxy.try_create_toxic(toxic.clone())?;
+            }
+        }
+
+        Ok(proxies)
+    }
+
+    /// Serializes the config in the given format, as loaded back by [`load`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::config::{Config, ConfigFormat};
+    /// let config = Config::from_json(r#"[
+    ///     {"name": "socket", "listen": "localhost:2001", "upstream": "localhost:2000"}
+    /// ]"#).unwrap();
+    ///
+    /// let json = config.render(ConfigFormat::Json).expect("config is serialized");
+    /// assert_eq!("socket", Config::from_json(&json).unwrap().proxies[0].name);
+    /// ```
+    ///
+    /// [`load`]: #method.load
+    pub fn render(&self, format: ConfigFormat) -> Result<String, String> {
+        match format {
+            ConfigFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|err| format!("json serialize failed: {}", err)),
+            #[cfg(feature = "toml")]
+            ConfigFormat::Toml => toml::to_string_pretty(self)
+                .map_err(|err| format!("toml serialize failed: {}", err)),
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => {
+                serde_yaml::to_string(self).map_err(|err| format!("yaml serialize failed: {}", err))
+            }
+        }
+    }
+
+    /// Writes the config to a file in the given format.
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: ConfigFormat) -> Result<(), String> {
+        let path = path.as_ref();
+        fs::write(path, self.render(format)?)
+            .map_err(|err| format!("cannot write config {}: {}", path.display(), err))
+    }
+
+    fn with_default_names(mut self) -> Self {
+        for proxy_pack in self.proxies.iter_mut() {
+            proxy_pack.toxics = proxy_pack
+                .toxics
+                .drain(..)
+                .map(ToxicPack::or_default_name)
+                .collect();
+        }
+        self
+    }
+}
+
+/// Reads a config file into a generic value, a list of proxies turned into a `proxies` object.
+fn read_value(path: &Path) -> Result<Value, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("cannot read config {}: {}", path.display(), err))?;
+    let content = expand_env(&content)
+        .map_err(|err| format!("cannot load config {}: {}", path.display(), err))?;
+
+    let value = match ConfigFormat::from_path(path)? {
+        ConfigFormat::Json => serde_json::from_str::<Value>(&content)
+            .map_err(|err| format!("invalid JSON config {}: {}", path.display(), err))?,
+        #[cfg(feature = "toml")]
+        ConfigFormat::Toml => toml::from_str::<Value>(&content)
+            .map_err(|err| format!("invalid TOML config {}: {}", path.display(), err))?,
+        #[cfg(feature = "yaml")]
+        ConfigFormat::Yaml => serde_yaml::from_str::<Value>(&content)
+            .map_err(|err| format!("invalid YAML config {}: {}", path.display(), err))?,
+    };
+
+    match value {
+        Value::Array(proxies) => Ok(json!({ "proxies": proxies })),
+        value => Ok(value),
+    }
+}
+
+/// Merges `overlay` onto `base`, see [`Config::load_all`].
+fn merge_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(Value::Array(items)) if key == "proxies" || key == "toxics" => {
+                        merge_named(items, value)
+                    }
+                    Some(base_value) => merge_values(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Merges a list of proxies or toxics onto another one, matching items by name.
+fn merge_named(items: &mut Vec<Value>, overlay: Value) {
+    let overlay_items = match overlay {
+        Value::Array(overlay_items) => overlay_items,
+        overlay => {
+            *items = vec![overlay];
+            return;
+        }
+    };
+
+    for overlay_item in overlay_items {
+        let key = item_name(&overlay_item);
+        match items
+            .iter_mut()
+            .find(|item| key.is_some() && item_name(item) == key)
+        {
+            Some(item) => merge_values(item, overlay_item),
+            None => items.push(overlay_item),
+        }
+    }
+}
+
+/// Name of a proxy or toxic, defaulting to `<type>_<stream>` for toxics.
+fn item_name(item: &Value) -> Option<String> {
+    if let Some(name) = item.get("name").and_then(Value::as_str) {
+        return Some(name.to_owned());
+    }
+
+    let r#type = item.get("type").and_then(Value::as_str)?;
+    let stream = item
+        .get("stream")
+        .and_then(Value::as_str)
+        .unwrap_or("downstream");
+    Some(format!("{}_{}", r#type, stream))
+}
+
+/// Substitutes environment variables in a config: `${NAME}`, `${NAME:-default}` and `$$` for a
+/// literal `$`. Every variable which is not set (and has no default) is reported.
+///
+/// # Examples
+///
+/// ```
+/// # use toxiproxy_rust::config::expand_env;
+/// std::env::set_var("EXPAND_ENV_DOC_HOST", "db");
+///
+/// assert_eq!(
+///     Ok("db:5432 $5".to_owned()),
+///     expand_env("${EXPAND_ENV_DOC_HOST}:${EXPAND_ENV_DOC_PORT:-5432} $$5"),
+/// );
+/// assert!(expand_env("${EXPAND_ENV_DOC_MISSING}").is_err());
+/// ```
+pub fn expand_env(text: &str) -> Result<String, String> {
+    expand_with(text, |name| env::var(name).ok())
+}
+
+fn expand_with<F>(text: &str, lookup: F) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut expanded = String::with_capacity(text.len());
+    let mut missing = vec![];
+    let mut rest = text;
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("$$") {
+            expanded.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("unterminated variable: {}", rest))?;
+            let (name, default) = match after[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&after[..end], None),
+            };
+
+            match lookup(name).or_else(|| default.map(|default| default.to_owned())) {
+                Some(value) => expanded.push_str(&value),
+                None => missing.push(name.to_owned()),
+            }
+            rest = &after[end + 1..];
+        } else {
+            expanded.push('$');
+            rest = &rest[1..];
+        }
+    }
+    expanded.push_str(rest);
+
+    if missing.is_empty() {
+        Ok(expanded)
+    } else {
+        Err(format!(
+            "missing environment variables: {}",
+            missing.join(", ")
+        ))
+    }
+}
@@ -1,2 +1,9 @@
+use std::time::Duration;
+
 pub const ERR_LOCK: &str = "Lock cannot be granted";
 pub const ERR_JSON_SERIALIZE: &str = "JSON serialization failed";
+pub const DEFAULT_TOXIPROXY_ADDR: &str = "127.0.0.1:8474";
+pub const ENV_TOXIPROXY_URL: &str = "TOXIPROXY_URL";
+pub const CORRELATION_ID_HEADER: &str = "X-Correlation-ID";
+pub const NOOP_VERSION: &str = "0.0.0-noop";
+pub const READY_POLL_INTERVAL: Duration = Duration::from_millis(100);
@@ -0,0 +1,123 @@
+//! Toxiproxy server started in a Docker container with [testcontainers], so a test suite doesn't
+//! need a server running beforehand. Needs the `testcontainers` feature, and Docker.
+//!
+//! Proxies listen inside the container: their listen ports have to be published when starting
+//! it (see [`ToxiproxyContainer::start_with_ports`]), and upstreams have to be reachable from the
+//! container.
+//!
+//! ```no_run
+//! use toxiproxy_rust::{container::ToxiproxyContainer, proxy::ProxyPack};
+//!
+//! let server = ToxiproxyContainer::start_with_ports(&[5433]).expect("Toxiproxy is started");
+//! let proxy = server
+//!     .client()
+//!     .create_proxy(ProxyPack::new(
+//!         "postgres".into(),
+//!         "0.0.0.0:5433".into(),
+//!         "postgres:5432".into(),
+//!     ))
+//!     .unwrap();
+//!
+//! // The application connects to the port published on the host.
+//! let addr = format!("127.0.0.1:{}", server.host_port(5433));
+//! ```
+//!
+//! [testcontainers]: https://docs.rs/testcontainers
+
+use super::client::*;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+use testcontainers::{clients::Cli, core::WaitFor, Container, GenericImage};
+
+/// Image of the official Toxiproxy server.
+pub const TOXIPROXY_IMAGE: &str = "ghcr.io/shopify/toxiproxy";
+/// Version of the image started by default.
+pub const TOXIPROXY_IMAGE_TAG: &str = "2.9.0";
+/// Port of the HTTP API inside the container.
+pub const API_PORT: u16 = 8474;
+/// How long the server has to become ready after the container started.
+pub const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    static ref DOCKER: Cli = Cli::default();
+}
+
+/// Running Toxiproxy container, removed when dropped.
+pub struct ToxiproxyContainer {
+    container: Container<'static, GenericImage>,
+    client: Client,
+}
+
+impl ToxiproxyContainer {
+    /// Starts the default image, without publishing any proxy port.
+    pub fn start() -> Result<Self, String> {
+        Self::start_with_ports(&[])
+    }
+
+    /// Starts the default image, publishing the given ports (on random host ports) for proxies to
+    /// listen on.
+    pub fn start_with_ports(proxy_ports: &[u16]) -> Result<Self, String> {
+        Self::start_image(TOXIPROXY_IMAGE_TAG, proxy_ports)
+    }
+
+    /// Starts the given version of the image, publishing the given proxy ports.
+    pub fn start_image(tag: &str, proxy_ports: &[u16]) -> Result<Self, String> {
+        let image = proxy_ports.iter().fold(
+            GenericImage::new(TOXIPROXY_IMAGE, tag)
+                .with_wait_for(WaitFor::Nothing)
+                .with_exposed_port(API_PORT),
+            |image, port| image.with_exposed_port(*port),
+        );
+
+        // The Docker client panics if it can't start the container.
+        let container = panic::catch_unwind(AssertUnwindSafe(|| DOCKER.run(image)))
+            .map_err(|err| format!("Toxiproxy container cannot be started: {}", reason(err)))?;
+
+        let client = Client::new(("127.0.0.1", container.get_host_port_ipv4(API_PORT)));
+        client.wait_until_running(READY_TIMEOUT)?;
+        Ok(Self { container, client })
+    }
+
+    /// Starts the container again, eg. after the server crashed. The server starts without
+    /// proxies, and the client follows the API port if Docker published it elsewhere.
+    pub fn restart(&mut self) -> Result<(), String> {
+        let container = &self.container;
+        let port = panic::catch_unwind(AssertUnwindSafe(|| {
+            container.stop();
+            container.start();
+            container.get_host_port_ipv4(API_PORT)
+        }))
+        .map_err(|err| format!("Toxiproxy container cannot be restarted: {}", reason(err)))?;
+
+        self.client.set_address(("127.0.0.1", port))?;
+        self.client.wait_until_running(READY_TIMEOUT).map(|_| ())
+    }
+
+    /// Client of the server in the container.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Port of the host a port of the container is published on.
+    pub fn host_port(&self, container_port: u16) -> u16 {
+        self.container.get_host_port_ipv4(container_port)
+    }
+
+    /// ID of the container, eg. to read its logs.
+    pub fn id(&self) -> &str {
+        self.container.id()
+    }
+}
+
+fn reason(panic: Box<dyn Any + Send>) -> String {
+    panic
+        .downcast_ref::<String>()
+        .cloned()
+        .or_else(|| {
+            panic
+                .downcast_ref::<&str>()
+                .map(|reason| reason.to_string())
+        })
+        .unwrap_or_default()
+}
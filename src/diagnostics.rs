@@ -0,0 +1,154 @@
+//! What the crate was doing recently: the latest requests sent to the server and the last failure
+//! with its context, see [`Client::diagnostics`] - eg. to include in the message of a failing
+//! assertion.
+//!
+//! [`Client::diagnostics`]: ../client/struct.Client.html#method.diagnostics
+
+use super::client::*;
+use super::error::*;
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Requests kept by default, see [`Client::set_history_capacity`].
+///
+/// [`Client::set_history_capacity`]: ../client/struct.Client.html#method.set_history_capacity
+pub const DEFAULT_HISTORY_CAPACITY: usize = 32;
+
+/// A request sent to the server. The timestamp is in milliseconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecentRequest {
+    pub timestamp_ms: u64,
+    pub method: String,
+    pub path: String,
+    /// Status of the answer, if the server answered.
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub elapsed: Duration,
+}
+
+/// Recent requests of a client, its clones and its proxies, the oldest first, and the last failed
+/// one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diagnostics {
+    pub recent: Vec<RecentRequest>,
+    pub last_error: Option<ApiError>,
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "last {} requests to Toxiproxy:", self.recent.len())?;
+        for request in self.recent.iter() {
+            let outcome = match (request.status, request.error.as_ref()) {
+                (_, Some(err)) => err.clone(),
+                (Some(status), None) => status.to_string(),
+                (None, None) => "-".into(),
+            };
+            writeln!(
+                f,
+                "  {} {} /{}: {} in {:?}",
+                request.timestamp_ms, request.method, request.path, outcome, request.elapsed
+            )?;
+        }
+        match self.last_error {
+            Some(ref err) => writeln!(f, "last error: {}", err),
+            None => writeln!(f, "no error"),
+        }
+    }
+}
+
+/// Bounded history of the requests of a client.
+#[derive(Debug)]
+pub(crate) struct History {
+    capacity: usize,
+    requests: VecDeque<RecentRequest>,
+    last_error: Option<ApiError>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_HISTORY_CAPACITY,
+            requests: VecDeque::new(),
+            last_error: None,
+        }
+    }
+}
+
+impl History {
+    pub(crate) fn record(
+        &mut self,
+        method: &str,
+        path: &str,
+        status: Option<u16>,
+        error: Option<String>,
+        elapsed: Duration,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.requests.len() == self.capacity {
+            self.requests.pop_front();
+        }
+        self.requests.push_back(RecentRequest {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_millis() as u64),
+            method: method.into(),
+            path: path.into(),
+            status,
+            error,
+            elapsed,
+        });
+    }
+
+    pub(crate) fn record_error(&mut self, err: ApiError) {
+        self.last_error = Some(err);
+    }
+
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.requests.len() > capacity {
+            self.requests.pop_front();
+        }
+    }
+
+    pub(crate) fn diagnostics(&self) -> Diagnostics {
+        Diagnostics {
+            recent: self.requests.iter().cloned().collect(),
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+impl Client {
+    /// The latest requests of the client, its clones and its proxies, and the last one which
+    /// failed (with its context, see [`ApiError`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::client::Client;
+    /// let client = Client::from_env();
+    /// let proxies = client.all().unwrap();
+    ///
+    /// assert!(proxies.len() < 1000, "too many proxies\n{}", client.diagnostics());
+    /// ```
+    ///
+    /// [`ApiError`]: ../error/struct.ApiError.html
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.http_client()
+            .lock()
+            .map(|client| client.diagnostics())
+            .unwrap_or_default()
+    }
+
+    /// Number of requests kept for [`diagnostics`], 32 by default. 0 keeps none.
+    ///
+    /// [`diagnostics`]: #method.diagnostics
+    pub fn set_history_capacity(&self, capacity: usize) {
+        if let Ok(client) = self.http_client().lock() {
+            client.set_history_capacity(capacity);
+        }
+    }
+}
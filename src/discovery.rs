@@ -0,0 +1,169 @@
+//! Finding the Toxiproxy server of the environment the tests run in, with errors pointing at the
+//! usual misconfigurations.
+//!
+//! ```no_run
+//! use toxiproxy_rust::client::Client;
+//!
+//! // In a docker-compose service next to a `toxiproxy` service.
+//! let client = Client::discover_compose("toxiproxy").expect("Toxiproxy is reachable");
+//!
+//! // In a Kubernetes Job next to the `toxiproxy` service of the `chaos` namespace.
+//! let client = Client::discover_k8s("chaos", "toxiproxy").expect("Toxiproxy is reachable");
+//! ```
+
+use super::address::*;
+use super::client::*;
+use super::consts::*;
+use super::http_client::*;
+use std::env;
+use std::fs;
+use std::net::ToSocketAddrs;
+
+/// Port of the API of a Toxiproxy server.
+pub const TOXIPROXY_PORT: u16 = 8474;
+
+/// Namespace of the pod, as mounted from its service account.
+const K8S_NAMESPACE_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount/namespace";
+
+impl Client {
+    /// Client of the Toxiproxy service of a docker-compose project, `service_name:8474` (or the
+    /// given port, eg. `toxiproxy:18474`), unless `TOXIPROXY_URL` overrides it. Fails if the
+    /// server is not reachable, with the likely reason.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::client::Client;
+    /// let client = Client::discover_compose("127.0.0.1").expect("Toxiproxy is reachable");
+    ///
+    /// let err = Client::discover_compose("127.0.0.1:1").err().unwrap();
+    /// assert!(err.contains("is the toxiproxy service started"));
+    /// ```
+    pub fn discover_compose(service_name: &str) -> Result<Self, String> {
+        let (addr, source) = match env::var(ENV_TOXIPROXY_URL) {
+            Ok(url) => (addr_from_url(&url).to_owned(), ENV_TOXIPROXY_URL),
+            Err(_) if service_name.contains(':') => (service_name.to_owned(), "service name"),
+            Err(_) => (
+                format!("{}:{}", service_name, TOXIPROXY_PORT),
+                "service name",
+            ),
+        };
+
+        connect(
+            &addr,
+            &format!(
+                "{} (from the {}) cannot be resolved: service names only resolve inside containers \
+                 on the network of the compose project; from the host, set {} to the published \
+                 port, eg. http://localhost:{}",
+                addr, source, ENV_TOXIPROXY_URL, TOXIPROXY_PORT
+            ),
+            &format!(
+                "{} (from the {}) is not reachable: is the toxiproxy service started (`depends_on` \
+                 only waits for its container, not for the server), listening on 0.0.0.0 (`-host \
+                 0.0.0.0`), and on the same network as this container?",
+                addr, source
+            ),
+        )
+    }
+
+    /// Client of a Toxiproxy service of a Kubernetes cluster, eg. for tests running as a Job next
+    /// to a Toxiproxy deployment, at the address [`k8s_address`] finds. Fails if the server is not
+    /// reachable, with the likely reason.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use toxiproxy_rust::client::Client;
+    /// let client = Client::discover_k8s("chaos", "toxiproxy").expect("Toxiproxy is reachable");
+    /// ```
+    ///
+    /// [`k8s_address`]: ../discovery/fn.k8s_address.html
+    pub fn discover_k8s(namespace: &str, service: &str) -> Result<Self, String> {
+        let pod_namespace = fs::read_to_string(K8S_NAMESPACE_PATH).ok();
+        let (addr, source) = k8s_address(namespace, service, pod_namespace.as_deref(), |name| {
+            env::var(name).ok()
+        })?;
+
+        connect(
+            &addr,
+            &format!(
+                "{} (from the {}) cannot be resolved: is the {} service in the {} namespace, and \
+                 are the tests running in the cluster? From outside, port-forward the service and \
+                 set {}, eg. http://localhost:{}",
+                addr, source, service, namespace, ENV_TOXIPROXY_URL, TOXIPROXY_PORT
+            ),
+            &format!(
+                "{} (from the {}) is not reachable: does the service select ready Toxiproxy pods, \
+                 expose port {} (`-host 0.0.0.0`), and do network policies allow this pod?",
+                addr, source, TOXIPROXY_PORT
+            ),
+        )
+    }
+}
+
+/// Address of a Toxiproxy service of a Kubernetes cluster, and where it comes from: unless
+/// `TOXIPROXY_URL` overrides it, the address Kubernetes injects in the environment of pods
+/// (`<SERVICE>_SERVICE_HOST` and `_PORT`, set for services of the pod's own namespace which existed
+/// when it started) if the pod runs in the service's namespace (`pod_namespace`, read from the
+/// service account), or the in-cluster DNS name `service.namespace.svc:8474`. Environment
+/// variables are looked up with `var`. Fails if the injected port is not a port.
+///
+/// # Examples
+///
+/// ```
+/// # use toxiproxy_rust::discovery::k8s_address;
+/// let var = |name: &str| match name {
+///     "TOXIPROXY_SERVICE_HOST" => Some("10.0.0.7".to_owned()),
+///     "TOXIPROXY_SERVICE_PORT" => Some("8474".to_owned()),
+///     _ => None,
+/// };
+///
+/// let (addr, _) = k8s_address("chaos", "toxiproxy", Some("chaos"), var).unwrap();
+/// assert_eq!("10.0.0.7:8474", addr);
+/// let (addr, _) = k8s_address("chaos", "toxiproxy", Some("default"), var).unwrap();
+/// assert_eq!("toxiproxy.chaos.svc:8474", addr);
+/// ```
+pub fn k8s_address<F>(
+    namespace: &str,
+    service: &str,
+    pod_namespace: Option<&str>,
+    var: F,
+) -> Result<(String, String), String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if let Some(url) = var(ENV_TOXIPROXY_URL) {
+        return Ok((addr_from_url(&url).to_owned(), ENV_TOXIPROXY_URL.to_owned()));
+    }
+
+    let prefix = service.to_uppercase().replace('-', "_");
+    let host_var = format!("{}_SERVICE_HOST", prefix);
+    let port_var = format!("{}_SERVICE_PORT", prefix);
+    match (pod_namespace.map(str::trim), var(&host_var), var(&port_var)) {
+        (Some(pod_namespace), Some(host), Some(port)) if pod_namespace == namespace => {
+            let port = port
+                .parse::<u16>()
+                .map_err(|err| format!("{} is not a port ({:?}): {}", port_var, port, err))?;
+            Ok((join_host_port(&host, port), host_var))
+        }
+        _ => Ok((
+            format!("{}.{}.svc:{}", service, namespace, TOXIPROXY_PORT),
+            "cluster DNS".to_owned(),
+        )),
+    }
+}
+
+/// Client of the server at the address, after checking it's reachable.
+fn connect(addr: &str, unresolved: &str, unreachable: &str) -> Result<Client, String> {
+    let socket_addr = addr
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| unresolved.to_owned())?;
+
+    let client = Client::new(socket_addr);
+    client
+        .health_check()
+        .map_err(|reason| format!("{}: {}", unreachable, reason))?;
+    Ok(client)
+}
@@ -0,0 +1,285 @@
+//! Downloading a `toxiproxy-server` release for the current platform into a cache directory, so a
+//! fresh machine can run the test suite without installing Toxiproxy. Needs the `download` feature.
+//!
+//! Binaries are only run if their SHA-256 checksum matches the one pinned for the platform: the
+//! checksums of the release assets (`toxiproxy-server-<os>-<arch>`) are published with the release,
+//! in its checksums file. The simplest way to pin them is to commit that file next to the tests,
+//! and to update the Toxiproxy version by replacing it with the one of the new release (after
+//! checking it against the GitHub page of the release):
+//!
+//! ```no_run
+//! use toxiproxy_rust::{download::Release, server::ToxiproxyServer};
+//!
+//! let checksums = std::fs::read_to_string("tests/toxiproxy_2.9.0_checksums.txt").unwrap();
+//! let release = Release::new("2.9.0").with_checksums(&checksums);
+//!
+//! let server =
+//!     ToxiproxyServer::download_and_spawn(&release, 18474).expect("Toxiproxy is started");
+//! ```
+//!
+//! As the cached binaries are run, the cache directory is private to the user: it's created
+//! readable by the user only, and a cache directory other users can write to is refused.
+//!
+//! Single checksums can be pinned too:
+//!
+//! ```no_run
+//! # use toxiproxy_rust::download::Release;
+//! let release = Release::new("2.9.0")
+//!     .with_sha256("linux-amd64", "<sha256 of toxiproxy-server-linux-amd64>")
+//!     .with_sha256("darwin-arm64", "<sha256 of toxiproxy-server-darwin-arm64>");
+//! ```
+
+use super::server::*;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Where releases are downloaded from by default.
+pub const RELEASES_URL: &str = "https://github.com/Shopify/toxiproxy/releases/download";
+/// Environment variable overriding the cache directory, see [`Release::cache_dir`]. The default is
+/// `toxiproxy_rust` in the user's cache directory (`$XDG_CACHE_HOME`, `~/.cache` or
+/// `%LOCALAPPDATA%`).
+///
+/// [`Release::cache_dir`]: struct.Release.html#structfield.cache_dir
+pub const ENV_TOXIPROXY_CACHE_DIR: &str = "TOXIPROXY_CACHE_DIR";
+/// How long downloading a binary may take.
+pub const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A pinned `toxiproxy-server` release, with the checksums of its binaries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Release {
+    pub version: String,
+    /// SHA-256 checksums (hex) by platform, eg. `linux-amd64`.
+    pub sha256: HashMap<String, String>,
+    /// Base URL of the releases, eg. a mirror.
+    pub base_url: String,
+    pub cache_dir: PathBuf,
+}
+
+impl Release {
+    /// The release, downloaded from GitHub into `$TOXIPROXY_CACHE_DIR` (or `toxiproxy_rust` in the
+    /// user's cache directory, see [`ENV_TOXIPROXY_CACHE_DIR`]).
+    ///
+    /// [`ENV_TOXIPROXY_CACHE_DIR`]: constant.ENV_TOXIPROXY_CACHE_DIR.html
+    pub fn new(version: &str) -> Self {
+        let cache_dir = env::var_os(ENV_TOXIPROXY_CACHE_DIR)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| user_cache_dir().join("toxiproxy_rust"));
+
+        Self {
+            version: version.trim_start_matches('v').into(),
+            sha256: HashMap::new(),
+            base_url: RELEASES_URL.into(),
+            cache_dir,
+        }
+    }
+
+    /// Pins the checksum of the binary of a platform.
+    pub fn with_sha256(mut self, platform: &str, sha256: &str) -> Self {
+        self.sha256
+            .insert(platform.into(), sha256.trim().to_lowercase());
+        self
+    }
+
+    /// Pins the checksums of the server binaries listed in a checksums file of the release
+    /// (`<sha256>  <asset>` lines, as published with it). Other assets are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::download::Release;
+    /// let release = Release::new("2.9.0").with_checksums(
+    ///     "0123abcd  toxiproxy-server-linux-amd64\n\
+    ///      4567ef01  toxiproxy-server-windows-amd64.exe\n\
+    ///      89abcdef  toxiproxy-cli-linux-amd64\n",
+    /// );
+    ///
+    /// assert_eq!("0123abcd", release.sha256["linux-amd64"]);
+    /// assert_eq!("4567ef01", release.sha256["windows-amd64"]);
+    /// assert_eq!(2, release.sha256.len());
+    /// ```
+    pub fn with_checksums(self, checksums: &str) -> Self {
+        checksums
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let sha256 = fields.next()?;
+                let platform = fields
+                    .next()?
+                    .trim_start_matches('*')
+                    .strip_prefix("toxiproxy-server-")?;
+                Some((platform.trim_end_matches(".exe"), sha256))
+            })
+            .fold(self, |release, (platform, sha256)| {
+                release.with_sha256(platform, sha256)
+            })
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').into();
+        self
+    }
+
+    pub fn with_cache_dir<P: AsRef<Path>>(mut self, cache_dir: P) -> Self {
+        self.cache_dir = cache_dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// Platform of the release assets for the current OS and architecture, eg. `linux-amd64`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::download::Release;
+    /// if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+    ///     assert_eq!(Ok("linux-amd64".to_string()), Release::platform());
+    /// }
+    /// ```
+    pub fn platform() -> Result<String, String> {
+        let os = match env::consts::OS {
+            "macos" => "darwin",
+            os @ ("linux" | "windows" | "freebsd") => os,
+            os => return Err(format!("no Toxiproxy release for {}", os)),
+        };
+        let arch = match env::consts::ARCH {
+            "x86_64" => "amd64",
+            "aarch64" => "arm64",
+            "x86" => "386",
+            "arm" => "armv7",
+            arch => return Err(format!("no Toxiproxy release for {}", arch)),
+        };
+        Ok(format!("{}-{}", os, arch))
+    }
+
+    /// Path of the binary in the cache, downloading it first if it's missing or doesn't match the
+    /// pinned checksum. Fails if other users can write to the cache directory (and so replace the
+    /// binary once it's checked).
+    pub fn fetch(&self) -> Result<PathBuf, String> {
+        let platform = Self::platform()?;
+        let expected = self.sha256.get(&platform).ok_or_else(|| {
+            format!(
+                "no checksum pinned for toxiproxy-server {} on {}",
+                self.version, platform
+            )
+        })?;
+
+        let asset = format!("toxiproxy-server-{}{}", platform, env::consts::EXE_SUFFIX);
+        let dir = self.cache_dir.join(&self.version);
+        create_private_dir(&self.cache_dir)?;
+        create_private_dir(&dir)?;
+        let path = dir.join(&asset);
+        if let Ok(binary) = fs::read(&path) {
+            if sha256(&binary) == *expected {
+                return Ok(path);
+            }
+        }
+
+        let url = format!("{}/v{}/{}", self.base_url, self.version, asset);
+        let binary = download(&url)?;
+        let actual = sha256(&binary);
+        if actual != *expected {
+            return Err(format!(
+                "checksum mismatch for {}: expected {}, got {}",
+                url, expected, actual
+            ));
+        }
+
+        // Written next to the final path first, so concurrent test processes never run a partial
+        // binary.
+        let partial = dir.join(format!("{}.{}.partial", asset, std::process::id()));
+        fs::write(&partial, &binary)
+            .map_err(|err| format!("cannot write {}: {}", partial.display(), err))?;
+        make_executable(&partial)?;
+        fs::rename(&partial, &path)
+            .map_err(|err| format!("cannot write {}: {}", path.display(), err))?;
+        Ok(path)
+    }
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+fn download(url: &str) -> Result<Vec<u8>, String> {
+    let response = reqwest::blocking::Client::builder()
+        .timeout(DOWNLOAD_TIMEOUT)
+        .build()
+        .and_then(|client| client.get(url).send())
+        .map_err(|err| format!("{} cannot be downloaded: {}", url, err))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "{} cannot be downloaded: {}",
+            url,
+            response.status()
+        ));
+    }
+    response
+        .bytes()
+        .map(|bytes| bytes.to_vec())
+        .map_err(|err| format!("{} cannot be downloaded: {}", url, err))
+}
+
+fn user_cache_dir() -> PathBuf {
+    env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .unwrap_or_else(env::temp_dir)
+}
+
+/// Creates the directory (and its parents) if it's missing, readable by the user only. Fails if
+/// other users can write to it.
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> Result<(), String> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .map_err(|err| format!("cannot create {}: {}", dir.display(), err))?;
+    let mode = fs::metadata(dir)
+        .map_err(|err| format!("cannot read {}: {}", dir.display(), err))?
+        .permissions()
+        .mode();
+    if mode & 0o022 != 0 {
+        return Err(format!(
+            "cache directory {} is writable by other users (mode {:o})",
+            dir.display(),
+            mode & 0o777
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|err| format!("cannot create {}: {}", dir.display(), err))
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o700))
+        .map_err(|err| format!("cannot make {} executable: {}", path.display(), err))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+impl ToxiproxyServer {
+    /// Downloads the release (unless it's cached) and starts it with its API on
+    /// `127.0.0.1:port`, see [`Release::fetch`] and [`spawn`].
+    ///
+    /// [`Release::fetch`]: ../download/struct.Release.html#method.fetch
+    /// [`spawn`]: #method.spawn
+    pub fn download_and_spawn(release: &Release, port: u16) -> Result<Self, String> {
+        Self::spawn(release.fetch()?, port)
+    }
+}
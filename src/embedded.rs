@@ -0,0 +1,634 @@
+//! In-process Toxiproxy-compatible server, so simple resiliency tests run without any external
+//! binary (requires the `embedded` feature). The [`Client`] talks to it over localhost, exactly as
+//! to the real server.
+//!
+//! It covers the proxies API (create, populate, update, enable/disable, delete, reset) and the
+//! `latency`, `bandwidth` and `timeout` toxics; other toxic types are refused. Like in Toxiproxy,
+//! toxicity is decided once per connection.
+//!
+//! ```
+//! use toxiproxy_rust::{embedded::EmbeddedServer, proxy::ProxyPack};
+//!
+//! let server = EmbeddedServer::start().expect("server is started");
+//! let proxies = server
+//!     .client()
+//!     .populate(vec![ProxyPack::new(
+//!         "db".into(),
+//!         "127.0.0.1:0".into(),
+//!         "127.0.0.1:5432".into(),
+//!     )])
+//!     .unwrap();
+//!
+//! proxies[0].with_latency("downstream".into(), 100, 0, 1.0);
+//! ```
+//!
+//! [`Client`]: ../client/struct.Client.html
+
+use super::client::*;
+use super::proxy::*;
+use super::rng::*;
+use super::toxic::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Version reported by the embedded server.
+pub const EMBEDDED_VERSION: &str = "2.9.0-embedded";
+/// Toxic types the embedded server applies.
+pub const SUPPORTED_TOXICS: [&str; 3] = ["latency", "bandwidth", "timeout"];
+
+// How often idle connections check for new toxics.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+// Largest request body accepted by the API, far above any proxy list a test would populate.
+const MAX_BODY: usize = 1024 * 1024;
+
+type Proxies = Arc<Mutex<HashMap<String, EmbeddedProxy>>>;
+type Response = Result<(u16, Option<Value>), (u16, String)>;
+
+/// Toxiproxy-compatible server running on background threads, stopped (with its proxies) when
+/// dropped.
+pub struct EmbeddedServer {
+    api: Listener,
+    proxies: Proxies,
+    client: Client,
+}
+
+impl EmbeddedServer {
+    /// Starts the server with its API on a free port of localhost.
+    pub fn start() -> Result<Self, String> {
+        Self::bind("127.0.0.1:0")
+    }
+
+    /// Starts the server with its API on the address, eg. `127.0.0.1:8474` to stand in for a
+    /// server expected on the default address.
+    pub fn bind(addr: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(addr).map_err(|err| format!("bind error: {}", err))?;
+        let proxies = Proxies::default();
+
+        let api_proxies = proxies.clone();
+        let api = Listener::start(listener, move |stream| {
+            let proxies = api_proxies.clone();
+            thread::spawn(move || serve_api(&proxies, stream));
+        })?;
+        let client = Client::new(api.addr);
+
+        Ok(Self {
+            api,
+            proxies,
+            client,
+        })
+    }
+
+    /// Address of the API.
+    pub fn addr(&self) -> SocketAddr {
+        self.api.addr
+    }
+
+    /// Client of the server.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl Drop for EmbeddedServer {
+    fn drop(&mut self) {
+        for (_, mut proxy) in lock(&self.proxies).drain() {
+            proxy.disable();
+        }
+    }
+}
+
+lazy_static! {
+    static ref FALLBACK: Mutex<Option<EmbeddedServer>> = Mutex::new(None);
+}
+
+impl Client {
+    /// Returns this client if the server is available, or otherwise a client of an embedded server
+    /// started in the process (once, and shared by every fallback client) - so tests run locally
+    /// without Toxiproxy, with disabled proxies and latency, while CI uses the real server.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{client::Client, embedded::EMBEDDED_VERSION};
+    /// let client = Client::new("127.0.0.1:1").or_embedded().unwrap();
+    /// assert_eq!(Ok(EMBEDDED_VERSION.to_string()), client.health_check());
+    /// ```
+    pub fn or_embedded(&self) -> Result<Self, String> {
+        if self.health_check().is_ok() {
+            return Ok(self.clone());
+        }
+
+        let mut fallback = lock(&FALLBACK);
+        if let Some(server) = fallback.as_ref() {
+            return Ok(server.client().clone());
+        }
+        let server = EmbeddedServer::start()?;
+        let client = server.client().clone();
+        *fallback = Some(server);
+        Ok(client)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Accepting thread of a listening socket, stopped when dropped.
+struct Listener {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Listener {
+    fn start<F>(listener: TcpListener, serve: F) -> Result<Self, String>
+    where
+        F: Fn(TcpStream) + Send + 'static,
+    {
+        let addr = listener
+            .local_addr()
+            .map_err(|err| format!("bind error: {}", err))?;
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let thread_stopped = stopped.clone();
+        let handle = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    serve(stream);
+                }
+            }
+        });
+
+        Ok(Self {
+            addr,
+            stopped,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wakes up the accepting thread so it can notice it's stopped.
+        let mut addr = self.addr;
+        if addr.ip().is_unspecified() {
+            addr.set_ip(match addr.ip() {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            });
+        }
+        let _ = TcpStream::connect(addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// State of a proxy shared with its connections.
+#[derive(Default)]
+struct Link {
+    upstream: Mutex<String>,
+    toxics: Mutex<Vec<ToxicPack>>,
+    connections: Mutex<HashMap<u64, [TcpStream; 2]>>,
+    next_connection: AtomicU64,
+}
+
+impl Link {
+    fn forward(&self, client: TcpStream) {
+        let upstream = lock(&self.upstream).clone();
+        let upstream = match TcpStream::connect(&upstream) {
+            Ok(upstream) => upstream,
+            Err(_) => {
+                let _ = client.shutdown(Shutdown::Both);
+                return;
+            }
+        };
+        let clones = || Some([client.try_clone().ok()?, upstream.try_clone().ok()?]);
+        let ([client_read, upstream_read], kept) = match (clones(), clones()) {
+            (Some(read), Some(kept)) => (read, kept),
+            _ => return,
+        };
+
+        // Kept to close the connection when the proxy is disabled.
+        let id = self.next_connection.fetch_add(1, Ordering::SeqCst);
+        lock(&self.connections).insert(id, kept);
+
+        thread::scope(|scope| {
+            scope.spawn(|| self.pipe(client_read, upstream, "upstream"));
+            self.pipe(upstream_read, client, "downstream");
+        });
+        lock(&self.connections).remove(&id);
+    }
+
+    fn pipe(&self, mut from: TcpStream, mut to: TcpStream, stream: &str) {
+        let _ = from.set_read_timeout(Some(POLL_INTERVAL));
+        let mut rng = Rng::new(Rng::random_seed());
+        let mut applied = HashMap::new();
+        let mut timeout_since = None;
+        let mut buf = [0u8; 16 * 1024];
+
+        loop {
+            let toxics = lock(&self.toxics)
+                .iter()
+                .filter(|toxic| toxic.stream == stream)
+                .filter(|toxic| {
+                    *applied
+                        .entry(toxic.name.clone())
+                        .or_insert_with(|| rng.chance(f64::from(toxic.toxicity)))
+                })
+                .cloned()
+                .collect::<Vec<ToxicPack>>();
+            let attribute = |toxic_type: &str, name: &str| {
+                toxics
+                    .iter()
+                    .find(|toxic| toxic.r#type == toxic_type)
+                    .map(|toxic| u64::from(toxic.attributes.get(name).copied().unwrap_or(0)))
+            };
+
+            // The timeout toxic drops the data, and closes the connection once it has passed.
+            match attribute("timeout", "timeout") {
+                Some(timeout) => {
+                    let since = *timeout_since.get_or_insert_with(Instant::now);
+                    if timeout > 0 && since.elapsed() >= Duration::from_millis(timeout) {
+                        let _ = from.shutdown(Shutdown::Both);
+                        let _ = to.shutdown(Shutdown::Both);
+                        return;
+                    }
+                }
+                None => timeout_since = None,
+            }
+
+            let read = match from.read(&mut buf) {
+                Ok(0) => {
+                    let _ = to.shutdown(Shutdown::Write);
+                    return;
+                }
+                Ok(read) => read,
+                Err(ref err)
+                    if matches!(
+                        err.kind(),
+                        ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted
+                    ) =>
+                {
+                    continue
+                }
+                Err(_) => {
+                    let _ = to.shutdown(Shutdown::Both);
+                    return;
+                }
+            };
+            if timeout_since.is_some() {
+                continue;
+            }
+
+            if let Some(latency) = attribute("latency", "latency") {
+                let jitter = attribute("latency", "jitter").unwrap_or(0).min(latency);
+                let delay = latency + rng.next_u64() % (2 * jitter + 1) - jitter;
+                thread::sleep(Duration::from_millis(delay));
+            }
+            // Rate in KB/s.
+            if let Some(rate) = attribute("bandwidth", "rate").filter(|rate| *rate > 0) {
+                thread::sleep(Duration::from_micros(read as u64 * 1000 / rate));
+            }
+
+            if to.write_all(&buf[..read]).is_err() {
+                let _ = from.shutdown(Shutdown::Both);
+                return;
+            }
+        }
+    }
+
+    fn close_connections(&self) {
+        for (_, streams) in lock(&self.connections).drain() {
+            for stream in streams.iter() {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+}
+
+struct EmbeddedProxy {
+    name: String,
+    listen: String,
+    link: Arc<Link>,
+    listener: Option<Listener>,
+}
+
+impl EmbeddedProxy {
+    fn new(pack: &ProxyPack) -> Result<Self, String> {
+        let mut proxy = Self {
+            name: pack.name.clone(),
+            listen: pack.listen.clone(),
+            link: Arc::new(Link::default()),
+            listener: None,
+        };
+        *lock(&proxy.link.upstream) = pack.upstream.clone();
+        if pack.enabled {
+            proxy.enable()?;
+        }
+        Ok(proxy)
+    }
+
+    fn enable(&mut self) -> Result<(), String> {
+        if self.listener.is_some() {
+            return Ok(());
+        }
+
+        let listener = TcpListener::bind(&self.listen)
+            .map_err(|err| format!("listen tcp {}: {}", self.listen, err))?;
+        let link = self.link.clone();
+        let listener = Listener::start(listener, move |client| {
+            let link = link.clone();
+            thread::spawn(move || link.forward(client));
+        })?;
+
+        // A free port (`:0`) is replaced by the one bound, as Toxiproxy does.
+        let host = self
+            .listen
+            .rsplit_once(':')
+            .map_or("", |(host, _)| host)
+            .to_owned();
+        self.listen = format!("{}:{}", host, listener.addr.port());
+        self.listener = Some(listener);
+        Ok(())
+    }
+
+    fn disable(&mut self) {
+        self.listener = None;
+        self.link.close_connections();
+    }
+
+    fn pack(&self) -> ProxyPack {
+        let mut pack = ProxyPack::new(
+            self.name.clone(),
+            self.listen.clone(),
+            lock(&self.link.upstream).clone(),
+        );
+        pack.enabled = self.listener.is_some();
+        pack.toxics = lock(&self.link.toxics).clone();
+        pack
+    }
+}
+
+fn serve_api(proxies: &Mutex<HashMap<String, EmbeddedProxy>>, mut stream: TcpStream) {
+    let response = read_request(&stream)
+        .and_then(|(method, path, body)| route(&mut lock(proxies), &method, &path, &body));
+    let (status, body) = match response {
+        Ok((status, body)) => (status, body.map(|body| body.to_string())),
+        Err((status, err)) => (
+            status,
+            Some(json!({ "error": err, "status": status }).to_string()),
+        ),
+    };
+    let body = body.unwrap_or_default();
+
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    );
+    let _ = stream.flush();
+}
+
+/// Method, path and body of a request, or the status and message to answer a bad one with.
+fn read_request(stream: &TcpStream) -> Result<(String, String, Vec<u8>), (u16, String)> {
+    let unreadable = |err: std::io::Error| (400, format!("request cannot be read: {}", err));
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(unreadable)?;
+    let mut request_line = line.split_whitespace();
+    let (method, path) = match (request_line.next(), request_line.next()) {
+        (Some(method), Some(path)) => (method.to_owned(), path.to_owned()),
+        _ => return Err((400, format!("invalid request line: {:?}", line.trim()))),
+    };
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        reader.read_line(&mut line).map_err(unreadable)?;
+        let header = line.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|err| (400, format!("invalid content length: {}", err)))?;
+            }
+        }
+    }
+
+    if content_length > MAX_BODY {
+        return Err((
+            413,
+            format!(
+                "request body of {} bytes is over {}",
+                content_length, MAX_BODY
+            ),
+        ));
+    }
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).map_err(unreadable)?;
+    Ok((method, path, body))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+fn parse<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, (u16, String)> {
+    serde_json::from_slice(body).map_err(|err| (400, format!("bad request body: {}", err)))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Option<Value> {
+    serde_json::to_value(value).ok()
+}
+
+fn route(
+    proxies: &mut HashMap<String, EmbeddedProxy>,
+    method: &str,
+    path: &str,
+    body: &[u8],
+) -> Response {
+    let path = path.split('?').next().unwrap_or_default();
+    let segments = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<&str>>();
+
+    match (method, segments.as_slice()) {
+        ("GET", ["version"]) => Ok((200, Some(json!({ "version": EMBEDDED_VERSION })))),
+        ("POST", ["reset"]) => {
+            for proxy in proxies.values_mut() {
+                lock(&proxy.link.toxics).clear();
+                proxy.enable().map_err(|err| (500, err))?;
+            }
+            Ok((204, None))
+        }
+        ("GET", ["proxies"]) => {
+            let packs = proxies
+                .iter()
+                .map(|(name, proxy)| (name.clone(), proxy.pack()))
+                .collect::<HashMap<String, ProxyPack>>();
+            Ok((200, to_json(&packs)))
+        }
+        ("POST", ["proxies"]) => {
+            let pack = parse::<ProxyPack>(body)?;
+            if proxies.contains_key(&pack.name) {
+                return Err((409, "proxy already exists".into()));
+            }
+            let proxy = EmbeddedProxy::new(&pack).map_err(|err| (500, err))?;
+            let pack = proxy.pack();
+            proxies.insert(pack.name.clone(), proxy);
+            Ok((201, to_json(&pack)))
+        }
+        ("POST", ["populate"]) => {
+            let mut populated = vec![];
+            for pack in parse::<Vec<ProxyPack>>(body)? {
+                let changed = proxies.get(&pack.name).map(|proxy| {
+                    *lock(&proxy.link.upstream) != pack.upstream
+                        || !(pack.listen == proxy.listen || pack.listen.ends_with(":0"))
+                });
+                if changed == Some(true) {
+                    if let Some(mut proxy) = proxies.remove(&pack.name) {
+                        proxy.disable();
+                    }
+                }
+                if changed != Some(false) {
+                    let proxy = EmbeddedProxy::new(&pack).map_err(|err| (500, err))?;
+                    proxies.insert(pack.name.clone(), proxy);
+                }
+                populated.push(proxies[&pack.name].pack());
+            }
+            Ok((201, Some(json!({ "proxies": populated }))))
+        }
+        (_, ["proxies", name, rest @ ..]) => {
+            let proxy = proxies
+                .get_mut(*name)
+                .ok_or_else(|| (404, "proxy not found".to_string()))?;
+            match (method, rest) {
+                ("GET", []) => Ok((200, to_json(&proxy.pack()))),
+                ("POST" | "PATCH", []) => {
+                    update_proxy(proxy, &parse::<Value>(body)?)?;
+                    Ok((200, to_json(&proxy.pack())))
+                }
+                ("DELETE", []) => {
+                    if let Some(mut proxy) = proxies.remove(*name) {
+                        proxy.disable();
+                    }
+                    Ok((204, None))
+                }
+                (_, ["toxics", toxic @ ..]) => route_toxics(proxy, method, toxic, body),
+                _ => Err((404, "not found".into())),
+            }
+        }
+        _ => Err((404, "not found".into())),
+    }
+}
+
+fn update_proxy(proxy: &mut EmbeddedProxy, update: &Value) -> Result<(), (u16, String)> {
+    if let Some(upstream) = update.get("upstream").and_then(Value::as_str) {
+        *lock(&proxy.link.upstream) = upstream.into();
+    }
+    let was_enabled = proxy.listener.is_some();
+    if let Some(listen) = update.get("listen").and_then(Value::as_str) {
+        if listen != proxy.listen {
+            proxy.disable();
+            proxy.listen = listen.into();
+        }
+    }
+
+    let enabled = update
+        .get("enabled")
+        .and_then(Value::as_bool)
+        .unwrap_or(was_enabled);
+    if enabled {
+        proxy.enable().map_err(|err| (500, err))
+    } else {
+        proxy.disable();
+        Ok(())
+    }
+}
+
+fn route_toxics(proxy: &EmbeddedProxy, method: &str, toxic: &[&str], body: &[u8]) -> Response {
+    let mut toxics = lock(&proxy.link.toxics);
+    match (method, toxic) {
+        ("GET", []) => Ok((200, to_json(&*toxics))),
+        ("POST", []) => {
+            let mut toxic = parse::<ToxicPack>(body)?;
+            if !SUPPORTED_TOXICS.contains(&toxic.r#type.as_str()) {
+                return Err((
+                    400,
+                    format!(
+                        "toxic type {:?} is not supported by the embedded server, only {}",
+                        toxic.r#type,
+                        SUPPORTED_TOXICS.join(", ")
+                    ),
+                ));
+            }
+            if toxic.name.is_empty() {
+                toxic.name = format!("{}_{}", toxic.r#type, toxic.stream);
+            }
+            if toxics.iter().any(|existing| existing.name == toxic.name) {
+                return Err((409, "toxic already exists".into()));
+            }
+            toxics.push(toxic.clone());
+            Ok((200, to_json(&toxic)))
+        }
+        (_, [name]) => {
+            let index = toxics
+                .iter()
+                .position(|toxic| toxic.name == *name)
+                .ok_or_else(|| (404, "toxic not found".to_string()))?;
+            match method {
+                "GET" => Ok((200, to_json(&toxics[index]))),
+                "POST" | "PATCH" => {
+                    let update = parse::<Value>(body)?;
+                    let toxic = &mut toxics[index];
+                    if let Some(toxicity) = update.get("toxicity").and_then(Value::as_f64) {
+                        toxic.toxicity = toxicity as f32;
+                    }
+                    if let Some(attributes) = update.get("attributes") {
+                        let attributes = serde_json::from_value::<HashMap<String, ToxicValueType>>(
+                            attributes.clone(),
+                        )
+                        .map_err(|err| (400, format!("bad request body: {}", err)))?;
+                        toxic.attributes.extend(attributes);
+                    }
+                    Ok((200, to_json(&*toxic)))
+                }
+                "DELETE" => {
+                    toxics.remove(index);
+                    Ok((204, None))
+                }
+                _ => Err((404, "not found".into())),
+            }
+        }
+        _ => Err((404, "not found".into())),
+    }
+}
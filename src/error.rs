@@ -0,0 +1,195 @@
+//! Structured context of failed requests to the server. The crate's operations return errors as
+//! strings; the request behind the latest failure of the current thread is kept as an
+//! [`ApiError`], with its method, URL, the proxy and toxic involved, and the payload - so a
+//! harness can categorize the failure (eg. to retry it) without parsing the message. It is
+//! cleared by the next request of the thread, except the requests cleaning up after an operation
+//! (eg. deleting the toxics of [`Proxy::apply`], or dropping a guard), and carried over from the
+//! background threads of the crate (eg. of a [`ChaosHandle`]) when joining them.
+//!
+//! ```
+//! # use toxiproxy_rust::{client::Client, error::{ApiError, ApiErrorKind}};
+//! let client = Client::from_env();
+//! client.set_address("127.0.0.1:1").unwrap();
+//!
+//! assert!(client.find_proxy("api_error_doc").is_err());
+//! let err = ApiError::last().expect("request failed");
+//! assert_eq!(ApiErrorKind::Connect, err.kind);
+//! assert_eq!(Some("api_error_doc".into()), err.proxy);
+//! assert!(err.is_retryable());
+//! ```
+//!
+//! [`ChaosHandle`]: ../chaos/struct.ChaosHandle.html
+//! [`Proxy::apply`]: ../proxy/struct.Proxy.html#method.apply
+
+use std::cell::RefCell;
+use std::fmt;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<ApiError>> = const { RefCell::new(None) };
+}
+
+/// Category of a failed request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiErrorKind {
+    /// The server couldn't be connected to.
+    Connect,
+    /// The server didn't answer in time.
+    Timeout,
+    /// The server answered with an error status.
+    Status,
+    /// The answer of the server couldn't be read (eg. unexpected JSON).
+    Decode,
+    /// Anything else (eg. an invalid address).
+    Other,
+}
+
+/// A failed request to the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiError {
+    pub kind: ApiErrorKind,
+    pub method: String,
+    pub url: String,
+    /// Proxy named in the path, if any.
+    pub proxy: Option<String>,
+    /// Toxic named in the path, if any.
+    pub toxic: Option<String>,
+    /// Body of the request, see [`redacted`].
+    ///
+    /// [`redacted`]: #method.redacted
+    pub payload: Option<String>,
+    /// Status of the answer, for [`ApiErrorKind::Status`] errors.
+    ///
+    /// [`ApiErrorKind::Status`]: enum.ApiErrorKind.html#variant.Status
+    pub status: Option<u16>,
+    pub correlation_id: Option<String>,
+    pub message: String,
+}
+
+impl ApiError {
+    pub(crate) fn new(kind: ApiErrorKind, method: &str, url: String, path: &str) -> Self {
+        let segments = path.split('/').collect::<Vec<&str>>();
+        let named = |collection: &str| {
+            segments
+                .windows(2)
+                .find(|pair| pair[0] == collection)
+                .map(|pair| pair[1].to_owned())
+        };
+
+        Self {
+            kind,
+            method: method.into(),
+            url,
+            proxy: named("proxies"),
+            toxic: named("toxics"),
+            payload: None,
+            status: None,
+            correlation_id: None,
+            message: String::new(),
+        }
+    }
+
+    /// Latest failed request sent by the current thread, if any.
+    pub fn last() -> Option<ApiError> {
+        LAST_ERROR.with(|last| last.borrow().clone())
+    }
+
+    pub(crate) fn record(self) {
+        LAST_ERROR.with(|last| *last.borrow_mut() = Some(self));
+    }
+
+    pub(crate) fn clear() {
+        LAST_ERROR.with(|last| *last.borrow_mut() = None);
+    }
+
+    /// Runs a cleanup (eg. deleting the toxics of [`Proxy::apply`]), keeping the failed request
+    /// recorded before it: the failure being cleaned up after is the one to report, not the
+    /// requests of the cleanup.
+    ///
+    /// [`Proxy::apply`]: ../proxy/struct.Proxy.html#method.apply
+    pub(crate) fn keep<T>(cleanup: impl FnOnce() -> T) -> T {
+        let failure = Self::last();
+        let value = cleanup();
+        if let Some(failure) = failure {
+            failure.record();
+        }
+        value
+    }
+
+    /// Runs an operation of a background thread, keeping the failed request behind its failure
+    /// for the thread joining it, see [`resume`].
+    ///
+    /// [`resume`]: #method.resume
+    pub(crate) fn capture<T>(
+        operation: impl FnOnce() -> Result<T, String>,
+    ) -> Result<T, ThreadFailure> {
+        operation().map_err(|message| ThreadFailure {
+            message,
+            api_error: Self::last().map(Box::new),
+        })
+    }
+
+    /// Result of an operation joined from a background thread, recording its failed request on
+    /// the current thread.
+    pub(crate) fn resume<T>(result: Result<T, ThreadFailure>) -> Result<T, String> {
+        result.map_err(|failure| {
+            match failure.api_error {
+                Some(api_error) => api_error.record(),
+                None => Self::clear(),
+            }
+            failure.message
+        })
+    }
+
+    /// The error without its payload, eg. to report it somewhere secrets shouldn't go.
+    pub fn redacted(mut self) -> Self {
+        self.payload = None;
+        self
+    }
+
+    /// Whether sending the request again may succeed: the server was unreachable, slow, or failed
+    /// with a server error (5xx).
+    pub fn is_retryable(&self) -> bool {
+        match self.kind {
+            ApiErrorKind::Connect | ApiErrorKind::Timeout => true,
+            ApiErrorKind::Status => matches!(self.status, Some(status) if status >= 500),
+            ApiErrorKind::Decode | ApiErrorKind::Other => false,
+        }
+    }
+}
+
+/// Failure of an operation run on a background thread, see [`ApiError::capture`].
+///
+/// [`ApiError::capture`]: struct.ApiError.html#method.capture
+#[derive(Debug)]
+pub(crate) struct ThreadFailure {
+    message: String,
+    api_error: Option<Box<ApiError>>,
+}
+
+impl From<&str> for ThreadFailure {
+    fn from(message: &str) -> Self {
+        Self {
+            message: message.into(),
+            api_error: None,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    /// `POST http://127.0.0.1:8474/proxies/db/toxics failed (proxy db): 409 Conflict`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} failed", self.method, self.url)?;
+        match (self.proxy.as_ref(), self.toxic.as_ref()) {
+            (Some(proxy), Some(toxic)) => write!(f, " (proxy {}, toxic {})", proxy, toxic)?,
+            (Some(proxy), None) => write!(f, " (proxy {})", proxy)?,
+            _ => {}
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl From<ApiError> for String {
+    fn from(err: ApiError) -> String {
+        err.to_string()
+    }
+}
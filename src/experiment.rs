@@ -0,0 +1,534 @@
+//! Experiments running code repeatedly under toxics and aggregating the results, eg. to check how
+//! often a call survives a flaky connection.
+
+use super::proxy::*;
+use super::toxic::*;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// `z` value of a 95% confidence interval, see [`SoakReport::failure_rate_interval`].
+pub const Z_95: f64 = 1.959964;
+
+/// How long a soak test runs, see [`Proxy::soak`].
+#[derive(Debug, Clone)]
+pub struct SoakConfig {
+    iterations: Option<usize>,
+    duration: Option<Duration>,
+    pause: Duration,
+}
+
+impl SoakConfig {
+    /// Runs the closure the given number of times.
+    pub fn iterations(iterations: usize) -> Self {
+        Self {
+            iterations: Some(iterations),
+            duration: None,
+            pause: Duration::from_secs(0),
+        }
+    }
+
+    /// Runs the closure again and again until the given duration has passed.
+    pub fn duration(duration: Duration) -> Self {
+        Self {
+            iterations: None,
+            duration: Some(duration),
+            pause: Duration::from_secs(0),
+        }
+    }
+
+    /// Pause between two runs, none by default.
+    pub fn pause(mut self, pause: Duration) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    fn is_done(&self, runs: usize, started: Instant) -> bool {
+        self.iterations.is_some_and(|iterations| runs >= iterations)
+            || self
+                .duration
+                .is_some_and(|duration| started.elapsed() >= duration)
+    }
+}
+
+/// Toxic values a sweep steps through, from `from` to `to` (both included).
+#[derive(Debug, Clone)]
+pub struct Sweep {
+    from: ToxicValueType,
+    to: ToxicValueType,
+    step: ToxicValueType,
+    stop_on_failure: bool,
+}
+
+impl Sweep {
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::experiment::Sweep;
+    /// assert!(Sweep::new(0, 5000, 250).is_ok());
+    /// assert!(Sweep::new(0, 5000, 0).is_err());
+    /// ```
+    pub fn new(
+        from: ToxicValueType,
+        to: ToxicValueType,
+        step: ToxicValueType,
+    ) -> Result<Self, String> {
+        if step == 0 {
+            return Err("sweep step cannot be 0".into());
+        }
+        if from > to {
+            return Err(format!("empty sweep range: {} - {}", from, to));
+        }
+
+        Ok(Self {
+            from,
+            to,
+            step,
+            stop_on_failure: false,
+        })
+    }
+
+    /// Stops the sweep after the first step with a failed run, instead of going through the whole
+    /// range.
+    pub fn stop_on_failure(mut self) -> Self {
+        self.stop_on_failure = true;
+        self
+    }
+
+    fn values(&self) -> Vec<ToxicValueType> {
+        (self.from..=self.to).step_by(self.step as usize).collect()
+    }
+}
+
+/// Results of one step of a sweep.
+#[derive(Debug, Clone)]
+pub struct SweepStep<V> {
+    pub value: V,
+    pub report: SoakReport,
+}
+
+/// Results of a sweep, one step per value.
+#[derive(Debug, Clone)]
+pub struct SweepReport<V> {
+    pub steps: Vec<SweepStep<V>>,
+}
+
+impl<V: Copy> SweepReport<V> {
+    /// First value at which any run failed.
+    pub fn breaking_point(&self) -> Option<V> {
+        self.breaking_point_at(0.0)
+    }
+
+    /// First value at which the failure rate went above the given one.
+    pub fn breaking_point_at(&self, failure_rate: f64) -> Option<V> {
+        self.steps
+            .iter()
+            .find(|step| step.report.failure_rate() > failure_rate)
+            .map(|step| step.value)
+    }
+}
+
+/// Result of a single run of a soak test.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    pub elapsed: Duration,
+    /// The error returned by the closure, if it failed.
+    pub error: Option<String>,
+    /// Bytes transferred, as reported by the closure of a bandwidth sweep (0 otherwise).
+    pub bytes: usize,
+}
+
+/// Results of a soak test, see [`Proxy::soak`].
+#[derive(Debug, Clone, Default)]
+pub struct SoakReport {
+    pub runs: Vec<Run>,
+}
+
+impl SoakReport {
+    pub fn successes(&self) -> usize {
+        self.runs.iter().filter(|run| run.error.is_none()).count()
+    }
+
+    pub fn failures(&self) -> usize {
+        self.runs.len() - self.successes()
+    }
+
+    /// Ratio of failed runs, between 0 and 1 (0 if nothing ran).
+    pub fn failure_rate(&self) -> f64 {
+        if self.runs.is_empty() {
+            0.0
+        } else {
+            self.failures() as f64 / self.runs.len() as f64
+        }
+    }
+
+    /// Confidence interval of the failure rate ([Wilson score interval]), eg. with [`Z_95`] the
+    /// range the real failure rate is in with 95% confidence. The more runs, the narrower.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use toxiproxy_rust::experiment::*;
+    /// let report = SoakReport {
+    ///     runs: (0..100)
+    ///         .map(|i| Run {
+    ///             elapsed: Duration::from_millis(1),
+    ///             error: if i % 4 == 0 { Some("failed".into()) } else { None },
+    ///             bytes: 0,
+    ///         })
+    ///         .collect(),
+    /// };
+    ///
+    /// let (low, high) = report.failure_rate_interval(Z_95);
+    /// assert!(low > 0.17 && high < 0.35);
+    /// ```
+    ///
+    /// [Wilson score interval]: https://en.wikipedia.org/wiki/Wilson_score_interval
+    pub fn failure_rate_interval(&self, z: f64) -> (f64, f64) {
+        if self.runs.is_empty() {
+            return (0.0, 1.0);
+        }
+
+        let n = self.runs.len() as f64;
+        let p = self.failure_rate();
+        let z2 = z * z;
+        let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let margin = z / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+
+        ((center - margin).max(0.0), (center + margin).min(1.0))
+    }
+
+    /// Errors of the failed runs.
+    pub fn errors(&self) -> Vec<&str> {
+        self.runs
+            .iter()
+            .filter_map(|run| run.error.as_deref())
+            .collect()
+    }
+
+    /// Mean duration of the runs.
+    pub fn mean_latency(&self) -> Option<Duration> {
+        if self.runs.is_empty() {
+            return None;
+        }
+        let total = self.runs.iter().map(|run| run.elapsed).sum::<Duration>();
+        Some(total / self.runs.len() as u32)
+    }
+
+    /// Duration under which the given percentage (0 - 100) of the runs finished, eg. `50.0` for the
+    /// median.
+    pub fn latency_percentile(&self, percentile: f64) -> Option<Duration> {
+        let mut latencies = self
+            .runs
+            .iter()
+            .map(|run| run.elapsed)
+            .collect::<Vec<Duration>>();
+        latencies.sort();
+
+        let rank = (percentile.clamp(0.0, 100.0) / 100.0 * latencies.len() as f64).ceil() as usize;
+        latencies.get(rank.max(1) - 1).cloned()
+    }
+
+    pub fn max_latency(&self) -> Option<Duration> {
+        self.runs.iter().map(|run| run.elapsed).max()
+    }
+
+    /// Bytes per second transferred by the successful runs.
+    pub fn throughput(&self) -> Option<f64> {
+        let (bytes, elapsed) = self
+            .runs
+            .iter()
+            .filter(|run| run.error.is_none())
+            .fold((0, Duration::from_secs(0)), |(bytes, elapsed), run| {
+                (bytes + run.bytes, elapsed + run.elapsed)
+            });
+
+        if elapsed.as_secs_f64() > 0.0 {
+            Some(bytes as f64 / elapsed.as_secs_f64())
+        } else {
+            None
+        }
+    }
+}
+
+impl Proxy {
+    /// Runs the closure repeatedly under the toxics registered on the proxy, recording the outcome
+    /// and duration of every run. Toxics are deleted afterwards, even if the closure panics (the
+    /// panic is resumed afterwards).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new(
+    /// #    "socket".into(),
+    /// #    "localhost:2001".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// use toxiproxy_rust::experiment::SoakConfig;
+    ///
+    /// let report = toxiproxy_rust::TOXIPROXY
+    ///   .find_proxy("socket")
+    ///   .unwrap()
+    ///   .with_latency("downstream".into(), 10, 0, 0.5)
+    ///   .soak(&SoakConfig::iterations(20), || {
+    ///     /* Example test:
+    ///        MyService::Server::call(params)
+    ///     */
+    ///     # Ok::<(), String>(())
+    ///   })
+    ///   .unwrap();
+    ///
+    /// assert!(report.failure_rate() < 0.1);
+    /// ```
+    pub fn soak<F, T, E>(&self, config: &SoakConfig, closure: F) -> Result<SoakReport, String>
+    where
+        F: FnMut() -> Result<T, E>,
+        E: ToString,
+    {
+        self.soak_measured(config, closure, |_| 0)
+    }
+
+    fn soak_measured<F, M, T, E>(
+        &self,
+        config: &SoakConfig,
+        mut closure: F,
+        measure: M,
+    ) -> Result<SoakReport, String>
+    where
+        F: FnMut() -> Result<T, E>,
+        M: Fn(&T) -> usize,
+        E: ToString,
+    {
+        self.apply(|| {
+            let mut report = SoakReport::default();
+            let started = Instant::now();
+
+            while !config.is_done(report.runs.len(), started) {
+                if !report.runs.is_empty() {
+                    thread::sleep(config.pause);
+                }
+
+                let run_started = Instant::now();
+                let result = closure();
+                let elapsed = run_started.elapsed();
+                report.runs.push(match result {
+                    Ok(value) => Run {
+                        elapsed,
+                        error: None,
+                        bytes: measure(&value),
+                    },
+                    Err(err) => Run {
+                        elapsed,
+                        error: Some(err.to_string()),
+                        bytes: 0,
+                    },
+                });
+            }
+
+            report
+        })
+    }
+
+    /// Runs a soak test (see [`Proxy::soak`]) for every latency (in milliseconds) of the sweep, to
+    /// find the latency at which the code under test starts failing. Toxics are deleted after each
+    /// step.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new_ephemeral(
+    /// #    "latency_sweep_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// use toxiproxy_rust::experiment::{SoakConfig, Sweep};
+    ///
+    /// let proxy = toxiproxy_rust::TOXIPROXY.find_proxy("latency_sweep_doc").unwrap();
+    /// let report = proxy
+    ///   .sweep_latency(
+    ///     "downstream".into(),
+    ///     &Sweep::new(0, 1000, 250).unwrap().stop_on_failure(),
+    ///     &SoakConfig::iterations(3),
+    ///     || {
+    ///       /* Example test:
+    ///          MyService::Server::call_with_timeout(params, Duration::from_millis(500))
+    ///       */
+    ///       # Ok::<(), String>(())
+    ///     },
+    ///   )
+    ///   .unwrap();
+    ///
+    /// if let Some(latency) = report.breaking_point() {
+    ///   println!("fails from {}ms latency", latency);
+    /// }
+    /// # proxy.delete().unwrap();
+    /// ```
+    pub fn sweep_latency<F, T, E>(
+        &self,
+        stream: String,
+        sweep: &Sweep,
+        config: &SoakConfig,
+        closure: F,
+    ) -> Result<SweepReport<ToxicValueType>, String>
+    where
+        F: FnMut() -> Result<T, E>,
+        E: ToString,
+    {
+        self.sweep(
+            sweep.values(),
+            sweep.stop_on_failure,
+            |proxy, latency| {
+                proxy.with_latency(stream.clone(), latency, 0, 1.0);
+            },
+            config,
+            closure,
+            |_| 0,
+        )
+    }
+
+    /// Runs a soak test (see [`Proxy::soak`]) for every bandwidth limit (in KB/s) of the sweep. The
+    /// closure returns the number of bytes it transferred, so the throughput of every step is
+    /// reported (see [`SoakReport::throughput`]). Toxics are deleted after each step.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new_ephemeral(
+    /// #    "bandwidth_sweep_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// use toxiproxy_rust::experiment::{SoakConfig, Sweep};
+    ///
+    /// let proxy = toxiproxy_rust::TOXIPROXY.find_proxy("bandwidth_sweep_doc").unwrap();
+    /// let report = proxy
+    ///   .sweep_bandwidth(
+    ///     "downstream".into(),
+    ///     &Sweep::new(32, 1024, 32).unwrap(),
+    ///     &SoakConfig::iterations(3),
+    ///     || {
+    ///       /* Example test:
+    ///          MyService::Server::download(file).map(|content| content.len())
+    ///       */
+    ///       # Ok::<usize, String>(1024)
+    ///     },
+    ///   )
+    ///   .unwrap();
+    ///
+    /// for step in report.steps {
+    ///   println!("{} KB/s: {:?} B/s", step.value, step.report.throughput());
+    /// }
+    /// # proxy.delete().unwrap();
+    /// ```
+    pub fn sweep_bandwidth<F, E>(
+        &self,
+        stream: String,
+        sweep: &Sweep,
+        config: &SoakConfig,
+        closure: F,
+    ) -> Result<SweepReport<ToxicValueType>, String>
+    where
+        F: FnMut() -> Result<usize, E>,
+        E: ToString,
+    {
+        self.sweep(
+            sweep.values(),
+            sweep.stop_on_failure,
+            |proxy, rate| {
+                proxy.with_bandwidth(stream.clone(), rate, 1.0);
+            },
+            config,
+            closure,
+            |bytes| *bytes,
+        )
+    }
+
+    /// Runs a soak test (see [`Proxy::soak`]) for every toxicity, `set_toxic` registering the toxic
+    /// under test with the given toxicity. Combined with [`SoakReport::failure_rate_interval`], it
+    /// validates probabilistic failure handling (eg. retries) quantitatively. Toxics are deleted
+    /// after each step.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new_ephemeral(
+    /// #    "toxicity_sweep_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// use toxiproxy_rust::experiment::{SoakConfig, Z_95};
+    ///
+    /// let proxy = toxiproxy_rust::TOXIPROXY.find_proxy("toxicity_sweep_doc").unwrap();
+    /// let report = proxy
+    ///   .sweep_toxicity(
+    ///     &[0.1, 0.3, 0.5],
+    ///     |proxy, toxicity| {
+    ///       proxy.with_timeout("downstream".into(), 100, toxicity);
+    ///     },
+    ///     &SoakConfig::iterations(50),
+    ///     || {
+    ///       /* Example test:
+    ///          MyService::Server::call_with_retries(params)
+    ///       */
+    ///       # Ok::<(), String>(())
+    ///     },
+    ///   )
+    ///   .unwrap();
+    ///
+    /// for step in report.steps {
+    ///   let (_, high) = step.report.failure_rate_interval(Z_95);
+    ///   assert!(high < 0.1, "too many failures at toxicity {}", step.value);
+    /// }
+    /// # proxy.delete().unwrap();
+    /// ```
+    pub fn sweep_toxicity<S, F, T, E>(
+        &self,
+        toxicities: &[f32],
+        set_toxic: S,
+        config: &SoakConfig,
+        closure: F,
+    ) -> Result<SweepReport<f32>, String>
+    where
+        S: Fn(&Self, f32),
+        F: FnMut() -> Result<T, E>,
+        E: ToString,
+    {
+        self.sweep(
+            toxicities.to_vec(),
+            false,
+            set_toxic,
+            config,
+            closure,
+            |_| 0,
+        )
+    }
+
+    fn sweep<V, S, F, M, T, E>(
+        &self,
+        values: Vec<V>,
+        stop_on_failure: bool,
+        set_toxic: S,
+        config: &SoakConfig,
+        mut closure: F,
+        measure: M,
+    ) -> Result<SweepReport<V>, String>
+    where
+        V: Copy,
+        S: Fn(&Self, V),
+        F: FnMut() -> Result<T, E>,
+        M: Fn(&T) -> usize,
+        E: ToString,
+    {
+        let mut steps = vec![];
+
+        for value in values {
+            set_toxic(self, value);
+            let report = self.soak_measured(config, &mut closure, &measure)?;
+            let failed = report.failures() > 0;
+            steps.push(SweepStep { value, report });
+
+            if failed && stop_on_failure {
+                break;
+            }
+        }
+
+        Ok(SweepReport { steps })
+    }
+}
@@ -0,0 +1,104 @@
+//! Proxies for multi-service environments without bespoke fixture code: a [`ProxyFactory`] mints a
+//! proxy per upstream, on ports of a fixed range.
+
+use super::address::*;
+use super::proxy::*;
+use std::ops::Range;
+
+/// Mints proxies listening on a host, on ports allocated from a range. Proxies are named after
+/// their upstream (eg. `db_5432` for `db:5432`).
+#[derive(Debug, Clone)]
+pub struct ProxyFactory {
+    host: String,
+    ports: Range<u16>,
+    allocated: Vec<(String, u16)>,
+}
+
+impl ProxyFactory {
+    /// Creates a factory for proxies listening on `host`, on the ports of the range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::factory::ProxyFactory;
+    /// let mut factory = ProxyFactory::new("127.0.0.1", 21000..21100).expect("factory is valid");
+    /// let proxy_packs = factory.mint_all(&["db:5432", "cache:6379"]).unwrap();
+    ///
+    /// assert_eq!("db_5432", proxy_packs[0].name);
+    /// assert_eq!("127.0.0.1:21001", proxy_packs[1].listen);
+    /// ```
+    pub fn new(host: &str, ports: Range<u16>) -> Result<Self, String> {
+        if ports.start == 0 || ports.is_empty() {
+            return Err(format!("invalid port range: {:?}", ports));
+        }
+        Listen::new(host, ports.start)?;
+
+        Ok(Self {
+            host: host.into(),
+            ports,
+            allocated: vec![],
+        })
+    }
+
+    /// Proxy for the upstream, on the first free port of the range. Fails if the upstream already
+    /// has a proxy, or the range is exhausted.
+    pub fn mint(&mut self, upstream: &str) -> Result<ProxyPack, String> {
+        let upstream: Upstream = upstream.parse()?;
+        let name = upstream.proxy_name();
+        if self.port_of(&name).is_some() {
+            return Err(format!("proxy {:?} is already minted", name));
+        }
+
+        let port = self
+            .ports
+            .clone()
+            .find(|port| {
+                !self
+                    .allocated
+                    .iter()
+                    .any(|(_, allocated)| allocated == port)
+            })
+            .ok_or_else(|| format!("no free port left in {:?}", self.ports))?;
+        let listen = Listen::new(&self.host, port)?;
+
+        self.allocated.push((name.clone(), port));
+        Ok(ProxyPack::from_addrs(name, listen, upstream))
+    }
+
+    /// Proxies for each of the upstreams, see [`mint`]. Nothing is allocated if any fails.
+    ///
+    /// [`mint`]: #method.mint
+    pub fn mint_all(&mut self, upstreams: &[&str]) -> Result<Vec<ProxyPack>, String> {
+        let allocated = self.allocated.len();
+        let result = upstreams
+            .iter()
+            .map(|upstream| self.mint(upstream))
+            .collect::<Result<Vec<ProxyPack>, String>>();
+        if result.is_err() {
+            self.allocated.truncate(allocated);
+        }
+        result
+    }
+
+    /// Frees the port of a minted proxy, eg. after the proxy is deleted.
+    pub fn release(&mut self, name: &str) -> Option<u16> {
+        let index = self
+            .allocated
+            .iter()
+            .position(|(allocated, _)| allocated == name)?;
+        Some(self.allocated.remove(index).1)
+    }
+
+    /// Port allocated to a minted proxy.
+    pub fn port_of(&self, name: &str) -> Option<u16> {
+        self.allocated
+            .iter()
+            .find(|(allocated, _)| allocated == name)
+            .map(|(_, port)| *port)
+    }
+
+    /// Names and ports of the minted proxies, in order of allocation.
+    pub fn allocated(&self) -> &[(String, u16)] {
+        &self.allocated
+    }
+}
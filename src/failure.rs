@@ -0,0 +1,75 @@
+//! Classification of the errors code under test runs into, to assert about the effect of a toxic
+//! (eg. "timed out, not refused") the same way on every OS.
+//!
+//! ```
+//! use toxiproxy_rust::failure::Failure;
+//!
+//! let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+//! let addr = listener.local_addr().unwrap();
+//! drop(listener);
+//!
+//! let err = std::net::TcpStream::connect(addr).unwrap_err();
+//! assert_eq!(Failure::Refused, Failure::of(&err));
+//! ```
+
+use std::error::Error;
+use std::io::{self, ErrorKind};
+
+/// Kind of a network failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Failure {
+    /// Nothing accepted the connection (eg. the proxy is disabled).
+    Refused,
+    /// An operation didn't complete in time (eg. a `timeout` or `latency` toxic).
+    TimedOut,
+    /// The connection was closed abruptly by the other side.
+    Reset,
+    /// The connection ended before the whole response arrived (eg. a `limit_data` toxic).
+    Truncated,
+    /// Anything else.
+    Other,
+}
+
+impl Failure {
+    /// Classifies an error, looking through its whole chain of sources. Works with
+    /// `std::io::Error`, `reqwest::Error` and errors wrapping them.
+    pub fn of(err: &(dyn Error + 'static)) -> Self {
+        let mut current = Some(err);
+        while let Some(err) = current {
+            if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+                if err.is_timeout() {
+                    return Failure::TimedOut;
+                }
+            }
+            if let Some(err) = err.downcast_ref::<io::Error>() {
+                match Self::of_io_kind(err.kind()) {
+                    Failure::Other => (),
+                    failure => return failure,
+                }
+            }
+            // The HTTP client reports a response cut short only through its message.
+            if err
+                .to_string()
+                .contains("connection closed before message completed")
+            {
+                return Failure::Truncated;
+            }
+            current = err.source();
+        }
+
+        Failure::Other
+    }
+
+    fn of_io_kind(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::ConnectionRefused => Failure::Refused,
+            // Read and write timeouts surface as `WouldBlock` on Unix and `TimedOut` on Windows.
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => Failure::TimedOut,
+            ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe => {
+                Failure::Reset
+            }
+            ErrorKind::UnexpectedEof => Failure::Truncated,
+            _ => Failure::Other,
+        }
+    }
+}
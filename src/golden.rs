@@ -0,0 +1,182 @@
+//! Golden files: the exact sequence of changes a scenario makes to the proxies, committed next to
+//! the tests and compared on every run, so an edit of a chaos definition changing its behavior
+//! doesn't go unnoticed.
+//!
+//! A [`GoldenSnapshot`] keeps the requests changing the server (reads are left out), without
+//! timing. Random runs (eg. [`chaos`](../chaos/index.html)) give the same snapshot with a fixed
+//! seed only.
+//! Golden files are written (or rewritten after an intended change) by running the tests with the
+//! `TOXIPROXY_UPDATE_GOLDEN` environment variable set.
+//!
+//! [`GoldenSnapshot`]: struct.GoldenSnapshot.html
+
+use super::client::*;
+use super::journal::*;
+use super::scenario::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Environment variable making [`GoldenSnapshot::assert_matches`] write the golden file instead of
+/// comparing against it.
+///
+/// [`GoldenSnapshot::assert_matches`]: struct.GoldenSnapshot.html#method.assert_matches
+pub const UPDATE_GOLDEN_ENV: &str = "TOXIPROXY_UPDATE_GOLDEN";
+
+/// Change sent to the server. JSON payloads are kept as JSON, to read well in golden files.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Step {
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Value>,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)?;
+        match self.payload.as_ref() {
+            Some(payload) => write!(f, " {}", payload),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Changes in the order they were made.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GoldenSnapshot {
+    pub steps: Vec<Step>,
+}
+
+impl From<Journal> for GoldenSnapshot {
+    fn from(journal: Journal) -> Self {
+        Self {
+            steps: journal
+                .entries
+                .into_iter()
+                .filter(|entry| entry.method != "GET")
+                .map(|entry| Step {
+                    method: entry.method,
+                    path: entry.path,
+                    payload: entry.payload.map(|payload| {
+                        serde_json::from_str(&payload).unwrap_or(Value::String(payload))
+                    }),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl GoldenSnapshot {
+    /// Snapshot of the changes the closure makes through the client it's given (a recording copy
+    /// of the client, see [`Client::record`]), its clones and its proxies. Proxy names in the paths
+    /// are the plain names of the client's namespace, so the snapshot of a namespaced (or
+    /// [`scoped`]) client is the same on every run.
+    ///
+    /// [`Client::record`]: ../client/struct.Client.html#method.record
+    /// [`scoped`]: ../client/struct.Client.html#method.scoped
+    pub fn record<F, T>(client: &Client, closure: F) -> (T, Self)
+    where
+        F: FnOnce(&Client) -> T,
+    {
+        let recorder = client.record();
+        let value = closure(recorder.client());
+        let mut snapshot: Self = recorder.stop().into();
+        for step in snapshot.steps.iter_mut() {
+            step.path = plain_path(client, &step.path);
+        }
+        (value, snapshot)
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|err| format!("snapshot error: {}", err))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|err| format!("invalid snapshot: {}", err))
+    }
+
+    /// Compares the snapshot against the golden file, failing on the first diverging step. With
+    /// `TOXIPROXY_UPDATE_GOLDEN` set, writes the golden file instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::golden::{GoldenSnapshot, Step};
+    /// # let path = std::env::temp_dir().join("toxiproxy_golden_doc.json");
+    /// let snapshot = GoldenSnapshot {
+    ///     steps: vec![Step {
+    ///         method: "POST".into(),
+    ///         path: "proxies/db".into(),
+    ///         payload: Some(serde_json::json!({"enabled": false})),
+    ///     }],
+    /// };
+    /// # std::fs::write(&path, snapshot.to_json().unwrap()).unwrap();
+    ///
+    /// snapshot.assert_matches(&path).expect("behavior is unchanged");
+    /// assert!(GoldenSnapshot::default().assert_matches(&path).is_err());
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn assert_matches<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        if env::var_os(UPDATE_GOLDEN_ENV).is_some() {
+            return fs::write(path, self.to_json()? + "\n")
+                .map_err(|err| format!("cannot write golden file {}: {}", path.display(), err));
+        }
+
+        let json = fs::read_to_string(path).map_err(|err| {
+            format!(
+                "cannot read golden file {} (set {} to write it): {}",
+                path.display(),
+                UPDATE_GOLDEN_ENV,
+                err
+            )
+        })?;
+        let golden = Self::from_json(&json)
+            .map_err(|err| format!("invalid golden file {}: {}", path.display(), err))?;
+
+        match self.divergence(&golden) {
+            Some((index, expected, actual)) => Err(format!(
+                "snapshot diverges from golden file {} at step {}: expected {}, got {}",
+                path.display(),
+                index + 1,
+                expected.map_or("no step".into(), |step| step.to_string()),
+                actual.map_or("no step".into(), |step| step.to_string()),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// First step (index, expected, actual) the snapshot differs from the golden one at.
+    fn divergence<'a>(
+        &'a self,
+        golden: &'a Self,
+    ) -> Option<(usize, Option<&'a Step>, Option<&'a Step>)> {
+        (0..self.steps.len().max(golden.steps.len()))
+            .map(|index| (index, golden.steps.get(index), self.steps.get(index)))
+            .find(|(_, expected, actual)| expected != actual)
+    }
+}
+
+/// Path with the proxy name (eg. in `proxies/db__1a2b_0/toxics`) as a plain name of the client.
+fn plain_path(client: &Client, path: &str) -> String {
+    match path.strip_prefix("proxies/") {
+        Some(rest) => {
+            let (name, tail) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+            format!("proxies/{}{}", client.plain_name(name), tail)
+        }
+        None => path.into(),
+    }
+}
+
+impl Scenario {
+    /// Runs the scenario, returning the snapshot of the changes it made. Requests sent meanwhile
+    /// by other users of the client are not recorded.
+    pub fn golden_snapshot(&self, client: &Client) -> Result<GoldenSnapshot, String> {
+        let (result, snapshot) = GoldenSnapshot::record(client, |client| self.run(client));
+        result.map(|_| snapshot)
+    }
+}
@@ -0,0 +1,217 @@
+//! Operations on several proxies at once, eg. every proxy of a service tier.
+
+use super::client::*;
+use super::proxy::*;
+use super::toxic::*;
+use std::fmt;
+
+/// Proxies operated on together. Operations are attempted on every member, failures being
+/// reported per member.
+#[derive(Debug)]
+pub struct ProxyGroup {
+    proxies: Vec<Proxy>,
+}
+
+/// Members of a [`ProxyGroup`] an operation failed on, with the reason.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupError {
+    pub failures: Vec<(String, String)>,
+}
+
+impl fmt::Display for GroupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failures = self
+            .failures
+            .iter()
+            .map(|(name, err)| format!("{}: {}", name, err))
+            .collect::<Vec<String>>();
+        write!(f, "group operation failed for {}", failures.join(", "))
+    }
+}
+
+impl From<GroupError> for String {
+    fn from(err: GroupError) -> String {
+        err.to_string()
+    }
+}
+
+impl Client {
+    /// Group of the proxies with the given names.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new(
+    /// #    "socket".into(),
+    /// #    "localhost:2001".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]).unwrap();
+    /// let group = toxiproxy_rust::TOXIPROXY.group(&["socket"]).expect("proxies are fetched");
+    /// group.delete_all_toxics().expect("toxics are deleted");
+    /// ```
+    pub fn group(&self, names: &[&str]) -> Result<ProxyGroup, String> {
+        names
+            .iter()
+            .map(|name| self.find_proxy(name))
+            .collect::<Result<Vec<Proxy>, String>>()
+            .map(ProxyGroup::new)
+    }
+
+    /// Disables the proxies with the given names while the closure runs, modeling a partition from
+    /// a subset of the dependencies, and enables them again afterwards (also when the closure
+    /// panics). See [`ProxyGroup::with_down`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{proxy::ProxyPack, TOXIPROXY};
+    /// # TOXIPROXY.populate(vec![
+    /// #    ProxyPack::new_ephemeral("partition_a".into(), "localhost:2000".into()),
+    /// #    ProxyPack::new_ephemeral("partition_b".into(), "localhost:2000".into()),
+    /// # ]).unwrap();
+    /// TOXIPROXY
+    ///     .with_partition(&["partition_a", "partition_b"], || {
+    ///         /* Example test:
+    ///            let service_result = MyService::Server::call(params);
+    ///            assert!(service_result.is_ok());
+    ///         */
+    ///     })
+    ///     .expect("proxies are restored");
+    /// # TOXIPROXY.group(&["partition_a", "partition_b"]).unwrap()
+    /// #     .for_each(|proxy| proxy.delete()).unwrap();
+    /// ```
+    ///
+    /// [`ProxyGroup::with_down`]: ../group/struct.ProxyGroup.html#method.with_down
+    pub fn with_partition<F, T>(&self, names: &[&str], closure: F) -> Result<T, String>
+    where
+        F: FnOnce() -> T,
+    {
+        self.group(names)?.with_down(closure)
+    }
+
+    /// Group of the proxies with the given tag, see [`proxies_with_tag`].
+    ///
+    /// [`proxies_with_tag`]: #method.proxies_with_tag
+    pub fn group_with_tag(&self, tag: &str) -> Result<ProxyGroup, String> {
+        self.proxies_with_tag(tag).map(ProxyGroup::new)
+    }
+
+    /// Group of the proxies (in the client's namespace) matching a predicate, sorted by name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let group = toxiproxy_rust::TOXIPROXY
+    ///     .group_matching(|proxy_pack| proxy_pack.upstream.ends_with(":5432"))
+    ///     .expect("proxies are fetched");
+    /// ```
+    pub fn group_matching<F>(&self, matcher: F) -> Result<ProxyGroup, String>
+    where
+        F: Fn(&ProxyPack) -> bool,
+    {
+        let mut proxies = self
+            .all()?
+            .into_values()
+            .filter(|proxy| matcher(&proxy.proxy_pack))
+            .collect::<Vec<Proxy>>();
+        proxies.sort_by(|a, b| a.proxy_pack.name.cmp(&b.proxy_pack.name));
+        Ok(ProxyGroup::new(proxies))
+    }
+}
+
+impl ProxyGroup {
+    pub fn new(proxies: Vec<Proxy>) -> Self {
+        Self { proxies }
+    }
+
+    pub fn proxies(&self) -> &[Proxy] {
+        &self.proxies
+    }
+
+    pub fn len(&self) -> usize {
+        self.proxies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.proxies.is_empty()
+    }
+
+    pub fn enable_all(&self) -> Result<(), GroupError> {
+        self.for_each(|proxy| proxy.enable())
+    }
+
+    pub fn disable_all(&self) -> Result<(), GroupError> {
+        self.for_each(|proxy| proxy.disable())
+    }
+
+    pub fn delete_all_toxics(&self) -> Result<(), GroupError> {
+        self.for_each(|proxy| proxy.delete_all_toxics())
+    }
+
+    /// Disables every member while the closure runs, and enables them again afterwards (also when
+    /// the closure panics). If some members can't be disabled, the others are enabled again and
+    /// the closure isn't run.
+    pub fn with_down<F, T>(&self, closure: F) -> Result<T, String>
+    where
+        F: FnOnce() -> T,
+    {
+        if let Err(err) = self.disable_all() {
+            let _ = self.enable_all();
+            return Err(err.into());
+        }
+
+        let (value, result) = Proxy::run_guarded(closure, || {
+            self.for_each(|proxy| proxy.report_cleanup(proxy.enable()))
+        });
+        result.map(|_| value).map_err(String::from)
+    }
+
+    /// Registers the toxic on every member.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::toxic::ToxicPack;
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new(
+    /// #    "socket".into(),
+    /// #    "localhost:2001".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]).unwrap();
+    /// let group = toxiproxy_rust::TOXIPROXY.group(&["socket"]).unwrap();
+    /// # group.delete_all_toxics().unwrap();
+    /// group
+    ///     .add_toxic(ToxicPack::new(
+    ///         "timeout".into(),
+    ///         "downstream".into(),
+    ///         1.0,
+    ///         vec![("timeout".to_owned(), 100)].into_iter().collect(),
+    ///     ))
+    ///     .expect("toxics are registered");
+    /// # group.delete_all_toxics().unwrap();
+    /// ```
+    pub fn add_toxic(&self, toxic: ToxicPack) -> Result<(), GroupError> {
+        self.for_each(|proxy| proxy.try_create_toxic(toxic.clone()).map(|_| ()))
+    }
+
+    /// Runs an operation on every member, collecting the failures.
+    pub fn for_each<F>(&self, operation: F) -> Result<(), GroupError>
+    where
+        F: Fn(&Proxy) -> Result<(), String>,
+    {
+        let failures = self
+            .proxies
+            .iter()
+            .filter_map(|proxy| {
+                operation(proxy)
+                    .err()
+                    .map(|err| (proxy.proxy_pack.name.clone(), err))
+            })
+            .collect::<Vec<(String, String)>>();
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(GroupError { failures })
+        }
+    }
+}
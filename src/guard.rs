@@ -0,0 +1,301 @@
+//! Scope guards restoring proxy state when they go out of scope, for tests where a closure based
+//! setup (such as [`Proxy::apply`]) does not fit.
+//!
+//! [`Proxy::apply`]: ../proxy/struct.Proxy.html#method.apply
+
+use super::client::*;
+use super::error::ApiError;
+use super::group::*;
+use super::proxy::*;
+use super::set::*;
+use std::mem;
+use std::ops::Deref;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+static FIXTURE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Guard deleting the toxics registered through it when dropped. Created by [`Proxy::toxic_scope`].
+///
+/// [`Proxy::toxic_scope`]: ../proxy/struct.Proxy.html#method.toxic_scope
+#[derive(Debug)]
+pub struct ToxicGuard {
+    proxy: Proxy,
+}
+
+impl ToxicGuard {
+    pub(crate) fn new(proxy: Proxy) -> Self {
+        #[cfg(feature = "teardown")]
+        super::teardown::register_proxy(&proxy);
+        Self { proxy }
+    }
+
+    /// Deletes the toxics registered through the guard right away, reporting any failure
+    /// (which is otherwise ignored on drop).
+    pub fn release(self) -> Result<(), String> {
+        self.proxy.delete_created_toxics()
+    }
+}
+
+impl Deref for ToxicGuard {
+    type Target = Proxy;
+
+    fn deref(&self) -> &Proxy {
+        &self.proxy
+    }
+}
+
+impl Drop for ToxicGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "teardown")]
+        super::teardown::unregister_proxy(&self.proxy);
+        let _ = ApiError::keep(|| {
+            self.proxy
+                .report_cleanup(self.proxy.delete_created_toxics())
+        });
+    }
+}
+
+/// Guard deleting the toxics registered through it on every member of a set when dropped.
+/// Created by [`ProxySet::toxic_scope`].
+///
+/// [`ProxySet::toxic_scope`]: ../set/struct.ProxySet.html#method.toxic_scope
+#[derive(Debug)]
+pub struct ToxicSetGuard {
+    set: ProxySet,
+}
+
+impl ToxicSetGuard {
+    pub(crate) fn new(set: ProxySet) -> Self {
+        Self { set }
+    }
+
+    /// Deletes the toxics registered through the guard right away, reporting any failure
+    /// (which is otherwise ignored on drop).
+    pub fn release(self) -> Result<(), GroupError> {
+        self.set.delete_created_toxics()
+    }
+}
+
+impl Deref for ToxicSetGuard {
+    type Target = ProxySet;
+
+    fn deref(&self) -> &ProxySet {
+        &self.set
+    }
+}
+
+impl Drop for ToxicSetGuard {
+    fn drop(&mut self) {
+        let _ = ApiError::keep(|| self.set.delete_created_toxics());
+    }
+}
+
+/// Guard keeping a proxy disabled until dropped. Created by [`Proxy::down_guard`].
+///
+/// [`Proxy::down_guard`]: ../proxy/struct.Proxy.html#method.down_guard
+#[derive(Debug)]
+pub struct DownGuard<'a> {
+    proxy: &'a Proxy,
+}
+
+impl<'a> DownGuard<'a> {
+    pub(crate) fn new(proxy: &'a Proxy) -> Self {
+        Self { proxy }
+    }
+
+    /// Enables the proxy right away, reporting any failure (which is otherwise ignored on drop).
+    pub fn release(self) -> Result<(), String> {
+        let result = self.proxy.enable();
+        mem::forget(self);
+        result
+    }
+}
+
+impl Drop for DownGuard<'_> {
+    fn drop(&mut self) {
+        let _ = ApiError::keep(|| self.proxy.report_cleanup(self.proxy.enable()));
+    }
+}
+
+/// Proxy living as long as the fixture: it's created when the fixture is built and deleted
+/// (together with its toxics) when the fixture is dropped.
+#[derive(Debug)]
+pub struct ProxyFixture {
+    proxy: Proxy,
+    deleted: bool,
+}
+
+impl ProxyFixture {
+    /// Creates the proxy described by `proxy_pack`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{guard::ProxyFixture, proxy::ProxyPack, TOXIPROXY};
+    /// let fixture = ProxyFixture::new(&TOXIPROXY, ProxyPack::new(
+    ///     "fixture".into(),
+    ///     "localhost:2003".into(),
+    ///     "localhost:2000".into(),
+    /// )).expect("proxy is created");
+    ///
+    /// fixture.with_latency("downstream".into(), 2000, 0, 1.0);
+    /// ```
+    pub fn new(client: &Client, proxy_pack: ProxyPack) -> Result<Self, String> {
+        client.create_proxy(proxy_pack).map(|proxy| Self {
+            proxy,
+            deleted: false,
+        })
+    }
+
+    /// Creates the proxy described by `proxy_pack` with its name suffixed to be unique to this
+    /// process and fixture, so it won't clash with proxies of other tests.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{guard::ProxyFixture, proxy::ProxyPack, TOXIPROXY};
+    /// let fixture = ProxyFixture::with_unique_name(&TOXIPROXY, ProxyPack::new(
+    ///     "fixture".into(),
+    ///     "localhost:2004".into(),
+    ///     "localhost:2000".into(),
+    /// )).expect("proxy is created");
+    ///
+    /// assert!(fixture.proxy_pack.name.starts_with("fixture_"));
+    /// ```
+    pub fn with_unique_name(client: &Client, mut proxy_pack: ProxyPack) -> Result<Self, String> {
+        proxy_pack.name = format!(
+            "{}_{}_{}",
+            proxy_pack.name,
+            process::id(),
+            FIXTURE_COUNTER.fetch_add(1, Ordering::SeqCst)
+        );
+        Self::new(client, proxy_pack)
+    }
+
+    /// Deletes the proxy right away, reporting any failure (which is otherwise ignored on drop).
+    pub fn release(mut self) -> Result<(), String> {
+        self.deleted = true;
+        self.proxy.delete()
+    }
+}
+
+impl Deref for ProxyFixture {
+    type Target = Proxy;
+
+    fn deref(&self) -> &Proxy {
+        &self.proxy
+    }
+}
+
+impl Drop for ProxyFixture {
+    fn drop(&mut self) {
+        if !self.deleted {
+            let _ = ApiError::keep(|| self.proxy.report_cleanup(self.proxy.delete()));
+        }
+    }
+}
+
+/// Client deleting every proxy created through it when dropped. Created by [`Client::scoped`].
+///
+/// [`Client::scoped`]: ../client/struct.Client.html#method.scoped
+pub struct ScopedClient {
+    client: Client,
+}
+
+impl ScopedClient {
+    pub(crate) fn new(client: Client) -> Self {
+        #[cfg(feature = "teardown")]
+        super::teardown::register_client(&client);
+        Self { client }
+    }
+
+    /// Deletes the proxies created through the client right away, reporting any failure (which is
+    /// otherwise ignored on drop).
+    pub fn release(self) -> Result<(), String> {
+        self.client.delete_created_proxies()
+    }
+}
+
+impl Deref for ScopedClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl Drop for ScopedClient {
+    fn drop(&mut self) {
+        #[cfg(feature = "teardown")]
+        super::teardown::unregister_client(&self.client);
+        let _ = ApiError::keep(|| self.client.delete_created_proxies());
+    }
+}
+
+/// Proxy handle watching for modifications made outside of it. Created by [`Proxy::watch`].
+///
+/// [`Proxy::watch`]: ../proxy/struct.Proxy.html#method.watch
+#[derive(Debug)]
+pub struct WatchGuard {
+    proxy: Proxy,
+    verified: bool,
+}
+
+impl WatchGuard {
+    pub(crate) fn new(proxy: Proxy) -> Self {
+        Self {
+            proxy,
+            verified: false,
+        }
+    }
+
+    /// Checks that the proxy was only modified through the guard, describing the unexpected
+    /// changes otherwise.
+    pub fn verify(&self) -> Result<(), String> {
+        let changes = self.proxy.unexpected_changes()?;
+        if changes.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "proxy {:?} was modified externally:\n{}",
+                self.proxy.proxy_pack.name,
+                changes.join("\n")
+            ))
+        }
+    }
+
+    /// Verifies the proxy right away and stops watching it, reporting any change or failure
+    /// instead of panicking on drop.
+    pub fn release(mut self) -> Result<(), String> {
+        self.verified = true;
+        self.verify()
+    }
+}
+
+impl Deref for WatchGuard {
+    type Target = Proxy;
+
+    fn deref(&self) -> &Proxy {
+        &self.proxy
+    }
+}
+
+impl Drop for WatchGuard {
+    fn drop(&mut self) {
+        if self.verified || thread::panicking() {
+            return;
+        }
+        // A proxy that can't be fetched (eg. deleted by the test) is not reported.
+        if let Ok(changes) = self.proxy.unexpected_changes() {
+            if !changes.is_empty() {
+                panic!(
+                    "proxy {:?} was modified externally:\n{}",
+                    self.proxy.proxy_pack.name,
+                    changes.join("\n")
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Callbacks run whenever the crate changes the state of the server - eg. for a test harness to
+//! log the changes, count them, or enforce a policy (by panicking).
+//!
+//! Hooks are registered on a [`Client`] and shared by its clones and its proxies, like its
+//! connection: hooks registered on `TOXIPROXY` (or a client scoped from it) see the changes made
+//! through any of them. They run on the thread making the change, after the server accepted it.
+//!
+//! [`Client`]: ../client/struct.Client.html
+
+use super::client::*;
+use super::http_client::*;
+use super::toxic::*;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Change made to the server, or a failure to restore it.
+#[derive(Debug, Clone)]
+pub enum Event {
+    ToxicCreated {
+        proxy: String,
+        toxic: ToxicPack,
+    },
+    ToxicRemoved {
+        proxy: String,
+        toxic: String,
+    },
+    ProxyDisabled {
+        proxy: String,
+    },
+    ProxyEnabled {
+        proxy: String,
+    },
+    /// Restoring the proxy after a call (eg. deleting the toxics after [`Proxy::apply`], or
+    /// enabling it after [`Proxy::with_down`]) failed.
+    ///
+    /// [`Proxy::apply`]: ../proxy/struct.Proxy.html#method.apply
+    /// [`Proxy::with_down`]: ../proxy/struct.Proxy.html#method.with_down
+    CleanupFailed {
+        proxy: String,
+        error: String,
+    },
+}
+
+pub(crate) type Listener = Arc<dyn Fn(&Event) + Send + Sync>;
+
+/// Listeners of a client's events.
+#[derive(Default, Clone)]
+pub(crate) struct Listeners(Vec<Listener>);
+
+impl Listeners {
+    pub(crate) fn push(&mut self, listener: Listener) {
+        self.0.push(listener);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Listeners {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Listeners({})", self.0.len())
+    }
+}
+
+/// Runs the listeners of the client on the event, built only if there are listeners. The client
+/// isn't locked meanwhile, so listeners can use it.
+pub(crate) fn emit<F>(client: &Arc<Mutex<HttpClient>>, event: F)
+where
+    F: FnOnce() -> Event,
+{
+    let listeners = match client.lock() {
+        Ok(client) if !client.listeners().is_empty() => client.listeners().clone(),
+        _ => return,
+    };
+
+    let event = event();
+    for listener in listeners.0.iter() {
+        listener(&event);
+    }
+}
+
+impl Client {
+    /// Runs the callback on every event.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{client::Client, hooks::Event, proxy::ProxyPack};
+    /// # use std::sync::{Arc, Mutex};
+    /// let client = Client::from_env();
+    /// let events = Arc::new(Mutex::new(vec![]));
+    /// let recorded = events.clone();
+    /// client.on_event(move |event| recorded.lock().unwrap().push(event.clone()));
+    ///
+    /// let proxy = client
+    ///     .create_proxy(ProxyPack::new_ephemeral("hooks_doc".into(), "localhost:2000".into()))
+    ///     .unwrap();
+    /// proxy.disable().unwrap();
+    ///
+    /// let events = events.lock().unwrap();
+    /// assert!(matches!(&events[..], [Event::ProxyDisabled { proxy }] if proxy == "hooks_doc"));
+    /// # proxy.delete().unwrap();
+    /// ```
+    pub fn on_event<F>(&self, listener: F)
+    where
+        F: Fn(&Event) + Send + Sync + 'static,
+    {
+        if let Ok(mut client) = self.http_client().lock() {
+            client.listeners_mut().push(Arc::new(listener));
+        }
+    }
+
+    /// Runs the callback with the proxy name and the toxic whenever a toxic is created.
+    pub fn on_toxic_created<F>(&self, listener: F)
+    where
+        F: Fn(&str, &ToxicPack) + Send + Sync + 'static,
+    {
+        self.on_event(move |event| {
+            if let Event::ToxicCreated { proxy, toxic } = event {
+                listener(proxy, toxic);
+            }
+        });
+    }
+
+    /// Runs the callback with the proxy and toxic names whenever a toxic is removed.
+    pub fn on_toxic_removed<F>(&self, listener: F)
+    where
+        F: Fn(&str, &str) + Send + Sync + 'static,
+    {
+        self.on_event(move |event| {
+            if let Event::ToxicRemoved { proxy, toxic } = event {
+                listener(proxy, toxic);
+            }
+        });
+    }
+
+    /// Runs the callback with the proxy name whenever a proxy is disabled.
+    pub fn on_proxy_disabled<F>(&self, listener: F)
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.on_event(move |event| {
+            if let Event::ProxyDisabled { proxy } = event {
+                listener(proxy);
+            }
+        });
+    }
+
+    /// Runs the callback with the proxy name whenever a proxy is enabled.
+    pub fn on_proxy_enabled<F>(&self, listener: F)
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.on_event(move |event| {
+            if let Event::ProxyEnabled { proxy } = event {
+                listener(proxy);
+            }
+        });
+    }
+
+    /// Runs the callback with the proxy name and the error whenever restoring a proxy fails, see
+    /// [`Event::CleanupFailed`].
+    ///
+    /// [`Event::CleanupFailed`]: ../hooks/enum.Event.html#variant.CleanupFailed
+    pub fn on_cleanup_failed<F>(&self, listener: F)
+    where
+        F: Fn(&str, &str) + Send + Sync + 'static,
+    {
+        self.on_event(move |event| {
+            if let Event::CleanupFailed { proxy, error } = event {
+                listener(proxy, error);
+            }
+        });
+    }
+
+    /// Removes every callback of the client (and of its clones).
+    pub fn clear_hooks(&self) {
+        if let Ok(mut client) = self.http_client().lock() {
+            client.listeners_mut().clear();
+        }
+    }
+}
@@ -1,59 +1,446 @@
-use reqwest::{blocking::Client, blocking::Response, Url};
+use super::audit::*;
+use super::compat::*;
+use super::consts::*;
+use super::diagnostics::*;
+use super::error::*;
+use super::hooks::*;
+use super::instrument;
+use super::journal::*;
+use super::metrics::*;
+use super::proxy::ProxyPack;
+use super::recovery::*;
+use super::verbosity::*;
+use reqwest::{blocking::Client, blocking::Response, Method, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::{
     net::{SocketAddr, ToSocketAddrs},
     str::FromStr,
+    sync::{Arc, Mutex},
+    time::Instant,
 };
 
 #[derive(Debug)]
 pub struct HttpClient {
     client: Client,
-    toxiproxy_addr: SocketAddr,
+    /// Address of the server, or why it couldn't be resolved - reported by every request.
+    toxiproxy_addr: Result<SocketAddr, String>,
+    /// Proxies answered with by a no-op client, by name - `None` for a client of a server.
+    noop: Option<Arc<Mutex<HashMap<String, Value>>>>,
+    journal: Option<Arc<Mutex<Vec<JournalEntry>>>>,
+    listeners: Listeners,
+    metrics: Mutex<ApiMetrics>,
+    audit: Option<Arc<AuditLog>>,
+    correlation_id: Option<String>,
+    debug_body_limit: Option<usize>,
+    verbosity: Verbosity,
+    history: Mutex<History>,
+    server_version: Option<ServerVersion>,
+    recovery: Mutex<Option<Recovery>>,
 }
 
 impl HttpClient {
     pub(crate) fn new<U: ToSocketAddrs>(toxiproxy_addr: U) -> Self {
         Self {
             client: Client::new(),
-            toxiproxy_addr: toxiproxy_addr.to_socket_addrs().unwrap().next().unwrap(),
+            toxiproxy_addr: resolve(toxiproxy_addr),
+            noop: None,
+            journal: None,
+            listeners: Listeners::default(),
+            metrics: Mutex::new(ApiMetrics::default()),
+            audit: None,
+            correlation_id: None,
+            debug_body_limit: None,
+            verbosity: Verbosity::from_env(),
+            history: Mutex::new(History::default()),
+            server_version: None,
+            recovery: Mutex::new(None),
         }
     }
 
+    /// Client answering every request locally, as if the server accepted it.
+    pub(crate) fn new_noop() -> Self {
+        Self {
+            client: Client::new(),
+            toxiproxy_addr: Ok(SocketAddr::from(([127, 0, 0, 1], 0))),
+            noop: Some(Arc::default()),
+            journal: None,
+            listeners: Listeners::default(),
+            metrics: Mutex::new(ApiMetrics::default()),
+            audit: None,
+            correlation_id: None,
+            debug_body_limit: None,
+            verbosity: Verbosity::from_env(),
+            history: Mutex::new(History::default()),
+            server_version: None,
+            recovery: Mutex::new(None),
+        }
+    }
+
+    /// Client with the settings of this one but state of its own, eg. to record its requests only.
+    /// Its metrics and history of requests start empty.
+    pub(crate) fn fork(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            toxiproxy_addr: self.toxiproxy_addr.clone(),
+            noop: self.noop.clone(),
+            journal: None,
+            listeners: self.listeners.clone(),
+            metrics: Mutex::new(ApiMetrics::default()),
+            audit: self.audit.clone(),
+            correlation_id: self.correlation_id.clone(),
+            debug_body_limit: self.debug_body_limit,
+            verbosity: self.verbosity,
+            history: Mutex::new(History::default()),
+            server_version: self.server_version,
+            recovery: Mutex::new(
+                self.recovery
+                    .lock()
+                    .ok()
+                    .and_then(|recovery| recovery.clone()),
+            ),
+        }
+    }
+
+    pub(crate) fn is_noop(&self) -> bool {
+        self.noop.is_some()
+    }
+
     pub(crate) fn get(&self, path: &str) -> Result<Response, String> {
-        self.client
-            .get(self.uri_with_path(path)?)
-            .header("Content-Type", "application/json")
-            .send()
-            .map_err(|err| format!("GET error: {}", err))
+        self.send(Method::GET, path, None)
     }
 
     pub(crate) fn post(&self, path: &str) -> Result<Response, String> {
-        self.client
-            .post(self.uri_with_path(path)?)
-            .header("Content-Type", "application/json")
-            .send()
-            .map_err(|err| format!("POST error: {}", err))
+        self.send(Method::POST, path, None)
     }
 
     pub(crate) fn post_with_data(&self, path: &str, body: String) -> Result<Response, String> {
-        self.client
-            .post(self.uri_with_path(path)?)
-            .header("Content-Type", "application/json")
-            .body(body)
-            .send()
-            .map_err(|err| format!("POST error: {}", err))
+        self.send(Method::POST, path, Some(body))
+    }
+
+    /// Updates a proxy or a toxic, with the method of the server version if it's known.
+    pub(crate) fn update(&self, path: &str, body: String) -> Result<Response, String> {
+        let method = self
+            .server_version
+            .map_or(Method::POST, |version| version.update_method());
+        self.send(method, path, Some(body))
     }
 
     pub(crate) fn delete(&self, path: &str) -> Result<Response, String> {
-        self.client
-            .delete(self.uri_with_path(path)?)
-            .header("Content-Type", "application/json")
-            .send()
-            .map_err(|err| format!("DELETE error: {}", err))
+        self.send(Method::DELETE, path, None)
+    }
+
+    pub(crate) fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        self.send_json(Method::GET, path, None)
+    }
+
+    pub(crate) fn post_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: String,
+    ) -> Result<T, String> {
+        self.send_json(Method::POST, path, Some(body))
+    }
+
+    /// Sends the request and reads its JSON answer. An answer of a successful request which can't
+    /// be read is recorded as a failed request too.
+    fn send_json<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<String>,
+    ) -> Result<T, String> {
+        let response = self.send(method.clone(), path, body.clone())?;
+        let status = response.status();
+        response.json().map_err(|err| {
+            let message = format!("json deserialize failed: {}", err);
+            if status.is_success() {
+                let mut err = self.api_error(ApiErrorKind::Decode, &method, path, body);
+                err.status = Some(status.as_u16());
+                err.message = message.clone();
+                if let Ok(mut history) = self.history.lock() {
+                    history.record_error(err.clone());
+                }
+                err.record();
+            }
+            message
+        })
+    }
+
+    /// Sends the request - and if it finds a proxy to put back gone, puts the proxies back and
+    /// sends it again (see the `recovery` module).
+    pub(crate) fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<String>,
+    ) -> Result<Response, String> {
+        ApiError::clear();
+        let result = self.send_once(method.clone(), path, body.clone(), true);
+
+        let lost = match self.recovery.lock() {
+            Ok(mut recovery) => match (recovery.as_mut(), result.as_ref()) {
+                (Some(recovery), Ok(response)) => match recovery.known_proxy(path) {
+                    Some(_) if response.status() == StatusCode::NOT_FOUND => true,
+                    Some(name) if method == Method::DELETE && response.status().is_success() => {
+                        if path.split('/').count() == 2 {
+                            recovery.forget(&name);
+                        }
+                        false
+                    }
+                    _ => false,
+                },
+                _ => false,
+            },
+            Err(_) => false,
+        };
+
+        if lost && self.recover()? {
+            self.send_once(method, path, body, true)
+        } else {
+            result
+        }
+    }
+
+    /// Puts the proxies missing from the server back. Returns whether any was.
+    fn recover(&self) -> Result<bool, String> {
+        let server_proxies = self
+            .send_once(Method::GET, "proxies", None, false)?
+            .json::<HashMap<String, Value>>()
+            .map_err(|err| format!("json deserialize failed: {}", err))?
+            .into_keys()
+            .collect::<Vec<String>>();
+
+        let requests = match self
+            .recovery
+            .lock()
+            .map_err(|err| format!("lock error: {}", err))?
+            .as_ref()
+        {
+            Some(recovery) => recovery.requests(&server_proxies)?,
+            None => return Ok(false),
+        };
+        if requests.is_empty() {
+            return Ok(false);
+        }
+
+        for (method, path, body) in requests {
+            let response = self.send_once(method.clone(), &path, Some(body), false)?;
+            if !response.status().is_success() {
+                return Err(format!(
+                    "putting the lost proxies back failed: {} {}: {}",
+                    method,
+                    path,
+                    response.status()
+                ));
+            }
+        }
+
+        if let Ok(mut recovery) = self.recovery.lock() {
+            if let Some(recovery) = recovery.as_mut() {
+                recovery.recovered();
+            }
+        }
+        Ok(true)
+    }
+
+    /// Sends the request, adding it to the journal and the audit log if it's `recorded`.
+    fn send_once(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<String>,
+        recorded: bool,
+    ) -> Result<Response, String> {
+        if let (true, Some(journal)) = (recorded, self.journal.as_ref()) {
+            journal
+                .lock()
+                .map_err(|err| format!("lock error: {}", err))?
+                .push(JournalEntry::new(method.as_str(), path, body.clone()));
+        }
+        let mut audit_entry = match self.audit {
+            Some(_) if recorded && method != Method::GET => {
+                Some(AuditEntry::new(method.as_str(), path, body.clone()))
+            }
+            _ => None,
+        };
+        let request_body = self.debug_body_limit.and(body.clone());
+        let payload = body.clone();
+        let mut kind = ApiErrorKind::Other;
+
+        let started = Instant::now();
+        let result = instrument::request(method.as_str(), path, || {
+            if let Some(ref proxies) = self.noop {
+                return noop_response(proxies, method.as_str(), path, body);
+            }
+
+            let mut request = self
+                .client
+                .request(method.clone(), self.uri_with_path(path)?)
+                .header("Content-Type", "application/json");
+            if let Some(ref correlation_id) = self.correlation_id {
+                request = request.header(CORRELATION_ID_HEADER, correlation_id);
+            }
+            if let Some(body) = body {
+                request = request.body(body);
+            }
+            request.send().map_err(|err| {
+                kind = if err.is_timeout() {
+                    ApiErrorKind::Timeout
+                } else if err.is_connect() {
+                    ApiErrorKind::Connect
+                } else {
+                    ApiErrorKind::Other
+                };
+                format!("{} error: {}", method, err)
+            })
+        });
+
+        if let Ok(mut metrics) = self.metrics.lock() {
+            let failed = !result
+                .as_ref()
+                .is_ok_and(|response| response.status().is_success());
+            metrics.record(method.as_str(), path, started.elapsed(), failed);
+        }
+        self.verbosity.log(
+            method.as_str(),
+            path,
+            payload.as_deref(),
+            &result,
+            started.elapsed(),
+        );
+        if let (Some(audit), Some(entry)) = (self.audit.as_ref(), audit_entry.as_mut()) {
+            match result.as_ref() {
+                Ok(response) => entry.status = Some(response.status().as_u16()),
+                Err(err) => entry.error = Some(err.clone()),
+            }
+            audit.append(entry);
+        }
+        let failure = match result.as_ref() {
+            Ok(response) if !response.status().is_success() => {
+                let mut err = self.api_error(ApiErrorKind::Status, &method, path, payload);
+                err.status = Some(response.status().as_u16());
+                err.message = response.status().to_string();
+                Some(err)
+            }
+            Ok(_) => None,
+            Err(message) => {
+                let mut err = self.api_error(kind, &method, path, payload);
+                err.message = message.clone();
+                Some(err)
+            }
+        };
+        if let Ok(mut history) = self.history.lock() {
+            let (status, error) = match result.as_ref() {
+                Ok(response) => (Some(response.status().as_u16()), None),
+                Err(err) => (None, Some(err.clone())),
+            };
+            history.record(method.as_str(), path, status, error, started.elapsed());
+            if let Some(ref err) = failure {
+                history.record_error(err.clone());
+            }
+        }
+        if let Some(err) = failure {
+            err.record();
+        }
+
+        let result = match self.debug_body_limit {
+            Some(limit) => with_bodies(method.as_str(), path, request_body, result, limit),
+            None => result,
+        };
+        result.map_err(|err| match self.correlation_id {
+            Some(ref correlation_id) => format!("{} (correlation id {})", err, correlation_id),
+            None => err,
+        })
+    }
+
+    fn api_error(
+        &self,
+        kind: ApiErrorKind,
+        method: &Method,
+        path: &str,
+        payload: Option<String>,
+    ) -> ApiError {
+        let url = self
+            .uri_with_path(path)
+            .map_or_else(|_| path.to_owned(), |url| url.to_string());
+        let mut err = ApiError::new(kind, method.as_str(), url, path);
+        err.payload = payload;
+        err.correlation_id = self.correlation_id.clone();
+        err
+    }
+
+    pub(crate) fn diagnostics(&self) -> Diagnostics {
+        self.history
+            .lock()
+            .map(|history| history.diagnostics())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn set_history_capacity(&self, capacity: usize) {
+        if let Ok(mut history) = self.history.lock() {
+            history.set_capacity(capacity);
+        }
+    }
+
+    pub(crate) fn verbosity(&self) -> Verbosity {
+        self.verbosity
+    }
+
+    pub(crate) fn set_verbosity(&mut self, verbosity: Verbosity) {
+        self.verbosity = verbosity;
+    }
+
+    /// Records the requests into the journal from now on, or stops recording.
+    pub(crate) fn set_journal(&mut self, journal: Option<Arc<Mutex<Vec<JournalEntry>>>>) {
+        self.journal = journal;
+    }
+
+    pub(crate) fn metrics(&self) -> ApiMetrics {
+        self.metrics
+            .lock()
+            .map(|metrics| metrics.clone())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn reset_metrics(&self) {
+        if let Ok(mut metrics) = self.metrics.lock() {
+            *metrics = ApiMetrics::default();
+        }
+    }
+
+    pub(crate) fn correlation_id(&self) -> Option<&str> {
+        self.correlation_id.as_deref()
+    }
+
+    /// Sends the ID as a header of every request from now on, or stops sending one.
+    pub(crate) fn set_correlation_id(&mut self, correlation_id: Option<String>) {
+        self.correlation_id = correlation_id;
+    }
+
+    /// Turns answers with an error status into errors with the request and response bodies
+    /// (truncated to the limit), and adds the request body to errors - or stops doing so.
+    pub(crate) fn set_debug_body_limit(&mut self, limit: Option<usize>) {
+        self.debug_body_limit = limit;
+    }
+
+    /// Appends the requests changing the server to the audit log from now on, or stops.
+    pub(crate) fn set_audit(&mut self, audit: Option<Arc<AuditLog>>) {
+        self.audit = audit;
+    }
+
+    pub(crate) fn listeners(&self) -> &Listeners {
+        &self.listeners
+    }
+
+    pub(crate) fn listeners_mut(&mut self) -> &mut Listeners {
+        &mut self.listeners
     }
 
     fn uri_with_path(&self, path: &str) -> Result<Url, String> {
         let mut base: String = "http://".into();
-        base.push_str(&self.toxiproxy_addr.to_string());
+        base.push_str(&self.toxiproxy_addr()?.to_string());
 
         let mut url = Url::from_str(&base).map_err(|err| format!("Incorrect address: {}", err))?;
 
@@ -63,9 +450,201 @@ impl HttpClient {
         Ok(url)
     }
 
-    pub(crate) fn is_alive(&self) -> bool {
-        std::net::TcpStream::connect(self.toxiproxy_addr)
-            .map(|_| true)
-            .unwrap_or(false)
+    pub(crate) fn toxiproxy_addr(&self) -> Result<SocketAddr, String> {
+        self.toxiproxy_addr.clone()
+    }
+
+    pub(crate) fn set_toxiproxy_addr<U: ToSocketAddrs>(
+        &mut self,
+        toxiproxy_addr: U,
+    ) -> Result<(), String> {
+        self.toxiproxy_addr = Ok(resolve(toxiproxy_addr)?);
+        self.server_version = None;
+        Ok(())
+    }
+
+    /// Puts proxies back after a restart of the server from now on, or stops.
+    pub(crate) fn set_recovery(&mut self, recovery: Option<Recovery>) {
+        if let Ok(current) = self.recovery.get_mut() {
+            *current = recovery;
+        }
+    }
+
+    /// Remembers populated proxies to put back after a restart, if enabled.
+    pub(crate) fn remember_populated(&self, proxies: &[ProxyPack]) {
+        if let Ok(mut recovery) = self.recovery.lock() {
+            if let Some(recovery) = recovery.as_mut() {
+                recovery.remember(proxies);
+            }
+        }
+    }
+
+    pub(crate) fn recoveries(&self) -> usize {
+        self.recovery
+            .lock()
+            .ok()
+            .and_then(|recovery| recovery.as_ref().map(|recovery| recovery.recoveries()))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn server_version(&self) -> Option<ServerVersion> {
+        self.server_version
+    }
+
+    /// Adapts the requests to the server version from now on, or stops adapting them.
+    pub(crate) fn set_server_version(&mut self, server_version: Option<ServerVersion>) {
+        self.server_version = server_version;
+    }
+}
+
+fn resolve<U: ToSocketAddrs>(toxiproxy_addr: U) -> Result<SocketAddr, String> {
+    toxiproxy_addr
+        .to_socket_addrs()
+        .map_err(|err| format!("Incorrect address: {}", err))?
+        .next()
+        .ok_or_else(|| "Incorrect address: no address resolved".to_owned())
+}
+
+/// Error for a request answered with an error status, with the bodies of the request and of the
+/// response, or the error of a request with its body.
+fn with_bodies(
+    method: &str,
+    path: &str,
+    request_body: Option<String>,
+    result: Result<Response, String>,
+    limit: usize,
+) -> Result<Response, String> {
+    let request_body = request_body.map_or_else(|| "(none)".into(), |body| truncate(&body, limit));
+    match result {
+        Ok(response) if !response.status().is_success() => {
+            let status = response.status();
+            let response_body = response
+                .text()
+                .unwrap_or_else(|err| format!("(unreadable: {})", err));
+            Err(format!(
+                "{} /{} answered {}, request body: {}, response body: {}",
+                method,
+                path,
+                status,
+                request_body,
+                truncate(&response_body, limit)
+            ))
+        }
+        Ok(response) => Ok(response),
+        Err(err) => Err(format!("{}, request body: {}", err, request_body)),
+    }
+}
+
+/// The first `limit` characters of the body, mentioning its length if it's cut.
+fn truncate(body: &str, limit: usize) -> String {
+    match body.char_indices().nth(limit) {
+        Some((end, _)) => format!("{}... ({} bytes)", &body[..end], body.len()),
+        None => body.into(),
+    }
+}
+
+/// Turns a Toxiproxy URL (`http://toxiproxy:8474/`) into a `host:port` address. Plain addresses are
+/// returned as is.
+pub(crate) fn addr_from_url(url: &str) -> &str {
+    let addr = url.trim();
+    let addr = addr
+        .strip_prefix("http://")
+        .or_else(|| addr.strip_prefix("tcp://"))
+        .unwrap_or(addr);
+    addr.trim_end_matches('/')
+}
+
+/// Answer of the no-op client, as if the server accepted the request. Proxies created through it
+/// are remembered (listening on their upstream), to answer with them later.
+fn noop_response(
+    proxies: &Mutex<HashMap<String, Value>>,
+    method: &str,
+    path: &str,
+    body: Option<String>,
+) -> Result<Response, String> {
+    let segments = path.split('/').collect::<Vec<&str>>();
+    let payload = body
+        .as_ref()
+        .and_then(|body| serde_json::from_str::<Value>(body).ok());
+    let mut proxies = proxies
+        .lock()
+        .map_err(|err| format!("lock error: {}", err))?;
+
+    let response = match (method, &segments[..]) {
+        ("GET", ["version"]) => Value::String(NOOP_VERSION.into()),
+        ("GET", ["proxies"]) => json!(*proxies),
+        ("GET", ["proxies", _, "toxics"]) => json!([]),
+        ("GET", ["proxies", name]) => proxies
+            .get(*name)
+            .cloned()
+            .unwrap_or_else(|| unknown_noop_proxy(name)),
+        ("POST" | "PATCH", ["proxies", name]) => {
+            let mut proxy = proxies
+                .remove(*name)
+                .unwrap_or_else(|| unknown_noop_proxy(name));
+            if let (Some(proxy), Some(Value::Object(changes))) = (proxy.as_object_mut(), payload) {
+                proxy.extend(changes);
+            }
+            listen_on_upstream(&mut proxy);
+            proxies.insert((*name).to_owned(), proxy.clone());
+            proxy
+        }
+        ("DELETE", ["proxies", name]) => {
+            proxies.remove(*name);
+            Value::Null
+        }
+        ("POST", ["populate"]) => {
+            let mut populated = payload.unwrap_or_else(|| json!([]));
+            if let Some(populated) = populated.as_array_mut() {
+                for proxy in populated.iter_mut() {
+                    remember_noop_proxy(&mut proxies, proxy);
+                }
+            }
+            json!({ "proxies": populated })
+        }
+        ("POST", ["proxies"]) => {
+            let mut proxy = payload.unwrap_or(Value::Null);
+            remember_noop_proxy(&mut proxies, &mut proxy);
+            proxy
+        }
+        ("POST" | "PATCH", _) => payload.unwrap_or(Value::Null),
+        _ => Value::Null,
+    };
+
+    let body = match response {
+        Value::Null => String::new(),
+        Value::String(text) => text,
+        json => json.to_string(),
+    };
+
+    http::Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(body)
+        .map(Response::from)
+        .map_err(|err| format!("noop response error: {}", err))
+}
+
+/// Proxy answered with for a name the no-op client wasn't given.
+fn unknown_noop_proxy(name: &str) -> Value {
+    json!({
+        "name": name,
+        "listen": "127.0.0.1:0",
+        "upstream": "127.0.0.1:0",
+        "enabled": true,
+        "toxics": [],
+    })
+}
+
+fn remember_noop_proxy(proxies: &mut HashMap<String, Value>, proxy: &mut Value) {
+    listen_on_upstream(proxy);
+    if let Some(name) = proxy.get("name").and_then(Value::as_str) {
+        proxies.insert(name.to_owned(), proxy.clone());
+    }
+}
+
+fn listen_on_upstream(proxy: &mut Value) {
+    if let Some(upstream) = proxy.get("upstream").cloned() {
+        proxy["listen"] = upstream;
     }
 }
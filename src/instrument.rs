@@ -0,0 +1,215 @@
+//! Instrumentation of the requests sent to the server and of the operations on proxies. With the
+//! `tracing` feature, they run in `tracing` spans, so chaos actions show up in the traces of the
+//! application under test. With the `log` feature, the same is logged with the `log` crate under
+//! the `toxiproxy` target: operations at info level, requests at debug level. With the
+//! `opentelemetry` feature, the operations (toxics attached and removed, proxies enabled and
+//! disabled, scenario phases...) are OpenTelemetry spans of the global tracer provider, exported
+//! by the pipeline of the application (eg. over OTLP). Without any of these, this compiles away.
+
+use reqwest::blocking::Response;
+use std::fmt;
+
+/// What an operation acts on, recorded as span fields.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Fields<'a> {
+    pub(crate) proxy: Option<&'a str>,
+    pub(crate) toxic: Option<&'a str>,
+    pub(crate) toxic_type: Option<&'a str>,
+    pub(crate) scenario: Option<&'a str>,
+    pub(crate) phase: Option<&'a str>,
+}
+
+impl<'a> Fields<'a> {
+    pub(crate) fn proxy(proxy: &'a str) -> Self {
+        Self {
+            proxy: Some(proxy),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn toxic(mut self, toxic: &'a str) -> Self {
+        self.toxic = Some(toxic);
+        self
+    }
+
+    pub(crate) fn toxic_type(mut self, toxic_type: &'a str) -> Self {
+        self.toxic_type = Some(toxic_type);
+        self
+    }
+
+    pub(crate) fn phase(scenario: &'a str, phase: &'a str) -> Self {
+        Self {
+            scenario: Some(scenario),
+            phase: Some(phase),
+            ..Default::default()
+        }
+    }
+
+    /// Fields which are set, by name.
+    fn set(&self) -> impl Iterator<Item = (&'static str, &'a str)> {
+        IntoIterator::into_iter([
+            ("proxy", self.proxy),
+            ("toxic", self.toxic),
+            ("toxic_type", self.toxic_type),
+            ("scenario", self.scenario),
+            ("phase", self.phase),
+        ])
+        .filter_map(|(field, value)| value.map(|value| (field, value)))
+    }
+}
+
+impl fmt::Display for Fields<'_> {
+    /// As logged: ` proxy=db toxic=latency_downstream toxic_type=latency`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (field, value) in self.set() {
+            write!(f, " {}={}", field, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs an operation (eg. `populate`, `add_toxic`) in a `toxiproxy` span, recording its outcome.
+pub(crate) fn operation<F, T>(name: &'static str, fields: Fields, operation: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String>,
+{
+    let result = operation_span(name, fields, || telemetry_span(name, fields, operation));
+    log_operation(name, fields, &result);
+    result
+}
+
+/// Sends a request in a `toxiproxy_http` span, recording the status of the response or the error.
+pub(crate) fn request<F>(method: &str, path: &str, request: F) -> Result<Response, String>
+where
+    F: FnOnce() -> Result<Response, String>,
+{
+    let result = request_span(method, path, request);
+    log_request(method, path, &result);
+    result
+}
+
+#[cfg(feature = "tracing")]
+fn operation_span<F, T>(name: &'static str, fields: Fields, operation: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String>,
+{
+    let span = tracing::info_span!(
+        "toxiproxy",
+        operation = name,
+        proxy = tracing::field::Empty,
+        toxic = tracing::field::Empty,
+        toxic_type = tracing::field::Empty,
+        scenario = tracing::field::Empty,
+        phase = tracing::field::Empty,
+        outcome = tracing::field::Empty,
+    );
+    for (field, value) in fields.set() {
+        span.record(field, &value);
+    }
+    let result = span.in_scope(operation);
+    span.record("outcome", &outcome(&result));
+    result
+}
+
+#[cfg(not(feature = "tracing"))]
+fn operation_span<F, T>(_name: &'static str, _fields: Fields, operation: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String>,
+{
+    operation()
+}
+
+#[cfg(feature = "tracing")]
+fn request_span<F>(method: &str, path: &str, request: F) -> Result<Response, String>
+where
+    F: FnOnce() -> Result<Response, String>,
+{
+    let span = tracing::debug_span!(
+        "toxiproxy_http",
+        method,
+        path,
+        status = tracing::field::Empty,
+        outcome = tracing::field::Empty,
+    );
+    let result = span.in_scope(request);
+    if let Ok(response) = result.as_ref() {
+        span.record("status", &response.status().as_u16());
+    }
+    span.record("outcome", &outcome(&result));
+    result
+}
+
+#[cfg(not(feature = "tracing"))]
+fn request_span<F>(_method: &str, _path: &str, request: F) -> Result<Response, String>
+where
+    F: FnOnce() -> Result<Response, String>,
+{
+    request()
+}
+
+#[cfg(feature = "opentelemetry")]
+fn telemetry_span<F, T>(name: &'static str, fields: Fields, operation: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String>,
+{
+    use opentelemetry::trace::{Status, TraceContextExt, Tracer};
+    use opentelemetry::{global, KeyValue};
+
+    global::tracer("toxiproxy_rust").in_span(format!("toxiproxy.{}", name), |context| {
+        let span = context.span();
+        span.set_attribute(KeyValue::new("toxiproxy.operation", name));
+        for (field, value) in fields.set() {
+            span.set_attribute(KeyValue::new(
+                format!("toxiproxy.{}", field),
+                value.to_owned(),
+            ));
+        }
+
+        let result = operation();
+        if let Err(err) = result.as_ref() {
+            span.set_status(Status::error(err.clone()));
+        }
+        result
+    })
+}
+
+#[cfg(not(feature = "opentelemetry"))]
+fn telemetry_span<F, T>(_name: &'static str, _fields: Fields, operation: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String>,
+{
+    operation()
+}
+
+#[cfg(feature = "log")]
+fn log_operation<T>(name: &str, fields: Fields, result: &Result<T, String>) {
+    log::info!(target: "toxiproxy", "{}{}: {}", name, fields, outcome(result));
+}
+
+#[cfg(not(feature = "log"))]
+fn log_operation<T>(_name: &str, _fields: Fields, _result: &Result<T, String>) {}
+
+#[cfg(feature = "log")]
+fn log_request(method: &str, path: &str, result: &Result<Response, String>) {
+    match result {
+        Ok(response) => log::debug!(
+            target: "toxiproxy",
+            "{} {}: {}",
+            method,
+            path,
+            response.status().as_u16()
+        ),
+        Err(err) => log::debug!(target: "toxiproxy", "{} {}: {}", method, path, err),
+    }
+}
+
+#[cfg(not(feature = "log"))]
+fn log_request(_method: &str, _path: &str, _result: &Result<Response, String>) {}
+
+#[cfg(any(feature = "tracing", feature = "log"))]
+fn outcome<T>(result: &Result<T, String>) -> &str {
+    match result {
+        Ok(_) => "ok",
+        Err(err) => err,
+    }
+}
@@ -0,0 +1,195 @@
+//! Journals of the requests sent to the server, to answer "what exactly did this test do to the
+//! network?": a [`Recorder`] captures every request of its client (and of its proxies), and the
+//! [`Journal`] can be saved, and replayed against a client to reproduce the conditions.
+//!
+//! [`Recorder`]: struct.Recorder.html
+//! [`Journal`]: struct.Journal.html
+
+use super::client::*;
+use super::http_client::*;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Request sent to the server. The timestamp is in milliseconds since the Unix epoch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<String>,
+    pub timestamp_ms: u64,
+}
+
+impl JournalEntry {
+    pub(crate) fn new(method: &str, path: &str, payload: Option<String>) -> Self {
+        Self {
+            method: method.into(),
+            path: path.into(),
+            payload,
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_millis() as u64),
+        }
+    }
+}
+
+/// Requests in the order they were sent.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Journal {
+    pub entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Writes the journal as JSON.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let json =
+            serde_json::to_string_pretty(self).map_err(|err| format!("journal error: {}", err))?;
+        fs::write(path, json)
+            .map_err(|err| format!("cannot write journal {}: {}", path.display(), err))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read journal {}: {}", path.display(), err))?;
+        serde_json::from_str(&json)
+            .map_err(|err| format!("invalid journal {}: {}", path.display(), err))
+    }
+
+    /// Sends the requests again through the client, one after the other. Paths are sent as
+    /// recorded, so proxy names keep the namespace of the recording client.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{proxy::ProxyPack, TOXIPROXY};
+    /// let recorder = TOXIPROXY.record();
+    /// let proxy = recorder
+    ///     .client()
+    ///     .create_proxy(ProxyPack::new_ephemeral("journal_doc".into(), "localhost:2000".into()))
+    ///     .unwrap();
+    /// proxy.with_latency("downstream".into(), 100, 0, 1.0);
+    /// let journal = recorder.stop();
+    /// proxy.delete().unwrap();
+    ///
+    /// journal.replay(&TOXIPROXY).expect("journal is replayed");
+    /// assert_eq!(1, TOXIPROXY.find_proxy("journal_doc").unwrap().toxics().unwrap().len());
+    /// # TOXIPROXY.find_proxy("journal_doc").unwrap().delete().unwrap();
+    /// ```
+    pub fn replay(&self, client: &Client) -> Result<(), String> {
+        self.replay_entries(client, false)
+    }
+
+    /// Replays the journal like [`replay`], waiting between the requests as long as when they
+    /// were recorded.
+    ///
+    /// [`replay`]: #method.replay
+    pub fn replay_timed(&self, client: &Client) -> Result<(), String> {
+        self.replay_entries(client, true)
+    }
+
+    fn replay_entries(&self, client: &Client, timed: bool) -> Result<(), String> {
+        let mut previous: Option<u64> = None;
+        for entry in self.entries.iter() {
+            if let (true, Some(previous)) = (timed, previous) {
+                thread::sleep(Duration::from_millis(
+                    entry.timestamp_ms.saturating_sub(previous),
+                ));
+            }
+            previous = Some(entry.timestamp_ms);
+
+            let method = Method::from_str(&entry.method)
+                .map_err(|err| format!("invalid journal method {}: {}", entry.method, err))?;
+            send(
+                client.http_client(),
+                method,
+                &entry.path,
+                entry.payload.clone(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn send(
+    client: &Arc<Mutex<HttpClient>>,
+    method: Method,
+    path: &str,
+    payload: Option<String>,
+) -> Result<(), String> {
+    client
+        .lock()
+        .map_err(|err| format!("lock error: {}", err))?
+        .send(method, path, payload)
+        .map(|_| ())
+}
+
+/// Records the requests of its client, see [`Client::record`]. Recording stops when the recorder
+/// is stopped or dropped.
+///
+/// [`Client::record`]: ../client/struct.Client.html#method.record
+pub struct Recorder {
+    client: Client,
+    entries: Arc<Mutex<Vec<JournalEntry>>>,
+}
+
+impl Recorder {
+    /// Client whose requests (and the requests of its clones and proxies) are recorded.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Requests recorded so far.
+    pub fn journal(&self) -> Journal {
+        Journal {
+            entries: self
+                .entries
+                .lock()
+                .map(|entries| entries.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Stops recording, returning the recorded requests.
+    pub fn stop(self) -> Journal {
+        self.journal()
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Ok(mut client) = self.client.http_client().lock() {
+            client.set_journal(None);
+        }
+    }
+}
+
+impl Client {
+    /// Starts recording the requests sent through the client of the recorder (see
+    /// [`Recorder::client`]), a copy of this client with state of its own: requests sent through
+    /// this client meanwhile - eg. by other tests sharing `TOXIPROXY` - are not recorded, and
+    /// recorders don't interfere with each other.
+    ///
+    /// [`Recorder::client`]: ../journal/struct.Recorder.html#method.client
+    pub fn record(&self) -> Recorder {
+        let entries = Arc::new(Mutex::new(vec![]));
+        let mut http_client = self
+            .http_client()
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .fork();
+        http_client.set_journal(Some(entries.clone()));
+
+        Recorder {
+            client: self.with_http_client(http_client),
+            entries,
+        }
+    }
+}
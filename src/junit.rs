@@ -0,0 +1,172 @@
+//! JUnit-style XML reports of scenario and soak runs, so chaos runs show up in the test reports of
+//! CI systems: a test case per scenario phase ([`ScenarioReport::junit`]) or per soak iteration
+//! ([`SoakReport::junit`]), with its timing and errors.
+//!
+//! [`ScenarioReport::junit`]: ../scenario/struct.ScenarioReport.html#method.junit
+//! [`SoakReport::junit`]: ../experiment/struct.SoakReport.html#method.junit
+
+use super::experiment::*;
+use super::scenario::*;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// A `<testsuite>` element.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TestSuite {
+    pub name: String,
+    pub cases: Vec<TestCase>,
+}
+
+/// A `<testcase>` element, failed if it has errors.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub time: Duration,
+    pub errors: Vec<String>,
+}
+
+impl TestSuite {
+    pub fn failures(&self) -> usize {
+        self.cases
+            .iter()
+            .filter(|case| !case.errors.is_empty())
+            .count()
+    }
+
+    /// The suite as an XML document.
+    pub fn to_xml(&self) -> String {
+        let time = self.cases.iter().map(|case| case.time).sum::<Duration>();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{:.3}\">",
+            escape(&self.name),
+            self.cases.len(),
+            self.failures(),
+            time.as_secs_f64()
+        );
+
+        for case in self.cases.iter() {
+            let _ = write!(
+                xml,
+                "  <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                escape(&case.name),
+                escape(&self.name),
+                case.time.as_secs_f64()
+            );
+            match case.errors.first() {
+                None => xml.push_str("/>\n"),
+                Some(message) => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n    <failure message=\"{}\">{}</failure>\n  </testcase>",
+                        escape(message),
+                        escape(&case.errors.join("\n"))
+                    );
+                }
+            }
+        }
+
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+
+    /// Writes the suite to the file, creating its directory if needed.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| format!("cannot create {}: {}", dir.display(), err))?;
+        }
+        fs::write(path, self.to_xml())
+            .map_err(|err| format!("cannot write {}: {}", path.display(), err))
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl ScenarioReport {
+    /// The report as a test suite named after the scenario, with a test case per phase run (eg.
+    /// `phase 2: outage`). Errors of the run itself (eg. cleaning up) fail an extra `cleanup` case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::scenario::*;
+    /// let report = ScenarioReport {
+    ///     scenario: "checkout".into(),
+    ///     phases: vec![PhaseReport {
+    ///         name: "outage".into(),
+    ///         elapsed_ms: 1500,
+    ///         errors: vec!["proxy \"db\" not found".into()],
+    ///         ..Default::default()
+    ///     }],
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let xml = report.junit().to_xml();
+    /// assert!(xml.contains(
+    ///     "<testcase name=\"phase 1: outage\" classname=\"checkout\" time=\"1.500\">"
+    /// ));
+    /// assert!(xml.contains("<failure message=\"proxy &quot;db&quot; not found\">"));
+    /// ```
+    pub fn junit(&self) -> TestSuite {
+        let mut cases = self
+            .phases
+            .iter()
+            .enumerate()
+            .map(|(index, phase)| TestCase {
+                name: format!("phase {}: {}", index + 1, phase.name),
+                time: Duration::from_millis(phase.elapsed_ms),
+                errors: phase.errors.clone(),
+            })
+            .collect::<Vec<TestCase>>();
+        if !self.errors.is_empty() {
+            cases.push(TestCase {
+                name: "cleanup".into(),
+                time: Duration::default(),
+                errors: self.errors.clone(),
+            });
+        }
+
+        TestSuite {
+            name: self.scenario.clone(),
+            cases,
+        }
+    }
+}
+
+impl SoakReport {
+    /// The report as a test suite with the given name, with a test case per run (`iteration 1`,
+    /// `iteration 2`, ...).
+    pub fn junit(&self, name: &str) -> TestSuite {
+        TestSuite {
+            name: name.into(),
+            cases: self
+                .runs
+                .iter()
+                .enumerate()
+                .map(|(index, run)| TestCase {
+                    name: format!("iteration {}", index + 1),
+                    time: run.elapsed,
+                    errors: run.error.iter().cloned().collect(),
+                })
+                .collect(),
+        }
+    }
+}
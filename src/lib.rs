@@ -53,15 +53,78 @@
 #[macro_use]
 extern crate lazy_static;
 
+#[macro_use]
+mod macros;
+
+pub mod address;
+pub mod assertions;
+pub mod audit;
+#[cfg(feature = "criterion")]
+pub mod bench;
+pub mod chaos;
+pub mod cli;
 pub mod client;
+pub mod cluster;
+pub mod compat;
+pub mod config;
 mod consts;
+#[cfg(feature = "testcontainers")]
+pub mod container;
+pub mod diagnostics;
+pub mod discovery;
+#[cfg(feature = "download")]
+pub mod download;
+#[cfg(feature = "embedded")]
+pub mod embedded;
+pub mod error;
+pub mod experiment;
+pub mod factory;
+pub mod failure;
+pub mod golden;
+pub mod group;
+pub mod guard;
+pub mod hooks;
 mod http_client;
+mod instrument;
+pub mod journal;
+pub mod junit;
+pub mod measure;
+pub mod metrics;
+pub mod namespace;
+pub mod preflight;
+pub mod profile;
+pub mod prometheus;
 pub mod proxy;
+pub mod reconcile;
+pub mod recovery;
+mod rng;
+pub mod scenario;
+pub mod server;
+pub mod set;
+pub mod setup;
+pub mod snapshot;
+#[cfg(feature = "steps")]
+pub mod steps;
+#[cfg(feature = "proptest")]
+pub mod strategies;
+pub mod supervisor;
+pub mod table;
+#[cfg(feature = "teardown")]
+pub mod teardown;
+#[cfg(feature = "test_support")]
+pub mod test_support;
+pub mod timeline;
 pub mod toxic;
+pub mod verbosity;
+pub mod watcher;
 
 use client::*;
 
 lazy_static! {
-    /// Pre-built client using the default connection address.
-    pub static ref TOXIPROXY: Client = Client::new("127.0.0.1:8474");
+    /// Pre-built client using the address from the `TOXIPROXY_URL` environment variable, or the
+    /// default connection address. See [`Client::from_env`] and [`Client::set_address`].
+    ///
+    /// [`Client::from_env`]: client/struct.Client.html#method.from_env
+    /// [`Client::set_address`]: client/struct.Client.html#method.set_address
+    pub static ref TOXIPROXY: Client = Client::from_env();
 }
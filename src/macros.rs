@@ -0,0 +1,92 @@
+/// Returns early from the current function (typically a test) if the Toxiproxy server is not
+/// available, printing the reason. Checks `toxiproxy_rust::TOXIPROXY` unless a client is given.
+///
+/// # Examples
+///
+/// ```
+/// # use toxiproxy_rust::skip_if_unavailable;
+/// fn test_service_with_latency() {
+///     skip_if_unavailable!();
+///
+///     /* Example test:
+///        toxiproxy_rust::TOXIPROXY.find_and_reset_proxy("socket").unwrap() ...
+///     */
+/// }
+///
+/// fn test_service_on_custom_server() {
+///     let client = toxiproxy_rust::client::Client::new("127.0.0.1:1");
+///     skip_if_unavailable!(client);
+///
+///     unreachable!();
+/// }
+/// # test_service_with_latency();
+/// # test_service_on_custom_server();
+/// ```
+#[macro_export]
+macro_rules! skip_if_unavailable {
+    () => {
+        $crate::skip_if_unavailable!($crate::TOXIPROXY)
+    };
+    ($client:expr) => {
+        if let Err(reason) = $client.health_check() {
+            eprintln!("Skipped, Toxiproxy is not available: {}", reason);
+            return;
+        }
+    };
+}
+
+/// Builds a [`ProxyPack`]: `name => listen -> upstream`, or `name -> upstream` for a proxy
+/// listening on a port picked by the server. Each part is a string literal or any other single
+/// token tree (eg. a variable, or an expression in parentheses).
+///
+/// # Examples
+///
+/// ```
+/// # use toxiproxy_rust::proxy;
+/// let db = proxy!("db" => "localhost:2001" -> "db:5432");
+/// assert_eq!("localhost:2001", db.listen);
+///
+/// let host = "cache";
+/// let cache = proxy!("cache" -> (format!("{}:6379", host)));
+/// assert_eq!("cache:6379", cache.upstream);
+/// ```
+///
+/// [`ProxyPack`]: proxy/struct.ProxyPack.html
+#[macro_export]
+macro_rules! proxy {
+    ($name:tt => $listen:tt -> $upstream:tt) => {
+        $crate::proxy::ProxyPack::new(($name).into(), ($listen).into(), ($upstream).into())
+    };
+    ($name:tt -> $upstream:tt) => {
+        $crate::proxy::ProxyPack::new_ephemeral(($name).into(), ($upstream).into())
+    };
+}
+
+/// Populates proxies declared as with [`proxy!`], on `toxiproxy_rust::TOXIPROXY` unless a client
+/// is given. Returns the result of [`Client::populate`].
+///
+/// # Examples
+///
+/// ```
+/// # use toxiproxy_rust::populate;
+/// let proxies = populate!({
+///     "socket" => "localhost:2001" -> "localhost:2000",
+///     "populate_doc" -> "localhost:2000",
+/// })
+/// .expect("proxies are populated");
+///
+/// assert_eq!(2, proxies.len());
+/// # proxies[1].delete().unwrap();
+/// ```
+///
+/// [`proxy!`]: macro.proxy.html
+/// [`Client::populate`]: client/struct.Client.html#method.populate
+#[macro_export]
+macro_rules! populate {
+    ({ $($name:tt $(=> $listen:tt)? -> $upstream:tt),* $(,)? }) => {
+        $crate::populate!($crate::TOXIPROXY, { $($name $(=> $listen)? -> $upstream),* })
+    };
+    ($client:expr, { $($name:tt $(=> $listen:tt)? -> $upstream:tt),* $(,)? }) => {
+        $client.populate(vec![$($crate::proxy!($name $(=> $listen)? -> $upstream)),*])
+    };
+}
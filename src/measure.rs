@@ -0,0 +1,187 @@
+//! Latencies of operations run under toxics, collected into histograms - eg. to assert a p99
+//! budget of a query while the database link is degraded.
+
+use super::proxy::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Collects the latencies of named operations, see [`Proxy::measure`]. It can be shared between
+/// threads.
+///
+/// [`Proxy::measure`]: ../proxy/struct.Proxy.html#method.measure
+#[derive(Debug, Default)]
+pub struct LatencyRecorder {
+    samples: Mutex<BTreeMap<String, Vec<Duration>>>,
+}
+
+impl LatencyRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs the operation, recording how long it took under the given name.
+    pub fn time<F, T>(&self, name: &str, operation: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let started = Instant::now();
+        let value = operation();
+        self.record(name, started.elapsed());
+        value
+    }
+
+    /// Records a latency measured elsewhere.
+    pub fn record(&self, name: &str, latency: Duration) {
+        if let Ok(mut samples) = self.samples.lock() {
+            samples.entry(name.to_owned()).or_default().push(latency);
+        }
+    }
+
+    /// Histograms of the latencies recorded so far, by operation name.
+    pub fn histograms(&self) -> BTreeMap<String, Histogram> {
+        self.samples
+            .lock()
+            .map(|samples| {
+                samples
+                    .iter()
+                    .map(|(name, latencies)| (name.clone(), Histogram::new(latencies.clone())))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Latencies of an operation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    /// Sorted latencies.
+    samples: Vec<Duration>,
+}
+
+impl Histogram {
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::measure::Histogram;
+    /// # use std::time::Duration;
+    /// let histogram = Histogram::new((1..=100).map(Duration::from_millis).collect());
+    ///
+    /// assert_eq!(Duration::from_millis(50), histogram.percentile(50.0));
+    /// assert_eq!(Duration::from_millis(99), histogram.p99());
+    /// ```
+    pub fn new(mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        Self { samples }
+    }
+
+    pub fn count(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn min(&self) -> Duration {
+        self.samples.first().copied().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples.last().copied().unwrap_or_default()
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.samples.len() {
+            0 => Duration::default(),
+            count => self.samples.iter().sum::<Duration>() / count as u32,
+        }
+    }
+
+    /// Latency the given percentage (0 to 100) of the samples are at or below (nearest rank).
+    pub fn percentile(&self, percent: f64) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::default();
+        }
+        let rank = (percent.clamp(0.0, 100.0) / 100.0 * self.samples.len() as f64).ceil() as usize;
+        self.samples[rank.clamp(1, self.samples.len()) - 1]
+    }
+
+    pub fn p50(&self) -> Duration {
+        self.percentile(50.0)
+    }
+
+    pub fn p99(&self) -> Duration {
+        self.percentile(99.0)
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "count {}, min {:?}, mean {:?}, p50 {:?}, p99 {:?}, max {:?}",
+            self.count(),
+            self.min(),
+            self.mean(),
+            self.p50(),
+            self.p99(),
+            self.max()
+        )
+    }
+}
+
+/// Value of a measured call, with the histograms of the operations it timed.
+#[derive(Debug)]
+pub struct Measurements<T> {
+    pub value: T,
+    pub histograms: BTreeMap<String, Histogram>,
+}
+
+impl<T> Measurements<T> {
+    /// Histogram of an operation, empty if it was never timed.
+    pub fn histogram(&self, name: &str) -> Histogram {
+        self.histograms.get(name).cloned().unwrap_or_default()
+    }
+}
+
+impl Proxy {
+    /// Same as [`apply`], handing a recorder to the call to time its operations with.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new(
+    /// #    "socket".into(),
+    /// #    "localhost:2001".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// let measurements = toxiproxy_rust::TOXIPROXY
+    ///   .find_proxy("socket")
+    ///   .unwrap()
+    ///   .with_latency("downstream".into(), 100, 0, 1.0)
+    ///   .measure(|recorder| {
+    ///     for _ in 0..10 {
+    ///       recorder.time("db_query", || {
+    ///         /* Example operation:
+    ///            MyService::Db::query(params)
+    ///         */
+    ///       });
+    ///     }
+    ///   })
+    ///   .unwrap();
+    ///
+    /// let histogram = measurements.histogram("db_query");
+    /// assert_eq!(10, histogram.count());
+    /// assert!(histogram.p99() < std::time::Duration::from_millis(500));
+    /// ```
+    ///
+    /// [`apply`]: #method.apply
+    pub fn measure<F, T>(&self, closure: F) -> Result<Measurements<T>, String>
+    where
+        F: FnOnce(&LatencyRecorder) -> T,
+    {
+        let recorder = LatencyRecorder::new();
+        self.apply(|| closure(&recorder)).map(|value| Measurements {
+            value,
+            histograms: recorder.histograms(),
+        })
+    }
+}
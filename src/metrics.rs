@@ -0,0 +1,117 @@
+//! Client-side metrics of the requests sent to the server - eg. to report at the end of a suite
+//! how much time orchestrating Toxiproxy added to CI.
+
+use super::client::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Requests sent by a client, its clones and its proxies. Endpoints are the method and the path
+/// with proxy and toxic names left out, eg. `POST /proxies/{proxy}/toxics`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ApiMetrics {
+    pub requests: u64,
+    /// Requests which failed to be sent, or were answered with an error status.
+    pub failures: u64,
+    pub total_time: Duration,
+    pub endpoints: BTreeMap<String, EndpointMetrics>,
+}
+
+/// Requests sent to an endpoint.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EndpointMetrics {
+    pub requests: u64,
+    pub failures: u64,
+    pub total_time: Duration,
+    pub max_time: Duration,
+}
+
+impl EndpointMetrics {
+    pub fn mean_time(&self) -> Duration {
+        match self.requests {
+            0 => Duration::default(),
+            requests => self.total_time / requests as u32,
+        }
+    }
+}
+
+impl ApiMetrics {
+    pub(crate) fn record(&mut self, method: &str, path: &str, elapsed: Duration, failed: bool) {
+        self.requests += 1;
+        self.total_time += elapsed;
+
+        let endpoint = self.endpoints.entry(endpoint(method, path)).or_default();
+        endpoint.requests += 1;
+        endpoint.total_time += elapsed;
+        endpoint.max_time = endpoint.max_time.max(elapsed);
+        if failed {
+            self.failures += 1;
+            endpoint.failures += 1;
+        }
+    }
+}
+
+impl fmt::Display for ApiMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} requests to Toxiproxy ({} failed) in {:?}",
+            self.requests, self.failures, self.total_time
+        )?;
+        for (name, endpoint) in self.endpoints.iter() {
+            writeln!(
+                f,
+                "  {}: {} requests ({} failed), total {:?}, mean {:?}, max {:?}",
+                name,
+                endpoint.requests,
+                endpoint.failures,
+                endpoint.total_time,
+                endpoint.mean_time(),
+                endpoint.max_time
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Method and path with the names of proxies and toxics replaced by placeholders.
+fn endpoint(method: &str, path: &str) -> String {
+    let mut segments = path.split('/').collect::<Vec<&str>>();
+    for index in 1..segments.len() {
+        match segments[index - 1] {
+            "proxies" => segments[index] = "{proxy}",
+            "toxics" => segments[index] = "{toxic}",
+            _ => {}
+        }
+    }
+    format!("{} /{}", method, segments.join("/"))
+}
+
+impl Client {
+    /// Metrics of the requests sent so far by the client, its clones and its proxies (clients
+    /// scoped from `TOXIPROXY` share its metrics).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::client::Client;
+    /// let client = Client::from_env();
+    /// client.all().unwrap();
+    ///
+    /// let metrics = client.metrics();
+    /// assert_eq!(1, metrics.endpoints["GET /proxies"].requests);
+    /// println!("{}", metrics);
+    /// ```
+    pub fn metrics(&self) -> ApiMetrics {
+        self.http_client()
+            .lock()
+            .map(|client| client.metrics())
+            .unwrap_or_default()
+    }
+
+    pub fn reset_metrics(&self) {
+        if let Ok(client) = self.http_client().lock() {
+            client.reset_metrics();
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Namespaces keeping proxy names of concurrently running tests (threads, test binaries or CI jobs
+//! sharing a server) apart. A namespaced client (see [`Client::namespaced`]) maps plain names like
+//! `"socket"` to namespaced ones transparently.
+//!
+//! [`Client::namespaced`]: ../client/struct.Client.html#method.namespaced
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const SEPARATOR: &str = "__";
+
+static NAMESPACE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    static ref PROCESS_ID: String = format!(
+        "{}{:06x}",
+        process::id(),
+        RandomState::new().build_hasher().finish() & 0xff_ffff
+    );
+}
+
+/// Suffix appended to proxy names.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Namespace(String);
+
+impl Namespace {
+    /// Creates a namespace with a fixed label.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::namespace::Namespace;
+    /// let namespace = Namespace::new("ci_job_42");
+    /// assert_eq!("socket__ci_job_42", namespace.qualify("socket"));
+    /// ```
+    pub fn new(label: &str) -> Self {
+        Self(label.into())
+    }
+
+    /// Namespace shared by everything in the current process, unique across processes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::namespace::Namespace;
+    /// assert_eq!(Namespace::per_process(), Namespace::per_process());
+    /// ```
+    pub fn per_process() -> Self {
+        Self(PROCESS_ID.clone())
+    }
+
+    /// Namespace unique to this call, eg. one per test.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::namespace::Namespace;
+    /// assert_ne!(Namespace::unique(), Namespace::unique());
+    /// ```
+    pub fn unique() -> Self {
+        Self(format!(
+            "{}_{}",
+            *PROCESS_ID,
+            NAMESPACE_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ))
+    }
+
+    pub fn label(&self) -> &str {
+        &self.0
+    }
+
+    /// Full proxy name for a plain name within the namespace.
+    pub fn qualify(&self, name: &str) -> String {
+        format!("{}{}{}", name, SEPARATOR, self.0)
+    }
+
+    /// Plain name of a full proxy name, if it belongs to the namespace.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::namespace::Namespace;
+    /// let namespace = Namespace::new("ci");
+    /// assert_eq!(Some("socket"), namespace.strip("socket__ci"));
+    /// assert_eq!(None, namespace.strip("socket__other"));
+    /// ```
+    pub fn strip<'a>(&self, full_name: &'a str) -> Option<&'a str> {
+        full_name
+            .strip_suffix(self.0.as_str())
+            .and_then(|name| name.strip_suffix(SEPARATOR))
+    }
+}
@@ -0,0 +1,160 @@
+//! Opt-in validation of proxy configurations before anything is created on the server, see
+//! [`Client::preflight`]: malformed addresses, upstreams which can't be resolved, and loopback
+//! addresses which mean something else on a server running elsewhere (eg. `127.0.0.1` as the
+//! upstream of a Toxiproxy in a container is the container itself, not the host).
+//!
+//! [`Client::preflight`]: ../client/struct.Client.html#method.preflight
+
+use super::address::*;
+use super::client::*;
+use super::proxy::*;
+use std::fmt;
+use std::net::{IpAddr, ToSocketAddrs};
+
+/// Problems found in proxy configurations: errors would make creating the proxies fail (or the
+/// proxies useless), warnings are likely mistakes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Preflight {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl Preflight {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Ok with the warnings, or the errors (joined).
+    pub fn into_result(self) -> Result<Vec<String>, String> {
+        if self.is_ok() {
+            Ok(self.warnings)
+        } else {
+            Err(self.errors.join("; "))
+        }
+    }
+}
+
+impl fmt::Display for Preflight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for error in self.errors.iter() {
+            writeln!(f, "error: {}", error)?;
+        }
+        for warning in self.warnings.iter() {
+            writeln!(f, "warning: {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_loopback(host: &str) -> bool {
+    host == "localhost" || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+impl Client {
+    /// Checks the proxy configurations, without sending anything to the server: every listen and
+    /// upstream address is parsed and every upstream resolved. An upstream which can't be
+    /// resolved is an error with a server on this machine, and a warning otherwise (the server may
+    /// resolve names this machine can't, eg. compose services). With a server elsewhere, loopback
+    /// upstreams and listen addresses are reported too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{client::Client, proxy::ProxyPack};
+    /// let client = Client::new("127.0.0.1:8474");
+    /// let preflight = client.preflight(&[
+    ///     ProxyPack::new("db".into(), "localhost:2001".into(), "localhost:5432".into()),
+    ///     ProxyPack::new("cache".into(), "localhost".into(), "cache.invalid:6379".into()),
+    /// ]);
+    ///
+    /// assert_eq!(2, preflight.errors.len());
+    /// assert!(preflight.errors[0].starts_with("proxy \"cache\": invalid listen address"));
+    /// ```
+    pub fn preflight(&self, proxies: &[ProxyPack]) -> Preflight {
+        let server = self.address().ok();
+        let local = server.is_none_or(|server| server.ip().is_loopback());
+        let mut preflight = Preflight::default();
+
+        for proxy in proxies.iter() {
+            let name = &proxy.name;
+            match proxy.listen.parse::<Listen>() {
+                Err(err) => preflight
+                    .errors
+                    .push(format!("proxy {:?}: invalid listen address: {}", name, err)),
+                Ok(listen) if !local => {
+                    if split_host_port(listen.as_str()).is_ok_and(|(host, _)| is_loopback(&host)) {
+                        preflight.warnings.push(format!(
+                            "proxy {:?}: listens on {} of the Toxiproxy server at {}, which only \
+                             the server's own host can connect to: listen on 0.0.0.0 instead",
+                            name,
+                            listen,
+                            server.map(|server| server.to_string()).unwrap_or_default()
+                        ));
+                    }
+                }
+                Ok(_) => {}
+            }
+
+            let upstream = match proxy.upstream.parse::<Upstream>() {
+                Ok(upstream) => upstream,
+                Err(err) => {
+                    preflight.errors.push(format!(
+                        "proxy {:?}: invalid upstream address: {}",
+                        name, err
+                    ));
+                    continue;
+                }
+            };
+            if let Err(err) = upstream.as_str().to_socket_addrs() {
+                let problem = format!(
+                    "proxy {:?}: upstream {} cannot be resolved: {}",
+                    name, upstream, err
+                );
+                if local {
+                    preflight.errors.push(problem);
+                } else {
+                    preflight
+                        .warnings
+                        .push(format!("{} (the Toxiproxy server may resolve it)", problem));
+                }
+            }
+            if !local
+                && split_host_port(upstream.as_str()).is_ok_and(|(host, _)| is_loopback(&host))
+            {
+                preflight.warnings.push(format!(
+                    "proxy {:?}: upstream {} is the loopback of the Toxiproxy server, not of this \
+                     machine (in a container, use the service name or host.docker.internal)",
+                    name, upstream
+                ));
+            }
+        }
+
+        preflight
+    }
+
+    /// Runs the [`preflight`] checks, and [`populate`]s the proxies if there are no errors.
+    /// Warnings are reported on stderr.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::proxy::ProxyPack;
+    /// let proxies = toxiproxy_rust::TOXIPROXY
+    ///     .populate_checked(vec![ProxyPack::new(
+    ///         "socket_checked".into(),
+    ///         "localhost:2005".into(),
+    ///         "localhost:2000".into(),
+    ///     )])
+    ///     .expect("proxies are valid");
+    /// # proxies[0].delete().unwrap();
+    /// ```
+    ///
+    /// [`preflight`]: #method.preflight
+    /// [`populate`]: #method.populate
+    pub fn populate_checked(&self, proxies: Vec<ProxyPack>) -> Result<Vec<Proxy>, String> {
+        for warning in self.preflight(&proxies).into_result()? {
+            eprintln!("warning: {}", warning);
+        }
+        self.populate(proxies)
+    }
+}
@@ -0,0 +1,214 @@
+//! Degradation profiles: toxics applied to every proxy whose name matches a pattern, kept as shared
+//! files (eg. by SREs, for a staging environment):
+//!
+//! ```json
+//! {
+//!   "name": "slow network",
+//!   "rules": [
+//!     {"proxies": "*", "toxics": [{"type": "latency", "attributes": {"latency": 100}}]},
+//!     {"proxies": "db_*", "toxics": [{"type": "bandwidth", "attributes": {"rate": 100}}]}
+//!   ]
+//! }
+//! ```
+//!
+//! Patterns match the whole proxy name (in the client's namespace), `*` standing for any sequence
+//! of characters and `?` for any single one. Toxic fields default as in config files (see
+//! [`config`](../config/index.html)), and the toxics are registered with a `profile_` prefix, so
+//! [`Client::clear_profile`] removes them without touching others.
+//!
+//! [`Client::clear_profile`]: ../client/struct.Client.html#method.clear_profile
+
+use super::client::*;
+use super::config::*;
+use super::proxy::*;
+use super::toxic::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Prefix of the names of the toxics registered by profiles.
+pub const PROFILE_TOXIC_PREFIX: &str = "profile_";
+
+/// Rules applied in order: when several rules register a toxic of the same name on a proxy, the
+/// last one wins.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(default)]
+    pub name: String,
+    pub rules: Vec<ProfileRule>,
+}
+
+/// Toxics for the proxies matching a name pattern.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileRule {
+    pub proxies: String,
+    pub toxics: Vec<ToxicPack>,
+}
+
+impl Profile {
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::profile::Profile;
+    /// let profile = Profile::from_json(r#"{"rules": [
+    ///     {"proxies": "db_*", "toxics": [{"type": "latency", "attributes": {"latency": 100}}]}
+    /// ]}"#).expect("profile is valid");
+    ///
+    /// assert_eq!("latency_downstream", profile.rules[0].toxics[0].name);
+    /// ```
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str::<Self>(json)
+            .map(Self::with_default_names)
+            .map_err(|err| format!("invalid profile: {}", err))
+    }
+
+    /// Loads a profile file, in any of the formats of config files (see [`ConfigFormat`]),
+    /// environment variables expanded.
+    ///
+    /// [`ConfigFormat`]: ../config/enum.ConfigFormat.html
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read profile {}: {}", path.display(), err))?;
+        let content = expand_env(&content)
+            .map_err(|err| format!("cannot load profile {}: {}", path.display(), err))?;
+
+        let profile = match ConfigFormat::from_path(path)? {
+            ConfigFormat::Json => {
+                serde_json::from_str::<Self>(&content).map_err(|err| err.to_string())
+            }
+            #[cfg(feature = "toml")]
+            ConfigFormat::Toml => toml::from_str::<Self>(&content).map_err(|err| err.to_string()),
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => {
+                serde_yaml::from_str::<Self>(&content).map_err(|err| err.to_string())
+            }
+        }
+        .map_err(|err| format!("invalid profile {}: {}", path.display(), err))?;
+
+        Ok(profile.with_default_names())
+    }
+
+    /// Toxics for the proxy with the given name, named as registered.
+    pub fn toxics_for(&self, proxy: &str) -> Vec<ToxicPack> {
+        let mut toxics: Vec<ToxicPack> = vec![];
+        for rule in self
+            .rules
+            .iter()
+            .filter(|rule| matches(&rule.proxies, proxy))
+        {
+            for toxic in rule.toxics.iter() {
+                let mut toxic = toxic.clone();
+                toxic.name = format!("{}{}", PROFILE_TOXIC_PREFIX, toxic.name);
+                toxics.retain(|other| other.name != toxic.name);
+                toxics.push(toxic);
+            }
+        }
+        toxics
+    }
+
+    fn with_default_names(mut self) -> Self {
+        for rule in self.rules.iter_mut() {
+            rule.toxics = rule
+                .toxics
+                .drain(..)
+                .map(ToxicPack::or_default_name)
+                .collect();
+        }
+        self
+    }
+}
+
+impl Client {
+    /// Applies a profile to the proxies (in the client's namespace), replacing the toxics of a
+    /// previously applied profile. Returns the proxies which got toxics, sorted by name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{profile::Profile, proxy::ProxyPack, TOXIPROXY};
+    /// # TOXIPROXY.populate(vec![ProxyPack::new_ephemeral(
+    /// #    "profile_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]).unwrap();
+    /// let profile = Profile::from_json(r#"{"rules": [
+    ///     {"proxies": "profile_*", "toxics": [
+    ///         {"type": "latency", "attributes": {"latency": 100}}
+    ///     ]}
+    /// ]}"#).unwrap();
+    ///
+    /// let proxies = TOXIPROXY.apply_profile(&profile).expect("profile is applied");
+    /// assert_eq!("profile_latency_downstream", proxies[0].toxics().unwrap()[0].name);
+    ///
+    /// TOXIPROXY.clear_profile().expect("profile is cleared");
+    /// # TOXIPROXY.find_proxy("profile_doc").unwrap().delete().unwrap();
+    /// ```
+    pub fn apply_profile(&self, profile: &Profile) -> Result<Vec<Proxy>, String> {
+        let mut proxies = vec![];
+        for (name, proxy) in self.all()? {
+            clear_profile_toxics(&proxy)?;
+
+            let toxics = profile.toxics_for(&name);
+            if toxics.is_empty() {
+                continue;
+            }
+            for toxic in toxics {
+                proxy.try_create_toxic(toxic)?;
+            }
+            proxies.push(proxy);
+        }
+
+        proxies.sort_by(|a, b| a.proxy_pack.name.cmp(&b.proxy_pack.name));
+        Ok(proxies)
+    }
+
+    /// Removes the toxics registered by profiles from the proxies (in the client's namespace).
+    pub fn clear_profile(&self) -> Result<(), String> {
+        for proxy in self.all()?.values() {
+            clear_profile_toxics(proxy)?;
+        }
+        Ok(())
+    }
+}
+
+fn clear_profile_toxics(proxy: &Proxy) -> Result<(), String> {
+    for toxic in proxy.toxics()? {
+        if toxic.name.starts_with(PROFILE_TOXIC_PREFIX) {
+            proxy.delete_toxic(&toxic.name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether the name matches the pattern, `*` matching any sequence and `?` any character.
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let name = name.chars().collect::<Vec<char>>();
+    // Position after the last `*`, and the position in the name it's matched up to.
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    backtrack = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
@@ -0,0 +1,241 @@
+//! Counters of the server, read from its Prometheus endpoint (`GET /metrics`, served when the
+//! server runs with `-proxy-metrics`). Comparing them before and after a test catches tests which
+//! believe they exercised a toxic but never connected through the proxy, see
+//! [`Proxy::assert_traffic`].
+//!
+//! [`Proxy::assert_traffic`]: ../proxy/struct.Proxy.html#method.assert_traffic
+
+use super::client::*;
+use super::http_client::*;
+use super::proxy::*;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Bytes received by a proxy, labelled with `proxy` and `direction` (`upstream` for the bytes
+/// sent by clients, `downstream` for the bytes sent by the upstream).
+pub const PROXY_RECEIVED_BYTES: &str = "toxiproxy_proxy_received_bytes_total";
+/// Bytes sent by a proxy, labelled like [`PROXY_RECEIVED_BYTES`].
+///
+/// [`PROXY_RECEIVED_BYTES`]: constant.PROXY_RECEIVED_BYTES.html
+pub const PROXY_SENT_BYTES: &str = "toxiproxy_proxy_sent_bytes_total";
+
+/// Value of a metric with a set of labels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub labels: BTreeMap<String, String>,
+    pub value: f64,
+}
+
+/// Metrics of the server at a point in time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServerMetrics {
+    pub samples: Vec<Sample>,
+}
+
+impl ServerMetrics {
+    /// Parses the Prometheus text format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::prometheus::ServerMetrics;
+    /// let metrics = ServerMetrics::parse(
+    ///     "# TYPE toxiproxy_proxy_received_bytes_total counter\n\
+    ///      toxiproxy_proxy_received_bytes_total{direction=\"upstream\",proxy=\"db\"} 120\n\
+    ///      toxiproxy_proxy_received_bytes_total{direction=\"downstream\",proxy=\"db\"} 4096\n",
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(4216.0, metrics.sum("toxiproxy_proxy_received_bytes_total", &[("proxy", "db")]));
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, String> {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| parse_sample(line).ok_or_else(|| format!("invalid metric line: {}", line)))
+            .collect::<Result<Vec<Sample>, String>>()
+            .map(|samples| Self { samples })
+    }
+
+    /// Sum of the samples of the metric having the given labels (and any others), 0 if there's
+    /// none.
+    pub fn sum(&self, name: &str, labels: &[(&str, &str)]) -> f64 {
+        self.samples
+            .iter()
+            .filter(|sample| sample.name == name)
+            .filter(|sample| {
+                labels.iter().all(|(label, value)| {
+                    sample.labels.get(*label).map(String::as_str) == Some(value)
+                })
+            })
+            .map(|sample| sample.value)
+            .sum()
+    }
+
+    /// How much the metric (summed like [`sum`]) grew since the earlier metrics.
+    ///
+    /// [`sum`]: #method.sum
+    pub fn delta(&self, earlier: &ServerMetrics, name: &str, labels: &[(&str, &str)]) -> f64 {
+        self.sum(name, labels) - earlier.sum(name, labels)
+    }
+
+    /// Asserts the metric grew by at least `min` since the earlier metrics.
+    #[track_caller]
+    pub fn assert_delta_at_least(
+        &self,
+        earlier: &ServerMetrics,
+        name: &str,
+        labels: &[(&str, &str)],
+        min: f64,
+    ) -> &Self {
+        let delta = self.delta(earlier, name, labels);
+        if delta < min {
+            panic!(
+                "expected {}{} to grow by at least {}, it grew by {}",
+                name,
+                describe_labels(labels),
+                min,
+                delta
+            );
+        }
+        self
+    }
+}
+
+fn describe_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let labels = labels
+        .iter()
+        .map(|(label, value)| format!("{}={:?}", label, value))
+        .collect::<Vec<String>>();
+    format!("{{{}}}", labels.join(","))
+}
+
+/// `name{label="value",...} value [timestamp]`
+fn parse_sample(line: &str) -> Option<Sample> {
+    let name_end = line.find(|c: char| c == '{' || c.is_whitespace())?;
+    let name = line[..name_end].to_owned();
+    let mut rest = &line[name_end..];
+
+    let mut labels = BTreeMap::new();
+    if let Some(mut inner) = rest.strip_prefix('{') {
+        loop {
+            inner = inner.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+            if let Some(after) = inner.strip_prefix('}') {
+                rest = after;
+                break;
+            }
+            let (label, after) = inner.split_once('=')?;
+            let (value, after) = parse_quoted(after.trim_start())?;
+            labels.insert(label.trim().to_owned(), value);
+            inner = after;
+        }
+    }
+
+    let value = rest.split_whitespace().next()?.parse::<f64>().ok()?;
+    Some(Sample {
+        name,
+        labels,
+        value,
+    })
+}
+
+/// Label value with its escapes resolved, and what follows the closing quote.
+fn parse_quoted(text: &str) -> Option<(String, &str)> {
+    let mut chars = text.strip_prefix('"')?.char_indices();
+    let mut value = String::new();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &text[index + 2..])),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                escaped => value.push(escaped),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+impl Client {
+    /// Metrics of the server. Fails if the server doesn't serve them (it needs to run with
+    /// `-proxy-metrics`).
+    pub fn server_metrics(&self) -> Result<ServerMetrics, String> {
+        fetch(self.http_client())
+    }
+}
+
+fn fetch(client: &Arc<Mutex<HttpClient>>) -> Result<ServerMetrics, String> {
+    let response = client
+        .lock()
+        .map_err(|err| format!("lock error: {}", err))?
+        .get("metrics")?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "unexpected status for metrics request: {}, is the server running with -proxy-metrics?",
+            response.status()
+        ));
+    }
+
+    let body = response
+        .text()
+        .map_err(|err| format!("metrics response cannot be read: {}", err))?;
+    ServerMetrics::parse(&body)
+}
+
+impl Proxy {
+    /// Runs the closure and asserts bytes went through the proxy meanwhile (according to
+    /// [`PROXY_RECEIVED_BYTES`]) - ie. the code under test really connected through it, and so
+    /// met its toxics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{proxy::ProxyPack, TOXIPROXY};
+    /// # use std::io::Write;
+    /// # let upstream = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    /// # let proxy = TOXIPROXY
+    /// #     .create_proxy(ProxyPack::new_ephemeral(
+    /// #         "traffic_doc".into(),
+    /// #         upstream.local_addr().unwrap().to_string(),
+    /// #     ))
+    /// #     .unwrap();
+    /// # let addr = proxy.proxy_pack.listen.clone();
+    /// proxy.with_latency("downstream".into(), 10, 0, 1.0).assert_traffic(|| {
+    ///     let mut stream = std::net::TcpStream::connect(&addr).unwrap();
+    ///     stream.write_all(b"ping").unwrap();
+    ///     # std::thread::sleep(std::time::Duration::from_millis(100));
+    /// });
+    /// # proxy.delete().unwrap();
+    /// ```
+    ///
+    /// [`PROXY_RECEIVED_BYTES`]: ../prometheus/constant.PROXY_RECEIVED_BYTES.html
+    #[track_caller]
+    pub fn assert_traffic<F, T>(&self, closure: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let before = self.expect_metrics();
+        let result = closure();
+        let after = self.expect_metrics();
+
+        let labels = [("proxy", self.proxy_pack.name.as_str())];
+        if after.delta(&before, PROXY_RECEIVED_BYTES, &labels) <= 0.0 {
+            panic!(
+                "expected traffic through proxy {:?}, but it received no bytes",
+                self.proxy_pack.name
+            );
+        }
+        result
+    }
+
+    #[track_caller]
+    fn expect_metrics(&self) -> ServerMetrics {
+        fetch(self.http_client())
+            .unwrap_or_else(|err| panic!("cannot fetch server metrics: {}", err))
+    }
+}
@@ -0,0 +1,290 @@
+//! Declarative desired-state setup: the full set of proxies and toxics is given, and only what
+//! differs on the server is changed - so running the setup again is cheap. The same comparison
+//! detects drift of a shared environment without changing anything, see [`Client::diff`].
+//!
+//! [`Client::diff`]: ../client/struct.Client.html#method.diff
+
+use super::address::*;
+use super::assertions::*;
+use super::client::*;
+use super::proxy::*;
+use super::toxic::*;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Differences between expected proxies and the server state, see [`Client::diff`].
+///
+/// [`Client::diff`]: ../client/struct.Client.html#method.diff
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StateDiff {
+    /// Expected proxies which don't exist on the server.
+    pub missing: Vec<String>,
+    /// Proxies on the server which are not expected.
+    pub extra: Vec<String>,
+    /// Proxies which exist but differ from the expected ones.
+    pub differing: Vec<ProxyDrift>,
+}
+
+/// Differences of a single proxy, eg. `upstream: expected "db:5432", found "localhost:5432"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProxyDrift {
+    pub name: String,
+    pub changes: Vec<String>,
+}
+
+impl StateDiff {
+    /// Whether the server state matches the expected proxies.
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.differing.is_empty()
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in self.missing.iter() {
+            writeln!(f, "missing proxy {:?}", name)?;
+        }
+        for name in self.extra.iter() {
+            writeln!(f, "extra proxy {:?}", name)?;
+        }
+        for drift in self.differing.iter() {
+            writeln!(f, "proxy {:?} differs:", drift.name)?;
+            for change in drift.changes.iter() {
+                writeln!(f, "  {}", change)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Client {
+    /// Makes the server state (within the client's namespace) match the given proxies: proxies not
+    /// listed are deleted, missing ones are created, and existing ones only get the addresses,
+    /// enabled flag and toxics which differ updated. Toxics are matched by name (`<type>_<stream>`
+    /// if empty); listen addresses are compared by port, as the server reports resolved
+    /// addresses, and a port of 0 matches any port.
+    ///
+    /// Returns the proxies in the given order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{namespace::Namespace, proxy::ProxyPack, TOXIPROXY};
+    /// let client = TOXIPROXY.namespaced(Namespace::unique());
+    /// let desired = vec![ProxyPack::new_ephemeral("ensure_doc".into(), "localhost:2000".into())];
+    ///
+    /// let proxies = client.ensure(&desired).expect("proxies are set up");
+    /// // Nothing to change the second time.
+    /// let proxies = client.ensure(&desired).expect("proxies are set up");
+    /// # proxies[0].delete().unwrap();
+    /// ```
+    pub fn ensure(&self, desired: &[ProxyPack]) -> Result<Vec<Proxy>, String> {
+        let mut current = self.all()?;
+
+        for (name, proxy) in current.iter() {
+            if !desired.iter().any(|proxy_pack| &proxy_pack.name == name) {
+                proxy.delete()?;
+            }
+        }
+
+        desired
+            .iter()
+            .map(|proxy_pack| match current.remove(&proxy_pack.name) {
+                Some(mut proxy) => {
+                    self.record_tags(&proxy.proxy_pack.name, &proxy_pack.tags)?;
+                    proxy.proxy_pack.tags = proxy_pack.tags.clone();
+                    self.reconcile(proxy, proxy_pack)
+                }
+                None => {
+                    let proxy = self.create_proxy(ProxyPack {
+                        toxics: vec![],
+                        ..proxy_pack.clone()
+                    })?;
+                    for toxic in proxy_pack.toxics.iter() {
+                        proxy.try_create_toxic(named(toxic))?;
+                    }
+                    Ok(proxy)
+                }
+            })
+            .collect()
+    }
+
+    /// Compares the server state (within the client's namespace) with the expected proxies,
+    /// without changing anything - eg. to fail fast when a shared environment has drifted from
+    /// what the tests expect. Proxies and toxics are compared as by [`ensure`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{namespace::Namespace, proxy::ProxyPack, TOXIPROXY};
+    /// let client = TOXIPROXY.namespaced(Namespace::unique());
+    /// let expected = vec![ProxyPack::new_ephemeral("diff_doc".into(), "localhost:2000".into())];
+    ///
+    /// let diff = client.diff(&expected).expect("state is fetched");
+    /// assert_eq!(vec!["diff_doc".to_owned()], diff.missing);
+    ///
+    /// let proxies = client.ensure(&expected).unwrap();
+    /// let diff = client.diff(&expected).unwrap();
+    /// assert!(diff.is_empty(), "environment has drifted:\n{}", diff);
+    /// # proxies[0].delete().unwrap();
+    /// ```
+    ///
+    /// [`ensure`]: #method.ensure
+    pub fn diff(&self, expected: &[ProxyPack]) -> Result<StateDiff, String> {
+        let mut current = self.all()?;
+        let mut diff = StateDiff::default();
+
+        for proxy_pack in expected {
+            match current.remove(&proxy_pack.name) {
+                Some(proxy) => {
+                    let changes = drift(proxy_pack, &proxy.proxy_pack);
+                    if !changes.is_empty() {
+                        diff.differing.push(ProxyDrift {
+                            name: proxy_pack.name.clone(),
+                            changes,
+                        });
+                    }
+                }
+                None => diff.missing.push(proxy_pack.name.clone()),
+            }
+        }
+
+        diff.extra = current.into_keys().collect();
+        diff.extra.sort();
+        Ok(diff)
+    }
+
+    fn reconcile(&self, proxy: Proxy, desired: &ProxyPack) -> Result<Proxy, String> {
+        let mut changed = false;
+
+        let mut addresses = HashMap::new();
+        if !listen_matches(&desired.listen, &proxy.proxy_pack.listen) {
+            addresses.insert("listen", desired.listen.as_str());
+        }
+        if desired.upstream != proxy.proxy_pack.upstream {
+            addresses.insert("upstream", desired.upstream.as_str());
+        }
+        if !addresses.is_empty() {
+            let body = serde_json::to_string(&addresses)
+                .map_err(|err| format!("json serialize failed: {}", err))?;
+            proxy.update(body)?;
+            changed = true;
+        }
+
+        if desired.enabled != proxy.proxy_pack.enabled {
+            if desired.enabled {
+                proxy.enable()?;
+            } else {
+                proxy.disable()?;
+            }
+            changed = true;
+        }
+
+        let desired_toxics = desired.toxics.iter().map(named).collect::<Vec<_>>();
+        for toxic in proxy.proxy_pack.toxics.iter() {
+            if !desired_toxics
+                .iter()
+                .any(|desired| toxic_matches(desired, toxic))
+            {
+                proxy.delete_toxic(&toxic.name)?;
+                changed = true;
+            }
+        }
+        for desired in desired_toxics {
+            if !proxy
+                .proxy_pack
+                .toxics
+                .iter()
+                .any(|toxic| toxic_matches(&desired, toxic))
+            {
+                proxy.try_create_toxic(desired)?;
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.find_proxy(&desired.name)
+        } else {
+            Ok(proxy)
+        }
+    }
+}
+
+fn drift(expected: &ProxyPack, actual: &ProxyPack) -> Vec<String> {
+    let mut changes = vec![];
+
+    if !listen_matches(&expected.listen, &actual.listen) {
+        changes.push(format!(
+            "listen: expected {:?}, found {:?}",
+            expected.listen, actual.listen
+        ));
+    }
+    if expected.upstream != actual.upstream {
+        changes.push(format!(
+            "upstream: expected {:?}, found {:?}",
+            expected.upstream, actual.upstream
+        ));
+    }
+    if expected.enabled != actual.enabled {
+        changes.push(format!(
+            "enabled: expected {}, found {}",
+            expected.enabled, actual.enabled
+        ));
+    }
+
+    let expected_toxics = expected.toxics.iter().map(named).collect::<Vec<_>>();
+    for toxic in expected_toxics.iter() {
+        match actual
+            .toxics
+            .iter()
+            .find(|actual| actual.name == toxic.name)
+        {
+            Some(actual) if !toxic_matches(toxic, actual) => changes.push(format!(
+                "toxic: expected {}, found {}",
+                describe_toxic(toxic),
+                describe_toxic(actual)
+            )),
+            Some(_) => {}
+            None => changes.push(format!("missing toxic: {}", describe_toxic(toxic))),
+        }
+    }
+    for toxic in actual.toxics.iter() {
+        if !expected_toxics
+            .iter()
+            .any(|expected| expected.name == toxic.name)
+        {
+            changes.push(format!("extra toxic: {}", describe_toxic(toxic)));
+        }
+    }
+
+    changes
+}
+
+fn named(toxic: &ToxicPack) -> ToxicPack {
+    toxic.clone().or_default_name()
+}
+
+pub(crate) fn listen_matches(desired: &str, actual: &str) -> bool {
+    if desired == actual {
+        return true;
+    }
+    match (split_host_port(desired), split_host_port(actual)) {
+        (Ok((_, desired_port)), Ok((_, actual_port))) => {
+            desired_port == 0 || desired_port == actual_port
+        }
+        _ => false,
+    }
+}
+
+/// Whether a toxic on the server is the desired one. Attributes left out of the desired toxic are
+/// not compared, as the server reports them with their default values.
+pub(crate) fn toxic_matches(desired: &ToxicPack, actual: &ToxicPack) -> bool {
+    desired.name == actual.name
+        && desired.r#type == actual.r#type
+        && desired.stream == actual.stream
+        && (desired.toxicity - actual.toxicity).abs() < f32::EPSILON
+        && desired
+            .attributes
+            .iter()
+            .all(|(name, value)| actual.attributes.get(name) == Some(value))
+}
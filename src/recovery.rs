@@ -0,0 +1,152 @@
+//! Recovering from a restart of the server mid-suite, which loses every proxy - so every test
+//! after it fails with `404`s. Once enabled (see [`Client::repopulate_on_restart`]), a `404` on a
+//! proxy the client put on the server is checked against the proxies of the server: the ones
+//! missing are put back (on the same listen addresses), and the failed request is sent once more.
+//! The requests putting them back are neither journaled nor audited.
+//!
+//! [`Client::repopulate_on_restart`]: ../client/struct.Client.html#method.repopulate_on_restart
+
+use super::client::*;
+use super::consts::*;
+use super::proxy::*;
+use super::snapshot::*;
+use reqwest::Method;
+
+/// Proxies put back after a restart of the server.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Recovery {
+    proxies: Vec<ProxyPack>,
+    /// Whether the proxies come from a snapshot, instead of the populated ones.
+    pinned: bool,
+    recoveries: usize,
+}
+
+impl Recovery {
+    /// Remembers populated proxies (as returned by the server), replacing those of the same name.
+    pub(crate) fn remember(&mut self, proxies: &[ProxyPack]) {
+        if self.pinned {
+            return;
+        }
+        for proxy in proxies.iter() {
+            self.proxies.retain(|known| known.name != proxy.name);
+            self.proxies.push(proxy.clone());
+        }
+    }
+
+    /// Forgets a proxy deleted on purpose.
+    pub(crate) fn forget(&mut self, name: &str) {
+        self.proxies.retain(|known| known.name != name);
+    }
+
+    /// Name of the proxy the request is about, if it's one to put back.
+    pub(crate) fn known_proxy(&self, path: &str) -> Option<String> {
+        match path.split('/').collect::<Vec<&str>>().as_slice() {
+            ["proxies", name, ..] if self.proxies.iter().any(|known| known.name == *name) => {
+                Some((*name).to_owned())
+            }
+            _ => None,
+        }
+    }
+
+    /// Requests putting the proxies missing from the server back: populating them, then
+    /// registering their toxics. None if no proxy is missing.
+    pub(crate) fn requests(
+        &self,
+        server_proxies: &[String],
+    ) -> Result<Vec<(Method, String, String)>, String> {
+        let lost = self
+            .proxies
+            .iter()
+            .filter(|known| !server_proxies.contains(&known.name))
+            .collect::<Vec<&ProxyPack>>();
+        if lost.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut requests = vec![(
+            Method::POST,
+            "populate".to_owned(),
+            serde_json::to_string(&lost).map_err(|_| ERR_JSON_SERIALIZE)?,
+        )];
+        for proxy in lost {
+            for toxic in proxy.toxics.iter() {
+                requests.push((
+                    Method::POST,
+                    format!("proxies/{}/toxics", proxy.name),
+                    serde_json::to_string(toxic).map_err(|_| ERR_JSON_SERIALIZE)?,
+                ));
+            }
+        }
+        Ok(requests)
+    }
+
+    pub(crate) fn recovered(&mut self) {
+        self.recoveries += 1;
+    }
+
+    pub(crate) fn recoveries(&self) -> usize {
+        self.recoveries
+    }
+}
+
+impl Client {
+    /// Puts the proxies populated through the client (or its clones) from now on back when the
+    /// server restarts mid-suite (or they are lost otherwise), and retries the request which found
+    /// them gone, see the [`recovery`] module. Toxics registered since are not put back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::proxy::ProxyPack;
+    /// let client = toxiproxy_rust::client::Client::from_env();
+    /// client.repopulate_on_restart().unwrap();
+    ///
+    /// client.populate(vec![ProxyPack::new(
+    ///     "socket_recovered".into(),
+    ///     "localhost:2006".into(),
+    ///     "localhost:2000".into(),
+    /// )]).unwrap();
+    /// # client.find_proxy("socket_recovered").unwrap().delete().unwrap();
+    /// ```
+    ///
+    /// [`recovery`]: ../recovery/index.html
+    pub fn repopulate_on_restart(&self) -> Result<(), String> {
+        self.http_client()
+            .lock()
+            .map_err(|err| format!("lock error: {}", err))?
+            .set_recovery(Some(Recovery::default()));
+        Ok(())
+    }
+
+    /// Puts the snapshot (with its enabled flags and toxics) back when the server restarts
+    /// mid-suite, instead of the populated proxies, see [`repopulate_on_restart`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let client = toxiproxy_rust::client::Client::from_env();
+    /// let snapshot = client.snapshot().unwrap();
+    /// client.repopulate_from(&snapshot).unwrap();
+    /// ```
+    ///
+    /// [`repopulate_on_restart`]: #method.repopulate_on_restart
+    pub fn repopulate_from(&self, snapshot: &Snapshot) -> Result<(), String> {
+        self.http_client()
+            .lock()
+            .map_err(|err| format!("lock error: {}", err))?
+            .set_recovery(Some(Recovery {
+                proxies: snapshot.proxies.clone(),
+                pinned: true,
+                recoveries: 0,
+            }));
+        Ok(())
+    }
+
+    /// Number of times the proxies were put back after a restart of the server.
+    pub fn repopulations(&self) -> usize {
+        self.http_client()
+            .lock()
+            .ok()
+            .map_or(0, |client| client.recoveries())
+    }
+}
@@ -0,0 +1,47 @@
+//! Small seedable pseudo-random generator ([SplitMix64]) for the randomized features, so a run can
+//! be reproduced from its seed without pulling in a dependency.
+//!
+//! [SplitMix64]: https://prng.di.unimi.it/splitmix64.c
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::RangeInclusive;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Seed which differs from run to run.
+    pub(crate) fn random_seed() -> u64 {
+        RandomState::new().build_hasher().finish()
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Value in `0..bound`, `bound` being positive.
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    pub(crate) fn in_range(&mut self, range: &RangeInclusive<u32>) -> u32 {
+        let span = u64::from(range.end().saturating_sub(*range.start())) + 1;
+        range.start() + (self.next_u64() % span) as u32
+    }
+
+    /// `true` with the given probability.
+    pub(crate) fn chance(&mut self, probability: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+}
@@ -0,0 +1,610 @@
+//! Scenarios: phases of network conditions with durations, kept as versioned files instead of
+//! ad-hoc chaos scripts:
+//!
+//! ```json
+//! {
+//!   "name": "database outage",
+//!   "phases": [
+//!     {
+//!       "name": "slow",
+//!       "duration_ms": 30000,
+//!       "effects": [
+//!         {
+//!           "effect": "toxic",
+//!           "proxy": "db",
+//!           "toxic": {"type": "latency", "attributes": {"latency": 500}}
+//!         }
+//!       ]
+//!     },
+//!     {"name": "down", "duration_ms": 10000, "effects": [{"effect": "down", "proxy": "db"}]},
+//!     {"name": "recover", "duration_ms": 0}
+//!   ]
+//! }
+//! ```
+//!
+//! Every phase starts from a clean state: the proxies the scenario refers to are enabled and their
+//! toxics removed, then the effects of the phase are applied for its duration. So a phase without
+//! effects is a recovery. The proxies are left clean after the scenario as well. Toxic fields
+//! default as in config files (see [`config`](../config/index.html)).
+//!
+//! The format is plain JSON so clients in other languages can share the same scenario files: its
+//! [JSON Schema] is available as [`SCHEMA`], and [`Scenario::validate`] checks what the schema
+//! can't express.
+//!
+//! Simple scenarios can also be declared in code, see [`Scenario::new`].
+//!
+//! [`Scenario::new`]: struct.Scenario.html#method.new
+//! [JSON Schema]: https://github.com/itarato/toxiproxy_rust/blob/master/schema/scenario.schema.json
+
+use super::client::*;
+use super::instrument::{self, Fields};
+use super::proxy::*;
+use super::toxic::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// [JSON Schema] (draft-07) of scenario files.
+///
+/// [JSON Schema]: https://json-schema.org
+pub const SCHEMA: &str = include_str!("../schema/scenario.schema.json");
+
+/// Toxic types supported by the server.
+const TOXIC_TYPES: &[&str] = &[
+    "latency",
+    "bandwidth",
+    "slow_close",
+    "timeout",
+    "reset_peer",
+    "slicer",
+    "limit_data",
+];
+
+/// Phases run one after the other.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    pub name: String,
+    pub phases: Vec<Phase>,
+}
+
+/// Effects applied for a duration.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Phase {
+    #[serde(default)]
+    pub name: String,
+    pub duration_ms: u64,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+/// Effect on a proxy during a phase.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "effect", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Effect {
+    /// Registers a toxic on the proxy.
+    Toxic { proxy: String, toxic: ToxicPack },
+    /// Disables the proxy.
+    Down { proxy: String },
+}
+
+impl Effect {
+    /// Name of the proxy the effect applies to.
+    pub fn proxy(&self) -> &str {
+        match self {
+            Effect::Toxic { proxy, .. } | Effect::Down { proxy } => proxy,
+        }
+    }
+}
+
+impl Phase {
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration_ms)
+    }
+}
+
+/// Effects of a phase declared in code, see [`Scenario::phase`]. Effects apply to the proxy
+/// picked with [`on`]. The toxics are downstream ones, see [`toxic`] for others.
+///
+/// [`Scenario::phase`]: struct.Scenario.html#method.phase
+/// [`on`]: #method.on
+/// [`toxic`]: #method.toxic
+#[derive(Debug, Clone, Default)]
+pub struct PhaseBuilder {
+    proxy: String,
+    effects: Vec<Effect>,
+}
+
+impl PhaseBuilder {
+    /// Proxy the following effects apply to.
+    pub fn on(mut self, proxy: &str) -> Self {
+        self.proxy = proxy.into();
+        self
+    }
+
+    pub fn latency(self, latency: ToxicValueType) -> Self {
+        self.downstream("latency", "latency", latency)
+    }
+
+    pub fn bandwidth(self, rate: ToxicValueType) -> Self {
+        self.downstream("bandwidth", "rate", rate)
+    }
+
+    pub fn timeout(self, timeout: ToxicValueType) -> Self {
+        self.downstream("timeout", "timeout", timeout)
+    }
+
+    pub fn toxic(mut self, toxic: ToxicPack) -> Self {
+        self.effects.push(Effect::Toxic {
+            proxy: self.proxy.clone(),
+            toxic,
+        });
+        self
+    }
+
+    /// Disables the proxy.
+    pub fn down(mut self) -> Self {
+        self.effects.push(Effect::Down {
+            proxy: self.proxy.clone(),
+        });
+        self
+    }
+
+    fn downstream(self, r#type: &str, attribute: &str, value: ToxicValueType) -> Self {
+        let mut attributes = HashMap::new();
+        attributes.insert(attribute.to_owned(), value);
+        self.toxic(ToxicPack::new(
+            r#type.into(),
+            "downstream".into(),
+            1.0,
+            attributes,
+        ))
+    }
+}
+
+impl Scenario {
+    /// Empty scenario, to declare its phases in code with [`phase`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{proxy::ProxyPack, scenario::Scenario, TOXIPROXY};
+    /// # use std::time::Duration;
+    /// # TOXIPROXY.populate(vec![ProxyPack::new_ephemeral(
+    /// #    "scenario_dsl_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]).unwrap();
+    /// Scenario::new()
+    ///     .phase("slow", Duration::from_millis(30), |p| p.on("scenario_dsl_doc").latency(500))
+    ///     .phase("outage", Duration::from_millis(10), |p| p.on("scenario_dsl_doc").down())
+    ///     .run_with(&TOXIPROXY, || {
+    ///         /* Example test:
+    ///            let service_result = MyService::Server::call_with_retries(params);
+    ///            assert!(service_result.is_ok());
+    ///         */
+    ///     })
+    ///     .expect("scenario has run");
+    /// # TOXIPROXY.find_proxy("scenario_dsl_doc").unwrap().delete().unwrap();
+    /// ```
+    ///
+    /// [`phase`]: #method.phase
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a phase, its effects declared by the closure.
+    pub fn phase<F>(mut self, name: &str, duration: Duration, effects: F) -> Self
+    where
+        F: FnOnce(PhaseBuilder) -> PhaseBuilder,
+    {
+        self.phases.push(Phase {
+            name: name.into(),
+            duration_ms: duration.as_millis() as u64,
+            effects: effects(PhaseBuilder::default()).effects,
+        });
+        self
+    }
+
+    /// Validates the scenario, and runs it on a background thread while the closure runs (see
+    /// [`run`]). Once the closure returned, waits for the scenario to end, and returns the value
+    /// of the closure - failing if the scenario failed.
+    ///
+    /// [`run`]: #method.run
+    pub fn run_with<F, T>(&self, client: &Client, closure: F) -> Result<T, String>
+    where
+        F: FnOnce() -> T,
+    {
+        self.validate()?;
+
+        let runner = {
+            let scenario = self.clone();
+            let client = client.clone();
+            thread::spawn(move || scenario.run(&client))
+        };
+        let value = closure();
+
+        runner
+            .join()
+            .unwrap_or_else(|_| Err("scenario thread panicked".into()))
+            .map(|_| value)
+    }
+
+    /// Parses a JSON scenario, rejecting unknown fields, and validates it (see [`validate`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::scenario::Scenario;
+    /// let scenario = Scenario::from_json(r#"{"phases": [
+    ///     {"duration_ms": 100, "effects": [{"effect": "down", "proxy": "socket"}]}
+    /// ]}"#).expect("scenario is valid");
+    ///
+    /// assert_eq!(std::time::Duration::from_millis(100), scenario.duration());
+    /// ```
+    ///
+    /// [`validate`]: #method.validate
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let scenario = serde_json::from_str::<Self>(json)
+            .map_err(|err| format!("invalid scenario: {}", err))?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Checks the scenario: it has phases, effects refer to a proxy, toxics have a known type and
+    /// stream, a toxicity between 0 and 1, and their names are unique per proxy within a phase.
+    /// Every problem is reported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::scenario::{Effect, Phase, Scenario};
+    /// let scenario = Scenario {
+    ///     name: "empty".into(),
+    ///     phases: vec![Phase {
+    ///         effects: vec![Effect::Down { proxy: "".into() }],
+    ///         ..Default::default()
+    ///     }],
+    /// };
+    ///
+    /// assert_eq!(
+    ///     Err("invalid scenario:\n  phase 1: effect without a proxy".to_owned()),
+    ///     scenario.validate(),
+    /// );
+    /// ```
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems = vec![];
+        if self.phases.is_empty() {
+            problems.push("no phases".to_owned());
+        }
+
+        for (index, phase) in self.phases.iter().enumerate() {
+            let at = match phase.name.as_str() {
+                "" => format!("phase {}", index + 1),
+                name => format!("phase {} ({:?})", index + 1, name),
+            };
+            let mut toxics: Vec<(&str, String)> = vec![];
+
+            for effect in phase.effects.iter() {
+                if effect.proxy().is_empty() {
+                    problems.push(format!("{}: effect without a proxy", at));
+                }
+                let toxic = match effect {
+                    Effect::Toxic { toxic, .. } => toxic.clone().or_default_name(),
+                    Effect::Down { .. } => continue,
+                };
+
+                if !TOXIC_TYPES.contains(&toxic.r#type.as_str()) {
+                    problems.push(format!("{}: unknown toxic type {:?}", at, toxic.r#type));
+                }
+                if toxic.stream != "upstream" && toxic.stream != "downstream" {
+                    problems.push(format!("{}: unknown stream {:?}", at, toxic.stream));
+                }
+                if !(0.0..=1.0).contains(&toxic.toxicity) {
+                    problems.push(format!(
+                        "{}: toxicity {} is not between 0 and 1",
+                        at, toxic.toxicity
+                    ));
+                }
+
+                let key = (effect.proxy(), toxic.name);
+                if toxics.contains(&key) {
+                    problems.push(format!(
+                        "{}: duplicate toxic {:?} on proxy {:?}",
+                        at, key.1, key.0
+                    ));
+                } else {
+                    toxics.push(key);
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("invalid scenario:\n  {}", problems.join("\n  ")))
+        }
+    }
+
+    /// Loads a JSON scenario file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read scenario {}: {}", path.display(), err))?;
+        Self::from_json(&json)
+    }
+
+    /// Total duration of the phases.
+    pub fn duration(&self) -> Duration {
+        self.phases.iter().map(Phase::duration).sum()
+    }
+
+    /// Names of the proxies the scenario refers to, in order of first use.
+    pub fn proxy_names(&self) -> Vec<&str> {
+        let mut names = vec![];
+        for effect in self.phases.iter().flat_map(|phase| phase.effects.iter()) {
+            if !names.contains(&effect.proxy()) {
+                names.push(effect.proxy());
+            }
+        }
+        names
+    }
+
+    /// Runs the phases against the proxies of the client, blocking until the scenario is over -
+    /// so it's usually run on a thread of its own while the code under test runs. The proxies are
+    /// cleaned up at the end, also when a phase fails. See [`run_with_report`] for the details of
+    /// the run.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{proxy::ProxyPack, scenario::Scenario, TOXIPROXY};
+    /// # TOXIPROXY.populate(vec![ProxyPack::new_ephemeral(
+    /// #    "scenario_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]).unwrap();
+    /// let scenario = Scenario::from_json(r#"{"phases": [
+    ///     {"duration_ms": 10, "effects": [
+    ///         {"effect": "toxic", "proxy": "scenario_doc",
+    ///          "toxic": {"type": "latency", "attributes": {"latency": 500}}}
+    ///     ]},
+    ///     {"duration_ms": 10, "effects": [{"effect": "down", "proxy": "scenario_doc"}]}
+    /// ]}"#).unwrap();
+    ///
+    /// let runner = std::thread::spawn(move || scenario.run(&TOXIPROXY));
+    /// /* Example test:
+    ///    let service_result = MyService::Server::call(params);
+    /// */
+    /// runner.join().unwrap().expect("scenario has run");
+    /// # TOXIPROXY.find_proxy("scenario_doc").unwrap().delete().unwrap();
+    /// ```
+    ///
+    /// [`run_with_report`]: #method.run_with_report
+    pub fn run(&self, client: &Client) -> Result<(), String> {
+        self.run_with_report(client).into_result()
+    }
+
+    /// Runs the scenario like [`run`], reporting when each phase started, the effects applied and
+    /// the errors - eg. to attach to the output of a CI run, to see which conditions were active
+    /// when an assertion failed. A phase failing ends the scenario.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{proxy::ProxyPack, scenario::Scenario, TOXIPROXY};
+    /// # TOXIPROXY.populate(vec![ProxyPack::new_ephemeral(
+    /// #    "scenario_report_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]).unwrap();
+    /// let scenario = Scenario::from_json(r#"{"name": "outage", "phases": [
+    ///     {"name": "down", "duration_ms": 10, "effects": [
+    ///         {"effect": "down", "proxy": "scenario_report_doc"}
+    ///     ]}
+    /// ]}"#).unwrap();
+    ///
+    /// let report = scenario.run_with_report(&TOXIPROXY);
+    /// assert!(report.is_ok());
+    /// assert_eq!(1, report.phases[0].effects.len());
+    /// println!("{}", report);
+    /// # TOXIPROXY.find_proxy("scenario_report_doc").unwrap().delete().unwrap();
+    /// ```
+    ///
+    /// [`run`]: #method.run
+    pub fn run_with_report(&self, client: &Client) -> ScenarioReport {
+        let mut report = ScenarioReport {
+            scenario: self.name.clone(),
+            started_at_ms: now_ms(),
+            ..Default::default()
+        };
+
+        let proxies = match self
+            .proxy_names()
+            .into_iter()
+            .map(|name| client.find_proxy(name).map(|proxy| (name, proxy)))
+            .collect::<Result<Vec<(&str, Proxy)>, String>>()
+        {
+            Ok(proxies) => proxies,
+            Err(err) => {
+                report.errors.push(err);
+                return report;
+            }
+        };
+
+        let phases = &mut report.phases;
+        let ((), cleanup_result) = Proxy::run_guarded(
+            || {
+                for phase in self.phases.iter() {
+                    let started = Instant::now();
+                    let mut phase_report = PhaseReport {
+                        name: phase.name.clone(),
+                        started_at_ms: now_ms(),
+                        ..Default::default()
+                    };
+
+                    let effects = &mut phase_report.effects;
+                    let result = instrument::operation(
+                        "scenario_phase",
+                        Fields::phase(&self.name, &phase.name),
+                        || {
+                            apply(phase, &proxies, effects)?;
+                            thread::sleep(phase.duration());
+                            Ok(())
+                        },
+                    );
+                    phase_report.elapsed_ms = started.elapsed().as_millis() as u64;
+                    phase_report.errors.extend(result.err());
+
+                    let failed = !phase_report.errors.is_empty();
+                    phases.push(phase_report);
+                    if failed {
+                        return;
+                    }
+                }
+            },
+            || reset(&proxies),
+        );
+
+        report.errors.extend(cleanup_result.err());
+        report
+    }
+}
+
+/// What happened during a scenario run, see [`Scenario::run_with_report`]. Timestamps are
+/// milliseconds since the Unix epoch.
+///
+/// [`Scenario::run_with_report`]: struct.Scenario.html#method.run_with_report
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ScenarioReport {
+    pub scenario: String,
+    pub started_at_ms: u64,
+    /// Phases run, the last one being the failed one if any.
+    pub phases: Vec<PhaseReport>,
+    /// Errors of fetching the proxies or cleaning them up.
+    pub errors: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PhaseReport {
+    pub name: String,
+    pub started_at_ms: u64,
+    pub elapsed_ms: u64,
+    /// Effects applied, toxics with their default fields filled in.
+    pub effects: Vec<Effect>,
+    pub errors: Vec<String>,
+}
+
+impl ScenarioReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty() && self.phases.iter().all(|phase| phase.errors.is_empty())
+    }
+
+    /// Errors of the phases and of the run, in order.
+    pub fn into_result(self) -> Result<(), String> {
+        let errors = self
+            .phases
+            .into_iter()
+            .flat_map(|phase| phase.errors)
+            .chain(self.errors)
+            .collect::<Vec<String>>();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    /// The report as pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|err| format!("report error: {}", err))
+    }
+}
+
+impl fmt::Display for ScenarioReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = if self.is_ok() { "ok" } else { "failed" };
+        writeln!(
+            f,
+            "scenario {:?} started at {}: {}",
+            self.scenario, self.started_at_ms, status
+        )?;
+
+        for (index, phase) in self.phases.iter().enumerate() {
+            writeln!(
+                f,
+                "  phase {} {:?} started at {}, for {} ms",
+                index + 1,
+                phase.name,
+                phase.started_at_ms,
+                phase.elapsed_ms
+            )?;
+            for effect in phase.effects.iter() {
+                match effect {
+                    Effect::Toxic { proxy, toxic } => writeln!(
+                        f,
+                        "    toxic {} on {}: {} {} {:?}",
+                        toxic.name, proxy, toxic.r#type, toxic.stream, toxic.attributes
+                    )?,
+                    Effect::Down { proxy } => writeln!(f, "    down {}", proxy)?,
+                }
+            }
+            for err in phase.errors.iter() {
+                writeln!(f, "    error: {}", err)?;
+            }
+        }
+
+        for err in self.errors.iter() {
+            writeln!(f, "  error: {}", err)?;
+        }
+        Ok(())
+    }
+}
+
+fn apply(
+    phase: &Phase,
+    proxies: &[(&str, Proxy)],
+    applied: &mut Vec<Effect>,
+) -> Result<(), String> {
+    reset(proxies)?;
+    for effect in phase.effects.iter() {
+        let proxy = proxies
+            .iter()
+            .find(|(name, _)| *name == effect.proxy())
+            .map(|(_, proxy)| proxy)
+            .expect("proxies of the effects are fetched");
+        match effect {
+            Effect::Toxic { proxy: name, toxic } => {
+                let toxic = toxic.clone().or_default_name();
+                proxy.try_create_toxic(toxic.clone())?;
+                applied.push(Effect::Toxic {
+                    proxy: name.clone(),
+                    toxic,
+                });
+            }
+            Effect::Down { .. } => {
+                proxy.disable()?;
+                applied.push(effect.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+fn reset(proxies: &[(&str, Proxy)]) -> Result<(), String> {
+    for (_, proxy) in proxies {
+        proxy.delete_all_toxics()?;
+        proxy.enable()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+//! Toxiproxy server started from a local `toxiproxy-server` binary, for environments without
+//! Docker (see the `container` module otherwise). The process is killed when the
+//! [`ToxiproxyServer`] is dropped.
+//!
+//! ```no_run
+//! use toxiproxy_rust::server::ToxiproxyServer;
+//!
+//! let server = ToxiproxyServer::spawn("/usr/local/bin/toxiproxy-server", 18474)
+//!     .expect("Toxiproxy is started");
+//! assert!(server.client().is_running());
+//! ```
+
+use super::client::*;
+use super::consts::*;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a spawned server has to become ready.
+pub const SPAWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Running `toxiproxy-server` process listening on localhost, killed when dropped.
+pub struct ToxiproxyServer {
+    path: PathBuf,
+    port: u16,
+    process: Child,
+    client: Client,
+}
+
+impl ToxiproxyServer {
+    /// Starts the binary with its API on `127.0.0.1:port`, and waits until it answers.
+    pub fn spawn<P: AsRef<Path>>(path: P, port: u16) -> Result<Self, String> {
+        Self::spawn_with_timeout(path, port, SPAWN_TIMEOUT)
+    }
+
+    /// Same as [`spawn`], waiting for the server at most for the timeout.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use toxiproxy_rust::server::ToxiproxyServer;
+    /// let result = ToxiproxyServer::spawn_with_timeout(
+    ///     "/missing/toxiproxy-server",
+    ///     18474,
+    ///     Duration::from_secs(1),
+    /// );
+    /// assert!(result.is_err());
+    /// ```
+    ///
+    /// [`spawn`]: #method.spawn
+    pub fn spawn_with_timeout<P: AsRef<Path>>(
+        path: P,
+        port: u16,
+        timeout: Duration,
+    ) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        let process = start_process(&path, port)?;
+
+        let mut server = Self {
+            path,
+            port,
+            process,
+            client: Client::new(("127.0.0.1", port)),
+        };
+        server.wait_until_ready(timeout)?;
+        Ok(server)
+    }
+
+    /// Kills the process (if it's still running) and starts the binary again on the same port,
+    /// eg. after it crashed. The server starts without proxies.
+    pub fn restart(&mut self) -> Result<(), String> {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        self.process = start_process(&self.path, self.port)?;
+        self.wait_until_ready(SPAWN_TIMEOUT).map(|_| ())
+    }
+
+    // Like `Client::wait_until_running`, but gives up as soon as the process exits (eg. the port
+    // is taken).
+    fn wait_until_ready(&mut self, timeout: Duration) -> Result<String, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Ok(Some(status)) = self.process.try_wait() {
+                return Err(format!(
+                    "{} exited before it was ready: {}",
+                    self.path.display(),
+                    status
+                ));
+            }
+            match self.client.health_check() {
+                Ok(version) => return Ok(version),
+                Err(reason) if Instant::now() >= deadline => {
+                    return Err(format!(
+                        "{} is not running after {:?}: {}",
+                        self.path.display(),
+                        timeout,
+                        reason
+                    ))
+                }
+                Err(_) => thread::sleep(READY_POLL_INTERVAL),
+            }
+        }
+    }
+
+    /// Client of the server.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Port of the API.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// ID of the server process.
+    pub fn pid(&self) -> u32 {
+        self.process.id()
+    }
+
+    /// Whether the process is still running.
+    pub fn is_alive(&mut self) -> bool {
+        matches!(self.process.try_wait(), Ok(None))
+    }
+}
+
+fn start_process(path: &Path, port: u16) -> Result<Child, String> {
+    Command::new(path)
+        .args(["-host", "127.0.0.1", "-port", &port.to_string()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("{} cannot be started: {}", path.display(), err))
+}
+
+impl Drop for ToxiproxyServer {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
@@ -0,0 +1,240 @@
+//! The same toxics on several proxies at once, eg. to slow down every downstream dependency in one
+//! line.
+
+use super::client::*;
+use super::group::*;
+use super::guard::*;
+use super::proxy::*;
+use super::toxic::*;
+use std::mem;
+use std::sync::Mutex;
+
+/// Proxies getting the same toxics. Registering is attempted on every member, and the failures are
+/// reported together by [`apply`] (or [`check`]) instead of panicking.
+///
+/// [`apply`]: #method.apply
+/// [`check`]: #method.check
+#[derive(Debug)]
+pub struct ProxySet {
+    group: ProxyGroup,
+    failures: Mutex<Vec<(String, String)>>,
+}
+
+impl Client {
+    /// Set of the proxies with the given names.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{proxy::ProxyPack, TOXIPROXY};
+    /// # TOXIPROXY.populate(vec![
+    /// #    ProxyPack::new_ephemeral("set_a".into(), "localhost:2000".into()),
+    /// #    ProxyPack::new_ephemeral("set_b".into(), "localhost:2000".into()),
+    /// # ]).unwrap();
+    /// TOXIPROXY
+    ///     .proxy_set(&["set_a", "set_b"])
+    ///     .expect("proxies are fetched")
+    ///     .with_latency("downstream".into(), 2000, 0, 1.0)
+    ///     .apply(|| {
+    ///         /* Example test:
+    ///            let service_result = MyService::Server::call(params);
+    ///            assert!(service_result.is_ok());
+    ///         */
+    ///     })
+    ///     .expect("toxics are registered and removed");
+    /// # TOXIPROXY.group(&["set_a", "set_b"]).unwrap()
+    /// #     .for_each(|proxy| proxy.delete()).unwrap();
+    /// ```
+    pub fn proxy_set(&self, names: &[&str]) -> Result<ProxySet, String> {
+        self.group(names).map(ProxySet::from)
+    }
+}
+
+impl From<ProxyGroup> for ProxySet {
+    fn from(group: ProxyGroup) -> Self {
+        Self {
+            group,
+            failures: Mutex::new(vec![]),
+        }
+    }
+}
+
+impl ProxySet {
+    pub fn new(proxies: Vec<Proxy>) -> Self {
+        ProxyGroup::new(proxies).into()
+    }
+
+    pub fn proxies(&self) -> &[Proxy] {
+        self.group.proxies()
+    }
+
+    /// Registers a latency toxic on every member, see [`Proxy::with_latency`].
+    ///
+    /// [`Proxy::with_latency`]: ../proxy/struct.Proxy.html#method.with_latency
+    pub fn with_latency(
+        &self,
+        stream: String,
+        latency: ToxicValueType,
+        jitter: ToxicValueType,
+        toxicity: f32,
+    ) -> &Self {
+        self.add_toxic(toxic(
+            "latency",
+            stream,
+            toxicity,
+            &[("latency", latency), ("jitter", jitter)],
+        ))
+    }
+
+    /// Registers a bandwidth toxic on every member, see [`Proxy::with_bandwidth`].
+    ///
+    /// [`Proxy::with_bandwidth`]: ../proxy/struct.Proxy.html#method.with_bandwidth
+    pub fn with_bandwidth(&self, stream: String, rate: ToxicValueType, toxicity: f32) -> &Self {
+        self.add_toxic(toxic("bandwidth", stream, toxicity, &[("rate", rate)]))
+    }
+
+    /// Registers a slow close toxic on every member, see [`Proxy::with_slow_close`].
+    ///
+    /// [`Proxy::with_slow_close`]: ../proxy/struct.Proxy.html#method.with_slow_close
+    pub fn with_slow_close(&self, stream: String, delay: ToxicValueType, toxicity: f32) -> &Self {
+        self.add_toxic(toxic("slow_close", stream, toxicity, &[("delay", delay)]))
+    }
+
+    /// Registers a timeout toxic on every member, see [`Proxy::with_timeout`].
+    ///
+    /// [`Proxy::with_timeout`]: ../proxy/struct.Proxy.html#method.with_timeout
+    pub fn with_timeout(&self, stream: String, timeout: ToxicValueType, toxicity: f32) -> &Self {
+        self.add_toxic(toxic("timeout", stream, toxicity, &[("timeout", timeout)]))
+    }
+
+    /// Registers a slicer toxic on every member, see [`Proxy::with_slicer`].
+    ///
+    /// [`Proxy::with_slicer`]: ../proxy/struct.Proxy.html#method.with_slicer
+    pub fn with_slicer(
+        &self,
+        stream: String,
+        average_size: ToxicValueType,
+        size_variation: ToxicValueType,
+        delay: ToxicValueType,
+        toxicity: f32,
+    ) -> &Self {
+        self.add_toxic(toxic(
+            "slicer",
+            stream,
+            toxicity,
+            &[
+                ("average_size", average_size),
+                ("size_variation", size_variation),
+                ("delay", delay),
+            ],
+        ))
+    }
+
+    /// Registers a limit data toxic on every member, see [`Proxy::with_limit_data`].
+    ///
+    /// [`Proxy::with_limit_data`]: ../proxy/struct.Proxy.html#method.with_limit_data
+    pub fn with_limit_data(&self, stream: String, bytes: ToxicValueType, toxicity: f32) -> &Self {
+        self.add_toxic(toxic("limit_data", stream, toxicity, &[("bytes", bytes)]))
+    }
+
+    /// Registers the toxic on every member, recording the failures.
+    pub fn add_toxic(&self, toxic: ToxicPack) -> &Self {
+        if let Err(err) = self.group.add_toxic(toxic) {
+            if let Ok(mut failures) = self.failures.lock() {
+                failures.extend(err.failures);
+            }
+        }
+        self
+    }
+
+    /// Reports (and forgets) the failures of registering toxics so far.
+    pub fn check(&self) -> Result<(), GroupError> {
+        let failures = match self.failures.lock() {
+            Ok(mut failures) => mem::take(&mut *failures),
+            Err(err) => vec![("*".into(), format!("lock error: {}", err))],
+        };
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(GroupError { failures })
+        }
+    }
+
+    /// Runs the closure with the toxics registered on every member, and deletes the toxics of
+    /// every member afterwards (also when the closure panics). If some toxics couldn't be
+    /// registered, the closure isn't run.
+    pub fn apply<F, T>(&self, closure: F) -> Result<T, GroupError>
+    where
+        F: FnOnce() -> T,
+    {
+        if let Err(err) = self.check() {
+            let _ = self.group.delete_all_toxics();
+            return Err(err);
+        }
+
+        let (value, result) = Proxy::run_guarded(closure, || {
+            self.group
+                .for_each(|proxy| proxy.report_cleanup(proxy.delete_all_toxics()))
+        });
+        result.map(|_| value)
+    }
+
+    pub fn delete_all_toxics(&self) -> Result<(), GroupError> {
+        self.group.delete_all_toxics()
+    }
+
+    /// Opens a scope for toxics on every member, see [`Proxy::toxic_scope`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{proxy::ProxyPack, TOXIPROXY};
+    /// # TOXIPROXY.populate(vec![
+    /// #    ProxyPack::new_ephemeral("set_scope_a".into(), "localhost:2000".into()),
+    /// #    ProxyPack::new_ephemeral("set_scope_b".into(), "localhost:2000".into()),
+    /// # ]).unwrap();
+    /// let set = TOXIPROXY.proxy_set(&["set_scope_a", "set_scope_b"]).unwrap();
+    /// {
+    ///     let scope = set.toxic_scope();
+    ///     scope.with_timeout("upstream".into(), 100, 1.0);
+    ///     scope.check().expect("toxics are registered");
+    ///     /* Example test:
+    ///        let service_result = MyService::Server::call(params);
+    ///        assert!(service_result.is_err());
+    ///     */
+    /// }
+    /// assert!(set.proxies().iter().all(|proxy| proxy.toxics().unwrap().is_empty()));
+    /// # TOXIPROXY.group(&["set_scope_a", "set_scope_b"]).unwrap()
+    /// #     .for_each(|proxy| proxy.delete()).unwrap();
+    /// ```
+    ///
+    /// [`Proxy::toxic_scope`]: ../proxy/struct.Proxy.html#method.toxic_scope
+    pub fn toxic_scope(&self) -> ToxicSetGuard {
+        ToxicSetGuard::new(ProxySet::new(
+            self.proxies().iter().map(Proxy::scoped).collect(),
+        ))
+    }
+
+    pub(crate) fn delete_created_toxics(&self) -> Result<(), GroupError> {
+        self.group
+            .for_each(|proxy| proxy.report_cleanup(proxy.delete_created_toxics()))
+    }
+}
+
+fn toxic(
+    r#type: &str,
+    stream: String,
+    toxicity: f32,
+    attributes: &[(&str, ToxicValueType)],
+) -> ToxicPack {
+    ToxicPack::new(
+        r#type.into(),
+        stream,
+        toxicity,
+        attributes
+            .iter()
+            .map(|(name, value)| ((*name).to_owned(), *value))
+            .collect(),
+    )
+}
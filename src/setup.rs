@@ -0,0 +1,185 @@
+//! One-time setup shared by test processes, for runners like [cargo-nextest] which run each test in
+//! its own process (so a `lazy_static` populate runs once per test). A setup step populates the
+//! proxies once and writes a manifest; tests attach to the proxies it lists.
+//!
+//! With nextest, the `toxiproxy_setup` binary of this crate can be the [setup script]. It takes a
+//! JSON file with a list of proxies (as accepted by [`Client::populate`]) and exports the manifest
+//! location to the tests:
+//!
+//! ```toml
+//! # .config/nextest.toml
+//! [scripts.setup.toxiproxy]
+//! command = "cargo run --bin toxiproxy_setup -- tests/proxies.json"
+//!
+//! [[profile.default.scripts]]
+//! filter = "all()"
+//! setup = "toxiproxy"
+//! ```
+//!
+//! Then in a test:
+//!
+//! ```no_run
+//! let proxy = toxiproxy_rust::setup::attach("socket").expect("proxy is set up");
+//! ```
+//!
+//! Once the run is over, `toxiproxy_setup --teardown` (or [`global_teardown`]) deletes the proxies
+//! of the manifest and the manifest itself.
+//!
+//! [cargo-nextest]: https://nexte.st
+//! [setup script]: https://nexte.st/docs/configuration/setup-scripts/
+//! [`Client::populate`]: ../client/struct.Client.html#method.populate
+
+use super::client::*;
+use super::consts::*;
+use super::proxy::*;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// Environment variable pointing to the manifest written by [`global_setup`].
+pub const ENV_TOXIPROXY_MANIFEST: &str = "TOXIPROXY_MANIFEST";
+
+/// Environment variable nextest sets for setup scripts: a file collecting `KEY=value` lines to
+/// export to the tests.
+const ENV_NEXTEST_ENV: &str = "NEXTEST_ENV";
+
+/// Server address and proxies set up by [`global_setup`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Manifest {
+    pub address: String,
+    /// Proxies as reported by the server (eg. with the ports picked for ephemeral proxies).
+    pub proxies: Vec<ProxyPack>,
+}
+
+impl Manifest {
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|err| format!("manifest error: {}", err))?;
+        serde_json::from_str(&json).map_err(|err| format!("json deserialize failed: {}", err))
+    }
+
+    /// Reads the manifest at the path in the `TOXIPROXY_MANIFEST` environment variable.
+    pub fn from_env() -> Result<Self, String> {
+        let path = env::var(ENV_TOXIPROXY_MANIFEST)
+            .map_err(|_| format!("{} is not set", ENV_TOXIPROXY_MANIFEST))?;
+        Self::read(path)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|_| "json serialize failed")?;
+        fs::write(path, json).map_err(|err| format!("manifest error: {}", err))
+    }
+
+    /// Client connected to the server the proxies were set up on.
+    pub fn client(&self) -> Client {
+        Client::new(self.address.as_str())
+    }
+
+    /// Proxy of the manifest with the given name.
+    pub fn attach(&self, name: &str) -> Result<Proxy, String> {
+        if !self
+            .proxies
+            .iter()
+            .any(|proxy_pack| proxy_pack.name == name)
+        {
+            return Err(format!("proxy {:?} is not in the manifest", name));
+        }
+        self.client().find_proxy(name)
+    }
+
+    /// Deletes the proxies of the manifest still on the server.
+    pub fn teardown(&self) -> Result<(), String> {
+        let existing = self.client().all()?;
+
+        // Attempts every deletion, reporting the first failure.
+        let mut result = Ok(());
+        for proxy_pack in self.proxies.iter() {
+            if let Some(proxy) = existing.get(&proxy_pack.name) {
+                result = result.and(proxy.delete());
+            }
+        }
+        result
+    }
+}
+
+/// Populates the proxies and writes a manifest of them to `path`. When run as a nextest setup
+/// script, the manifest location (`TOXIPROXY_MANIFEST`) and the server address (`TOXIPROXY_URL`)
+/// are exported to the tests.
+///
+/// # Examples
+///
+/// ```
+/// # use toxiproxy_rust::{proxy::ProxyPack, setup, TOXIPROXY};
+/// let path = std::env::temp_dir().join("toxiproxy_manifest_doc.json");
+/// let manifest = setup::global_setup(
+///     &TOXIPROXY,
+///     vec![ProxyPack::new_ephemeral("setup_doc".into(), "localhost:2000".into())],
+///     &path,
+/// )
+/// .expect("proxies are set up");
+///
+/// let proxy = setup::Manifest::read(&path).unwrap().attach("setup_doc").unwrap();
+/// assert_ne!("0.0.0.0:0", manifest.proxies[0].listen);
+/// # proxy.delete().unwrap();
+/// ```
+pub fn global_setup<P: AsRef<Path>>(
+    client: &Client,
+    proxies: Vec<ProxyPack>,
+    path: P,
+) -> Result<Manifest, String> {
+    let proxies = client.populate(proxies)?;
+    let manifest = Manifest {
+        address: client.address()?.to_string(),
+        proxies: proxies.into_iter().map(|proxy| proxy.proxy_pack).collect(),
+    };
+    manifest.write(&path)?;
+
+    if let Ok(nextest_env) = env::var(ENV_NEXTEST_ENV) {
+        let path = fs::canonicalize(&path).map_err(|err| format!("manifest error: {}", err))?;
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(nextest_env)
+            .map_err(|err| format!("{} error: {}", ENV_NEXTEST_ENV, err))?;
+        writeln!(
+            file,
+            "{}={}\n{}=http://{}",
+            ENV_TOXIPROXY_MANIFEST,
+            path.display(),
+            ENV_TOXIPROXY_URL,
+            manifest.address
+        )
+        .map_err(|err| format!("{} error: {}", ENV_NEXTEST_ENV, err))?;
+    }
+
+    Ok(manifest)
+}
+
+/// Proxy with the given name from the manifest pointed to by `TOXIPROXY_MANIFEST`.
+pub fn attach(name: &str) -> Result<Proxy, String> {
+    Manifest::from_env()?.attach(name)
+}
+
+/// Deletes the proxies set up by [`global_setup`] and the manifest at `path`.
+///
+/// # Examples
+///
+/// ```
+/// # use toxiproxy_rust::{proxy::ProxyPack, setup, TOXIPROXY};
+/// let path = std::env::temp_dir().join("toxiproxy_manifest_teardown_doc.json");
+/// setup::global_setup(
+///     &TOXIPROXY,
+///     vec![ProxyPack::new_ephemeral("teardown_doc".into(), "localhost:2000".into())],
+///     &path,
+/// )
+/// .expect("proxies are set up");
+///
+/// setup::global_teardown(&path).expect("proxies are deleted");
+/// assert!(TOXIPROXY.find_proxy("teardown_doc").is_err());
+/// assert!(!path.exists());
+/// ```
+pub fn global_teardown<P: AsRef<Path>>(path: P) -> Result<(), String> {
+    Manifest::read(&path)?.teardown()?;
+    fs::remove_file(&path).map_err(|err| format!("manifest error: {}", err))
+}
@@ -0,0 +1,82 @@
+//! Programmatic snapshots of the server state, to save a clean state, mess things up, and put the
+//! clean state back - eg. to isolate test suites sharing a server.
+
+use super::client::*;
+use super::config::*;
+use super::proxy::*;
+use serde::{Deserialize, Serialize};
+
+/// Proxies (in the namespace of the client which took the snapshot) with their enabled flags and
+/// toxics, see [`Client::snapshot`].
+///
+/// [`Client::snapshot`]: ../client/struct.Client.html#method.snapshot
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Snapshot {
+    pub proxies: Vec<ProxyPack>,
+}
+
+impl Client {
+    /// Captures the current proxies, their enabled flags and toxics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let snapshot = toxiproxy_rust::TOXIPROXY.snapshot().expect("state is captured");
+    /// ```
+    pub fn snapshot(&self) -> Result<Snapshot, String> {
+        self.config().map(|config| Snapshot {
+            proxies: config.proxies,
+        })
+    }
+
+    /// Puts the state of a snapshot back: proxies created since are deleted, deleted ones are
+    /// created again (on the same listen address), and every proxy gets back its upstream,
+    /// enabled flag and toxics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{proxy::ProxyPack, TOXIPROXY};
+    /// let client = TOXIPROXY.namespaced(toxiproxy_rust::namespace::Namespace::unique());
+    /// let snapshot = client.snapshot().unwrap();
+    ///
+    /// client.populate(vec![ProxyPack::new_ephemeral(
+    ///     "snapshot_doc".into(),
+    ///     "localhost:2000".into(),
+    /// )]).unwrap();
+    ///
+    /// client.restore(&snapshot).expect("state is restored");
+    /// assert!(client.find_proxy("snapshot_doc").is_err());
+    /// ```
+    pub fn restore(&self, snapshot: &Snapshot) -> Result<(), String> {
+        for (name, proxy) in self.all()? {
+            if !snapshot
+                .proxies
+                .iter()
+                .any(|proxy_pack| proxy_pack.name == name)
+            {
+                proxy.delete()?;
+            }
+        }
+
+        let config = Config {
+            proxies: snapshot.proxies.clone(),
+        };
+        let proxies = config.populate(self)?;
+
+        for proxy_pack in snapshot.proxies.iter() {
+            let name = self.qualify(&proxy_pack.name);
+            let proxy = proxies
+                .iter()
+                .find(|proxy| proxy.proxy_pack.name == name)
+                .ok_or_else(|| format!("proxy {:?} was not restored", name))?;
+            if proxy_pack.enabled {
+                proxy.enable()?;
+            } else {
+                proxy.disable()?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+//! Gherkin steps (requires the `steps` feature), for acceptance tests written as feature files.
+//! [`ToxiproxyWorld::run_feature`] runs the scenarios of a feature, and
+//! [`ToxiproxyWorld::run_step`] a single step - eg. from the catch-all step of another Gherkin
+//! runner:
+//!
+//! ```
+//! # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new_ephemeral(
+//! #    "steps_feature_doc".into(),
+//! #    "localhost:2000".into(),
+//! # )]).unwrap();
+//! let mut world = toxiproxy_rust::steps::ToxiproxyWorld::default();
+//!
+//! world
+//!     .run_feature(
+//!         r#"
+//! Feature: Slow database
+//!   Scenario: Latency on the connection
+//!     Given proxy steps_feature_doc has 100ms latency downstream
+//!     Then proxy steps_feature_doc should have toxic latency_downstream
+//! "#,
+//!     )
+//!     .expect("scenarios pass");
+//! # toxiproxy_rust::TOXIPROXY.find_proxy("steps_feature_doc").unwrap().delete().unwrap();
+//! ```
+//!
+//! With [cucumber], the world goes in a field of the suite's own `World`, and a catch-all step
+//! hands the steps of this module to it:
+//!
+//! ```ignore
+//! #[derive(Debug, Default, cucumber::World)]
+//! struct World {
+//!     toxiproxy: toxiproxy_rust::steps::ToxiproxyWorld,
+//! }
+//!
+//! #[cucumber::given(regex = r"^(proxy .+)$")]
+//! #[cucumber::when(regex = r"^(proxy .+)$")]
+//! #[cucumber::then(regex = r"^(proxy .+)$")]
+//! fn toxiproxy_step(world: &mut World, step: String) {
+//!     world.toxiproxy.run_step(&step).unwrap();
+//! }
+//! ```
+//!
+//! Supported steps (the leading `Given`/`When`/`Then`/`And`/`But` keyword is optional, and
+//! arguments may be quoted, eg. names with spaces):
+//!
+//! - `proxy <name> has <n>ms latency <stream>`
+//! - `proxy <name> has <n>KB/s bandwidth <stream>`
+//! - `proxy <name> times out after <n>ms <stream>`
+//! - `proxy <name> has no toxics`
+//! - `proxy <name> is down`, `proxy <name> is up`
+//! - `proxy <name> should have toxic <toxic>`
+//! - `proxy <name> should be down`, `proxy <name> should be up`
+//!
+//! [cucumber]: https://docs.rs/cucumber
+
+use super::client::*;
+use super::proxy::*;
+use super::toxic::*;
+use super::TOXIPROXY;
+use gherkin::{Feature, GherkinEnv, Scenario, Step};
+use std::collections::HashMap;
+use std::fmt;
+
+const KEYWORDS: [&str; 5] = ["Given", "When", "Then", "And", "But"];
+
+/// State shared by the steps of a scenario: the client and the proxies the steps touched.
+pub struct ToxiproxyWorld {
+    client: Client,
+    proxies: HashMap<String, Proxy>,
+}
+
+impl ToxiproxyWorld {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            proxies: HashMap::new(),
+        }
+    }
+
+    /// Proxy with the given name, fetched once per scenario.
+    pub fn proxy(&mut self, name: &str) -> Result<&Proxy, String> {
+        if !self.proxies.contains_key(name) {
+            let proxy = self.client.find_proxy(name)?;
+            self.proxies.insert(name.into(), proxy);
+        }
+        Ok(&self.proxies[name])
+    }
+
+    /// Runs a step.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new_ephemeral(
+    /// #    "steps_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// let mut world = toxiproxy_rust::steps::ToxiproxyWorld::default();
+    ///
+    /// world.run_step("Given proxy steps_doc has 2000ms latency downstream").unwrap();
+    /// world.run_step("Then proxy steps_doc should have toxic latency_downstream").unwrap();
+    /// assert!(world.run_step("Given the moon is full").is_err());
+    ///
+    /// world.reset().unwrap();
+    /// # toxiproxy_rust::TOXIPROXY.find_proxy("steps_doc").unwrap().delete().unwrap();
+    /// ```
+    pub fn run_step(&mut self, step: &str) -> Result<(), String> {
+        let words = split_words(step)?;
+        let mut words = words.iter().map(String::as_str).collect::<Vec<&str>>();
+        if matches!(words.first(), Some(word) if KEYWORDS.contains(word)) {
+            words.remove(0);
+        }
+
+        match words[..] {
+            ["proxy", name, "has", latency, "latency", stream] => {
+                let latency = parse_number(latency, "ms")?;
+                self.proxy(name)?
+                    .with_latency(stream.into(), latency, 0, 1.0);
+                Ok(())
+            }
+            ["proxy", name, "has", rate, "bandwidth", stream] => {
+                let rate = parse_number(rate, "KB/s")?;
+                self.proxy(name)?.with_bandwidth(stream.into(), rate, 1.0);
+                Ok(())
+            }
+            ["proxy", name, "times", "out", "after", timeout, stream] => {
+                let timeout = parse_number(timeout, "ms")?;
+                self.proxy(name)?.with_timeout(stream.into(), timeout, 1.0);
+                Ok(())
+            }
+            ["proxy", name, "has", "no", "toxics"] => self.proxy(name)?.delete_all_toxics(),
+            ["proxy", name, "is", "down"] => self.proxy(name)?.disable(),
+            ["proxy", name, "is", "up"] => self.proxy(name)?.enable(),
+            ["proxy", name, "should", "have", "toxic", toxic] => {
+                let toxics = self.proxy(name)?.toxics()?;
+                if toxics.iter().any(|pack| pack.name == toxic) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "proxy {:?} has no toxic {:?}, found: {:?}",
+                        name,
+                        toxic,
+                        toxics
+                            .iter()
+                            .map(|pack| pack.name.as_str())
+                            .collect::<Vec<&str>>()
+                    ))
+                }
+            }
+            ["proxy", name, "should", "be", state @ ("down" | "up")] => {
+                let enabled = self.proxy(name)?.is_enabled()?;
+                if enabled == (state == "up") {
+                    Ok(())
+                } else {
+                    Err(format!("proxy {:?} is not {}", name, state))
+                }
+            }
+            _ => Err(format!("unknown step: {:?}", step)),
+        }
+    }
+
+    /// Runs the scenarios of a Gherkin feature, each after the background steps, and resets the
+    /// proxies after each of them. Stops at the first failing step. Scenario outlines are not
+    /// supported.
+    pub fn run_feature(&mut self, feature: &str) -> Result<(), String> {
+        let feature = Feature::parse(feature, GherkinEnv::default())
+            .map_err(|err| format!("invalid feature: {}", err))?;
+
+        let mut scenarios = feature
+            .scenarios
+            .iter()
+            .map(|scenario| (None, scenario))
+            .collect::<Vec<_>>();
+        for rule in feature.rules.iter() {
+            scenarios.extend(
+                rule.scenarios
+                    .iter()
+                    .map(|scenario| (rule.background.as_ref(), scenario)),
+            );
+        }
+
+        for (rule_background, scenario) in scenarios {
+            let steps = feature
+                .background
+                .iter()
+                .chain(rule_background)
+                .flat_map(|background| background.steps.iter())
+                .chain(scenario.steps.iter());
+            let result = self.run_scenario(scenario, steps);
+            result.and(self.reset())?;
+        }
+        Ok(())
+    }
+
+    fn run_scenario<'a>(
+        &mut self,
+        scenario: &Scenario,
+        steps: impl Iterator<Item = &'a Step>,
+    ) -> Result<(), String> {
+        if !scenario.examples.is_empty() {
+            return Err(format!(
+                "scenario {:?}: scenario outlines are not supported",
+                scenario.name
+            ));
+        }
+        for step in steps {
+            self.run_step(&step.value).map_err(|err| {
+                format!(
+                    "scenario {:?}, step {:?}: {}",
+                    scenario.name, step.value, err
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Removes the toxics of the proxies touched by the steps and enables them, eg. after a
+    /// scenario.
+    pub fn reset(&mut self) -> Result<(), String> {
+        let mut result = Ok(());
+        for (_, proxy) in self.proxies.drain() {
+            result = result.and(proxy.delete_all_toxics()).and(proxy.enable());
+        }
+        result
+    }
+}
+
+impl Default for ToxiproxyWorld {
+    /// World using the shared [`TOXIPROXY`] client.
+    ///
+    /// [`TOXIPROXY`]: ../struct.TOXIPROXY.html
+    fn default() -> Self {
+        Self::new(TOXIPROXY.clone())
+    }
+}
+
+impl fmt::Debug for ToxiproxyWorld {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToxiproxyWorld")
+            .field("proxies", &self.proxies.keys().collect::<Vec<&String>>())
+            .finish()
+    }
+}
+
+/// Words of a step, a quoted word being the text between the quotes (spaces included).
+fn split_words(step: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut chars = step.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut word = String::new();
+        if c == '"' {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => word.push(c),
+                    None => return Err(format!("unterminated quote in step: {:?}", step)),
+                }
+            }
+        } else {
+            word.push(c);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                word.push(c);
+            }
+        }
+        words.push(word);
+    }
+    Ok(words)
+}
+
+fn parse_number(word: &str, unit: &str) -> Result<ToxicValueType, String> {
+    word.strip_suffix(unit)
+        .and_then(|number| number.parse().ok())
+        .ok_or_else(|| format!("expected a number in {}, got {:?}", unit, word))
+}
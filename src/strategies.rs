@@ -0,0 +1,156 @@
+//! [proptest] strategies generating valid proxy and toxic configurations (requires the `proptest`
+//! feature), to property-test resilience logic across the parameter space of the toxics.
+//!
+//! ```
+//! use proptest::prelude::*;
+//! use toxiproxy_rust::strategies;
+//!
+//! proptest!(|(toxic in strategies::toxic_pack())| {
+//!     prop_assert!(toxic.toxicity >= 0.0 && toxic.toxicity <= 1.0);
+//! });
+//! ```
+//!
+//! [proptest]: https://docs.rs/proptest
+
+use super::proxy::*;
+use super::toxic::*;
+use proptest::collection;
+use proptest::prelude::*;
+use std::collections::HashMap;
+
+/// `"upstream"` or `"downstream"`.
+pub fn stream() -> impl Strategy<Value = String> {
+    prop_oneof![Just("upstream".to_owned()), Just("downstream".to_owned())]
+}
+
+/// Toxicity between 0 and 1.
+pub fn toxicity() -> impl Strategy<Value = f32> {
+    0.0f32..=1.0
+}
+
+/// Latency toxic of up to 10s, with up to 1s jitter.
+pub fn latency() -> impl Strategy<Value = ToxicPack> {
+    (
+        stream(),
+        toxicity(),
+        0..=10_000 as ToxicValueType,
+        0..=1_000 as ToxicValueType,
+    )
+        .prop_map(|(stream, toxicity, latency, jitter)| {
+            toxic(
+                "latency",
+                stream,
+                toxicity,
+                &[("latency", latency), ("jitter", jitter)],
+            )
+        })
+}
+
+/// Bandwidth toxic limiting to 1 KB/s - 10 MB/s.
+pub fn bandwidth() -> impl Strategy<Value = ToxicPack> {
+    (stream(), toxicity(), 1..=10_000 as ToxicValueType).prop_map(|(stream, toxicity, rate)| {
+        toxic("bandwidth", stream, toxicity, &[("rate", rate)])
+    })
+}
+
+/// Slow close toxic delaying the close by up to 10s.
+pub fn slow_close() -> impl Strategy<Value = ToxicPack> {
+    (stream(), toxicity(), 0..=10_000 as ToxicValueType).prop_map(|(stream, toxicity, delay)| {
+        toxic("slow_close", stream, toxicity, &[("delay", delay)])
+    })
+}
+
+/// Timeout toxic of up to 10s (0 meaning no timeout: data is held until the toxic is removed).
+pub fn timeout() -> impl Strategy<Value = ToxicPack> {
+    (stream(), toxicity(), 0..=10_000 as ToxicValueType).prop_map(|(stream, toxicity, timeout)| {
+        toxic("timeout", stream, toxicity, &[("timeout", timeout)])
+    })
+}
+
+/// Slicer toxic with slices of 1 - 4096 bytes, the variation being smaller than the average size.
+pub fn slicer() -> impl Strategy<Value = ToxicPack> {
+    (
+        stream(),
+        toxicity(),
+        1..=4_096 as ToxicValueType,
+        0..=1_000 as ToxicValueType,
+    )
+        .prop_flat_map(|(stream, toxicity, average_size, delay)| {
+            (0..average_size).prop_map(move |size_variation| {
+                toxic(
+                    "slicer",
+                    stream.clone(),
+                    toxicity,
+                    &[
+                        ("average_size", average_size),
+                        ("size_variation", size_variation),
+                        ("delay", delay),
+                    ],
+                )
+            })
+        })
+}
+
+/// Limit data toxic closing the connection after up to 1 MB.
+pub fn limit_data() -> impl Strategy<Value = ToxicPack> {
+    (stream(), toxicity(), 0..=1_000_000 as ToxicValueType).prop_map(|(stream, toxicity, bytes)| {
+        toxic("limit_data", stream, toxicity, &[("bytes", bytes)])
+    })
+}
+
+/// Any of the supported toxics.
+pub fn toxic_pack() -> impl Strategy<Value = ToxicPack> {
+    prop_oneof![
+        latency(),
+        bandwidth(),
+        slow_close(),
+        timeout(),
+        slicer(),
+        limit_data(),
+    ]
+}
+
+/// Up to `max` toxics with distinct names, so they can all be set on the same proxy.
+pub fn toxic_packs(max: usize) -> impl Strategy<Value = Vec<ToxicPack>> {
+    collection::vec(toxic_pack(), 0..=max).prop_map(|mut toxics| {
+        let mut seen = vec![];
+        toxics.retain(|toxic| {
+            let is_new = !seen.contains(&toxic.name);
+            seen.push(toxic.name.clone());
+            is_new
+        });
+        toxics
+    })
+}
+
+/// Proxy listening on a free local port, with a valid upstream address and up to 3 toxics.
+pub fn proxy_pack() -> impl Strategy<Value = ProxyPack> {
+    (
+        "[a-z][a-z0-9_]{0,15}",
+        "[a-z]([a-z0-9-]{0,14}[a-z0-9])?",
+        1..=u16::MAX,
+        any::<bool>(),
+        toxic_packs(3),
+    )
+        .prop_map(|(name, host, port, enabled, toxics)| ProxyPack {
+            name,
+            listen: "127.0.0.1:0".into(),
+            upstream: format!("{}:{}", host, port),
+            enabled,
+            toxics,
+            tags: vec![],
+        })
+}
+
+fn toxic(
+    r#type: &str,
+    stream: String,
+    toxicity: f32,
+    attributes: &[(&str, ToxicValueType)],
+) -> ToxicPack {
+    let attributes = attributes
+        .iter()
+        .map(|(name, value)| ((*name).to_owned(), *value))
+        .collect::<HashMap<String, ToxicValueType>>();
+    ToxicPack::new(r#type.into(), stream, toxicity, attributes)
+}
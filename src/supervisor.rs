@@ -0,0 +1,254 @@
+//! Supervision of a server started by the crate (a [`ToxiproxyServer`], or a `ToxiproxyContainer`
+//! with the `testcontainers` feature), so a crash of the server midway doesn't doom the rest of a
+//! test suite: the server is checked on a background thread, started again when it's gone, and
+//! its proxies are put back as they were at the last check.
+//!
+//! ```no_run
+//! use toxiproxy_rust::{server::ToxiproxyServer, supervisor::*};
+//!
+//! let server = ToxiproxyServer::spawn("/usr/local/bin/toxiproxy-server", 18474).unwrap();
+//! let supervisor = Supervisor::builder(server)
+//!     .on_event(|event| eprintln!("Toxiproxy supervisor: {:?}", event))
+//!     .start();
+//! let client = supervisor.client();
+//! ```
+//!
+//! [`ToxiproxyServer`]: ../server/struct.ToxiproxyServer.html
+
+use super::client::*;
+use super::server::*;
+use super::snapshot::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often the server is checked by default.
+pub const DEFAULT_SUPERVISION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A server the crate manages the process of.
+pub trait Supervised: Send + 'static {
+    fn client(&self) -> &Client;
+
+    /// Whether the server is still running.
+    fn is_alive(&mut self) -> bool;
+
+    /// Starts the server again, without proxies.
+    fn restart(&mut self) -> Result<(), String>;
+}
+
+impl Supervised for ToxiproxyServer {
+    fn client(&self) -> &Client {
+        ToxiproxyServer::client(self)
+    }
+
+    fn is_alive(&mut self) -> bool {
+        ToxiproxyServer::is_alive(self)
+    }
+
+    fn restart(&mut self) -> Result<(), String> {
+        ToxiproxyServer::restart(self)
+    }
+}
+
+#[cfg(feature = "testcontainers")]
+impl Supervised for super::container::ToxiproxyContainer {
+    fn client(&self) -> &Client {
+        super::container::ToxiproxyContainer::client(self)
+    }
+
+    fn is_alive(&mut self) -> bool {
+        self.client().is_running()
+    }
+
+    fn restart(&mut self) -> Result<(), String> {
+        super::container::ToxiproxyContainer::restart(self)
+    }
+}
+
+/// What the supervisor did about a crash.
+#[derive(Debug, Clone)]
+pub enum SupervisorEvent {
+    /// The server was started again; `restored` tells whether its proxies were put back.
+    Restarted {
+        restarts: usize,
+        restored: Result<(), String>,
+    },
+    /// Starting the server failed, it's tried again at the next check.
+    RestartFailed { error: String },
+}
+
+type Hook = Box<dyn Fn(&SupervisorEvent) + Send>;
+
+/// Configuration of a [`Supervisor`].
+pub struct SupervisorBuilder<S: Supervised> {
+    server: S,
+    interval: Duration,
+    hooks: Vec<Hook>,
+}
+
+impl<S: Supervised> SupervisorBuilder<S> {
+    /// How often the server is checked, every second by default. Proxies changed less than an
+    /// interval before a crash may be restored in their previous state.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Runs the callback (on the supervising thread) after each restart attempt.
+    pub fn on_event<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&SupervisorEvent) + Send + 'static,
+    {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Starts checking the server on a background thread.
+    pub fn start(self) -> Supervisor<S> {
+        let client = self.server.client().clone();
+        let snapshot = client.snapshot().ok();
+        let server = Arc::new(Mutex::new(self.server));
+        let restarts = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+
+        let worker = {
+            let server = server.clone();
+            let client = client.clone();
+            let restarts = restarts.clone();
+            let stopped = stopped.clone();
+            let (interval, hooks) = (self.interval, self.hooks);
+            thread::spawn(move || {
+                run(
+                    &server, &client, interval, snapshot, &hooks, &restarts, &stopped,
+                )
+            })
+        };
+
+        Supervisor {
+            server,
+            client,
+            restarts,
+            stopped,
+            worker: Some(worker),
+        }
+    }
+}
+
+/// Supervised server, stopped (with its server) when dropped. Use [`Supervisor::builder`] to
+/// configure it.
+pub struct Supervisor<S: Supervised> {
+    server: Arc<Mutex<S>>,
+    client: Client,
+    restarts: Arc<AtomicUsize>,
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl<S: Supervised> Supervisor<S> {
+    pub fn builder(server: S) -> SupervisorBuilder<S> {
+        SupervisorBuilder {
+            server,
+            interval: DEFAULT_SUPERVISION_INTERVAL,
+            hooks: vec![],
+        }
+    }
+
+    /// Client of the server, which keeps working across restarts.
+    pub fn client(&self) -> Client {
+        self.client.clone()
+    }
+
+    /// Number of times the server was started again.
+    pub fn restarts(&self) -> usize {
+        self.restarts.load(Ordering::SeqCst)
+    }
+
+    /// Runs the closure on the server, eg. to read its address. Checks wait meanwhile.
+    pub fn with_server<F, T>(&self, closure: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut S) -> T,
+    {
+        self.server
+            .lock()
+            .map(|mut server| closure(&mut server))
+            .map_err(|err| format!("lock error: {}", err))
+    }
+
+    /// Stops supervising, and the server.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        let (stopped, wakeup) = &*self.stopped;
+        if let Ok(mut stopped) = stopped.lock() {
+            *stopped = true;
+        }
+        wakeup.notify_all();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl<S: Supervised> Drop for Supervisor<S> {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Waits for the interval, returns whether the supervisor was stopped meanwhile.
+fn wait(stopped: &(Mutex<bool>, Condvar), interval: Duration) -> bool {
+    let deadline = Instant::now() + interval;
+    let (stopped, wakeup) = stopped;
+    let mut stopped = match stopped.lock() {
+        Ok(stopped) => stopped,
+        Err(_) => return true,
+    };
+    while !*stopped && Instant::now() < deadline {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        stopped = match wakeup.wait_timeout(stopped, timeout) {
+            Ok((stopped, _)) => stopped,
+            Err(_) => return true,
+        };
+    }
+    *stopped
+}
+
+fn run<S: Supervised>(
+    server: &Mutex<S>,
+    client: &Client,
+    interval: Duration,
+    mut snapshot: Option<Snapshot>,
+    hooks: &[Hook],
+    restarts: &AtomicUsize,
+    stopped: &(Mutex<bool>, Condvar),
+) {
+    while !wait(stopped, interval) {
+        let mut server = match server.lock() {
+            Ok(server) => server,
+            Err(_) => return,
+        };
+        if server.is_alive() {
+            if let Ok(current) = client.snapshot() {
+                snapshot = Some(current);
+            }
+            continue;
+        }
+
+        let event = match server.restart() {
+            Ok(()) => SupervisorEvent::Restarted {
+                restarts: restarts.fetch_add(1, Ordering::SeqCst) + 1,
+                restored: snapshot
+                    .as_ref()
+                    .map_or(Ok(()), |snapshot| client.restore(snapshot)),
+            },
+            Err(error) => SupervisorEvent::RestartFailed { error },
+        };
+        drop(server);
+        for hook in hooks.iter() {
+            hook(&event);
+        }
+    }
+}
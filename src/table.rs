@@ -0,0 +1,130 @@
+//! Human-readable table of proxies and their toxics - eg. to print the network conditions when a
+//! test fails, see [`Client::dump`].
+//!
+//! [`Client::dump`]: ../client/struct.Client.html#method.dump
+
+use super::client::*;
+use super::proxy::*;
+use std::fmt;
+
+const HEADER: [&str; 9] = [
+    "PROXY",
+    "ENABLED",
+    "LISTEN",
+    "UPSTREAM",
+    "TOXIC",
+    "TYPE",
+    "STREAM",
+    "TOXICITY",
+    "ATTRIBUTES",
+];
+
+/// Proxies sorted by name, displayed as an aligned table with a row per toxic:
+///
+/// ```text
+/// PROXY  ENABLED  LISTEN          UPSTREAM    TOXIC  TYPE     STREAM      TOXICITY  ATTRIBUTES
+/// db     yes      127.0.0.1:5433  db:5432     slow   latency  downstream  100%      latency=500
+/// redis  no       127.0.0.1:6380  redis:6379
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProxyTable {
+    pub proxies: Vec<ProxyPack>,
+}
+
+impl From<Vec<ProxyPack>> for ProxyTable {
+    fn from(mut proxies: Vec<ProxyPack>) -> Self {
+        proxies.sort_by(|a, b| a.name.cmp(&b.name));
+        Self { proxies }
+    }
+}
+
+impl ProxyTable {
+    fn rows(&self) -> Vec<Vec<String>> {
+        let mut rows = vec![HEADER.iter().map(|title| title.to_string()).collect()];
+        for proxy in self.proxies.iter() {
+            let mut cells = vec![
+                proxy.name.clone(),
+                if proxy.enabled { "yes" } else { "no" }.into(),
+                proxy.listen.clone(),
+                proxy.upstream.clone(),
+            ];
+            if proxy.toxics.is_empty() {
+                rows.push(cells);
+                continue;
+            }
+
+            for toxic in proxy.toxics.iter() {
+                let mut attributes = toxic
+                    .attributes
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect::<Vec<String>>();
+                attributes.sort();
+
+                cells.extend(vec![
+                    toxic.name.clone(),
+                    toxic.r#type.clone(),
+                    toxic.stream.clone(),
+                    format!("{:.0}%", toxic.toxicity * 100.0),
+                    attributes.join(" "),
+                ]);
+                rows.push(cells);
+                // Further toxics of the proxy go on their own rows, under the first one.
+                cells = vec![String::new(); 4];
+            }
+        }
+        rows
+    }
+}
+
+impl fmt::Display for ProxyTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = self.rows();
+        let mut widths = vec![0; HEADER.len()];
+        for row in rows.iter() {
+            for (width, cell) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        for row in rows.iter() {
+            let line = row
+                .iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<String>>()
+                .join("  ");
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl Client {
+    /// Every proxy on the server (within the client's namespace) with its enabled flag and toxics,
+    /// to print as a table.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{namespace::Namespace, proxy::ProxyPack, TOXIPROXY};
+    /// let client = TOXIPROXY.namespaced(Namespace::unique());
+    /// let proxy = client
+    ///     .create_proxy(ProxyPack::new_ephemeral("dump_doc".into(), "localhost:2000".into()))
+    ///     .unwrap();
+    ///
+    /// let table = client.dump().expect("proxies are fetched");
+    /// println!("{}", table);
+    /// assert!(table.to_string().starts_with("PROXY "));
+    /// # proxy.delete().unwrap();
+    /// ```
+    pub fn dump(&self) -> Result<ProxyTable, String> {
+        self.all().map(|proxies| {
+            proxies
+                .into_values()
+                .map(|proxy| proxy.proxy_pack)
+                .collect::<Vec<ProxyPack>>()
+                .into()
+        })
+    }
+}
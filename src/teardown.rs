@@ -0,0 +1,138 @@
+//! Teardown when the test process terminates - at exit, on Ctrl-C and on `SIGTERM` - so an aborted
+//! local run doesn't leave proxies and toxics behind on a shared server. Once [`on_exit`] is
+//! called, every proxy created through a scoped client (see [`Client::scoped`]) and every toxic
+//! registered through a toxic scope (see [`Proxy::toxic_scope`]) opened from then on is deleted
+//! when the process terminates, unless the client or the scope was dropped meanwhile (deleting them
+//! already).
+//!
+//! ```
+//! toxiproxy_rust::teardown::on_exit().expect("teardown is installed");
+//!
+//! let client = toxiproxy_rust::TOXIPROXY.scoped();
+//! ```
+//!
+//! [`on_exit`]: fn.on_exit.html
+//! [`Client::scoped`]: ../client/struct.Client.html#method.scoped
+//! [`Proxy::toxic_scope`]: ../proxy/struct.Proxy.html#method.toxic_scope
+
+use super::client::*;
+use super::proxy::*;
+use std::collections::HashMap;
+use std::panic;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread;
+
+/// Exit code after a teardown on a signal, as shells report a process interrupted by Ctrl-C. It's
+/// the same for `SIGTERM` and `SIGHUP`, as the Ctrl-C handler doesn't tell the signals apart.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static INSTALL: Once = Once::new();
+
+lazy_static! {
+    static ref INSTALLED: Mutex<Result<(), String>> = Mutex::new(Ok(()));
+    static ref LIVE: Mutex<Live> = Mutex::new(Live::default());
+}
+
+/// Scoped clients and toxic scopes alive, by the address of what they record.
+#[derive(Default)]
+struct Live {
+    clients: HashMap<usize, Client>,
+    proxies: HashMap<usize, Proxy>,
+}
+
+/// Deletes the proxies and toxics of live scoped clients and toxic scopes when the process exits,
+/// is interrupted (Ctrl-C) or terminated (`SIGTERM`, `SIGHUP`), then exits with code 130 on any of
+/// these signals (not 128 + the signal number, as they can't be told apart). Fails if another
+/// Ctrl-C handler is installed already. Calling it again does nothing.
+pub fn on_exit() -> Result<(), String> {
+    INSTALL.call_once(|| {
+        let installed = ctrlc::set_handler(|| {
+            report(run());
+            process::exit(INTERRUPTED_EXIT_CODE);
+        })
+        .map_err(|err| format!("Ctrl-C handler cannot be installed: {}", err))
+        // The handler is a plain function, which doesn't unwind.
+        .and_then(|_| match unsafe { libc::atexit(at_exit) } {
+            0 => Ok(()),
+            code => Err(format!("exit handler cannot be installed: {}", code)),
+        });
+
+        ENABLED.store(installed.is_ok(), Ordering::SeqCst);
+        if let Ok(mut result) = INSTALLED.lock() {
+            *result = installed;
+        }
+    });
+
+    INSTALLED
+        .lock()
+        .map_err(|err| format!("lock error: {}", err))?
+        .clone()
+}
+
+/// Deletes the proxies and toxics of live scoped clients and toxic scopes right away (which is
+/// otherwise done when the process terminates), attempting every deletion and reporting the first
+/// failure.
+pub fn run() -> Result<(), String> {
+    let live = match LIVE.lock() {
+        Ok(mut live) => std::mem::take(&mut *live),
+        Err(err) => return Err(format!("lock error: {}", err)),
+    };
+
+    let mut result = Ok(());
+    for proxy in live.proxies.values() {
+        result = result.and(proxy.delete_created_toxics());
+    }
+    for client in live.clients.values() {
+        result = result.and(client.delete_created_proxies());
+    }
+    result
+}
+
+extern "C" fn at_exit() {
+    // Unwinding out of an exit handler would abort the process. The thread-locals of the exiting
+    // thread may be destroyed already (eg. the instrumentation context), hence a thread of its own.
+    let _ = panic::catch_unwind(|| {
+        if let Ok(result) = thread::spawn(run).join() {
+            report(result);
+        }
+    });
+}
+
+fn report(result: Result<(), String>) {
+    if let Err(err) = result {
+        eprintln!("Toxiproxy teardown has failed: {}", err);
+    }
+}
+
+/// Records a scoped client, to clean up after at exit.
+pub(crate) fn register_client(client: &Client) {
+    if let (true, Some(key)) = (ENABLED.load(Ordering::SeqCst), client.scope_key()) {
+        if let Ok(mut live) = LIVE.lock() {
+            live.clients.insert(key, client.clone());
+        }
+    }
+}
+
+pub(crate) fn unregister_client(client: &Client) {
+    if let (Some(key), Ok(mut live)) = (client.scope_key(), LIVE.lock()) {
+        live.clients.remove(&key);
+    }
+}
+
+/// Records a toxic scope, to clean up after at exit.
+pub(crate) fn register_proxy(proxy: &Proxy) {
+    if let (true, Some(key)) = (ENABLED.load(Ordering::SeqCst), proxy.scope_key()) {
+        if let Ok(mut live) = LIVE.lock() {
+            live.proxies.insert(key, proxy.share_scope());
+        }
+    }
+}
+
+pub(crate) fn unregister_proxy(proxy: &Proxy) {
+    if let (Some(key), Ok(mut live)) = (proxy.scope_key(), LIVE.lock()) {
+        live.proxies.remove(&key);
+    }
+}
@@ -0,0 +1,204 @@
+//! Helpers for testing code through proxies (requires the `test_support` feature).
+//!
+//! [`EchoServer`] is a small TCP server to put behind a proxy as its upstream:
+//!
+//! ```
+//! use toxiproxy_rust::{proxy::ProxyPack, test_support::EchoServer, TOXIPROXY};
+//!
+//! let server = EchoServer::builder().start().expect("server is started");
+//! let proxies = TOXIPROXY
+//!     .populate(vec![ProxyPack::new_ephemeral(
+//!         "echo".into(),
+//!         server.addr().to_string(),
+//!     )])
+//!     .unwrap();
+//! # proxies[0].delete().unwrap();
+//! ```
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// What the [`EchoServer`] does with a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseMode {
+    /// Keep echoing data until the client closes the connection.
+    KeepOpen,
+    /// Close the connection once the first response is sent.
+    AfterResponse,
+    /// Close the connection right after accepting it, without reading or responding.
+    Immediately,
+}
+
+/// Configuration of an [`EchoServer`].
+#[derive(Debug, Clone)]
+pub struct EchoServerBuilder {
+    bind: String,
+    respond_after: usize,
+    response: Option<Vec<u8>>,
+    delay: Duration,
+    close_mode: CloseMode,
+}
+
+impl EchoServerBuilder {
+    /// Address to bind, `127.0.0.1:0` (a free port) by default.
+    pub fn bind(mut self, addr: &str) -> Self {
+        self.bind = addr.into();
+        self
+    }
+
+    /// Number of bytes to receive before the first response is sent, 1 by default.
+    pub fn respond_after(mut self, bytes: usize) -> Self {
+        self.respond_after = bytes;
+        self
+    }
+
+    /// Fixed payload sent as the first response, instead of echoing the received data.
+    pub fn response(mut self, response: &[u8]) -> Self {
+        self.response = Some(response.to_vec());
+        self
+    }
+
+    /// Delay before each response, none by default.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// What to do with a connection, [`CloseMode::KeepOpen`] by default.
+    pub fn close_mode(mut self, close_mode: CloseMode) -> Self {
+        self.close_mode = close_mode;
+        self
+    }
+
+    /// Binds the address and starts serving connections on a background thread.
+    pub fn start(self) -> Result<EchoServer, String> {
+        let listener =
+            TcpListener::bind(&self.bind).map_err(|err| format!("bind error: {}", err))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|err| format!("bind error: {}", err))?;
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let thread_stopped = stopped.clone();
+        let handle = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let config = self.clone();
+                    thread::spawn(move || config.serve(stream));
+                }
+            }
+        });
+
+        Ok(EchoServer {
+            addr,
+            stopped,
+            handle: Some(handle),
+        })
+    }
+
+    fn serve(&self, mut stream: TcpStream) {
+        if self.close_mode == CloseMode::Immediately {
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+
+        let mut received = vec![];
+        let mut buf = [0u8; 4096];
+        while received.len() < self.respond_after.max(1) {
+            match stream.read(&mut buf) {
+                Ok(0) | Err(_) => return,
+                Ok(n) => received.extend_from_slice(&buf[..n]),
+            }
+        }
+
+        thread::sleep(self.delay);
+        let response = self.response.as_ref().unwrap_or(&received);
+        if stream.write_all(response).is_err() {
+            return;
+        }
+
+        if self.close_mode == CloseMode::AfterResponse {
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => return,
+                Ok(n) => {
+                    thread::sleep(self.delay);
+                    if stream.write_all(&buf[..n]).is_err() {
+                        return;
+                    }
+                }
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+/// TCP server echoing what it receives, stopped when dropped. Use [`EchoServer::builder`] to
+/// configure it.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{Read, Write};
+/// use toxiproxy_rust::test_support::{CloseMode, EchoServer};
+///
+/// let server = EchoServer::builder()
+///     .respond_after(5)
+///     .response(b"byebye")
+///     .close_mode(CloseMode::AfterResponse)
+///     .start()
+///     .unwrap();
+///
+/// let mut stream = std::net::TcpStream::connect(server.addr()).unwrap();
+/// stream.write_all(b"hello").unwrap();
+///
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response).unwrap();
+/// assert_eq!("byebye", response);
+/// ```
+#[derive(Debug)]
+pub struct EchoServer {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EchoServer {
+    pub fn builder() -> EchoServerBuilder {
+        EchoServerBuilder {
+            bind: "127.0.0.1:0".into(),
+            respond_after: 1,
+            response: None,
+            delay: Duration::from_secs(0),
+            close_mode: CloseMode::KeepOpen,
+        }
+    }
+
+    /// Address the server is bound to.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for EchoServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wakes up the accepting thread so it can notice it's stopped.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,365 @@
+//! Timelines: changes to proxies scheduled at points in time - "at 5s add latency, at 20s remove
+//! it, at 25s disable the proxy" - played on a background thread while the test body runs.
+//!
+//! Points are set with [`Timeline::at`] (from the start) or [`Timeline::after`] (from the previous
+//! point). The timeline can be cancelled at any time: the remaining changes are skipped, and the
+//! proxies are restored - toxics it registered are removed and proxies it disabled are enabled.
+//!
+//! [`Timeline::at`]: struct.Timeline.html#method.at
+//! [`Timeline::after`]: struct.Timeline.html#method.after
+
+use super::client::*;
+use super::error::*;
+use super::proxy::*;
+use super::toxic::*;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Change to a proxy.
+#[derive(Debug, Clone)]
+pub enum Action {
+    AddToxic { proxy: String, toxic: ToxicPack },
+    RemoveToxic { proxy: String, toxic: String },
+    Disable { proxy: String },
+    Enable { proxy: String },
+}
+
+impl Action {
+    fn proxy(&self) -> &str {
+        match self {
+            Action::AddToxic { proxy, .. }
+            | Action::RemoveToxic { proxy, .. }
+            | Action::Disable { proxy }
+            | Action::Enable { proxy } => proxy,
+        }
+    }
+}
+
+/// Changes scheduled at points in time from the start of the timeline.
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    events: Vec<(Duration, Action)>,
+    cursor: Duration,
+}
+
+impl Timeline {
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{proxy::ProxyPack, timeline::Timeline, toxic::ToxicPack, TOXIPROXY};
+    /// # use std::time::Duration;
+    /// # TOXIPROXY.populate(vec![ProxyPack::new_ephemeral(
+    /// #    "timeline_doc".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]).unwrap();
+    /// let latency = ToxicPack::new(
+    ///     "latency".into(),
+    ///     "downstream".into(),
+    ///     1.0,
+    ///     vec![("latency".to_owned(), 500)].into_iter().collect(),
+    /// );
+    ///
+    /// Timeline::new()
+    ///     .at(Duration::from_millis(5))
+    ///     .add_toxic("timeline_doc", latency)
+    ///     .at(Duration::from_millis(20))
+    ///     .remove_toxic("timeline_doc", "latency_downstream")
+    ///     .after(Duration::from_millis(5))
+    ///     .disable("timeline_doc")
+    ///     .run(&TOXIPROXY, || {
+    ///         /* Example test:
+    ///            let service_result = MyService::Server::call_with_retries(params);
+    ///            assert!(service_result.is_ok());
+    ///         */
+    ///         # std::thread::sleep(Duration::from_millis(30));
+    ///     })
+    ///     .expect("timeline has run");
+    /// # TOXIPROXY.find_proxy("timeline_doc").unwrap().delete().unwrap();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves to the given time from the start. The following changes are scheduled there.
+    pub fn at(mut self, time: Duration) -> Self {
+        self.cursor = time;
+        self
+    }
+
+    /// Moves forward from the previous point.
+    pub fn after(mut self, delay: Duration) -> Self {
+        self.cursor += delay;
+        self
+    }
+
+    /// Registers a toxic, its name defaulting to `type_stream`.
+    pub fn add_toxic(self, proxy: &str, toxic: ToxicPack) -> Self {
+        self.schedule(Action::AddToxic {
+            proxy: proxy.into(),
+            toxic: toxic.or_default_name(),
+        })
+    }
+
+    pub fn remove_toxic(self, proxy: &str, toxic: &str) -> Self {
+        self.schedule(Action::RemoveToxic {
+            proxy: proxy.into(),
+            toxic: toxic.into(),
+        })
+    }
+
+    pub fn disable(self, proxy: &str) -> Self {
+        self.schedule(Action::Disable {
+            proxy: proxy.into(),
+        })
+    }
+
+    pub fn enable(self, proxy: &str) -> Self {
+        self.schedule(Action::Enable {
+            proxy: proxy.into(),
+        })
+    }
+
+    /// Schedules a change at the current point.
+    pub fn schedule(mut self, action: Action) -> Self {
+        self.events.push((self.cursor, action));
+        self
+    }
+
+    /// Time of the last change.
+    pub fn duration(&self) -> Duration {
+        self.events
+            .iter()
+            .map(|(time, _)| *time)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Plays the timeline while the closure runs. When the closure returns (or panics), the
+    /// changes not made yet are cancelled and the proxies are restored. Fails if a change failed.
+    pub fn run<F, T>(&self, client: &Client, closure: F) -> Result<T, String>
+    where
+        F: FnOnce() -> T,
+    {
+        let handle = self.start(client)?;
+        let (value, result) = Proxy::run_guarded(closure, || handle.cancel());
+        result.map(|_| value)
+    }
+
+    /// Starts playing the timeline on a background thread. Fails if a proxy can't be fetched.
+    pub fn start(&self, client: &Client) -> Result<TimelineHandle, String> {
+        let mut names: Vec<&str> = vec![];
+        for (_, action) in self.events.iter() {
+            if !names.contains(&action.proxy()) {
+                names.push(action.proxy());
+            }
+        }
+        let proxies = names
+            .into_iter()
+            .map(|name| {
+                client
+                    .find_proxy(name)
+                    .map(|proxy| (name.to_owned(), proxy))
+            })
+            .collect::<Result<Vec<(String, Proxy)>, String>>()?;
+
+        let mut events = self.events.clone();
+        events.sort_by_key(|(time, _)| *time);
+
+        let control = Arc::new(Control::default());
+        let worker = {
+            let control = control.clone();
+            thread::spawn(move || ApiError::capture(|| play(&events, &proxies, &control)))
+        };
+
+        Ok(TimelineHandle {
+            control,
+            worker: Some(worker),
+        })
+    }
+}
+
+/// Timeline playing on a background thread, see [`Timeline::start`]. The changes are held until
+/// the timeline is cancelled (or waited for), which restores the proxies. Dropping the handle
+/// cancels it too, ignoring errors.
+///
+/// [`Timeline::start`]: struct.Timeline.html#method.start
+pub struct TimelineHandle {
+    control: Arc<Control>,
+    worker: Option<JoinHandle<Result<(), ThreadFailure>>>,
+}
+
+impl TimelineHandle {
+    /// Whether every change has been made, or a change failed.
+    pub fn is_finished(&self) -> bool {
+        self.control.is_played()
+            || self
+                .worker
+                .as_ref()
+                .is_none_or(|worker| worker.is_finished())
+    }
+
+    /// Waits for every change to be made, then restores the proxies.
+    pub fn wait(mut self) -> Result<(), String> {
+        if let Some(worker) = self.worker.as_ref() {
+            self.control.wait_played(worker);
+        }
+        self.shutdown()
+    }
+
+    /// Skips the changes not made yet, and restores the proxies.
+    pub fn cancel(mut self) -> Result<(), String> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> Result<(), String> {
+        self.control.cancel();
+        match self.worker.take() {
+            Some(worker) => ApiError::resume(
+                worker
+                    .join()
+                    .unwrap_or_else(|_| Err("timeline thread panicked".into())),
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for TimelineHandle {
+    fn drop(&mut self) {
+        let _ = ApiError::keep(|| self.shutdown());
+    }
+}
+
+fn play(
+    events: &[(Duration, Action)],
+    proxies: &[(String, Proxy)],
+    control: &Control,
+) -> Result<(), String> {
+    let proxy = |name: &str| {
+        proxies
+            .iter()
+            .find(|(proxy_name, _)| proxy_name == name)
+            .map(|(_, proxy)| proxy)
+            .expect("proxies of the actions are fetched")
+    };
+    let start = Instant::now();
+    // Changes to undo, as proxy and toxic name, or proxy only for a disabled proxy.
+    let mut changes: Vec<(&str, Option<String>)> = vec![];
+
+    let mut result = Ok(());
+    for (time, action) in events.iter() {
+        if control.wait_until(Some(start + *time)) {
+            break;
+        }
+
+        let name = action.proxy();
+        result = match action {
+            Action::AddToxic { toxic, .. } => {
+                changes.push((name, Some(toxic.name.clone())));
+                proxy(name).try_create_toxic(toxic.clone()).map(|_| ())
+            }
+            Action::RemoveToxic { toxic, .. } => {
+                changes.retain(|change| *change != (name, Some(toxic.clone())));
+                proxy(name).delete_toxic(toxic)
+            }
+            Action::Disable { .. } => {
+                changes.push((name, None));
+                proxy(name).disable()
+            }
+            Action::Enable { .. } => {
+                changes.retain(|change| *change != (name, None));
+                proxy(name).enable()
+            }
+        };
+        if result.is_err() {
+            break;
+        }
+    }
+
+    // Holds the changes until cancelled.
+    if result.is_ok() {
+        control.played();
+        control.wait_until(None);
+    }
+
+    for (name, toxic) in changes.into_iter().rev() {
+        result = result.and(match toxic {
+            Some(toxic) => proxy(name).delete_toxic(&toxic),
+            None => proxy(name).enable(),
+        });
+    }
+    result
+}
+
+#[derive(Default)]
+struct State {
+    cancelled: bool,
+    played: bool,
+}
+
+/// Cancel signal of a timeline thread, and its progress.
+#[derive(Default)]
+struct Control {
+    state: Mutex<State>,
+    wakeup: Condvar,
+}
+
+impl Control {
+    fn cancel(&self) {
+        self.update(|state| state.cancelled = true);
+    }
+
+    fn played(&self) {
+        self.update(|state| state.played = true);
+    }
+
+    fn is_played(&self) -> bool {
+        self.state.lock().is_ok_and(|state| state.played)
+    }
+
+    fn update<F: FnOnce(&mut State)>(&self, change: F) {
+        if let Ok(mut state) = self.state.lock() {
+            change(&mut state);
+        }
+        self.wakeup.notify_all();
+    }
+
+    /// Waits until the deadline (if any) unless cancelled meanwhile. Returns whether it's
+    /// cancelled.
+    fn wait_until(&self, deadline: Option<Instant>) -> bool {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return true,
+        };
+
+        while !state.cancelled {
+            let timeout = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(timeout) if timeout > Duration::from_secs(0) => timeout,
+                    _ => return false,
+                },
+                None => Duration::from_secs(1),
+            };
+            state = match self.wakeup.wait_timeout(state, timeout) {
+                Ok((state, _)) => state,
+                Err(_) => return true,
+            };
+        }
+        true
+    }
+
+    /// Waits until every change has been made, unless the thread is finished.
+    fn wait_played(&self, worker: &JoinHandle<Result<(), ThreadFailure>>) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        while !state.played && !worker.is_finished() {
+            state = match self.wakeup.wait_timeout(state, Duration::from_millis(10)) {
+                Ok((state, _)) => state,
+                Err(_) => return,
+            };
+        }
+    }
+}
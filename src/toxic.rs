@@ -2,23 +2,44 @@
 //!
 //! [Toxic]: https://github.com/Shopify/toxiproxy#toxics
 
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
 
 pub type ToxicValueType = u32;
 
 /// Config of a Toxic.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ToxicPack {
+    /// Defaults to `<type>_<stream>` when loaded from a config file.
+    #[serde(default)]
     pub name: String,
     pub r#type: String,
+    #[serde(default = "default_stream")]
     pub stream: String,
+    #[serde(default = "default_toxicity")]
     pub toxicity: f32,
+    #[serde(default, serialize_with = "sorted")]
     pub attributes: HashMap<String, ToxicValueType>,
 }
 
 impl ToxicPack {
-    pub(crate) fn new(
+    /// Creates a toxic config named `<type>_<stream>`, eg. to register the same toxic on several
+    /// proxies.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::toxic::ToxicPack;
+    /// let toxic = ToxicPack::new(
+    ///     "latency".into(),
+    ///     "downstream".into(),
+    ///     1.0,
+    ///     vec![("latency".to_owned(), 500)].into_iter().collect(),
+    /// );
+    ///
+    /// assert_eq!("latency_downstream", toxic.name);
+    /// ```
+    pub fn new(
         r#type: String,
         stream: String,
         toxicity: f32,
@@ -33,4 +54,31 @@ impl ToxicPack {
             attributes,
         }
     }
+
+    /// The toxic with its default name (`<type>_<stream>`) if it has none.
+    pub(crate) fn or_default_name(mut self) -> Self {
+        if self.name.is_empty() {
+            self.name = format!("{}_{}", self.r#type, self.stream);
+        }
+        self
+    }
+}
+
+fn default_stream() -> String {
+    "downstream".into()
+}
+
+fn default_toxicity() -> f32 {
+    1.0
+}
+
+/// Serializes attributes in a stable order, eg. for exported configs to diff cleanly.
+fn sorted<S: Serializer>(
+    attributes: &HashMap<String, ToxicValueType>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    attributes
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .serialize(serializer)
 }
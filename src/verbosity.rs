@@ -0,0 +1,146 @@
+//! Logging of the requests sent to the server on stderr, turned on without code changes by the
+//! `TOXIPROXY_RUST_LOG` environment variable - eg. to re-run a flaky CI job with diagnostics:
+//!
+//! ```text
+//! TOXIPROXY_RUST_LOG=debug cargo test
+//! ```
+//!
+//! Levels follow `RUST_LOG`: `error` logs failed requests, `info` and `debug` every request, and
+//! `trace` the request bodies too. It's independent of the `log` and `tracing` features, which hand
+//! the same events to the application's logger.
+
+use super::client::*;
+use reqwest::blocking::Response;
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Environment variable setting the verbosity of new clients, see [`Verbosity`].
+///
+/// [`Verbosity`]: enum.Verbosity.html
+pub const ENV_TOXIPROXY_RUST_LOG: &str = "TOXIPROXY_RUST_LOG";
+
+/// Requests logged on stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Verbosity {
+    /// Nothing logged.
+    #[default]
+    Off,
+    /// Failed requests (including answers with an error status).
+    Errors,
+    /// Every request, without its body.
+    Requests,
+    /// Every request, with its body.
+    Bodies,
+}
+
+impl FromStr for Verbosity {
+    type Err = String;
+
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::verbosity::Verbosity;
+    /// assert_eq!(Ok(Verbosity::Requests), "DEBUG".parse());
+    /// assert!("loud".parse::<Verbosity>().is_err());
+    /// ```
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        match level.trim().to_lowercase().as_str() {
+            "" | "off" | "0" => Ok(Verbosity::Off),
+            "error" | "warn" => Ok(Verbosity::Errors),
+            "info" | "debug" | "1" => Ok(Verbosity::Requests),
+            "trace" | "2" => Ok(Verbosity::Bodies),
+            _ => Err(format!(
+                "invalid {}: {:?}, expected off, error, info, debug or trace",
+                ENV_TOXIPROXY_RUST_LOG, level
+            )),
+        }
+    }
+}
+
+impl Verbosity {
+    /// Verbosity set by `TOXIPROXY_RUST_LOG`, off if it's not set or invalid (which is reported
+    /// on stderr).
+    pub fn from_env() -> Self {
+        Self::from_value(env::var(ENV_TOXIPROXY_RUST_LOG).ok().as_deref())
+    }
+
+    /// Verbosity for a value of `TOXIPROXY_RUST_LOG`, off if it's not set or invalid (which is
+    /// reported on stderr).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::verbosity::Verbosity;
+    /// assert_eq!(Verbosity::Bodies, Verbosity::from_value(Some("trace")));
+    /// assert_eq!(Verbosity::Off, Verbosity::from_value(Some("verbose")));
+    /// assert_eq!(Verbosity::Off, Verbosity::from_value(None));
+    /// ```
+    pub fn from_value(level: Option<&str>) -> Self {
+        match level {
+            Some(level) => level.parse().unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                Verbosity::Off
+            }),
+            None => Verbosity::Off,
+        }
+    }
+
+    /// Logs a request according to the verbosity, eg.
+    /// `[toxiproxy] POST /proxies/db/toxics: 200 OK in 3.1ms`.
+    pub(crate) fn log(
+        self,
+        method: &str,
+        path: &str,
+        body: Option<&str>,
+        result: &Result<Response, String>,
+        elapsed: Duration,
+    ) {
+        let (outcome, failed) = match result {
+            Ok(response) => (
+                response.status().to_string(),
+                !response.status().is_success(),
+            ),
+            Err(err) => (err.clone(), true),
+        };
+        if self == Verbosity::Off || (self == Verbosity::Errors && !failed) {
+            return;
+        }
+
+        match body {
+            Some(body) if self == Verbosity::Bodies => eprintln!(
+                "[toxiproxy] {} /{} {}: {} in {:?}",
+                method, path, body, outcome, elapsed
+            ),
+            _ => eprintln!(
+                "[toxiproxy] {} /{}: {} in {:?}",
+                method, path, outcome, elapsed
+            ),
+        }
+    }
+}
+
+impl Client {
+    /// Requests logged on stderr by the client, its clones and its proxies. Clients start with
+    /// the verbosity set by `TOXIPROXY_RUST_LOG`.
+    pub fn verbosity(&self) -> Verbosity {
+        self.http_client()
+            .lock()
+            .map(|client| client.verbosity())
+            .unwrap_or_default()
+    }
+
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{client::Client, verbosity::Verbosity};
+    /// let client = Client::from_env();
+    /// client.set_verbosity(Verbosity::Errors);
+    /// assert_eq!(Verbosity::Errors, client.verbosity());
+    /// ```
+    pub fn set_verbosity(&self, verbosity: Verbosity) {
+        if let Ok(mut client) = self.http_client().lock() {
+            client.set_verbosity(verbosity);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Watching the server for changes: the proxies are polled on a background thread, and the
+//! differences between two polls are sent to a channel - eg. for a dashboard or a test harness to
+//! react to changes made by other processes. See [`Client::watch`].
+//!
+//! Changes are detected between polls, so a change undone before the next poll goes unnoticed.
+//!
+//! [`Client::watch`]: ../client/struct.Client.html#method.watch
+
+use super::client::*;
+use super::proxy::*;
+use super::toxic::*;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Difference between two polls of the server. Proxies are named as by [`Client::all`], without
+/// the client's namespace.
+///
+/// [`Client::all`]: ../client/struct.Client.html#method.all
+#[derive(Debug, Clone)]
+pub enum StateChange {
+    ProxyAdded {
+        proxy: ProxyPack,
+    },
+    ProxyRemoved {
+        proxy: String,
+    },
+    /// The proxy was enabled or disabled.
+    EnabledChanged {
+        proxy: String,
+        enabled: bool,
+    },
+    ToxicAdded {
+        proxy: String,
+        toxic: ToxicPack,
+    },
+    ToxicRemoved {
+        proxy: String,
+        toxic: String,
+    },
+    /// Attributes, toxicity or stream of the toxic changed.
+    ToxicUpdated {
+        proxy: String,
+        toxic: ToxicPack,
+    },
+    /// Polling the server failed. Sent once until a poll succeeds again.
+    PollFailed {
+        error: String,
+    },
+}
+
+/// Proxies polled on a background thread, see [`Client::watch`]. Dropping the watcher stops the
+/// thread.
+///
+/// [`Client::watch`]: ../client/struct.Client.html#method.watch
+pub struct Watcher {
+    changes: Receiver<StateChange>,
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Watcher {
+    /// Channel of the changes, in the order they were detected.
+    pub fn changes(&self) -> &Receiver<StateChange> {
+        &self.changes
+    }
+
+    /// Stops polling. Changes detected so far can't be received anymore.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        let (stopped, wakeup) = &*self.stopped;
+        if let Ok(mut stopped) = stopped.lock() {
+            *stopped = true;
+        }
+        wakeup.notify_all();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+type State = HashMap<String, ProxyPack>;
+
+fn poll(client: &Client) -> Result<State, String> {
+    client.all().map(|proxies| {
+        proxies
+            .into_iter()
+            .map(|(name, proxy)| (name, proxy.proxy_pack))
+            .collect()
+    })
+}
+
+/// Changes turning the old state into the new one, by proxy name.
+fn diff(old: &State, new: &State) -> Vec<StateChange> {
+    let names = old.keys().chain(new.keys()).collect::<BTreeSet<&String>>();
+    let mut changes = vec![];
+    for name in names {
+        match (old.get(name), new.get(name)) {
+            (None, Some(proxy)) => changes.push(StateChange::ProxyAdded {
+                proxy: proxy.clone(),
+            }),
+            (Some(_), None) => changes.push(StateChange::ProxyRemoved {
+                proxy: name.clone(),
+            }),
+            (Some(old), Some(new)) => diff_proxy(name, old, new, &mut changes),
+            (None, None) => {}
+        }
+    }
+    changes
+}
+
+fn diff_proxy(name: &str, old: &ProxyPack, new: &ProxyPack, changes: &mut Vec<StateChange>) {
+    if old.enabled != new.enabled {
+        changes.push(StateChange::EnabledChanged {
+            proxy: name.into(),
+            enabled: new.enabled,
+        });
+    }
+
+    for toxic in old.toxics.iter() {
+        if !new.toxics.iter().any(|new| new.name == toxic.name) {
+            changes.push(StateChange::ToxicRemoved {
+                proxy: name.into(),
+                toxic: toxic.name.clone(),
+            });
+        }
+    }
+    for toxic in new.toxics.iter() {
+        match old.toxics.iter().find(|old| old.name == toxic.name) {
+            None => changes.push(StateChange::ToxicAdded {
+                proxy: name.into(),
+                toxic: toxic.clone(),
+            }),
+            Some(old) if serde_json::to_value(old).ok() != serde_json::to_value(toxic).ok() => {
+                changes.push(StateChange::ToxicUpdated {
+                    proxy: name.into(),
+                    toxic: toxic.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+/// Polls the server until stopped, or until nobody receives the changes anymore.
+fn run(
+    client: Client,
+    interval: Duration,
+    mut state: State,
+    changes: Sender<StateChange>,
+    stopped: &(Mutex<bool>, Condvar),
+) {
+    let mut failing = false;
+    loop {
+        let deadline = Instant::now() + interval;
+        {
+            let (stopped, wakeup) = stopped;
+            let mut stopped = match stopped.lock() {
+                Ok(stopped) => stopped,
+                Err(_) => return,
+            };
+            while !*stopped && Instant::now() < deadline {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                stopped = match wakeup.wait_timeout(stopped, timeout) {
+                    Ok((stopped, _)) => stopped,
+                    Err(_) => return,
+                };
+            }
+            if *stopped {
+                return;
+            }
+        }
+
+        let detected = match poll(&client) {
+            Ok(new) => {
+                failing = false;
+                let detected = diff(&state, &new);
+                state = new;
+                detected
+            }
+            Err(_) if failing => vec![],
+            Err(error) => {
+                failing = true;
+                vec![StateChange::PollFailed { error }]
+            }
+        };
+        for change in detected {
+            if changes.send(change).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+impl Client {
+    /// Polls the proxies (within the client's namespace) every `interval` on a background thread,
+    /// sending what changed since the previous poll - starting from the state when called. Fails
+    /// if the first poll fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::{namespace::Namespace, proxy::ProxyPack, watcher::StateChange};
+    /// # use toxiproxy_rust::TOXIPROXY;
+    /// # use std::time::Duration;
+    /// let client = TOXIPROXY.namespaced(Namespace::unique());
+    /// let watcher = client.watch(Duration::from_millis(10)).expect("server is polled");
+    ///
+    /// let proxy = client
+    ///     .create_proxy(ProxyPack::new_ephemeral("watch_doc".into(), "localhost:2000".into()))
+    ///     .unwrap();
+    ///
+    /// let change = watcher.changes().recv_timeout(Duration::from_secs(5)).unwrap();
+    /// assert!(matches!(change, StateChange::ProxyAdded { .. }));
+    /// # proxy.delete().unwrap();
+    /// ```
+    pub fn watch(&self, interval: Duration) -> Result<Watcher, String> {
+        let state = poll(self)?;
+        let (sender, changes) = mpsc::channel();
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+        let worker = {
+            let client = self.clone();
+            let stopped = stopped.clone();
+            thread::spawn(move || run(client, interval, state, sender, &stopped))
+        };
+
+        Ok(Watcher {
+            changes,
+            stopped,
+            worker: Some(worker),
+        })
+    }
+}
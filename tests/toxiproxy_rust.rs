@@ -2,10 +2,15 @@
 
 use std::net::TcpListener;
 use std::net::TcpStream;
+use std::panic;
 use std::thread::spawn;
 use std::time::SystemTime;
 use std::{io::prelude::*, time::Duration};
 
+use address::*;
+use client::*;
+use guard::*;
+use namespace::*;
 use proxy::*;
 use toxiproxy_rust::*;
 
@@ -18,76 +23,4045 @@ fn test_is_running() {
     assert!(TOXIPROXY.is_running());
 }
 
+#[test]
+fn test_health_check() {
+    let version = TOXIPROXY.health_check();
+    assert!(version.is_ok());
+    assert!(!version.unwrap().is_empty());
+}
+
+#[test]
+fn test_health_check_other_service() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("TcpListener cannot connect");
+    let client = Client::new(listener.local_addr().unwrap());
+
+    let server_thread = spawn(move || {
+        let (mut stream, _) = listener.accept().expect("Request failes");
+        let _ = stream
+            .read(&mut [0u8; 1024])
+            .expect("Server failed reading request");
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\n<html></html>")
+            .expect("Server failed writing response");
+    });
+
+    let result = client.health_check();
+    server_thread.join().expect("Failed closing server thread");
+
+    assert!(result.is_err());
+    assert!(result.unwrap_err().contains("<html></html>"));
+}
+
+#[test]
+fn test_skip_if_unavailable() {
+    fn run(client: &Client, ran: &mut bool) {
+        skip_if_unavailable!(client);
+        *ran = true;
+    }
+
+    let mut ran = false;
+    run(&Client::new("127.0.0.1:1"), &mut ran);
+    assert!(!ran);
+
+    run(&TOXIPROXY, &mut ran);
+    assert!(ran);
+}
+
+#[test]
+fn test_noop_client() {
+    let client = Client::new("127.0.0.1:1").or_noop();
+    assert!(client.is_noop());
+    assert!(client.is_running());
+    assert!(!TOXIPROXY.or_noop().is_noop());
+
+    let proxies = client.populate(vec![ProxyPack::new(
+        "socket".into(),
+        "localhost:2001".into(),
+        "localhost:2000".into(),
+    )]);
+    assert!(proxies.is_ok());
+    assert_eq!("localhost:2000", proxies.unwrap()[0].proxy_pack.listen);
+
+    let proxy = client.find_and_reset_proxy("socket");
+    assert!(proxy.is_ok());
+    assert_eq!("localhost:2000", proxy.as_ref().unwrap().proxy_pack.listen);
+
+    let result = proxy
+        .unwrap()
+        .with_latency("downstream".into(), 2000, 0, 1.0)
+        .apply(|| 42);
+    assert_eq!(Ok(42), result);
+    assert!(client.reset().is_ok());
+
+    let proxy = client.find_proxy("socket").unwrap();
+    proxy.disable().unwrap();
+    assert!(!proxy.is_enabled().unwrap());
+    assert_eq!(2000, proxy.listen_port().unwrap());
+    assert_eq!(
+        vec!["socket"],
+        client.all().unwrap().into_keys().collect::<Vec<_>>()
+    );
+    proxy.delete().unwrap();
+    assert!(client.all().unwrap().is_empty());
+}
+
 #[test]
 fn test_reset() {
     assert!(TOXIPROXY.reset().is_ok());
 }
 
 #[test]
-fn test_populate() {
-    let result = TOXIPROXY.populate(vec![ProxyPack::new(
-        "socket".into(),
-        "localhost:2001".into(),
-        "localhost:2000".into(),
-    )]);
+fn test_populate() {
+    let result = TOXIPROXY.populate(vec![ProxyPack::new(
+        "socket".into(),
+        "localhost:2001".into(),
+        "localhost:2000".into(),
+    )]);
+
+    assert!(result.is_ok());
+
+    assert_eq!(1, result.as_ref().unwrap().len());
+    assert_eq!("socket", result.as_ref().unwrap()[0].proxy_pack.name);
+}
+
+#[test]
+fn test_all() {
+    populate_example();
+
+    let result = TOXIPROXY.all();
+    assert!(result.is_ok());
+    assert_eq!(1, result.as_ref().unwrap().len());
+}
+
+#[test]
+fn test_client_from_env() {
+    let client = Client::from_url(Some(" http://127.0.0.1:8474/ "));
+    assert_eq!(Ok("127.0.0.1:8474".parse().unwrap()), client.address());
+    assert!(client.is_running());
+
+    let client = Client::from_url(Some("tcp://127.0.0.1:1"));
+    assert_eq!(Ok("127.0.0.1:1".parse().unwrap()), client.address());
+    assert_eq!(
+        Ok("127.0.0.1:8474".parse().unwrap()),
+        Client::from_url(None).address()
+    );
+}
+
+#[test]
+fn test_client_set_address() {
+    let client = Client::new("127.0.0.1:1");
+    assert!(!client.is_running());
+
+    assert!(client.set_address("127.0.0.1:8474").is_ok());
+    assert!(client.is_running());
+    assert!(client.set_address("not an address").is_err());
+
+    let unresolved = Client::new("toxiproxy.invalid:8474");
+    assert!(unresolved.address().is_err());
+    assert!(unresolved
+        .version()
+        .unwrap_err()
+        .contains("Incorrect address"));
+    assert!(unresolved.set_address("127.0.0.1:8474").is_ok());
+    assert!(unresolved.is_running());
+}
+
+#[test]
+fn test_version() {
+    assert!(TOXIPROXY.version().is_ok());
+}
+
+#[test]
+fn test_find_and_reset_proxy() {
+    populate_example();
+
+    let result = TOXIPROXY.find_and_reset_proxy("socket");
+    assert!(result.is_ok());
+
+    assert_eq!("socket", result.as_ref().unwrap().proxy_pack.name);
+}
+
+#[test]
+fn test_find_and_reset_proxy_invalid() {
+    let result = TOXIPROXY.find_and_reset_proxy("bad-proxy");
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_proxy_down() {
+    populate_example();
+
+    let result = TOXIPROXY.find_and_reset_proxy("socket");
+    assert!(result.is_ok());
+    assert!(result.as_ref().unwrap().proxy_pack.enabled);
+
+    assert!(result
+        .as_ref()
+        .unwrap()
+        .with_down(|| {
+            let result = TOXIPROXY.find_and_reset_proxy("socket");
+            assert!(result.is_ok());
+            assert!(!result.as_ref().unwrap().proxy_pack.enabled);
+        })
+        .is_ok());
+
+    let result = TOXIPROXY.find_and_reset_proxy("socket");
+    assert!(result.is_ok());
+    assert!(result.as_ref().unwrap().proxy_pack.enabled);
+}
+
+#[test]
+fn test_proxy_down_returns_closure_value() {
+    populate_example();
+
+    let result = TOXIPROXY
+        .find_and_reset_proxy("socket")
+        .unwrap()
+        .with_down(|| {
+            TOXIPROXY
+                .find_proxy("socket")
+                .map(|proxy| proxy.proxy_pack.enabled)
+        });
+
+    assert!(result.is_ok());
+    assert_eq!(Ok(false), result.unwrap());
+}
+
+#[test]
+fn test_proxy_down_reenables_after_panic() {
+    populate_example();
+
+    let proxy = TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+    let result = panic::catch_unwind(|| {
+        let _ = proxy.with_down(|| panic!("failing assertion"));
+    });
+    assert!(result.is_err());
+
+    let proxy = TOXIPROXY.find_proxy("socket");
+    assert!(proxy.is_ok());
+    assert!(proxy.as_ref().unwrap().proxy_pack.enabled);
+}
+
+#[test]
+fn test_proxy_apply_deletes_toxics_after_panic() {
+    populate_example();
+
+    let proxy = TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+    let result = panic::catch_unwind(|| {
+        let _ = proxy
+            .with_latency("downstream".into(), 2000, 0, 1.0)
+            .apply(|| panic!("failing assertion"));
+    });
+    assert!(result.is_err());
+
+    let proxy_toxics = proxy.toxics();
+    assert!(proxy_toxics.is_ok());
+    assert_eq!(0, proxy_toxics.as_ref().unwrap().len());
+}
+
+#[test]
+fn test_proxy_try_apply_deletes_toxics_on_error() {
+    populate_example();
+
+    let proxy = TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+    let outcome = proxy
+        .with_latency("downstream".into(), 2000, 0, 1.0)
+        .try_apply(|| -> Result<(), String> { Err("service call failed".into()) });
+
+    assert_eq!(Err("service call failed".into()), outcome.result);
+    assert!(outcome.cleanup.is_ok());
+
+    let proxy_toxics = proxy.toxics();
+    assert!(proxy_toxics.is_ok());
+    assert_eq!(0, proxy_toxics.as_ref().unwrap().len());
+}
+
+#[test]
+fn test_proxy_try_with_down_reenables_on_error() {
+    populate_example();
+
+    let outcome = TOXIPROXY
+        .find_and_reset_proxy("socket")
+        .unwrap()
+        .try_with_down(|| TcpStream::connect("localhost:2001"));
+
+    assert!(outcome.is_ok());
+    assert!(outcome.as_ref().unwrap().result.is_err());
+    assert!(outcome.as_ref().unwrap().cleanup.is_ok());
+
+    let proxy = TOXIPROXY.find_proxy("socket");
+    assert!(proxy.is_ok());
+    assert!(proxy.as_ref().unwrap().proxy_pack.enabled);
+}
+
+#[test]
+fn test_proxy_apply_async_within_runtime() {
+    populate_example();
+
+    let proxy = TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+    proxy.with_latency("downstream".into(), 2000, 0, 1.0);
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("runtime is built");
+    let result = runtime.block_on(proxy.apply_async(async { 42 }));
+
+    assert_eq!(Ok(42), result);
+
+    let proxy_toxics = proxy.toxics();
+    assert!(proxy_toxics.is_ok());
+    assert_eq!(0, proxy_toxics.as_ref().unwrap().len());
+}
+
+#[test]
+fn test_proxy_with_down_async_within_runtime() {
+    populate_example();
+
+    let proxy = TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("runtime is built");
+    let result = runtime
+        .block_on(proxy.with_down_async(async { TcpStream::connect("localhost:2001").is_ok() }));
+
+    assert_eq!(Ok(false), result);
+
+    let proxy = TOXIPROXY.find_proxy("socket");
+    assert!(proxy.is_ok());
+    assert!(proxy.as_ref().unwrap().proxy_pack.enabled);
+}
+
+#[test]
+fn test_toxic_scope_deletes_toxics_on_drop() {
+    populate_example();
+
+    let proxy = TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+    proxy.with_bandwidth("upstream".into(), 500, 1.0);
+
+    {
+        let scope = proxy.toxic_scope();
+        scope.with_latency("downstream".into(), 2000, 0, 1.0);
+        scope.with_timeout("upstream".into(), 5000, 1.0);
+
+        let proxy_toxics = proxy.toxics();
+        assert!(proxy_toxics.is_ok());
+        assert_eq!(3, proxy_toxics.as_ref().unwrap().len());
+    }
+
+    let proxy_toxics = proxy.toxics();
+    assert!(proxy_toxics.is_ok());
+    assert_eq!(1, proxy_toxics.as_ref().unwrap().len());
+    assert_eq!("bandwidth_upstream", proxy_toxics.as_ref().unwrap()[0].name);
+}
+
+#[test]
+fn test_toxic_scope_deletes_toxics_after_panic() {
+    populate_example();
+
+    let proxy = TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+    let result = panic::catch_unwind(|| {
+        let scope = proxy.toxic_scope();
+        scope.with_latency("downstream".into(), 2000, 0, 1.0);
+        panic!("failing assertion");
+    });
+    assert!(result.is_err());
+
+    let proxy_toxics = proxy.toxics();
+    assert!(proxy_toxics.is_ok());
+    assert_eq!(0, proxy_toxics.as_ref().unwrap().len());
+}
+
+#[test]
+fn test_toxic_scope_keeps_toxics_it_failed_to_create() {
+    let proxy = TOXIPROXY
+        .populate(vec![ProxyPack::new_ephemeral(
+            "scope_conflict".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap()
+        .remove(0);
+    proxy.with_latency("downstream".into(), 100, 0, 1.0);
+
+    {
+        // Conflicts with the toxic created outside of the scope.
+        let scope = proxy.toxic_scope();
+        scope.with_latency("downstream".into(), 2000, 0, 1.0);
+    }
+
+    let proxy_toxics = proxy.toxics().unwrap();
+    assert_eq!(1, proxy_toxics.len());
+    assert_eq!(100, proxy_toxics[0].attributes["latency"]);
+
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_down_guard_reenables_on_drop() {
+    populate_example();
+
+    let proxy = TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+
+    {
+        let down = proxy.down_guard();
+        assert!(down.is_ok());
+
+        let result = TOXIPROXY.find_proxy("socket");
+        assert!(result.is_ok());
+        assert!(!result.as_ref().unwrap().proxy_pack.enabled);
+    }
+
+    let result = TOXIPROXY.find_proxy("socket");
+    assert!(result.is_ok());
+    assert!(result.as_ref().unwrap().proxy_pack.enabled);
+}
+
+#[test]
+fn test_down_guard_release() {
+    populate_example();
+
+    let proxy = TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+    let down = proxy.down_guard().unwrap();
+    assert!(down.release().is_ok());
+
+    let result = TOXIPROXY.find_proxy("socket");
+    assert!(result.is_ok());
+    assert!(result.as_ref().unwrap().proxy_pack.enabled);
+}
+
+#[test]
+fn test_proxy_fixture_deletes_proxy_on_drop() {
+    let name = {
+        let fixture = ProxyFixture::with_unique_name(
+            &TOXIPROXY,
+            ProxyPack::new(
+                "fixture".into(),
+                "localhost:2010".into(),
+                "localhost:2000".into(),
+            ),
+        );
+        assert!(fixture.is_ok());
+
+        let name = fixture.as_ref().unwrap().proxy_pack.name.clone();
+        assert!(TOXIPROXY.find_proxy(&name).is_ok());
+        name
+    };
+
+    assert!(TOXIPROXY.find_proxy(&name).is_err());
+}
+
+#[test]
+fn test_proxy_fixture_release() {
+    let fixture = ProxyFixture::with_unique_name(
+        &TOXIPROXY,
+        ProxyPack::new(
+            "fixture".into(),
+            "localhost:2011".into(),
+            "localhost:2000".into(),
+        ),
+    )
+    .unwrap();
+    let name = fixture.proxy_pack.name.clone();
+
+    assert!(fixture.release().is_ok());
+    assert!(TOXIPROXY.find_proxy(&name).is_err());
+}
+
+#[test]
+fn test_proxy_apply_with_teardown() {
+    populate_example();
+
+    let proxy = TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+    let outcome = proxy
+        .with_latency("downstream".into(), 2000, 0, 1.0)
+        .apply_with_teardown(
+            || 42,
+            || {
+                let proxy_toxics = TOXIPROXY.find_proxy("socket").and_then(|p| p.toxics());
+                assert!(proxy_toxics.is_ok());
+                assert_eq!(0, proxy_toxics.as_ref().unwrap().len());
+
+                Err::<(), _>("teardown failed")
+            },
+        );
+
+    assert_eq!(42, outcome.value);
+    assert!(outcome.cleanup.is_ok());
+    assert_eq!(Err("teardown failed"), outcome.teardown);
+}
+
+#[test]
+fn test_proxy_disable_for() {
+    let proxy = TOXIPROXY
+        .populate(vec![ProxyPack::new_ephemeral(
+            "disable_for".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap()
+        .remove(0);
+    let listen_addr = proxy.listen_addr().unwrap();
+
+    let outage = spawn(|| {
+        TOXIPROXY
+            .find_proxy("disable_for")
+            .unwrap()
+            .disable_for(Duration::from_millis(500))
+    });
+
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(TcpStream::connect(listen_addr).is_err());
+
+    assert!(outage.join().unwrap().is_ok());
+    assert!(TcpStream::connect(listen_addr).is_ok());
+
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_proxy_disable_for_async() {
+    populate_example();
+
+    let proxy = TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("runtime is built");
+
+    let t_start = SystemTime::now();
+    let result = runtime.block_on(proxy.disable_for_async(Duration::from_millis(300)));
+
+    assert!(result.is_ok());
+    assert!(t_start.elapsed().unwrap() >= Duration::from_millis(300));
+
+    let proxy = TOXIPROXY.find_proxy("socket");
+    assert!(proxy.is_ok());
+    assert!(proxy.as_ref().unwrap().proxy_pack.enabled);
+}
+
+#[test]
+fn test_proxy_refresh() {
+    populate_example();
+
+    let mut proxy = TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+    assert!(proxy.proxy_pack.enabled);
+    assert_eq!(0, proxy.proxy_pack.toxics.len());
+
+    proxy.with_latency("downstream".into(), 2000, 0, 1.0);
+    assert!(proxy.disable().is_ok());
+
+    assert!(proxy.refresh().is_ok());
+    assert!(!proxy.proxy_pack.enabled);
+    assert_eq!(1, proxy.proxy_pack.toxics.len());
+
+    assert!(TOXIPROXY.reset().is_ok());
+}
+
+#[test]
+fn test_proxy_is_enabled() {
+    populate_example();
+
+    let proxy = TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+    assert_eq!(Ok(true), proxy.is_enabled());
+
+    let result = proxy.with_down(|| proxy.is_enabled());
+    assert_eq!(Ok(Ok(false)), result);
+
+    assert_eq!(Ok(true), proxy.is_enabled());
+}
+
+#[test]
+fn test_proxy_listen_addr() {
+    let proxies = TOXIPROXY
+        .populate(vec![ProxyPack::new(
+            "listen_any".into(),
+            "0.0.0.0:2012".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+
+    let addr = proxies[0].listen_addr();
+    assert!(addr.is_ok());
+    assert_eq!("127.0.0.1:2012", addr.unwrap().to_string());
+    assert_eq!(Ok(2012), proxies[0].listen_port());
+
+    assert!(proxies[0].delete().is_ok());
+}
+
+#[test]
+fn test_proxy_ephemeral_port() {
+    let proxies = TOXIPROXY
+        .populate(vec![
+            ProxyPack::new_ephemeral("ephemeral_a".into(), "localhost:2000".into()),
+            ProxyPack::new_ephemeral("ephemeral_b".into(), "localhost:2000".into()),
+        ])
+        .unwrap();
+
+    let port_a = proxies[0].listen_port();
+    let port_b = proxies[1].listen_port();
+    assert!(port_a.is_ok());
+    assert!(port_b.is_ok());
+    assert_ne!(Ok(0), port_a);
+    assert_ne!(port_a, port_b);
+
+    assert!(TcpStream::connect(proxies[0].listen_addr().unwrap()).is_ok());
+
+    for proxy in proxies {
+        assert!(proxy.delete().is_ok());
+    }
+}
+
+#[test]
+fn test_proxy_rewrite_url() {
+    populate_example();
+
+    let proxy = TOXIPROXY.find_proxy("socket").unwrap();
+
+    assert_eq!(
+        Ok("redis://127.0.0.1:2001".to_owned()),
+        proxy.rewrite_url("redis://cache:6379")
+    );
+    assert_eq!(
+        Ok("http://127.0.0.1:2001/health".to_owned()),
+        proxy.rewrite_url("http://service/health")
+    );
+    assert_eq!(
+        Ok("127.0.0.1:2001".to_owned()),
+        proxy.rewrite_url("cache:6379")
+    );
+    assert!(proxy.rewrite_url("postgres://db:port/app").is_err());
+}
+
+#[test]
+fn test_proxy_for_upstream() {
+    let wired = TOXIPROXY.proxy_for_upstream("localhost:2099");
+    assert!(wired.is_ok());
+
+    let wired = wired.unwrap();
+    assert!(wired.created);
+    assert_eq!("localhost_2099", wired.proxy.proxy_pack.name);
+    assert_eq!(
+        wired.proxy.listen_addr().map(|addr| addr.to_string()),
+        Ok(wired.addr.clone())
+    );
+
+    let reused = TOXIPROXY.proxy_for_upstream("localhost:2099");
+    assert!(reused.is_ok());
+    let reused = reused.unwrap();
+    assert!(!reused.created);
+    assert_eq!(wired.addr, reused.addr);
+
+    assert!(TOXIPROXY.proxy_for_upstream("localhost").is_err());
+    assert!(wired.proxy.delete().is_ok());
+}
+
+#[test]
+fn test_namespaced_client() {
+    let namespace = Namespace::unique();
+    let client = TOXIPROXY.namespaced(namespace.clone());
+
+    let result = client.populate(vec![ProxyPack::new_ephemeral(
+        "socket".into(),
+        "localhost:2000".into(),
+    )]);
+    assert!(result.is_ok());
+    assert_eq!(
+        namespace.qualify("socket"),
+        result.as_ref().unwrap()[0].proxy_pack.name
+    );
+
+    let all = client.all();
+    assert!(all.is_ok());
+    assert_eq!(1, all.as_ref().unwrap().len());
+    assert!(all.as_ref().unwrap().contains_key("socket"));
+
+    let proxy = client.find_and_reset_proxy("socket");
+    assert!(proxy.is_ok());
+    assert!(TOXIPROXY.find_proxy(&namespace.qualify("socket")).is_ok());
+    assert!(TOXIPROXY
+        .namespaced(Namespace::unique())
+        .find_proxy("socket")
+        .is_err());
+
+    assert!(proxy.unwrap().delete().is_ok());
+}
+
+#[test]
+fn test_scoped_client_deletes_proxies_on_drop() {
+    let name = {
+        let client = TOXIPROXY.scoped();
+        let result = client.populate(vec![ProxyPack::new_ephemeral(
+            "socket".into(),
+            "localhost:2000".into(),
+        )]);
+        assert!(result.is_ok());
+
+        let created = client.create_proxy(ProxyPack::new_ephemeral(
+            "other".into(),
+            "localhost:2000".into(),
+        ));
+        assert!(created.is_ok());
+        assert_eq!(2, client.all().unwrap().len());
+
+        result.unwrap()[0].proxy_pack.name.clone()
+    };
+
+    assert!(TOXIPROXY.find_proxy(&name).is_err());
+    assert!(TOXIPROXY
+        .all()
+        .unwrap()
+        .keys()
+        .all(|key| !key.starts_with("other__")));
+}
+
+#[test]
+fn test_assertions() {
+    let proxy = TOXIPROXY
+        .populate(vec![ProxyPack::new_ephemeral(
+            "assertions".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap()
+        .remove(0);
+    proxy.assert_no_toxics().assert_enabled();
+    TOXIPROXY
+        .assert_proxy_exists("assertions")
+        .assert_proxy_enabled("assertions")
+        .assert_proxy_missing("missing_proxy");
+
+    proxy
+        .with_latency("downstream".into(), 100, 0, 1.0)
+        .assert_has_toxic("latency_downstream")
+        .assert_no_toxic("bandwidth_downstream");
+
+    let result = panic::catch_unwind(|| {
+        proxy.assert_no_toxics();
+    });
+    let message = result.unwrap_err().downcast::<String>().unwrap();
+    assert!(message.contains("latency_downstream (latency, downstream"));
+
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_snapshot_diff() {
+    let proxy = TOXIPROXY
+        .populate(vec![ProxyPack::new_ephemeral(
+            "snapshot".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap()
+        .remove(0);
+    let snapshot = proxy.snapshot().unwrap();
+
+    proxy
+        .with_latency("downstream".into(), 100, 0, 1.0)
+        .apply(|| {})
+        .unwrap();
+    proxy.assert_unchanged_since(&snapshot);
+
+    proxy.with_timeout("upstream".into(), 10, 1.0);
+    proxy.disable().unwrap();
+    let diff = snapshot.diff(&proxy.snapshot().unwrap());
+    assert_eq!(3, diff.len());
+    assert_eq!("- enabled: true", diff[0]);
+    assert_eq!("+ enabled: false", diff[1]);
+    assert!(diff[2].starts_with("+ toxic: timeout_upstream (timeout, upstream"));
+
+    let result = panic::catch_unwind(|| {
+        proxy.assert_unchanged_since(&snapshot);
+    });
+    let message = result.unwrap_err().downcast::<String>().unwrap();
+    assert!(message.contains("+ enabled: false"));
+
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_watch_guard() {
+    let proxy = TOXIPROXY
+        .populate(vec![ProxyPack::new_ephemeral(
+            "watched".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap()
+        .remove(0);
+    let watch = proxy.watch().unwrap();
+
+    watch.with_latency("downstream".into(), 100, 0, 1.0);
+    watch.with_down(|| {}).unwrap();
+    {
+        let scope = watch.toxic_scope();
+        scope.with_timeout("upstream".into(), 10, 1.0);
+    }
+    assert!(watch.verify().is_ok());
+
+    proxy.with_bandwidth("upstream".into(), 32, 1.0);
+    let err = watch.verify().unwrap_err();
+    assert!(err.contains("+ toxic: bandwidth_upstream"));
+
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        let _watch = proxy.watch().unwrap();
+        proxy.disable().unwrap();
+    }));
+    assert!(result.is_err());
+
+    assert!(watch.release().is_err());
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_failure_classification() {
+    use failure::Failure;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream = listener.local_addr().unwrap().to_string();
+    let proxy = TOXIPROXY.proxy_for_upstream(&upstream).unwrap().proxy;
+    let addr = proxy.listen_addr().unwrap();
+
+    proxy
+        .with_down(|| {
+            let err = TcpStream::connect(addr).unwrap_err();
+            assert_eq!(Failure::Refused, Failure::of(&err));
+        })
+        .unwrap();
+
+    proxy
+        .with_timeout("downstream".into(), 0, 1.0)
+        .apply(|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_millis(200)))
+                .unwrap();
+            stream.write_all(b"hello").unwrap();
+            let err = stream.read_exact(&mut [0u8; 5]).unwrap_err();
+            assert_eq!(Failure::TimedOut, Failure::of(&err));
+        })
+        .unwrap();
+
+    let err = std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "eof");
+    assert_eq!(Failure::Truncated, Failure::of(&err));
+
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_global_setup_and_attach() {
+    let path = std::env::temp_dir().join(format!("toxiproxy_manifest_{}.json", std::process::id()));
+    let manifest = setup::global_setup(
+        &TOXIPROXY,
+        vec![ProxyPack::new_ephemeral(
+            "global_setup".into(),
+            "localhost:2000".into(),
+        )],
+        &path,
+    )
+    .unwrap();
+
+    let read = setup::Manifest::read(&path).unwrap();
+    assert_eq!(manifest.address, read.address);
+    assert_eq!(manifest.proxies[0].listen, read.proxies[0].listen);
+
+    let proxy = read.attach("global_setup").unwrap();
+    assert_eq!(manifest.proxies[0].listen, proxy.proxy_pack.listen);
+    assert!(read.attach("not_in_manifest").is_err());
+
+    proxy.delete().unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_global_teardown() {
+    let path = std::env::temp_dir().join(format!("toxiproxy_teardown_{}.json", std::process::id()));
+    setup::global_setup(
+        &TOXIPROXY,
+        vec![
+            ProxyPack::new_ephemeral("global_teardown_1".into(), "localhost:2000".into()),
+            ProxyPack::new_ephemeral("global_teardown_2".into(), "localhost:2000".into()),
+        ],
+        &path,
+    )
+    .unwrap();
+    // Proxies already gone don't fail the teardown.
+    TOXIPROXY
+        .find_proxy("global_teardown_2")
+        .unwrap()
+        .delete()
+        .unwrap();
+
+    setup::global_teardown(&path).unwrap();
+
+    assert!(TOXIPROXY.find_proxy("global_teardown_1").is_err());
+    assert!(!path.exists());
+    assert!(setup::global_teardown(&path).is_err());
+}
+
+#[test]
+fn test_proxy_soak() {
+    use experiment::SoakConfig;
+
+    let proxy = TOXIPROXY
+        .populate(vec![ProxyPack::new_ephemeral(
+            "soak".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap()
+        .remove(0);
+
+    let mut count = 0;
+    let report = proxy
+        .with_latency("downstream".into(), 10, 0, 1.0)
+        .soak(&SoakConfig::iterations(10), || {
+            count += 1;
+            if count % 5 == 0 {
+                Err(format!("failure {}", count))
+            } else {
+                Ok(count)
+            }
+        })
+        .unwrap();
+
+    assert_eq!(10, report.runs.len());
+    assert_eq!(8, report.successes());
+    assert_eq!(vec!["failure 5", "failure 10"], report.errors());
+    assert_eq!(0.2, report.failure_rate());
+    assert!(report.latency_percentile(50.0) <= report.max_latency());
+    assert!(proxy.toxics().unwrap().is_empty());
+
+    let report = proxy
+        .soak(
+            &SoakConfig::duration(Duration::from_millis(100)).pause(Duration::from_millis(30)),
+            || Ok::<(), String>(()),
+        )
+        .unwrap();
+    assert!(report.runs.len() >= 2 && report.runs.len() <= 5);
+
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_proxy_sweep_latency() {
+    use experiment::{SoakConfig, Sweep};
+
+    let proxy = TOXIPROXY
+        .populate(vec![ProxyPack::new_ephemeral(
+            "latency_sweep".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap()
+        .remove(0);
+
+    let report = proxy
+        .sweep_latency(
+            "downstream".into(),
+            &Sweep::new(0, 300, 100).unwrap(),
+            &SoakConfig::iterations(2),
+            || {
+                let latency = proxy.toxics().unwrap()[0].attributes["latency"];
+                if latency >= 200 {
+                    Err("too slow")
+                } else {
+                    Ok(())
+                }
+            },
+        )
+        .unwrap();
+
+    assert_eq!(
+        vec![0, 100, 200, 300],
+        report
+            .steps
+            .iter()
+            .map(|step| step.value)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(Some(200), report.breaking_point());
+    assert_eq!(None, report.breaking_point_at(1.0));
+    assert!(proxy.toxics().unwrap().is_empty());
+
+    let report = proxy
+        .sweep_latency(
+            "downstream".into(),
+            &Sweep::new(0, 300, 100).unwrap().stop_on_failure(),
+            &SoakConfig::iterations(1),
+            || Err::<(), _>("always"),
+        )
+        .unwrap();
+    assert_eq!(1, report.steps.len());
+
+    proxy.delete().unwrap();
+}
+
+#[cfg(feature = "test_support")]
+#[test]
+fn test_proxy_sweep_bandwidth() {
+    use experiment::{SoakConfig, Sweep};
+    use test_support::*;
+
+    let server = EchoServer::builder()
+        .respond_after(4096)
+        .close_mode(CloseMode::AfterResponse)
+        .start()
+        .unwrap();
+    let proxy = TOXIPROXY
+        .proxy_for_upstream(&server.addr().to_string())
+        .unwrap()
+        .proxy;
+    let addr = proxy.listen_addr().unwrap();
+
+    let report = proxy
+        .sweep_bandwidth(
+            "downstream".into(),
+            &Sweep::new(100, 200, 100).unwrap(),
+            &SoakConfig::iterations(1),
+            || {
+                let mut stream = TcpStream::connect(addr)?;
+                stream.write_all(&[0u8; 4096])?;
+                let mut response = vec![];
+                stream.read_to_end(&mut response).map(|_| response.len())
+            },
+        )
+        .unwrap();
+
+    assert_eq!(2, report.steps.len());
+    for step in report.steps.iter() {
+        assert_eq!(4096, step.report.runs[0].bytes);
+        assert!(step.report.throughput().unwrap() > 0.0);
+    }
+    assert_eq!(None, report.breaking_point());
+
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_proxy_sweep_toxicity() {
+    use experiment::{SoakConfig, Z_95};
+
+    let proxy = TOXIPROXY
+        .populate(vec![ProxyPack::new_ephemeral(
+            "toxicity_sweep".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap()
+        .remove(0);
+
+    let mut count = 0;
+    let report = proxy
+        .sweep_toxicity(
+            &[0.0, 0.5],
+            |proxy, toxicity| {
+                proxy.with_timeout("downstream".into(), 100, toxicity);
+            },
+            &SoakConfig::iterations(40),
+            || {
+                count += 1;
+                let toxicity = proxy.toxics().unwrap()[0].toxicity;
+                if toxicity > 0.0 && count % 2 == 0 {
+                    Err("timed out")
+                } else {
+                    Ok(())
+                }
+            },
+        )
+        .unwrap();
+
+    assert_eq!(Some(0.5), report.breaking_point());
+    let (low, high) = report.steps[0].report.failure_rate_interval(Z_95);
+    assert!(low == 0.0 && high < 0.1);
+    let (low, high) = report.steps[1].report.failure_rate_interval(Z_95);
+    assert!(low < 0.5 && high > 0.5);
+
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_config_populate() {
+    use config::Config;
+
+    let path = std::env::temp_dir().join(format!("toxiproxy_config_{}.json", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"{"proxies": [{
+            "name": "config",
+            "listen": "127.0.0.1:0",
+            "upstream": "localhost:2000",
+            "toxics": [
+                {"type": "latency", "attributes": {"latency": 100}},
+                {"name": "slow", "type": "bandwidth", "stream": "upstream", "toxicity": 0.5, "attributes": {"rate": 32}}
+            ]
+        }]}"#,
+    )
+    .unwrap();
+
+    let config = Config::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let toxic = &config.proxies[0].toxics[0];
+    assert_eq!("latency_downstream", toxic.name);
+    assert_eq!(1.0, toxic.toxicity);
+
+    let proxies = config.populate(&TOXIPROXY).unwrap();
+    proxies[0]
+        .assert_has_toxic("latency_downstream")
+        .assert_has_toxic("slow");
+
+    config.populate(&TOXIPROXY).unwrap();
+    assert_eq!(2, proxies[0].toxics().unwrap().len());
+
+    assert!(Config::from_json("{\"proxies\": [{\"name\": \"missing_addresses\"}]}").is_err());
+    assert!(Config::load("proxies.ini").is_err());
+
+    proxies[0].delete().unwrap();
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn test_config_from_toml() {
+    let config = config::Config::from_toml(
+        r#"
+        [[proxies]]
+        name = "db"
+        listen = "127.0.0.1:0"
+        upstream = "db:5432"
+        enabled = false
+
+        [[proxies.toxics]]
+        type = "timeout"
+        stream = "upstream"
+        attributes = { timeout = 100 }
+        "#,
+    )
+    .unwrap();
+
+    let proxy_pack = &config.proxies[0];
+    assert!(!proxy_pack.enabled);
+    assert_eq!("timeout_upstream", proxy_pack.toxics[0].name);
+    assert_eq!(100, proxy_pack.toxics[0].attributes["timeout"]);
+}
+
+#[test]
+fn test_config_env_templating() {
+    std::env::set_var("TOXIPROXY_TEST_UPSTREAM_HOST", "db");
+
+    let path =
+        std::env::temp_dir().join(format!("toxiproxy_config_env_{}.json", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"[{"name": "db", "listen": "127.0.0.1:${TOXIPROXY_TEST_LISTEN_PORT:-0}", "upstream": "${TOXIPROXY_TEST_UPSTREAM_HOST}:5432"}]"#,
+    )
+    .unwrap();
+    let config = config::Config::load(&path).unwrap();
+    assert_eq!("127.0.0.1:0", config.proxies[0].listen);
+    assert_eq!("db:5432", config.proxies[0].upstream);
+
+    std::fs::write(
+        &path,
+        r#"[{"name": "db", "listen": "${TOXIPROXY_TEST_MISSING_A}", "upstream": "${TOXIPROXY_TEST_MISSING_B}"}]"#,
+    )
+    .unwrap();
+    let err = config::Config::load(&path).unwrap_err();
+    assert!(err.contains("TOXIPROXY_TEST_MISSING_A, TOXIPROXY_TEST_MISSING_B"));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_config_merge() {
+    let dir = std::env::temp_dir();
+    let base = dir.join(format!("toxiproxy_base_{}.json", std::process::id()));
+    let overlay = dir.join(format!("toxiproxy_overlay_{}.json", std::process::id()));
+    std::fs::write(
+        &base,
+        r#"{"proxies": [
+            {"name": "db", "listen": "127.0.0.1:0", "upstream": "localhost:5432",
+             "toxics": [{"type": "latency", "attributes": {"latency": 100, "jitter": 10}}]},
+            {"name": "cache", "listen": "127.0.0.1:0", "upstream": "localhost:6379"}
+        ]}"#,
+    )
+    .unwrap();
+    std::fs::write(
+        &overlay,
+        r#"[
+            {"name": "db", "upstream": "db:5432",
+             "toxics": [
+                 {"type": "latency", "attributes": {"latency": 500}},
+                 {"type": "timeout", "stream": "upstream", "attributes": {"timeout": 10}}
+             ]},
+            {"name": "queue", "listen": "127.0.0.1:0", "upstream": "queue:5672", "enabled": false}
+        ]"#,
+    )
+    .unwrap();
+
+    let config = config::Config::load_all(&[&base, &overlay]).unwrap();
+    std::fs::remove_file(&base).unwrap();
+    std::fs::remove_file(&overlay).unwrap();
+
+    let names = config
+        .proxies
+        .iter()
+        .map(|proxy_pack| proxy_pack.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["db", "cache", "queue"], names);
+
+    let db = &config.proxies[0];
+    assert_eq!("127.0.0.1:0", db.listen);
+    assert_eq!("db:5432", db.upstream);
+    assert_eq!(2, db.toxics.len());
+    assert_eq!(500, db.toxics[0].attributes["latency"]);
+    assert_eq!(10, db.toxics[0].attributes["jitter"]);
+    assert_eq!("timeout_upstream", db.toxics[1].name);
+    assert!(!config.proxies[2].enabled);
+}
+
+#[test]
+fn test_client_export() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "exported".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    client
+        .find_proxy("exported")
+        .unwrap()
+        .with_latency("downstream".into(), 100, 10, 0.5);
+
+    let path = std::env::temp_dir().join(format!("toxiproxy_export_{}.json", std::process::id()));
+    client.export(&path, config::ConfigFormat::Json).unwrap();
+    let config = config::Config::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(1, config.proxies.len());
+    assert_eq!("exported", config.proxies[0].name);
+    assert_eq!("localhost:2000", config.proxies[0].upstream);
+    assert_eq!(1, config.proxies[0].toxics.len());
+    assert_eq!(100, config.proxies[0].toxics[0].attributes["latency"]);
+    assert_eq!(0.5, config.proxies[0].toxics[0].toxicity);
+
+    #[cfg(feature = "toml")]
+    {
+        let toml = config.render(config::ConfigFormat::Toml).unwrap();
+        let config = config::Config::from_toml(&toml).unwrap();
+        assert_eq!(10, config.proxies[0].toxics[0].attributes["jitter"]);
+    }
+}
+
+#[test]
+fn test_client_snapshot_restore() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![
+            ProxyPack::new_ephemeral("kept".into(), "localhost:2000".into()),
+            ProxyPack::new_ephemeral("deleted".into(), "localhost:2000".into()),
+        ])
+        .unwrap();
+    let kept = client.find_proxy("kept").unwrap();
+    kept.with_latency("downstream".into(), 100, 0, 1.0);
+    let deleted = client.find_proxy("deleted").unwrap();
+    deleted.disable().unwrap();
+
+    let snapshot = client.snapshot().unwrap();
+
+    kept.delete_all_toxics().unwrap();
+    kept.with_bandwidth("upstream".into(), 10, 1.0);
+    kept.disable().unwrap();
+    deleted.delete().unwrap();
+    client
+        .create_proxy(ProxyPack::new_ephemeral(
+            "created".into(),
+            "localhost:2000".into(),
+        ))
+        .unwrap();
+
+    client.restore(&snapshot).unwrap();
+
+    let kept = client.find_proxy("kept").unwrap();
+    assert!(kept.proxy_pack.enabled);
+    let toxics = kept.toxics().unwrap();
+    assert_eq!(1, toxics.len());
+    assert_eq!("latency_downstream", toxics[0].name);
+
+    let deleted = client.find_proxy("deleted").unwrap();
+    assert!(!deleted.proxy_pack.enabled);
+    assert_eq!(snapshot.proxies[0].listen, deleted.proxy_pack.listen);
+    assert!(client.find_proxy("created").is_err());
+}
+
+#[test]
+fn test_client_ensure() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![
+            ProxyPack::new_ephemeral("stale".into(), "localhost:2000".into()),
+            ProxyPack::new_ephemeral("changed".into(), "localhost:2000".into()),
+        ])
+        .unwrap();
+    client
+        .find_proxy("changed")
+        .unwrap()
+        .with_timeout("downstream".into(), 100, 1.0);
+
+    let mut changed = ProxyPack::new_ephemeral("changed".into(), "localhost:2001".into());
+    changed.enabled = false;
+    changed.toxics = vec![serde_json::from_str(
+        r#"{"name": "", "type": "latency", "attributes": {"latency": 100}}"#,
+    )
+    .unwrap()];
+    let desired = vec![
+        changed,
+        ProxyPack::new_ephemeral("created".into(), "localhost:2000".into()),
+    ];
+
+    let proxies = client.ensure(&desired).unwrap();
+    assert_eq!(2, proxies.len());
+    assert!(client.find_proxy("stale").is_err());
+
+    let changed = client.find_proxy("changed").unwrap();
+    assert_eq!("localhost:2001", changed.proxy_pack.upstream);
+    assert!(!changed.proxy_pack.enabled);
+    let toxics = changed.toxics().unwrap();
+    assert_eq!(1, toxics.len());
+    assert_eq!("latency_downstream", toxics[0].name);
+    assert!(proxies[1].proxy_pack.enabled);
+
+    let listen = proxies[1].proxy_pack.listen.clone();
+    let proxies = client.ensure(&desired).unwrap();
+    assert_eq!(listen, proxies[1].proxy_pack.listen);
+    assert_eq!(
+        1,
+        client
+            .find_proxy("changed")
+            .unwrap()
+            .toxics()
+            .unwrap()
+            .len()
+    );
+}
+
+#[test]
+fn test_client_diff() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![
+            ProxyPack::new_ephemeral("extra".into(), "localhost:2000".into()),
+            ProxyPack::new_ephemeral("drifted".into(), "localhost:2000".into()),
+        ])
+        .unwrap();
+    client
+        .find_proxy("drifted")
+        .unwrap()
+        .with_timeout("downstream".into(), 100, 1.0);
+
+    let expected = vec![
+        ProxyPack::new_ephemeral("drifted".into(), "localhost:2001".into()),
+        ProxyPack::new_ephemeral("missing".into(), "localhost:2000".into()),
+    ];
+    let diff = client.diff(&expected).unwrap();
+
+    assert_eq!(vec!["missing".to_owned()], diff.missing);
+    assert_eq!(vec!["extra".to_owned()], diff.extra);
+    assert_eq!(1, diff.differing.len());
+    assert_eq!("drifted", diff.differing[0].name);
+    assert_eq!(
+        vec![
+            "upstream: expected \"localhost:2001\", found \"localhost:2000\"".to_owned(),
+            "extra toxic: timeout_downstream (timeout, downstream, 1, {\"timeout\": 100})"
+                .to_owned(),
+        ],
+        diff.differing[0].changes
+    );
+    assert!(diff.to_string().contains("missing proxy \"missing\""));
+
+    // Nothing is changed.
+    assert!(client.find_proxy("extra").is_ok());
+    assert!(client.find_proxy("missing").is_err());
+}
+
+#[test]
+fn test_scenario_run() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "scenario".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let scenario = scenario::Scenario::from_json(
+        r#"{"name": "outage", "phases": [
+            {"name": "slow", "duration_ms": 500, "effects": [
+                {"effect": "toxic", "proxy": "scenario", "toxic": {"type": "latency", "attributes": {"latency": 100}}}
+            ]},
+            {"name": "down", "duration_ms": 500, "effects": [{"effect": "down", "proxy": "scenario"}]}
+        ]}"#,
+    )
+    .unwrap();
+    assert_eq!(vec!["scenario"], scenario.proxy_names());
+    assert_eq!(Duration::from_secs(1), scenario.duration());
+
+    let runner = {
+        let client = (*client).clone();
+        spawn(move || scenario.run(&client))
+    };
+    let proxy = client.find_proxy("scenario").unwrap();
+
+    std::thread::sleep(Duration::from_millis(250));
+    assert_eq!(1, proxy.toxics().unwrap().len());
+    assert!(proxy.is_enabled().unwrap());
+
+    std::thread::sleep(Duration::from_millis(500));
+    assert!(proxy.toxics().unwrap().is_empty());
+    assert!(!proxy.is_enabled().unwrap());
+
+    runner.join().unwrap().unwrap();
+    assert!(proxy.is_enabled().unwrap());
+    assert!(proxy.toxics().unwrap().is_empty());
+}
+
+#[test]
+fn test_scenario_report() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "scenario".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let scenario = scenario::Scenario::from_json(
+        r#"{"name": "outage", "phases": [
+            {"name": "slow", "duration_ms": 50, "effects": [
+                {"effect": "toxic", "proxy": "scenario", "toxic": {"type": "latency", "attributes": {"latency": 100}}}
+            ]},
+            {"name": "down", "duration_ms": 50, "effects": [{"effect": "down", "proxy": "scenario"}]}
+        ]}"#,
+    )
+    .unwrap();
+
+    let report = scenario.run_with_report(&client);
+    assert!(report.is_ok(), "{}", report);
+    assert_eq!("outage", report.scenario);
+    assert_eq!(2, report.phases.len());
+    assert!(report.phases[1].started_at_ms >= report.phases[0].started_at_ms + 50);
+    assert!(report.phases[0].elapsed_ms >= 50);
+    match &report.phases[0].effects[0] {
+        scenario::Effect::Toxic { toxic, .. } => assert_eq!("latency_downstream", toxic.name),
+        effect => panic!("unexpected effect {:?}", effect),
+    }
+    assert!(report.to_string().contains("phase 2 \"down\""));
+    let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+    assert_eq!("down", json["phases"][1]["effects"][0]["effect"]);
+
+    let report = scenario::Scenario::from_json(
+        r#"{"phases": [{"duration_ms": 10, "effects": [{"effect": "down", "proxy": "missing"}]}]}"#,
+    )
+    .unwrap()
+    .run_with_report(&client);
+    assert!(!report.is_ok());
+    assert!(report.phases.is_empty());
+    assert_eq!(1, report.errors.len());
+    assert!(report.into_result().is_err());
+}
+
+#[test]
+fn test_scenario_builder() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "scenario".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let proxy = client.find_proxy("scenario").unwrap();
+    let scenario = scenario::Scenario::new()
+        .phase("slow", Duration::from_millis(200), |p| {
+            p.on("scenario").latency(100).timeout(50)
+        })
+        .phase("down", Duration::from_millis(200), |p| {
+            p.on("scenario").down()
+        });
+    assert_eq!(vec!["scenario"], scenario.proxy_names());
+    assert_eq!(Duration::from_millis(400), scenario.duration());
+
+    let (slow, down) = scenario
+        .run_with(&client, || {
+            std::thread::sleep(Duration::from_millis(100));
+            let slow = proxy.toxics().unwrap();
+            std::thread::sleep(Duration::from_millis(200));
+            (slow, proxy.is_enabled().unwrap())
+        })
+        .unwrap();
+    assert_eq!(
+        vec!["latency_downstream", "timeout_downstream"],
+        slow.iter()
+            .map(|toxic| toxic.name.as_str())
+            .collect::<Vec<&str>>()
+    );
+    assert!(!down);
+    assert!(proxy.is_enabled().unwrap());
+    assert!(proxy.toxics().unwrap().is_empty());
+
+    let err = scenario::Scenario::new()
+        .phase("nowhere", Duration::from_millis(10), |p| p.down())
+        .run_with(&client, || unreachable!())
+        .unwrap_err();
+    assert!(err.contains("effect without a proxy"), "{}", err);
+}
+
+#[test]
+fn test_scenario_validation() {
+    let schema: serde_json::Value = serde_json::from_str(scenario::SCHEMA).unwrap();
+    assert_eq!(serde_json::json!(["phases"]), schema["required"]);
+
+    let err = scenario::Scenario::from_json(r#"{"phases": [], "loop": true}"#).unwrap_err();
+    assert!(err.contains("unknown field `loop`"), "{}", err);
+
+    let err = scenario::Scenario::from_json(
+        r#"{"phases": [{"name": "broken", "duration_ms": 10, "effects": [
+            {"effect": "toxic", "proxy": "db", "toxic": {"type": "jitter", "stream": "sideways"}},
+            {"effect": "toxic", "proxy": "db", "toxic": {"type": "latency", "toxicity": 1.5}},
+            {"effect": "toxic", "proxy": "db", "toxic": {"type": "latency"}}
+        ]}]}"#,
+    )
+    .unwrap_err();
+    assert_eq!(
+        "invalid scenario:\n  \
+         phase 1 (\"broken\"): unknown toxic type \"jitter\"\n  \
+         phase 1 (\"broken\"): unknown stream \"sideways\"\n  \
+         phase 1 (\"broken\"): toxicity 1.5 is not between 0 and 1\n  \
+         phase 1 (\"broken\"): duplicate toxic \"latency_downstream\" on proxy \"db\"",
+        err
+    );
+}
+
+#[test]
+fn test_client_to_cli_script() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "socket".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let proxy = client.find_proxy("socket").unwrap();
+    proxy.with_timeout("upstream".into(), 100, 0.5);
+    proxy.disable().unwrap();
+
+    let command = format!(
+        "toxiproxy-cli --host http://{}",
+        TOXIPROXY.address().unwrap()
+    );
+    assert_eq!(
+        format!(
+            "#!/bin/sh\n\
+             # Proxies and toxics of the Toxiproxy server at {address}.\n\
+             set -e\n\
+             \n\
+             {command} create -l {listen} -u localhost:2000 {name}\n\
+             {command} toxic add -n timeout_upstream -t timeout --upstream --tox 0.5 -a timeout=100 {name}\n\
+             {command} toggle {name}\n",
+            address = TOXIPROXY.address().unwrap(),
+            command = command,
+            listen = proxy.proxy_pack.listen,
+            name = proxy.proxy_pack.name,
+        ),
+        client.to_cli_script().unwrap()
+    );
+}
+
+#[test]
+fn test_proxy_and_populate_macros() {
+    let db = proxy!("db" => "localhost:2001" -> "db:5432");
+    assert_eq!(
+        ("db", "localhost:2001", "db:5432"),
+        (db.name.as_str(), db.listen.as_str(), db.upstream.as_str())
+    );
+
+    let upstream = String::from("localhost:2000");
+    let client = TOXIPROXY.scoped();
+    let proxies = populate!(client, {
+        "first" -> (upstream.clone()),
+        "second" -> upstream,
+    })
+    .unwrap();
+    assert_eq!(2, proxies.len());
+    assert!(client.find_proxy("second").is_ok());
+}
+
+#[test]
+fn test_proxy_pack_from_str() {
+    let proxy_pack: ProxyPack = " db = 127.0.0.1:2001 -> db:5432 ".parse().unwrap();
+    assert_eq!(
+        ("db", "127.0.0.1:2001", "db:5432", true),
+        (
+            proxy_pack.name.as_str(),
+            proxy_pack.listen.as_str(),
+            proxy_pack.upstream.as_str(),
+            proxy_pack.enabled
+        )
+    );
+
+    let proxy_pack: ProxyPack = "cache->[::1]:6379".parse().unwrap();
+    assert_eq!("0.0.0.0:0", proxy_pack.listen);
+    assert_eq!("[::1]:6379", proxy_pack.upstream);
+
+    assert!("db".parse::<ProxyPack>().is_err());
+    assert!("=127.0.0.1:2001->db:5432".parse::<ProxyPack>().is_err());
+    assert!("db->db".parse::<ProxyPack>().is_err());
+
+    let proxy_packs = ProxyPack::parse_list("a->a:1,b=127.0.0.1:2->b:2\nc->c:3,").unwrap();
+    assert_eq!(
+        vec!["a", "b", "c"],
+        proxy_packs
+            .iter()
+            .map(|proxy_pack| proxy_pack.name.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert!(ProxyPack::parse_list("a->a:1,b").is_err());
+}
+
+#[test]
+fn test_proxy_factory() {
+    assert!(factory::ProxyFactory::new("127.0.0.1", 2000..2000).is_err());
+    assert!(factory::ProxyFactory::new("not a host", 2000..2001).is_err());
+
+    let mut factory = factory::ProxyFactory::new("127.0.0.1", 21200..21202).unwrap();
+    let proxy_packs = factory.mint_all(&["localhost:2000", "db:5432"]).unwrap();
+    assert_eq!("localhost_2000", proxy_packs[0].name);
+    assert_eq!("127.0.0.1:21200", proxy_packs[0].listen);
+    assert_eq!("db:5432", proxy_packs[1].upstream);
+    assert_eq!(Some(21201), factory.port_of("db_5432"));
+
+    assert!(factory.mint("db:5432").is_err());
+    assert!(factory.mint_all(&["cache:6379"]).is_err());
+    assert_eq!(2, factory.allocated().len());
+
+    assert_eq!(Some(21200), factory.release("localhost_2000"));
+    assert_eq!(
+        "127.0.0.1:21200",
+        factory.mint("cache:6379").unwrap().listen
+    );
+
+    let client = TOXIPROXY.scoped();
+    let proxies = client.populate(vec![proxy_packs[1].clone()]).unwrap();
+    assert_eq!(Ok(21201), proxies[0].listen_port());
+}
+
+#[test]
+fn test_proxies_with_tag() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![
+            ProxyPack::new_ephemeral("orders_db".into(), "localhost:2000".into())
+                .tagged(&["db", "critical"]),
+            ProxyPack::new_ephemeral("users_db".into(), "localhost:2000".into()).tagged(&["db"]),
+            ProxyPack::new_ephemeral("cache".into(), "localhost:2000".into()),
+        ])
+        .unwrap();
+    client
+        .create_proxy(
+            ProxyPack::new_ephemeral("search".into(), "localhost:2000".into())
+                .tagged(&["critical", "critical"]),
+        )
+        .unwrap();
+
+    let names = |tag: &str| {
+        client
+            .proxies_with_tag(tag)
+            .unwrap()
+            .into_iter()
+            .map(|proxy| proxy.proxy_pack.name)
+            .collect::<Vec<String>>()
+    };
+    let db = names("db");
+    assert_eq!(2, db.len());
+    assert!(db[0].starts_with("orders_db__") && db[1].starts_with("users_db__"));
+    assert_eq!(2, names("critical").len());
+    assert!(names("missing").is_empty());
+
+    let search = client.find_proxy("search").unwrap();
+    assert_eq!(vec!["critical".to_owned()], search.proxy_pack.tags);
+
+    search.delete().unwrap();
+    assert_eq!(1, names("critical").len());
+
+    // The same name created again elsewhere doesn't inherit the tags of the deleted proxy.
+    let other = Client::new("127.0.0.1:8474")
+        .create_proxy(ProxyPack::new_ephemeral(
+            search.proxy_pack.name.clone(),
+            "localhost:2000".into(),
+        ))
+        .unwrap();
+    assert_eq!(1, names("critical").len());
+    other.delete().unwrap();
+
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "users_db".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    assert_eq!(1, names("db").len());
+}
+
+#[test]
+fn test_proxy_group() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![
+            ProxyPack::new_ephemeral("orders_db".into(), "localhost:2000".into()).tagged(&["db"]),
+            ProxyPack::new_ephemeral("users_db".into(), "localhost:2000".into()).tagged(&["db"]),
+            ProxyPack::new_ephemeral("cache".into(), "localhost:2001".into()),
+        ])
+        .unwrap();
+
+    let group = client.group_with_tag("db").unwrap();
+    assert_eq!(2, group.len());
+    group
+        .add_toxic(toxic::ToxicPack::new(
+            "latency".into(),
+            "downstream".into(),
+            1.0,
+            vec![("latency".to_owned(), 100)].into_iter().collect(),
+        ))
+        .unwrap();
+    group.disable_all().unwrap();
+    for proxy in group.proxies() {
+        assert_eq!(1, proxy.toxics().unwrap().len());
+        assert!(!proxy.is_enabled().unwrap());
+    }
+    assert!(client.find_proxy("cache").unwrap().is_enabled().unwrap());
+
+    group.enable_all().unwrap();
+    group.delete_all_toxics().unwrap();
+    assert!(group.proxies()[0].toxics().unwrap().is_empty());
+
+    let group = client
+        .group_matching(|proxy_pack| proxy_pack.upstream == "localhost:2001")
+        .unwrap();
+    assert_eq!(1, group.len());
+    assert!(client.group(&["cache", "missing"]).is_err());
+
+    let group = client.group(&["cache", "users_db"]).unwrap();
+    let err = group
+        .for_each(|proxy| {
+            if proxy.proxy_pack.name.starts_with("cache") {
+                Err("boom".into())
+            } else {
+                Ok(())
+            }
+        })
+        .unwrap_err();
+    assert_eq!(1, err.failures.len());
+    assert_eq!("boom", err.failures[0].1);
+    assert!(String::from(err).starts_with("group operation failed for cache__"));
+}
+
+#[test]
+fn test_chaos_run() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "chaos".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let proxy = client.find_proxy("chaos").unwrap();
+    let config = chaos::ChaosConfig::new(&["chaos"])
+        .toxic(chaos::ChaosToxic::new("latency", "downstream").attribute("latency", 100..=200))
+        .interval(Duration::from_millis(10));
+
+    let observed = chaos::Chaos::run(&client, config, || {
+        let mut observed = vec![];
+        for _ in 0..20 {
+            observed.extend(proxy.toxics().unwrap());
+            std::thread::sleep(Duration::from_millis(15));
+        }
+        observed
+    })
+    .unwrap();
+
+    assert!(!observed.is_empty());
+    for toxic in observed {
+        assert_eq!("chaos_latency_downstream", toxic.name);
+        assert!((100..=200).contains(&toxic.attributes["latency"]));
+    }
+    assert!(proxy.toxics().unwrap().is_empty());
+
+    assert!(chaos::Chaos::run(&client, chaos::ChaosConfig::new(&["chaos"]), || ()).is_err());
+    let config = chaos::ChaosConfig::new(&["missing"])
+        .toxic(chaos::ChaosToxic::new("timeout", "downstream"));
+    assert!(chaos::Chaos::run(&client, config, || ()).is_err());
+
+    // A toxic chaos failed to create is not its own to delete.
+    let mut existing = toxic::ToxicPack::new(
+        "reset_peer".into(),
+        "downstream".into(),
+        1.0,
+        vec![("timeout".to_owned(), 0)].into_iter().collect(),
+    );
+    existing.name = "chaos_reset_peer_downstream".into();
+    client
+        .group(&["chaos"])
+        .unwrap()
+        .add_toxic(existing)
+        .unwrap();
+    client
+        .set_server_version(Some(compat::ServerVersion::new(2, 1, 0)))
+        .unwrap();
+    let config = chaos::ChaosConfig::new(&["chaos"])
+        .toxic(chaos::ChaosToxic::new("reset_peer", "downstream"))
+        .interval(Duration::from_millis(10));
+    let result = chaos::Chaos::run(&client, config, || {
+        std::thread::sleep(Duration::from_millis(50))
+    });
+    client.set_server_version(None).unwrap();
+    assert!(result.unwrap_err().contains("requires Toxiproxy 2.2.0"));
+    assert_eq!(1, proxy.toxics().unwrap().len());
+}
+
+#[test]
+fn test_chaos_seed() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "chaos".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let proxy = client.find_proxy("chaos").unwrap();
+
+    // The first change registers a toxic with a latency picked from the seed only.
+    let first_latency = |seed: u64| {
+        let config = chaos::ChaosConfig::new(&["chaos"])
+            .toxic(
+                chaos::ChaosToxic::new("latency", "downstream").attribute("latency", 0..=1_000_000),
+            )
+            .interval(Duration::from_millis(200))
+            .seed(seed);
+        chaos::Chaos::run(&client, config, || {
+            std::thread::sleep(Duration::from_millis(300));
+            proxy.toxics().unwrap()[0].attributes["latency"]
+        })
+        .unwrap()
+    };
+
+    assert_eq!(first_latency(42), first_latency(42));
+    assert_ne!(first_latency(42), first_latency(43));
+}
+
+#[test]
+fn test_chaos_start() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "chaos".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let proxy = client.find_proxy("chaos").unwrap();
+    let config = chaos::ChaosConfig::new(&["chaos"])
+        .toxic(chaos::ChaosToxic::new("timeout", "upstream").attribute("timeout", 0..=1000))
+        .interval(Duration::from_millis(50))
+        .seed(7);
+
+    let handle = chaos::Chaos::start(&client, config.clone()).unwrap();
+    assert_eq!(7, handle.seed());
+    std::thread::sleep(Duration::from_millis(75));
+    assert!(!handle.is_finished());
+    assert_eq!(1, proxy.toxics().unwrap().len());
+    handle.stop().unwrap();
+    assert!(proxy.toxics().unwrap().is_empty());
+
+    let handle = chaos::Chaos::start(&client, config).unwrap();
+    std::thread::sleep(Duration::from_millis(75));
+    drop(handle);
+    assert!(proxy.toxics().unwrap().is_empty());
+}
+
+#[test]
+fn test_chaos_pause_resume() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "chaos".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let proxy = client.find_proxy("chaos").unwrap();
+    let config = chaos::ChaosConfig::new(&["chaos"])
+        .toxic(chaos::ChaosToxic::new("timeout", "upstream").attribute("timeout", 0..=1000))
+        .interval(Duration::from_millis(20))
+        .seed(7);
+
+    let handle = chaos::Chaos::start(&client, config).unwrap();
+    std::thread::sleep(Duration::from_millis(30));
+    handle.pause();
+    assert!(handle.is_paused());
+    let toxics = proxy.toxics().unwrap().len();
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(toxics, proxy.toxics().unwrap().len());
+
+    handle.pause_and_clear();
+    assert!(proxy.toxics().unwrap().is_empty());
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(proxy.toxics().unwrap().is_empty());
+
+    handle.resume();
+    assert!(!handle.is_paused());
+    let resumed = std::time::Instant::now();
+    while proxy.toxics().unwrap().is_empty() && resumed.elapsed() < Duration::from_secs(1) {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(1, proxy.toxics().unwrap().len());
+    handle.stop().unwrap();
+    assert!(proxy.toxics().unwrap().is_empty());
+}
+
+#[test]
+fn test_proxy_flap() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "flap".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let proxy = client.find_proxy("flap").unwrap();
+
+    assert!(proxy
+        .flap(Duration::from_millis(10), 1.5, Duration::from_millis(10))
+        .unwrap_err()
+        .contains("duty cycle"));
+    assert!(proxy
+        .flap(Duration::from_millis(0), 0.5, Duration::from_millis(10))
+        .is_err());
+
+    let flapping = {
+        let proxy = client.find_proxy("flap").unwrap();
+        std::thread::spawn(move || {
+            proxy.flap(Duration::from_millis(60), 0.5, Duration::from_millis(300))
+        })
+    };
+    let mut states = vec![];
+    for _ in 0..12 {
+        std::thread::sleep(Duration::from_millis(20));
+        states.push(proxy.is_enabled().unwrap());
+    }
+    flapping.join().unwrap().unwrap();
+
+    assert!(states.contains(&true));
+    assert!(states.contains(&false));
+    assert!(proxy.is_enabled().unwrap());
+}
+
+#[test]
+fn test_proxy_ramp_latency() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "ramp".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let proxy = client.find_proxy("ramp").unwrap();
+
+    assert!(proxy
+        .ramp_latency(0, 100, 0, Duration::from_millis(10))
+        .is_err());
+
+    let ramping = {
+        let proxy = client.find_proxy("ramp").unwrap();
+        std::thread::spawn(move || proxy.ramp_latency(300, 0, 100, Duration::from_millis(100)))
+    };
+    std::thread::sleep(Duration::from_millis(150));
+    let toxics = proxy.toxics().unwrap();
+    assert_eq!(1, toxics.len());
+    assert_eq!("latency_ramp", toxics[0].name);
+    assert!(toxics[0].attributes["latency"] < 300);
+    ramping.join().unwrap().unwrap();
+
+    let toxics = proxy.toxics().unwrap();
+    assert_eq!(1, toxics.len());
+    assert_eq!(0, toxics[0].attributes["latency"]);
+}
+
+#[test]
+fn test_chaos_downtime() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "downtime".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let proxy = client.find_proxy("downtime").unwrap();
+
+    let empty = Duration::from_millis(20)..=Duration::from_millis(10);
+    assert!(chaos::Downtime::new(empty)
+        .run(&proxy, Duration::from_millis(10))
+        .is_err());
+
+    let outages = {
+        let proxy = client.find_proxy("downtime").unwrap();
+        std::thread::spawn(move || {
+            chaos::Downtime::new(Duration::from_millis(40)..=Duration::from_millis(60))
+                .gap(Duration::from_millis(40)..=Duration::from_millis(60))
+                .seed(3)
+                .run(&proxy, Duration::from_millis(400))
+        })
+    };
+    let mut states = vec![];
+    for _ in 0..15 {
+        std::thread::sleep(Duration::from_millis(20));
+        states.push(proxy.is_enabled().unwrap());
+    }
+    outages.join().unwrap().unwrap();
+
+    assert!(states.contains(&true));
+    assert!(states.contains(&false));
+    assert!(proxy.is_enabled().unwrap());
+}
+
+#[test]
+fn test_client_with_partition() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![
+            ProxyPack::new_ephemeral("a".into(), "localhost:2000".into()),
+            ProxyPack::new_ephemeral("b".into(), "localhost:2000".into()),
+            ProxyPack::new_ephemeral("c".into(), "localhost:2000".into()),
+        ])
+        .unwrap();
+    let enabled = |name: &str| client.find_proxy(name).unwrap().is_enabled().unwrap();
+
+    let states = client
+        .with_partition(&["a", "b"], || (enabled("a"), enabled("b"), enabled("c")))
+        .unwrap();
+    assert_eq!((false, false, true), states);
+    assert!(enabled("a") && enabled("b"));
+
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        client.with_partition(&["a"], || panic!("inside the partition"))
+    }));
+    assert!(result.is_err());
+    assert!(enabled("a"));
+
+    assert!(client.with_partition(&["a", "missing"], || ()).is_err());
+    assert!(enabled("a"));
+}
+
+#[test]
+fn test_proxy_set() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![
+            ProxyPack::new_ephemeral("a".into(), "localhost:2000".into()),
+            ProxyPack::new_ephemeral("b".into(), "localhost:2000".into()),
+        ])
+        .unwrap();
+    let set = client.proxy_set(&["a", "b"]).unwrap();
+    let toxic_counts = || {
+        set.proxies()
+            .iter()
+            .map(|proxy| proxy.toxics().unwrap().len())
+            .collect::<Vec<usize>>()
+    };
+
+    let counts = set
+        .with_latency("downstream".into(), 100, 0, 1.0)
+        .with_bandwidth("upstream".into(), 10, 1.0)
+        .apply(toxic_counts)
+        .unwrap();
+    assert_eq!(vec![2, 2], counts);
+    assert_eq!(vec![0, 0], toxic_counts());
+
+    let names = set
+        .proxies()
+        .iter()
+        .map(|proxy| proxy.proxy_pack.name.clone())
+        .collect::<Vec<String>>();
+    let unreachable = Client::from_env();
+    let unreachable_set = unreachable.proxy_set(&[&names[0], &names[1]]).unwrap();
+    unreachable.set_address("127.0.0.1:1").unwrap();
+    let err = unreachable_set
+        .with_timeout("downstream".into(), 100, 1.0)
+        .apply(|| unreachable!())
+        .unwrap_err();
+    assert_eq!(
+        names,
+        err.failures
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<String>>()
+    );
+
+    {
+        let scope = set.toxic_scope();
+        scope.with_slow_close("downstream".into(), 10, 1.0);
+        scope.check().unwrap();
+        assert_eq!(vec![1, 1], toxic_counts());
+    }
+    assert_eq!(vec![0, 0], toxic_counts());
+}
+
+#[test]
+fn test_timeline() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "timeline".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let proxy = client.find_proxy("timeline").unwrap();
+    let latency = toxic::ToxicPack::new(
+        "latency".into(),
+        "downstream".into(),
+        1.0,
+        vec![("latency".to_owned(), 100)].into_iter().collect(),
+    );
+    let timeline = timeline::Timeline::new()
+        .at(Duration::from_millis(50))
+        .add_toxic("timeline", latency)
+        .at(Duration::from_millis(200))
+        .remove_toxic("timeline", "latency_downstream")
+        .after(Duration::from_millis(100))
+        .disable("timeline");
+    assert_eq!(Duration::from_millis(300), timeline.duration());
+
+    let states = timeline
+        .run(&client, || {
+            let mut states = vec![];
+            for _ in 0..3 {
+                std::thread::sleep(Duration::from_millis(125));
+                states.push((proxy.toxics().unwrap().len(), proxy.is_enabled().unwrap()));
+            }
+            states
+        })
+        .unwrap();
+    assert_eq!(vec![(1, true), (0, true), (0, false)], states);
+    assert!(proxy.is_enabled().unwrap());
+
+    let handle = timeline.start(&client).unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(1, proxy.toxics().unwrap().len());
+    handle.cancel().unwrap();
+    std::thread::sleep(Duration::from_millis(250));
+    assert!(proxy.toxics().unwrap().is_empty());
+    assert!(proxy.is_enabled().unwrap());
+
+    let handle = timeline.start(&client).unwrap();
+    assert!(!handle.is_finished());
+    handle.wait().unwrap();
+    assert!(proxy.is_enabled().unwrap());
+}
+
+#[test]
+fn test_client_apply_profile() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![
+            ProxyPack::new_ephemeral("db_main".into(), "localhost:2000".into()),
+            ProxyPack::new_ephemeral("db_replica".into(), "localhost:2000".into()),
+            ProxyPack::new_ephemeral("cache".into(), "localhost:2000".into()),
+        ])
+        .unwrap();
+    let toxic_names = |name: &str| {
+        client
+            .find_proxy(name)
+            .unwrap()
+            .toxics()
+            .unwrap()
+            .into_iter()
+            .map(|toxic| toxic.name)
+            .collect::<Vec<String>>()
+    };
+    client
+        .find_proxy("cache")
+        .unwrap()
+        .with_timeout("upstream".into(), 100, 1.0);
+
+    let profile = profile::Profile::from_json(
+        r#"{"name": "slow", "rules": [
+            {"proxies": "*", "toxics": [{"type": "latency", "attributes": {"latency": 10}}]},
+            {"proxies": "db_?ain", "toxics": [
+                {"type": "latency", "attributes": {"latency": 500}},
+                {"type": "bandwidth", "stream": "upstream", "attributes": {"rate": 100}}
+            ]}
+        ]}"#,
+    )
+    .unwrap();
+    let proxies = client.apply_profile(&profile).unwrap();
+    assert_eq!(3, proxies.len());
+    assert_eq!(
+        vec!["profile_latency_downstream", "profile_bandwidth_upstream"],
+        toxic_names("db_main")
+    );
+    assert_eq!(
+        500,
+        client.find_proxy("db_main").unwrap().toxics().unwrap()[0].attributes["latency"]
+    );
+    assert_eq!(
+        vec!["profile_latency_downstream"],
+        toxic_names("db_replica")
+    );
+
+    let profile = profile::Profile::from_json(
+        r#"{"rules": [{"proxies": "db_*", "toxics": [{"type": "timeout"}]}]}"#,
+    )
+    .unwrap();
+    assert_eq!(2, client.apply_profile(&profile).unwrap().len());
+    assert_eq!(vec!["timeout_upstream"], toxic_names("cache"));
+    assert_eq!(vec!["profile_timeout_downstream"], toxic_names("db_main"));
+
+    client.clear_profile().unwrap();
+    assert!(toxic_names("db_main").is_empty());
+    assert_eq!(vec!["timeout_upstream"], toxic_names("cache"));
+
+    let err = profile::Profile::from_json(r#"{"rules": [], "proxies": []}"#).unwrap_err();
+    assert!(err.contains("unknown field"), "{}", err);
+}
+
+#[test]
+fn test_cluster_client() {
+    let client = TOXIPROXY.scoped();
+    let unreachable = Client::new("127.0.0.1:1");
+    let cluster = cluster::ClusterClient::new(vec![(*client).clone(), (*client).clone()]);
+
+    let proxies = cluster
+        .populate(vec![ProxyPack::new_ephemeral(
+            "cluster".into(),
+            "localhost:2000".into(),
+        )])
+        .into_result()
+        .unwrap();
+    assert_eq!(2, proxies.len());
+    let proxy = client.find_proxy("cluster").unwrap();
+
+    let latency = toxic::ToxicPack::new(
+        "latency".into(),
+        "downstream".into(),
+        1.0,
+        vec![("latency".to_owned(), 100)].into_iter().collect(),
+    );
+    let outcome = cluster::ClusterClient::new(vec![(*client).clone(), unreachable.clone()])
+        .add_toxic("cluster", latency);
+    assert!(!outcome.is_ok());
+    assert_eq!(2, outcome.results.len());
+    assert!(outcome.results[0].1.is_ok());
+    assert_eq!(
+        vec!["127.0.0.1:1"],
+        outcome
+            .failures()
+            .into_iter()
+            .map(|(endpoint, _)| endpoint)
+            .collect::<Vec<&str>>()
+    );
+    assert!(outcome
+        .into_result()
+        .unwrap_err()
+        .starts_with("cluster operation failed for 127.0.0.1:1: "));
+    assert_eq!(1, proxy.toxics().unwrap().len());
+
+    assert!(cluster.disable("cluster").is_ok());
+    assert!(!proxy.is_enabled().unwrap());
+    assert!(cluster.enable("cluster").is_ok());
+    assert!(cluster.delete_all_toxics("cluster").is_ok());
+    assert!(proxy.toxics().unwrap().is_empty());
+    assert!(proxy.is_enabled().unwrap());
+
+    let outcome = cluster::ClusterClient::new(vec![unreachable]).reset();
+    assert_eq!(1, outcome.failures().len());
+
+    let outcome = cluster::ClusterClient::from_addresses(&["toxiproxy.invalid:8474"]).reset();
+    assert!(outcome.failures()[0].1.contains("Incorrect address"));
+}
+
+#[test]
+fn test_client_record_and_replay() {
+    let client = Client::from_env().scoped();
+    let recorder = client.record();
+    let other = client.record();
+    let proxy = recorder
+        .client()
+        .create_proxy(ProxyPack::new_ephemeral(
+            "journal".into(),
+            "localhost:2000".into(),
+        ))
+        .unwrap();
+    proxy.with_timeout("upstream".into(), 100, 1.0);
+    client.version().unwrap();
+    proxy.disable().unwrap();
+    assert!(other.stop().entries.is_empty());
+    let journal = recorder.stop();
+    client.find_proxy("journal").unwrap().delete().unwrap();
+
+    let requests = journal
+        .entries
+        .iter()
+        .map(|entry| format!("{} {}", entry.method, entry.path))
+        .collect::<Vec<String>>();
+    let name = &proxy.proxy_pack.name;
+    assert_eq!(
+        vec![
+            "POST proxies".to_owned(),
+            format!("POST proxies/{}/toxics", name),
+            format!("POST proxies/{}", name),
+        ],
+        requests
+    );
+    assert!(journal.entries[1]
+        .payload
+        .as_ref()
+        .unwrap()
+        .contains("\"timeout\""));
+    assert!(journal
+        .entries
+        .windows(2)
+        .all(|pair| pair[0].timestamp_ms <= pair[1].timestamp_ms));
+
+    let path = std::env::temp_dir().join(format!("toxiproxy_journal_{}.json", std::process::id()));
+    journal.save(&path).unwrap();
+    let loaded = journal::Journal::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(journal, loaded);
+
+    loaded.replay(&client).unwrap();
+    let replayed = client.find_proxy("journal").unwrap();
+    assert_eq!(1, replayed.toxics().unwrap().len());
+    assert!(!replayed.is_enabled().unwrap());
+    replayed.delete().unwrap();
+
+    let recorder = client.record();
+    let recording = recorder.client().clone();
+    drop(recorder);
+    recording.version().unwrap();
+    assert!(client.record().stop().entries.is_empty());
+}
+
+#[test]
+fn test_proxy_apply_reported() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "reported".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let proxy = client.find_proxy("reported").unwrap();
+
+    let report = proxy
+        .with_latency("downstream".into(), 100, 0, 1.0)
+        .apply_reported(|| {
+            std::thread::sleep(Duration::from_millis(20));
+            42
+        })
+        .unwrap();
+    assert_eq!(42, report.value);
+    assert!(report.enabled);
+    assert!(report.elapsed >= Duration::from_millis(20));
+    assert_eq!(1, report.toxics.len());
+    assert_eq!(100, report.toxics[0].attributes["latency"]);
+    assert!(report.cleanup.is_ok());
+    assert!(proxy.toxics().unwrap().is_empty());
+    assert!(report
+        .to_string()
+        .contains("enabled proxy with toxics [latency_downstream (latency, downstream, 1, "));
+
+    proxy.delete().unwrap();
+    assert!(proxy.apply_reported(|| unreachable!()).is_err());
+}
+
+#[test]
+fn test_proxy_measure() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "measure".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let proxy = client.find_proxy("measure").unwrap();
+
+    let measurements = proxy
+        .with_latency("downstream".into(), 100, 0, 1.0)
+        .measure(|recorder| {
+            let toxics = recorder.time("toxics", || proxy.toxics().unwrap().len());
+            recorder.time("sleep", || std::thread::sleep(Duration::from_millis(10)));
+            for millis in 1..=200 {
+                recorder.record("synthetic", Duration::from_millis(millis));
+            }
+            toxics
+        })
+        .unwrap();
+    assert_eq!(1, measurements.value);
+    assert!(proxy.toxics().unwrap().is_empty());
+
+    assert_eq!(1, measurements.histogram("toxics").count());
+    assert!(measurements.histogram("sleep").min() >= Duration::from_millis(10));
+    assert_eq!(0, measurements.histogram("missing").count());
+
+    let synthetic = measurements.histogram("synthetic");
+    assert_eq!(200, synthetic.count());
+    assert_eq!(Duration::from_millis(1), synthetic.min());
+    assert_eq!(Duration::from_millis(200), synthetic.max());
+    assert_eq!(Duration::from_micros(100_500), synthetic.mean());
+    assert_eq!(Duration::from_millis(100), synthetic.p50());
+    assert_eq!(Duration::from_millis(198), synthetic.p99());
+    assert_eq!(Duration::from_millis(1), synthetic.percentile(0.0));
+    assert!(synthetic.to_string().starts_with("count 200, min 1ms"));
+}
+
+#[test]
+fn test_scenario_golden_snapshot() {
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "golden".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let scenario = |latency| {
+        scenario::Scenario::new()
+            .phase("slow", Duration::from_millis(0), |phase| {
+                phase.on("golden").latency(latency)
+            })
+            .phase("down", Duration::from_millis(0), |phase| {
+                phase.on("golden").down()
+            })
+    };
+
+    let snapshot = scenario(500).golden_snapshot(&client).unwrap();
+    assert!(snapshot.steps.iter().all(|step| step.method != "GET"));
+    snapshot
+        .assert_matches("tests/golden/scenario.json")
+        .expect("scenario matches its golden file");
+
+    // Compared in memory, so that only the snapshot above writes the golden file on updates.
+    let golden = golden::GoldenSnapshot::from_json(
+        &std::fs::read_to_string("tests/golden/scenario.json").unwrap(),
+    )
+    .unwrap();
+    assert_ne!(golden, scenario(600).golden_snapshot(&client).unwrap());
+
+    let proxy = client.find_proxy("golden").unwrap();
+    let (_, snapshot) = golden::GoldenSnapshot::record(&client, |_| proxy.enable().unwrap());
+    assert!(snapshot.steps.is_empty());
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_tracing_spans() {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    type Spans = Arc<Mutex<Vec<(&'static str, HashMap<String, String>)>>>;
+
+    struct Fields<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().into(), value.into());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name().into(), format!("{:?}", value));
+        }
+    }
+
+    struct Collector(Spans);
+
+    impl Subscriber for Collector {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut spans = self.0.lock().unwrap();
+            let mut fields = HashMap::new();
+            span.record(&mut Fields(&mut fields));
+            spans.push((span.metadata().name(), fields));
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.0.lock().unwrap();
+            values.record(&mut Fields(&mut spans[span.into_u64() as usize - 1].1));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    let spans = Spans::default();
+    let proxy_name = tracing::subscriber::with_default(Collector(spans.clone()), || {
+        let client = TOXIPROXY.scoped();
+        client
+            .populate(vec![ProxyPack::new_ephemeral(
+                "traced".into(),
+                "localhost:2000".into(),
+            )])
+            .unwrap();
+        let proxy = client.find_proxy("traced").unwrap();
+        proxy
+            .with_latency("downstream".into(), 100, 0, 1.0)
+            .apply(|| {})
+            .unwrap();
+        proxy.delete().unwrap();
+        proxy.proxy_pack.name
+    });
+
+    let spans = spans.lock().unwrap();
+    let operation = |name: &str| {
+        spans
+            .iter()
+            .find(|(span, fields)| {
+                *span == "toxiproxy" && fields.get("operation").map(String::as_str) == Some(name)
+            })
+            .map(|(_, fields)| fields.clone())
+            .unwrap_or_else(|| panic!("no {} span", name))
+    };
+
+    assert!(operation("populate")["proxy"].starts_with("traced"));
+    assert_eq!("ok", operation("populate")["outcome"]);
+    let add_toxic = operation("add_toxic");
+    assert_eq!(proxy_name, add_toxic["proxy"]);
+    assert_eq!("latency", add_toxic["toxic_type"]);
+    assert_eq!("latency_downstream", add_toxic["toxic"]);
+    assert_eq!("ok", operation("apply")["outcome"]);
+    assert_eq!("latency_downstream", operation("remove_toxic")["toxic"]);
+
+    assert!(spans.iter().any(|(span, fields)| *span == "toxiproxy_http"
+        && fields["method"] == "POST"
+        && fields["path"] == "populate"
+        && fields["status"] == "201"));
+}
+
+#[cfg(feature = "log")]
+#[test]
+fn test_log_records() {
+    use log::{Level, Log, Metadata, Record};
+    use std::sync::Mutex;
+
+    struct Collector(Mutex<Vec<(Level, String)>>);
+
+    impl Log for Collector {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.target() == "toxiproxy"
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                let message = record.args().to_string();
+                self.0.lock().unwrap().push((record.level(), message));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    let collector: &'static Collector = Box::leak(Box::new(Collector(Mutex::new(vec![]))));
+    log::set_logger(collector).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+
+    let client = TOXIPROXY.scoped();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "logged".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let proxy = client.find_proxy("logged").unwrap();
+    proxy
+        .with_latency("downstream".into(), 100, 0, 1.0)
+        .apply(|| {})
+        .unwrap();
+    proxy.delete().unwrap();
+
+    let name = &proxy.proxy_pack.name;
+    let records = collector.0.lock().unwrap();
+    let logged = |level: Level, message: String| {
+        assert!(
+            records.contains(&(level, message.clone())),
+            "{} not logged",
+            message
+        )
+    };
+    logged(Level::Info, format!("populate proxy={}: ok", name));
+    logged(
+        Level::Info,
+        format!(
+            "add_toxic proxy={} toxic=latency_downstream toxic_type=latency: ok",
+            name
+        ),
+    );
+    logged(Level::Info, format!("apply proxy={}: ok", name));
+    logged(Level::Debug, "POST populate: 201".into());
+}
+
+#[cfg(feature = "opentelemetry")]
+#[test]
+fn test_opentelemetry_spans() {
+    use opentelemetry::trace::Status;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    opentelemetry::global::set_tracer_provider(provider.clone());
+
+    let client = Client::from_env();
+    client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "otel".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    scenario::Scenario::new()
+        .phase("slow", Duration::from_millis(0), |phase| {
+            phase.on("otel").latency(100)
+        })
+        .run(&client)
+        .unwrap();
+
+    let proxy = client.find_proxy("otel").unwrap();
+    proxy.disable().unwrap();
+    client.set_address("127.0.0.1:1").unwrap();
+    assert!(proxy.enable().is_err());
+    client.set_address("127.0.0.1:8474").unwrap();
+    proxy.delete().unwrap();
+
+    provider.force_flush().unwrap();
+    let spans = exporter.get_finished_spans().unwrap();
+    let attribute = |span: &opentelemetry_sdk::trace::SpanData, key: &str| {
+        span.attributes
+            .iter()
+            .find(|attribute| attribute.key.as_str() == key)
+            .map(|attribute| attribute.value.to_string())
+    };
+    // Last span of the name with the attribute.
+    let span = |name: &str, key: &str, value: &str| {
+        spans
+            .iter()
+            .rev()
+            .find(|span| span.name == name && attribute(span, key).as_deref() == Some(value))
+            .unwrap_or_else(|| panic!("no {} span with {}={}", name, key, value))
+    };
+
+    let phase = span("toxiproxy.scenario_phase", "toxiproxy.phase", "slow");
+    let add_toxic = span("toxiproxy.add_toxic", "toxiproxy.proxy", "otel");
+    assert_eq!(phase.span_context.span_id(), add_toxic.parent_span_id);
+    assert_eq!(
+        Some("latency".into()),
+        attribute(add_toxic, "toxiproxy.toxic_type")
+    );
+    assert_eq!(
+        Status::Unset,
+        span("toxiproxy.disable", "toxiproxy.proxy", "otel").status
+    );
+    assert!(matches!(
+        span("toxiproxy.enable", "toxiproxy.proxy", "otel").status,
+        Status::Error { .. }
+    ));
+}
+
+#[test]
+fn test_client_hooks() {
+    use std::sync::{Arc, Mutex};
+
+    let client = Client::from_env();
+    let events = Arc::new(Mutex::new(Vec::<String>::new()));
+    let record = |events: &Arc<Mutex<Vec<String>>>| {
+        let events = events.clone();
+        move |event: String| events.lock().unwrap().push(event)
+    };
+
+    let recorded = record(&events);
+    client.on_toxic_created(move |proxy, toxic| {
+        recorded(format!("created {} on {}", toxic.name, proxy))
+    });
+    let recorded = record(&events);
+    client.on_toxic_removed(move |proxy, toxic| {
+        recorded(format!("removed {} from {}", toxic, proxy))
+    });
+    // Hooks can use the client.
+    let (recorded, hook_client) = (record(&events), client.clone());
+    client.on_proxy_disabled(move |proxy| {
+        let enabled = hook_client.find_proxy(proxy).unwrap().proxy_pack.enabled;
+        recorded(format!("disabled {} ({})", proxy, enabled))
+    });
+    let recorded = record(&events);
+    client.on_proxy_enabled(move |proxy| recorded(format!("enabled {}", proxy)));
+    let recorded = record(&events);
+    client.on_cleanup_failed(move |proxy, _| recorded(format!("cleanup failed on {}", proxy)));
+
+    let proxy = client
+        .create_proxy(ProxyPack::new_ephemeral(
+            "hooked".into(),
+            "localhost:2000".into(),
+        ))
+        .unwrap();
+    proxy.with_down(|| {}).unwrap();
+    assert!(proxy
+        .with_latency("downstream".into(), 100, 0, 1.0)
+        .apply(|| client.set_address("127.0.0.1:1").unwrap())
+        .is_err());
+    client.set_address("127.0.0.1:8474").unwrap();
+    proxy.delete_all_toxics().unwrap();
+
+    client.clear_hooks();
+    proxy.disable().unwrap();
+    proxy.delete().unwrap();
+
+    assert_eq!(
+        vec![
+            "disabled hooked (false)",
+            "enabled hooked",
+            "created latency_downstream on hooked",
+            "cleanup failed on hooked",
+            "removed latency_downstream from hooked",
+        ],
+        *events.lock().unwrap()
+    );
+}
+
+#[test]
+fn test_client_metrics() {
+    let client = Client::from_env();
+    let proxy = client
+        .create_proxy(ProxyPack::new_ephemeral(
+            "measured".into(),
+            "localhost:2000".into(),
+        ))
+        .unwrap();
+    client.reset_metrics();
+
+    proxy
+        .with_latency("downstream".into(), 100, 0, 1.0)
+        .with_timeout("upstream".into(), 100, 1.0)
+        .apply(|| {})
+        .unwrap();
+    client.set_address("127.0.0.1:1").unwrap();
+    assert!(proxy.disable().is_err());
+    client.set_address("127.0.0.1:8474").unwrap();
+    proxy.delete().unwrap();
+
+    let metrics = client.metrics();
+    assert_eq!(7, metrics.requests);
+    assert_eq!(1, metrics.failures);
+    let endpoint = |name: &str| metrics.endpoints[name].clone();
+    assert_eq!(2, endpoint("POST /proxies/{proxy}/toxics").requests);
+    assert_eq!(1, endpoint("GET /proxies/{proxy}/toxics").requests);
+    assert_eq!(
+        2,
+        endpoint("DELETE /proxies/{proxy}/toxics/{toxic}").requests
+    );
+    assert_eq!(1, endpoint("POST /proxies/{proxy}").failures);
+    assert!(metrics.total_time >= endpoint("POST /proxies/{proxy}/toxics").max_time);
+    assert!(metrics
+        .to_string()
+        .starts_with("7 requests to Toxiproxy (1 failed) in "));
+
+    client.reset_metrics();
+    assert_eq!(0, client.metrics().requests);
+}
+
+#[test]
+fn test_client_audit_log() {
+    let path = std::env::temp_dir().join("toxiproxy_audit_test.jsonl");
+    let _ = std::fs::remove_file(&path);
+    let client = Client::from_env();
+    client.audit_to(&path).unwrap();
+
+    let proxy = client
+        .create_proxy(ProxyPack::new_ephemeral(
+            "audited".into(),
+            "localhost:2000".into(),
+        ))
+        .unwrap();
+    proxy
+        .with_latency("downstream".into(), 100, 0, 1.0)
+        .apply(|| {})
+        .unwrap();
+    client.set_address("127.0.0.1:1").unwrap();
+    assert!(proxy.disable().is_err());
+    client.set_address("127.0.0.1:8474").unwrap();
+    proxy.delete().unwrap();
+    client.stop_audit();
+    client.all().unwrap();
+
+    let entries = audit::read(&path).unwrap();
+    let requests = entries
+        .iter()
+        .map(|entry| format!("{} {}", entry.method, entry.path))
+        .collect::<Vec<String>>();
+    assert_eq!(
+        vec![
+            "POST proxies",
+            "POST proxies/audited/toxics",
+            "DELETE proxies/audited/toxics/latency_downstream",
+            "POST proxies/audited",
+            "DELETE proxies/audited",
+        ],
+        requests
+    );
+    assert!(entries[1]
+        .payload
+        .as_ref()
+        .is_some_and(|payload| payload.contains("\"latency\"")));
+    assert_eq!(Some(200), entries[1].status);
+    assert!(entries[3].status.is_none() && entries[3].error.is_some());
+    assert!(entries
+        .iter()
+        .all(|entry| entry.pid == std::process::id() && entry.timestamp_ms > 0));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_client_correlation_id() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = vec![];
+        let mut buf = [0u8; 1024];
+        while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+            let read = stream.read(&mut buf).unwrap();
+            request.extend_from_slice(&buf[..read]);
+        }
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}")
+            .unwrap();
+        String::from_utf8_lossy(&request).to_lowercase()
+    });
+
+    let client = Client::from_env();
+    client.set_address(addr).unwrap();
+    let correlation_id = client.new_correlation_id();
+    assert!(correlation_id.starts_with("toxiproxy-"));
+    assert_eq!(Some(correlation_id.clone()), client.correlation_id());
+    assert!(client.all().unwrap().is_empty());
+
+    let request = server.join().unwrap();
+    assert!(request.contains(&format!("x-correlation-id: {}", correlation_id)));
+
+    client.set_address("127.0.0.1:1").unwrap();
+    let err = client.all().unwrap_err();
+    assert!(err.contains(&correlation_id));
+
+    client.clear_correlation_id();
+    assert!(client.correlation_id().is_none());
+    assert!(!client.all().unwrap_err().contains("correlation id"));
+}
+
+#[test]
+fn test_client_debug_bodies() {
+    let client = Client::from_env();
+    let pack = ProxyPack::new_ephemeral("debugged".into(), "localhost:2000".into());
+    let proxy = client.create_proxy(pack.clone()).unwrap();
+
+    client.debug_bodies(None);
+    let err = client.create_proxy(pack.clone()).unwrap_err();
+    assert!(err.starts_with("POST /proxies answered 409"));
+    assert!(err.contains("request body: {\"name\":\"debugged\""));
+    assert!(err.contains("response body: {"));
+
+    client.debug_bodies(Some(10));
+    let err = client.create_proxy(pack.clone()).unwrap_err();
+    assert!(err.contains("request body: {\"name\":\"d... ("));
+
+    client.set_address("127.0.0.1:1").unwrap();
+    let err = proxy.disable().unwrap_err();
+    assert!(err.starts_with("POST error: "));
+    assert!(err.contains("request body: {\"enabled"));
+    client.set_address("127.0.0.1:8474").unwrap();
+
+    client.stop_debug_bodies();
+    assert!(!client
+        .create_proxy(pack)
+        .unwrap_err()
+        .contains("request body"));
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_client_dump() {
+    let client = TOXIPROXY.namespaced(Namespace::unique());
+    let proxy = client
+        .create_proxy(ProxyPack::new_ephemeral(
+            "dumped".into(),
+            "localhost:2000".into(),
+        ))
+        .unwrap();
+    proxy
+        .with_latency("downstream".into(), 500, 0, 1.0)
+        .with_timeout("upstream".into(), 100, 0.5)
+        .apply(|| {
+            let table = client.dump().unwrap();
+            assert_eq!(1, table.proxies.len());
+
+            let lines = table
+                .to_string()
+                .lines()
+                .map(String::from)
+                .collect::<Vec<String>>();
+            assert_eq!(3, lines.len());
+            assert!(lines[0].starts_with("PROXY "));
+            assert!(lines[0].ends_with("TOXICITY  ATTRIBUTES"));
+            assert!(lines[1].starts_with(&proxy.proxy_pack.name));
+            assert!(lines[1].ends_with("latency  downstream  100%      jitter=0 latency=500"));
+            assert!(lines[2].starts_with(' '));
+            assert!(lines[2].ends_with("timeout  upstream    50%       timeout=100"));
+            // Columns are aligned.
+            let column = |line: &str| line.find("latency").or_else(|| line.find("timeout"));
+            assert_eq!(column(&lines[1]), column(&lines[2]));
+        })
+        .unwrap();
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_client_watch() {
+    use watcher::StateChange;
+
+    let client = Client::from_env().namespaced(Namespace::unique());
+    let watcher = client.watch(Duration::from_millis(10)).unwrap();
+    let next = || {
+        watcher
+            .changes()
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap()
+    };
+
+    let proxy = client
+        .create_proxy(ProxyPack::new_ephemeral(
+            "watched".into(),
+            "localhost:2000".into(),
+        ))
+        .unwrap();
+    assert!(matches!(next(), StateChange::ProxyAdded { proxy } if proxy.enabled));
+
+    proxy.disable().unwrap();
+    assert!(matches!(
+        next(),
+        StateChange::EnabledChanged { proxy, enabled: false } if proxy == "watched"
+    ));
+
+    proxy.with_latency("downstream".into(), 100, 0, 1.0);
+    assert!(matches!(
+        next(),
+        StateChange::ToxicAdded { toxic, .. } if toxic.name == "latency_downstream"
+    ));
+    let mut toxic = proxy.toxics().unwrap().remove(0);
+    toxic.attributes.insert("latency".into(), 200);
+    proxy.update_toxic(toxic).unwrap();
+    assert!(matches!(
+        next(),
+        StateChange::ToxicUpdated { toxic, .. } if toxic.attributes["latency"] == 200
+    ));
+    proxy.delete_all_toxics().unwrap();
+    assert!(matches!(
+        next(),
+        StateChange::ToxicRemoved { toxic, .. } if toxic == "latency_downstream"
+    ));
+
+    client.set_address("127.0.0.1:1").unwrap();
+    assert!(matches!(next(), StateChange::PollFailed { .. }));
+    assert!(watcher
+        .changes()
+        .recv_timeout(Duration::from_millis(50))
+        .is_err());
+    client.set_address("127.0.0.1:8474").unwrap();
+
+    proxy.delete().unwrap();
+    assert!(matches!(
+        next(),
+        StateChange::ProxyRemoved { proxy } if proxy == "watched"
+    ));
+    watcher.stop();
+}
+
+#[test]
+fn test_server_metrics() {
+    use prometheus::*;
 
-    assert!(result.is_ok());
+    let metrics = ServerMetrics::parse(
+        "# HELP toxiproxy_proxy_sent_bytes_total Bytes sent\n\
+         toxiproxy_proxy_sent_bytes_total{proxy=\"a \\\"b\\\"\", direction=\"upstream\"} 1.5e3 1700000000\n\
+         toxiproxy_up 1\n",
+    )
+    .unwrap();
+    assert_eq!(2, metrics.samples.len());
+    assert_eq!("a \"b\"", metrics.samples[0].labels["proxy"]);
+    assert_eq!(
+        1500.0,
+        metrics.sum(PROXY_SENT_BYTES, &[("proxy", "a \"b\"")])
+    );
+    assert_eq!(1.0, metrics.sum("toxiproxy_up", &[]));
+    assert_eq!(0.0, metrics.sum("toxiproxy_up", &[("proxy", "a")]));
+    assert!(ServerMetrics::parse("toxiproxy_up{proxy=\"a} 1").is_err());
 
-    assert_eq!(1, result.as_ref().unwrap().len());
-    assert_eq!("socket", result.as_ref().unwrap()[0].proxy_pack.name);
+    let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy = TOXIPROXY
+        .create_proxy(ProxyPack::new_ephemeral(
+            "metered".into(),
+            upstream.local_addr().unwrap().to_string(),
+        ))
+        .unwrap();
+    let labels = [("proxy", "metered"), ("direction", "upstream")];
+
+    let before = TOXIPROXY.server_metrics().unwrap();
+    let sent = proxy.assert_traffic(|| {
+        let mut stream = TcpStream::connect(&proxy.proxy_pack.listen).unwrap();
+        stream.write_all(b"hello").unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        5
+    });
+    assert_eq!(5, sent);
+    TOXIPROXY.server_metrics().unwrap().assert_delta_at_least(
+        &before,
+        PROXY_RECEIVED_BYTES,
+        &labels,
+        5.0,
+    );
+
+    let result = panic::catch_unwind(|| proxy.assert_traffic(|| {}));
+    let message = result.unwrap_err().downcast::<String>().unwrap();
+    assert_eq!(
+        "expected traffic through proxy \"metered\", but it received no bytes",
+        *message
+    );
+    let result = panic::catch_unwind(|| {
+        let now = TOXIPROXY.server_metrics().unwrap();
+        now.assert_delta_at_least(&now, PROXY_RECEIVED_BYTES, &labels, 1.0);
+    });
+    assert!(result.is_err());
+
+    proxy.delete().unwrap();
 }
 
 #[test]
-fn test_all() {
-    populate_example();
+fn test_junit_reports() {
+    use experiment::SoakConfig;
+    use scenario::{PhaseReport, ScenarioReport};
 
-    let result = TOXIPROXY.all();
-    assert!(result.is_ok());
-    assert_eq!(1, result.as_ref().unwrap().len());
+    let proxy = TOXIPROXY
+        .create_proxy(ProxyPack::new_ephemeral(
+            "junit".into(),
+            "localhost:2000".into(),
+        ))
+        .unwrap();
+    let mut iteration = 0;
+    let report = proxy
+        .soak(&SoakConfig::iterations(3), || {
+            iteration += 1;
+            if iteration == 2 {
+                Err("connection <reset>")
+            } else {
+                Ok(())
+            }
+        })
+        .unwrap();
+    proxy.delete().unwrap();
+
+    let suite = report.junit("soak & retry");
+    assert_eq!(3, suite.cases.len());
+    assert_eq!(1, suite.failures());
+
+    let path = std::env::temp_dir()
+        .join("toxiproxy_junit_test")
+        .join("soak.xml");
+    suite.write(&path).unwrap();
+    let xml = std::fs::read_to_string(&path).unwrap();
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuite name=\"soak &amp; retry\" tests=\"3\" failures=\"1\" errors=\"0\" time=\""));
+    assert!(xml.contains("<testcase name=\"iteration 1\" classname=\"soak &amp; retry\" time=\""));
+    assert!(xml.contains(
+        "<failure message=\"connection &lt;reset&gt;\">connection &lt;reset&gt;</failure>"
+    ));
+    assert!(xml.ends_with("</testsuite>\n"));
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+    let report = ScenarioReport {
+        scenario: "checkout".into(),
+        phases: vec![
+            PhaseReport {
+                name: "warmup".into(),
+                elapsed_ms: 20,
+                ..Default::default()
+            },
+            PhaseReport {
+                name: "outage".into(),
+                elapsed_ms: 1500,
+                ..Default::default()
+            },
+        ],
+        errors: vec!["reset failed".into(), "proxy gone".into()],
+        ..Default::default()
+    };
+    let suite = report.junit();
+    assert_eq!(
+        vec!["phase 1: warmup", "phase 2: outage", "cleanup"],
+        suite
+            .cases
+            .iter()
+            .map(|case| case.name.as_str())
+            .collect::<Vec<&str>>()
+    );
+    assert!(suite
+        .to_xml()
+        .contains("<failure message=\"reset failed\">reset failed\nproxy gone</failure>"));
 }
 
 #[test]
-fn test_version() {
-    assert!(TOXIPROXY.version().is_ok());
+fn test_api_error_context() {
+    use error::{ApiError, ApiErrorKind};
+
+    let client = Client::from_env();
+    let server = client.address().unwrap();
+    let pack = ProxyPack::new_ephemeral("erroneous".into(), "localhost:2000".into());
+    let proxy = client.create_proxy(pack.clone()).unwrap();
+    assert!(client.create_proxy(pack).is_err());
+
+    let err = ApiError::last().unwrap();
+    assert_eq!(ApiErrorKind::Status, err.kind);
+    assert_eq!("POST", err.method);
+    assert_eq!(format!("http://{}/proxies", server), err.url);
+    assert_eq!(Some(409), err.status);
+    assert!(err.proxy.is_none());
+    assert!(err.payload.as_ref().unwrap().contains("\"erroneous\""));
+    assert!(!err.is_retryable());
+    assert!(err.clone().redacted().payload.is_none());
+    assert_eq!(
+        format!("POST http://{}/proxies failed: 409 Conflict", server),
+        String::from(err)
+    );
+
+    // Errors are cleared by the next request.
+    client.version().unwrap();
+    assert!(ApiError::last().is_none());
+
+    client.set_correlation_id("api_error_test");
+    client.set_address("127.0.0.1:1").unwrap();
+    assert!(proxy.delete().is_err());
+    let err = ApiError::last().unwrap();
+    assert_eq!(ApiErrorKind::Connect, err.kind);
+    assert_eq!("DELETE", err.method);
+    assert_eq!(Some("erroneous".into()), err.proxy);
+    assert_eq!(Some("api_error_test".into()), err.correlation_id);
+    assert!(err.status.is_none() && err.is_retryable());
+    assert!(err
+        .to_string()
+        .starts_with("DELETE http://127.0.0.1:1/proxies/erroneous failed (proxy erroneous): "));
+
+    // Errors are kept per thread, and carried over from the threads of the crate.
+    assert!(spawn(ApiError::last).join().unwrap().is_none());
+    let outcome = cluster::ClusterClient::from_addresses(&["127.0.0.1:1"]).reset();
+    assert!(!outcome.is_ok());
+    assert_eq!(ApiErrorKind::Connect, ApiError::last().unwrap().kind);
+
+    client.set_address(server).unwrap();
+    proxy.delete().unwrap();
+
+    // Cleaning up after a failure keeps it.
+    let deleted = client
+        .create_proxy(ProxyPack::new_ephemeral(
+            "erroneous_deleted".into(),
+            "localhost:2000".into(),
+        ))
+        .unwrap();
+    client
+        .find_proxy("erroneous_deleted")
+        .unwrap()
+        .delete()
+        .unwrap();
+    assert!(deleted.apply_reported(|| ()).is_err());
+    let err = ApiError::last().unwrap();
+    assert_eq!(
+        format!("http://{}/proxies/erroneous_deleted", server),
+        err.url
+    );
+    assert_eq!(Some(404), err.status);
 }
 
 #[test]
-fn test_find_and_reset_proxy() {
-    populate_example();
+fn test_verbosity_from_env() {
+    use verbosity::*;
 
-    let result = TOXIPROXY.find_and_reset_proxy("socket");
-    assert!(result.is_ok());
+    assert_eq!(Ok(Verbosity::Off), "off".parse());
+    assert_eq!(Ok(Verbosity::Errors), "error".parse());
+    assert_eq!(Ok(Verbosity::Requests), " info ".parse());
+    assert_eq!(Ok(Verbosity::Bodies), "Trace".parse());
+    assert!("verbose".parse::<Verbosity>().is_err());
 
-    assert_eq!("socket", result.as_ref().unwrap().proxy_pack.name);
+    assert_eq!(Verbosity::Bodies, Verbosity::from_value(Some("trace")));
+    assert_eq!(Verbosity::Off, Verbosity::from_value(Some("verbose")));
+    assert_eq!(Verbosity::Off, Verbosity::from_value(None));
+
+    let client = Client::from_env();
+    client.set_verbosity(Verbosity::Bodies);
+    assert_eq!(Verbosity::Bodies, client.verbosity());
+    assert!(client.all().is_ok());
+    client.set_verbosity(Verbosity::Off);
+    assert_eq!(Verbosity::Off, client.clone().verbosity());
 }
 
 #[test]
-fn test_find_and_reset_proxy_invalid() {
-    let result = TOXIPROXY.find_and_reset_proxy("bad-proxy");
-    assert!(result.is_err());
+fn test_client_diagnostics() {
+    let client = Client::from_env();
+    client.set_history_capacity(3);
+    assert!(client.diagnostics().recent.is_empty());
+    assert!(client
+        .diagnostics()
+        .to_string()
+        .ends_with("last 0 requests to Toxiproxy:\nno error\n"));
+
+    let proxy = client
+        .create_proxy(ProxyPack::new_ephemeral(
+            "diagnosed".into(),
+            "localhost:2000".into(),
+        ))
+        .unwrap();
+    assert!(client.find_proxy("diagnosed_missing").is_err());
+    proxy.disable().unwrap();
+    proxy.delete().unwrap();
+
+    let diagnostics = client.diagnostics();
+    let requests = diagnostics
+        .recent
+        .iter()
+        .map(|request| format!("{} {} {:?}", request.method, request.path, request.status))
+        .collect::<Vec<String>>();
+    assert_eq!(
+        vec![
+            "GET proxies/diagnosed_missing Some(404)",
+            "POST proxies/diagnosed Some(200)",
+            "DELETE proxies/diagnosed Some(204)",
+        ],
+        requests
+    );
+    let last_error = diagnostics.last_error.clone().unwrap();
+    assert_eq!(Some(404), last_error.status);
+    assert_eq!(Some("diagnosed_missing".into()), last_error.proxy);
+
+    let text = diagnostics.to_string();
+    assert!(text.starts_with("last 3 requests to Toxiproxy:\n"));
+    assert!(text.contains(" GET /proxies/diagnosed_missing: 404 in "));
+    assert!(text.ends_with(
+        "last error: GET http://127.0.0.1:8474/proxies/diagnosed_missing failed (proxy diagnosed_missing): 404 Not Found\n"
+    ));
+
+    client.set_history_capacity(0);
+    assert!(client.diagnostics().recent.is_empty());
 }
 
+#[cfg(feature = "testcontainers")]
 #[test]
-fn test_proxy_down() {
-    populate_example();
+#[ignore = "requires Docker, run with --ignored"]
+fn test_toxiproxy_container() {
+    use container::ToxiproxyContainer;
 
-    let result = TOXIPROXY.find_and_reset_proxy("socket");
-    assert!(result.is_ok());
-    assert!(result.as_ref().unwrap().proxy_pack.enabled);
+    let server = ToxiproxyContainer::start_with_ports(&[5433]).expect("container is started");
+    let client = server.client();
+    assert!(client.is_running());
 
-    assert!(result
-        .as_ref()
+    // A proxy to the API of the container itself, reached through the published port.
+    let proxy = client
+        .create_proxy(ProxyPack::new(
+            "container".into(),
+            "0.0.0.0:5433".into(),
+            "127.0.0.1:8474".into(),
+        ))
+        .unwrap();
+    let mut stream = TcpStream::connect(("127.0.0.1", server.host_port(5433))).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+        .write_all(b"GET /version HTTP/1.0\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.0 200"), "{}", response);
+
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_toxiproxy_server_spawn_failure() {
+    let err = toxiproxy_rust::server::ToxiproxyServer::spawn("/missing/toxiproxy-server", 18474)
+        .err()
+        .expect("missing binary is not started");
+    assert!(err.starts_with("/missing/toxiproxy-server cannot be started"));
+
+    #[cfg(unix)]
+    {
+        let err = toxiproxy_rust::server::ToxiproxyServer::spawn("false", 18474)
+            .err()
+            .expect("exiting binary is not ready");
+        assert!(
+            err.starts_with("false exited before it was ready"),
+            "{}",
+            err
+        );
+    }
+}
+
+#[cfg(feature = "download")]
+#[test]
+fn test_release_fetch() {
+    use toxiproxy_rust::download::Release;
+
+    let platform = Release::platform().expect("platform is supported");
+    let cache_dir = std::env::temp_dir().join(format!("toxiproxy_fetch_{}", std::process::id()));
+    let binary = cache_dir.join("2.9.0").join(format!(
+        "toxiproxy-server-{}{}",
+        platform,
+        std::env::consts::EXE_SUFFIX
+    ));
+    std::fs::create_dir_all(binary.parent().unwrap()).unwrap();
+    std::fs::write(&binary, "#!/bin/sh\n").unwrap();
+
+    let release = Release::new("v2.9.0")
+        .with_cache_dir(&cache_dir)
+        .with_base_url("http://127.0.0.1:1");
+
+    let err = release.fetch().expect_err("unpinned binary is refused");
+    assert!(err.starts_with("no checksum pinned"), "{}", err);
+
+    // Cached binaries matching the checksum are not downloaded again.
+    let release = release.with_sha256(
+        &platform,
+        "A8076D3D28D21E02012B20EAF7DBF75409A6277134439025F282E368E3305ABF",
+    );
+    assert_eq!(Ok(binary.clone()), release.fetch());
+    assert_eq!(
+        release,
+        Release::new("2.9.0")
+            .with_cache_dir(&cache_dir)
+            .with_base_url("http://127.0.0.1:1")
+            .with_checksums(&format!(
+                "a8076d3d28d21e02012b20eaf7dbf75409a6277134439025f282e368e3305abf  \
+                 toxiproxy-server-{}{}\n0123abcd  toxiproxy-cli-{}\n",
+                platform,
+                std::env::consts::EXE_SUFFIX,
+                platform
+            ))
+    );
+
+    std::fs::write(&binary, "#!/bin/sh\nexit 1\n").unwrap();
+    let err = release
+        .fetch()
+        .expect_err("modified binary is downloaded again");
+    assert!(err.contains("cannot be downloaded"), "{}", err);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A binary other users could swap after it's checked is not run.
+        let shared = std::fs::Permissions::from_mode(0o777);
+        std::fs::set_permissions(binary.parent().unwrap(), shared).unwrap();
+        let err = release.fetch().expect_err("shared cache is refused");
+        assert!(err.contains("is writable by other users"), "{}", err);
+    }
+
+    std::fs::remove_dir_all(&cache_dir).unwrap();
+}
+
+#[cfg(feature = "embedded")]
+#[test]
+fn test_embedded_server() {
+    use embedded::EmbeddedServer;
+    use std::time::Instant;
+
+    let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream_addr = upstream.local_addr().unwrap().to_string();
+    spawn(move || {
+        for mut stream in upstream.incoming().flatten() {
+            spawn(move || {
+                let mut buf = [0u8; 64];
+                while let Ok(read) = stream.read(&mut buf) {
+                    if read == 0 || stream.write_all(&buf[..read]).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    let echo = |proxy: &Proxy| -> Result<Duration, std::io::Error> {
+        let started = Instant::now();
+        let mut stream = TcpStream::connect(&proxy.proxy_pack.listen)?;
+        stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+        stream.write_all(b"ping")?;
+        let mut response = [0u8; 4];
+        stream.read_exact(&mut response)?;
+        assert_eq!(b"ping", &response);
+        Ok(started.elapsed())
+    };
+
+    let server = EmbeddedServer::start().unwrap();
+    let client = server.client();
+    assert_eq!(Ok("2.9.0-embedded".to_string()), client.health_check());
+
+    let proxy = client
+        .populate(vec![ProxyPack::new_ephemeral(
+            "embedded".into(),
+            upstream_addr,
+        )])
         .unwrap()
-        .with_down(|| {
-            let result = TOXIPROXY.find_and_reset_proxy("socket");
-            assert!(result.is_ok());
-            assert!(!result.as_ref().unwrap().proxy_pack.enabled);
+        .remove(0);
+    assert_ne!("127.0.0.1:0", proxy.proxy_pack.listen);
+    assert!(echo(&proxy).is_ok());
+
+    let elapsed = proxy
+        .with_latency("downstream".into(), 200, 0, 1.0)
+        .apply(|| echo(&proxy).unwrap())
+        .unwrap();
+    assert!(elapsed >= Duration::from_millis(200));
+    assert!(client
+        .find_proxy("embedded")
+        .unwrap()
+        .toxics()
+        .unwrap()
+        .is_empty());
+
+    proxy.with_timeout("upstream".into(), 100, 1.0);
+    assert!(echo(&proxy).is_err());
+    client.reset().unwrap();
+    assert!(echo(&proxy).is_ok());
+
+    proxy.disable().unwrap();
+    assert!(echo(&proxy).is_err());
+    proxy.enable().unwrap();
+    assert!(echo(&proxy).is_ok());
+
+    let response = reqwest::blocking::Client::new()
+        .post(&format!("http://{}/proxies/embedded/toxics", server.addr()))
+        .body(r#"{"type": "slicer", "attributes": {"average_size": 10}}"#)
+        .send()
+        .unwrap();
+    assert_eq!(400, response.status().as_u16());
+    assert!(response.text().unwrap().contains("not supported"));
+
+    let mut stream = TcpStream::connect(server.addr()).unwrap();
+    stream
+        .write_all(b"POST /populate HTTP/1.1\r\nContent-Length: 18446744073709551615\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 413 Payload Too Large"));
+
+    assert_eq!(1, client.all().unwrap().len());
+    proxy.delete().unwrap();
+    assert!(client.all().unwrap().is_empty());
+}
+
+#[cfg(feature = "embedded")]
+#[test]
+fn test_or_embedded() {
+    assert_eq!(
+        TOXIPROXY.address(),
+        TOXIPROXY.or_embedded().unwrap().address()
+    );
+
+    let unavailable = Client::new("127.0.0.1:1");
+    let fallback = unavailable.or_embedded().unwrap();
+    assert_ne!(unavailable.address(), fallback.address());
+    assert_eq!(
+        fallback.address(),
+        unavailable.or_embedded().unwrap().address()
+    );
+
+    let proxy = fallback
+        .create_proxy(ProxyPack::new_ephemeral(
+            "fallback".into(),
+            "127.0.0.1:1".into(),
+        ))
+        .unwrap();
+    proxy.disable().unwrap();
+    assert!(TcpStream::connect(&proxy.proxy_pack.listen).is_err());
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_discover_compose() {
+    let client = Client::discover_compose("127.0.0.1").unwrap();
+    assert_eq!(TOXIPROXY.address(), client.address());
+
+    let err = Client::discover_compose("toxiproxy.invalid").err().unwrap();
+    assert!(
+        err.starts_with("toxiproxy.invalid:8474 (from the service name) cannot be resolved"),
+        "{}",
+        err
+    );
+
+    let err = Client::discover_compose("127.0.0.1:1").err().unwrap();
+    assert!(
+        err.starts_with("127.0.0.1:1 (from the service name) is not reachable"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_discover_k8s() {
+    let err = Client::discover_k8s("chaos", "toxiproxy-missing")
+        .err()
+        .unwrap();
+    assert!(
+        err.starts_with(
+            "toxiproxy-missing.chaos.svc:8474 (from the cluster DNS) cannot be resolved"
+        ),
+        "{}",
+        err
+    );
+
+    let env = |port: &'static str| {
+        move |name: &str| match name {
+            "TOXIPROXY_DISCOVERY_SERVICE_HOST" => Some("127.0.0.1".to_owned()),
+            "TOXIPROXY_DISCOVERY_SERVICE_PORT" => Some(port.to_owned()),
+            _ => None,
+        }
+    };
+    let address = |pod_namespace, port| {
+        discovery::k8s_address("chaos", "toxiproxy-discovery", pod_namespace, env(port))
+    };
+    assert_eq!(
+        Ok((
+            "127.0.0.1:8474".to_owned(),
+            "TOXIPROXY_DISCOVERY_SERVICE_HOST".to_owned()
+        )),
+        address(Some("chaos\n"), "8474")
+    );
+    // Services of other namespaces are reached through the cluster DNS.
+    for pod_namespace in [Some("default"), None] {
+        assert_eq!(
+            Ok((
+                "toxiproxy-discovery.chaos.svc:8474".to_owned(),
+                "cluster DNS".to_owned()
+            )),
+            address(pod_namespace, "8474")
+        );
+    }
+    let err = address(Some("chaos"), "tcp://10.0.0.7:8474").unwrap_err();
+    assert!(
+        err.starts_with("TOXIPROXY_DISCOVERY_SERVICE_PORT is not a port"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_supervisor_restores_proxies() {
+    use std::sync::mpsc;
+    use supervisor::*;
+
+    struct CrashingServer {
+        client: Client,
+        alive: bool,
+    }
+
+    impl Supervised for CrashingServer {
+        fn client(&self) -> &Client {
+            &self.client
+        }
+
+        fn is_alive(&mut self) -> bool {
+            self.alive
+        }
+
+        fn restart(&mut self) -> Result<(), String> {
+            self.alive = true;
+            Ok(())
+        }
+    }
+
+    let client = TOXIPROXY.namespaced(Namespace::unique());
+    let proxy = client
+        .create_proxy(ProxyPack::new_ephemeral(
+            "supervised".into(),
+            "localhost:2000".into(),
+        ))
+        .unwrap();
+    proxy.with_latency("downstream".into(), 100, 0, 1.0);
+
+    let (events, received) = mpsc::channel();
+    let supervisor = Supervisor::builder(CrashingServer {
+        client: client.clone(),
+        alive: true,
+    })
+    .interval(Duration::from_millis(20))
+    .on_event(move |event| events.send(event.clone()).unwrap())
+    .start();
+
+    // The server "crashes", losing its proxies.
+    std::thread::sleep(Duration::from_millis(100));
+    supervisor
+        .with_server(|server| {
+            proxy.delete().unwrap();
+            server.alive = false;
         })
+        .unwrap();
+
+    let event = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(matches!(
+        event,
+        SupervisorEvent::Restarted {
+            restarts: 1,
+            restored: Ok(())
+        }
+    ));
+    assert_eq!(1, supervisor.restarts());
+
+    let restored = supervisor.client().find_proxy("supervised").unwrap();
+    assert_eq!(proxy.proxy_pack.listen, restored.proxy_pack.listen);
+    assert_eq!(1, restored.toxics().unwrap().len());
+
+    supervisor.stop();
+    restored.delete().unwrap();
+}
+
+#[test]
+fn test_populate_listen_conflicts() {
+    let client = TOXIPROXY.namespaced(Namespace::unique());
+
+    let err = client
+        .populate(vec![
+            ProxyPack::new("a".into(), "localhost:2201".into(), "localhost:2000".into()),
+            ProxyPack::new("b".into(), "127.0.0.1:2202".into(), "localhost:2000".into()),
+            ProxyPack::new("c".into(), "0.0.0.0:2201".into(), "localhost:2000".into()),
+        ])
+        .unwrap_err();
+    assert_eq!(
+        "listen address conflict: proxies \"a\" (localhost:2201) and \"c\" (0.0.0.0:2201) cannot listen together",
+        err
+    );
+    assert!(client.all().unwrap().is_empty());
+
+    let existing = client
+        .populate(vec![ProxyPack::new(
+            "a".into(),
+            "localhost:2201".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    let err = client
+        .populate(vec![ProxyPack::new(
+            "b".into(),
+            "127.0.0.1:2201".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap_err();
+    assert!(
+        err.starts_with("listen address conflict: proxy \"b\" (127.0.0.1:2201) cannot listen with the existing proxy"),
+        "{}",
+        err
+    );
+
+    // Populating the same proxy again, or on a port picked by the server, is fine.
+    assert!(client
+        .populate(vec![
+            ProxyPack::new("a".into(), "localhost:2201".into(), "localhost:2000".into()),
+            ProxyPack::new_ephemeral("b".into(), "localhost:2000".into()),
+        ])
+        .is_ok());
+    // So is taking the address of a proxy the batch moves away.
+    assert!(client
+        .populate(vec![
+            ProxyPack::new("a".into(), "localhost:2203".into(), "localhost:2000".into()),
+            ProxyPack::new("d".into(), "127.0.0.1:2201".into(), "localhost:2000".into()),
+        ])
+        .is_ok());
+    client.find_proxy("d").unwrap().delete().unwrap();
+    client
+        .populate(vec![ProxyPack::new(
+            "a".into(),
+            "localhost:2201".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap();
+    // A disabled proxy doesn't hold its port.
+    existing[0].disable().unwrap();
+    assert!(client
+        .populate(vec![ProxyPack::new(
+            "c".into(),
+            "127.0.0.1:2201".into(),
+            "localhost:2000".into(),
+        )])
         .is_ok());
 
-    let result = TOXIPROXY.find_and_reset_proxy("socket");
-    assert!(result.is_ok());
-    assert!(result.as_ref().unwrap().proxy_pack.enabled);
+    for (_, proxy) in client.all().unwrap() {
+        proxy.delete().unwrap();
+    }
+}
+
+#[test]
+fn test_preflight() {
+    let proxies = vec![
+        ProxyPack::new(
+            "db".into(),
+            "127.0.0.1:2001".into(),
+            "localhost:5432".into(),
+        ),
+        ProxyPack::new(
+            "cache".into(),
+            "0.0.0.0:2002".into(),
+            "cache.invalid:6379".into(),
+        ),
+        ProxyPack::new("queue".into(), "0.0.0.0:2003".into(), "queue:0".into()),
+    ];
+
+    let local = Client::new("127.0.0.1:8474").preflight(&proxies);
+    assert_eq!(2, local.errors.len(), "{}", local);
+    assert!(local.errors[0]
+        .starts_with("proxy \"cache\": upstream cache.invalid:6379 cannot be resolved"));
+    assert!(local.errors[1].starts_with("proxy \"queue\": invalid upstream address"));
+    assert!(local.warnings.is_empty());
+
+    let remote = Client::new("192.0.2.1:8474").preflight(&proxies);
+    assert_eq!(1, remote.errors.len(), "{}", remote);
+    assert_eq!(3, remote.warnings.len(), "{}", remote);
+    assert!(remote.warnings[0].starts_with(
+        "proxy \"db\": listens on 127.0.0.1:2001 of the Toxiproxy server at 192.0.2.1:8474"
+    ));
+    assert!(remote.warnings[1].starts_with(
+        "proxy \"db\": upstream localhost:5432 is the loopback of the Toxiproxy server"
+    ));
+    assert!(remote.warnings[2].ends_with("(the Toxiproxy server may resolve it)"));
+
+    let client = TOXIPROXY.namespaced(Namespace::unique());
+    assert!(client.populate_checked(proxies).is_err());
+    assert!(client.all().unwrap().is_empty());
+}
+
+#[test]
+fn test_proxy_point_to_closed_port() {
+    // Closed right away, rather than left hanging until the read times out.
+    let refused = |proxy: &Proxy| {
+        let mut stream = TcpStream::connect(proxy.listen_addr().unwrap()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let _ = stream.write_all(b"hello");
+        let mut buf = [0u8; 8];
+        let started = std::time::Instant::now();
+        let closed = match stream.read(&mut buf) {
+            Ok(0) => true,
+            Err(err) => err.kind() == std::io::ErrorKind::ConnectionReset,
+            Ok(_) => false,
+        };
+        closed && started.elapsed() < Duration::from_millis(500)
+    };
+
+    let client = TOXIPROXY.namespaced(Namespace::unique());
+    let blackhole = client
+        .create_proxy(ProxyPack::to_blackhole(
+            "blackhole".into(),
+            "127.0.0.1:0".into(),
+        ))
+        .unwrap();
+    assert_eq!(BLACKHOLE_UPSTREAM, blackhole.proxy_pack.upstream);
+    assert!(refused(&blackhole));
+
+    let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy = client
+        .create_proxy(ProxyPack::new_ephemeral(
+            "closed".into(),
+            upstream.local_addr().unwrap().to_string(),
+        ))
+        .unwrap();
+    proxy.point_to_closed_port().unwrap();
+    assert_eq!(
+        BLACKHOLE_UPSTREAM,
+        client.find_proxy("closed").unwrap().proxy_pack.upstream
+    );
+    assert!(refused(&proxy));
+
+    proxy.set_upstream(&proxy.proxy_pack.upstream).unwrap();
+    assert_eq!(
+        proxy.proxy_pack.upstream,
+        client.find_proxy("closed").unwrap().proxy_pack.upstream
+    );
+
+    blackhole.delete().unwrap();
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_proxy_point_to_unroutable() {
+    let client = TOXIPROXY.namespaced(Namespace::unique());
+    let proxy = client
+        .create_proxy(ProxyPack::to_unroutable(
+            "unroutable".into(),
+            "127.0.0.1:0".into(),
+        ))
+        .unwrap();
+    assert_eq!(UNROUTABLE_UPSTREAM, proxy.proxy_pack.upstream);
+
+    // Connections hang without a byte, unless this environment refuses the address right away.
+    let hangs = matches!(
+        TcpStream::connect_timeout(
+            &UNROUTABLE_UPSTREAM.parse().unwrap(),
+            Duration::from_millis(300)
+        ),
+        Err(err) if err.kind() == std::io::ErrorKind::TimedOut
+    );
+    let mut stream = TcpStream::connect(proxy.listen_addr().unwrap()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_millis(300)))
+        .unwrap();
+    let mut buf = [0u8; 8];
+    match stream.read(&mut buf) {
+        Err(err) if hangs => assert!(matches!(
+            err.kind(),
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+        )),
+        Ok(0) => assert!(!hangs),
+        Err(err) => assert_eq!(std::io::ErrorKind::ConnectionReset, err.kind()),
+        Ok(read) => panic!("unexpected {} bytes from an unroutable upstream", read),
+    }
+
+    proxy.point_to_closed_port().unwrap();
+    proxy.point_to_unroutable().unwrap();
+    assert_eq!(
+        UNROUTABLE_UPSTREAM,
+        client.find_proxy("unroutable").unwrap().proxy_pack.upstream
+    );
+
+    proxy.delete().unwrap();
+}
+
+#[test]
+fn test_server_version_compat() {
+    use compat::ServerVersion;
+    use toxic::ToxicPack;
+
+    let client = Client::from_env().scoped();
+    assert_eq!(None, client.server_version());
+    let version = client.detect_server_version().unwrap();
+    assert_eq!(Some(version), client.server_version());
+    assert_eq!(version.to_string(), client.version().unwrap());
+
+    client
+        .set_server_version(Some(ServerVersion::new(2, 1, 4)))
+        .unwrap();
+    let proxy = client
+        .create_proxy(ProxyPack::new_ephemeral(
+            "compat".into(),
+            "localhost:2000".into(),
+        ))
+        .unwrap();
+    let reset_peer = ToxicPack::new(
+        "reset_peer".into(),
+        "downstream".into(),
+        1.0,
+        vec![("timeout".to_owned(), 0)].into_iter().collect(),
+    );
+    let err = group::ProxyGroup::new(vec![client.find_proxy("compat").unwrap()])
+        .add_toxic(reset_peer)
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("toxic type \"reset_peer\" requires Toxiproxy 2.2.0 or newer"));
+    assert!(proxy.toxics().unwrap().is_empty());
+    proxy.disable().unwrap();
+    assert!(!proxy.is_enabled().unwrap());
+    proxy.delete().unwrap();
+
+    client.set_address("127.0.0.1:8474").unwrap();
+    assert_eq!(None, client.server_version());
+
+    let noop = Client::noop();
+    noop.set_server_version(Some(ServerVersion::new(2, 9, 0)))
+        .unwrap();
+    let recorder = noop.record();
+    let proxy = recorder.client().find_proxy("compat").unwrap();
+    proxy.disable().unwrap();
+    let journal = recorder.stop();
+    assert_eq!("PATCH", journal.entries[1].method);
+}
+
+#[test]
+fn test_repopulate_on_restart() {
+    let client = Client::from_env().namespaced(Namespace::unique());
+    client.repopulate_on_restart().unwrap();
+    let proxies = client
+        .populate(vec![
+            ProxyPack::new_ephemeral("db".into(), "localhost:2000".into()),
+            ProxyPack::new_ephemeral("cache".into(), "localhost:2000".into()),
+        ])
+        .unwrap();
+    let lose = |proxy: &Proxy| {
+        TOXIPROXY
+            .find_proxy(&proxy.proxy_pack.name)
+            .unwrap()
+            .delete()
+            .unwrap()
+    };
+
+    let audit_path = std::env::temp_dir().join(format!(
+        "toxiproxy_recovery_audit_{}.jsonl",
+        std::process::id()
+    ));
+    client.audit_to(&audit_path).unwrap();
+
+    proxies.iter().for_each(lose);
+    let db = client.find_proxy("db").unwrap();
+    assert_eq!(proxies[0].proxy_pack.listen, db.proxy_pack.listen);
+    assert!(TOXIPROXY.find_proxy(&proxies[1].proxy_pack.name).is_ok());
+    assert_eq!(1, client.repopulations());
+
+    // A single lost proxy is put back too.
+    lose(&proxies[1]);
+    let cache = client.find_proxy("cache").unwrap();
+    assert_eq!(proxies[1].proxy_pack.listen, cache.proxy_pack.listen);
+    assert_eq!(2, client.repopulations());
+
+    // Putting the proxies back is not audited.
+    client.stop_audit();
+    let audited = audit::read(&audit_path).unwrap();
+    let _ = std::fs::remove_file(&audit_path);
+    assert!(audited.is_empty(), "{:?}", audited);
+
+    // Proxies deleted through the client are not put back.
+    db.delete().unwrap();
+    lose(&proxies[1]);
+    assert!(client.find_proxy("cache").is_ok());
+    assert!(client.find_proxy("db").is_err());
+    assert_eq!(3, client.repopulations());
+
+    cache.delete().unwrap();
+}
+
+#[cfg(feature = "teardown")]
+#[test]
+fn test_teardown_on_interrupt() {
+    use std::process::Command;
+
+    // The interrupted process: the test binary running this test only.
+    if let Ok(shared) = std::env::var("TOXIPROXY_TEARDOWN_SHARED") {
+        teardown::on_exit().unwrap();
+        assert!(teardown::on_exit().is_ok());
+
+        let client = TOXIPROXY.scoped();
+        let proxy = client
+            .create_proxy(ProxyPack::new_ephemeral(
+                "teardown".into(),
+                "localhost:2000".into(),
+            ))
+            .unwrap();
+        let scope = TOXIPROXY.find_proxy(&shared).unwrap().toxic_scope();
+        scope.with_latency("downstream".into(), 100, 0, 1.0);
+        println!("created proxy={}", proxy.proxy_pack.name);
+
+        Command::new("kill")
+            .args(["-INT", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+        std::thread::sleep(Duration::from_secs(10));
+        panic!("the process was not interrupted");
+    }
+
+    let shared = ProxyFixture::new(
+        &TOXIPROXY,
+        ProxyPack::new_ephemeral(
+            format!("teardown_shared_{}", std::process::id()),
+            "localhost:2000".into(),
+        ),
+    )
+    .unwrap();
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "test_teardown_on_interrupt", "--nocapture"])
+        .env("TOXIPROXY_TEARDOWN_SHARED", &shared.proxy_pack.name)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let created = stdout
+        .split("created proxy=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or_else(|| panic!("no proxy was created: {}", stdout));
+
+    assert_eq!(Some(130), output.status.code());
+    assert!(TOXIPROXY.find_proxy(created).is_err());
+    assert!(shared.toxics().unwrap().is_empty());
+}
+
+#[cfg(feature = "teardown")]
+#[test]
+fn test_teardown_at_exit() {
+    use std::process::Command;
+
+    // The exiting process: the test binary running this test only.
+    if let Ok(shared) = std::env::var("TOXIPROXY_TEARDOWN_AT_EXIT") {
+        teardown::on_exit().unwrap();
+
+        let client = TOXIPROXY.scoped();
+        let proxy = client
+            .create_proxy(ProxyPack::new_ephemeral(
+                "teardown_at_exit".into(),
+                "localhost:2000".into(),
+            ))
+            .unwrap();
+        let scope = TOXIPROXY.find_proxy(&shared).unwrap().toxic_scope();
+        scope.with_latency("downstream".into(), 100, 0, 1.0);
+        println!("created proxy={}", proxy.proxy_pack.name);
+
+        // Exiting without dropping the client and the scope, which would delete them already.
+        std::process::exit(0);
+    }
+
+    let shared = ProxyFixture::new(
+        &TOXIPROXY,
+        ProxyPack::new_ephemeral(
+            format!("teardown_at_exit_shared_{}", std::process::id()),
+            "localhost:2000".into(),
+        ),
+    )
+    .unwrap();
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "test_teardown_at_exit", "--nocapture"])
+        .env("TOXIPROXY_TEARDOWN_AT_EXIT", &shared.proxy_pack.name)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let created = stdout
+        .split("created proxy=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or_else(|| panic!("no proxy was created: {}", stdout));
+
+    assert_eq!(Some(0), output.status.code());
+    assert!(TOXIPROXY.find_proxy(created).is_err());
+    assert!(shared.toxics().unwrap().is_empty());
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn test_config_from_yaml() {
+    let path = std::env::temp_dir().join(format!("toxiproxy_config_{}.yaml", std::process::id()));
+    std::fs::write(
+        &path,
+        "proxies:\n  - name: db\n    listen: 127.0.0.1:0\n    upstream: db:5432\n    toxics:\n      - type: slicer\n        stream: upstream\n        toxicity: 0.5\n        attributes:\n          average_size: 64\n          size_variation: 32\n          delay: 10\n",
+    )
+    .unwrap();
+
+    let config = config::Config::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let toxic = &config.proxies[0].toxics[0];
+    assert!(config.proxies[0].enabled);
+    assert_eq!("slicer_upstream", toxic.name);
+    assert_eq!(0.5, toxic.toxicity);
+    assert_eq!(32, toxic.attributes["size_variation"]);
+    assert!(config::Config::from_yaml("proxies: [{name: db}]").is_err());
 }
 
 #[test]
@@ -151,7 +4125,7 @@ fn test_proxy_apply_with_latency_as_separate_calls_for_test() {
 
 #[test]
 fn test_proxy_apply_with_latency_with_real_request() {
-    let server_thread = spawn(|| one_take_server());
+    let server_thread = spawn(one_take_server);
     populate_example();
 
     let proxy_result = TOXIPROXY.find_and_reset_proxy("socket");
@@ -162,7 +4136,7 @@ fn test_proxy_apply_with_latency_with_real_request() {
         .unwrap()
         .with_latency("downstream".into(), 2000, 0, 1.0)
         .apply(|| {
-            let client_thread = spawn(|| one_shot_client());
+            let client_thread = spawn(one_shot_client);
 
             server_thread.join().expect("Failed closing server thread");
             let duration = client_thread.join().expect("Failed closing client thread");
@@ -173,7 +4147,301 @@ fn test_proxy_apply_with_latency_with_real_request() {
     assert!(apply_result.is_ok());
 }
 
-/**
+#[test]
+fn test_address_parsing() {
+    assert_eq!(
+        Ok("localhost:2001"),
+        "localhost:2001"
+            .parse::<Listen>()
+            .as_ref()
+            .map(Listen::as_str)
+    );
+    assert_eq!(
+        Ok("[::]:0"),
+        "[::]:0".parse::<Listen>().as_ref().map(Listen::as_str)
+    );
+    assert_eq!(
+        Ok("[::1]:5432"),
+        Upstream::new("::1", 5432).as_ref().map(Upstream::as_str)
+    );
+    assert_eq!(
+        Ok(":0"),
+        ":0".parse::<Listen>().as_ref().map(Listen::as_str)
+    );
+
+    assert!("localhost".parse::<Listen>().is_err());
+    assert!("localhost:port".parse::<Listen>().is_err());
+    assert!("localhost:70000".parse::<Listen>().is_err());
+    assert!("bad host:2001".parse::<Upstream>().is_err());
+    assert!(":5432".parse::<Upstream>().is_err());
+    assert!("db:0".parse::<Upstream>().is_err());
+}
+
+#[test]
+fn test_proxy_pack_from_addrs() {
+    let proxy_pack = ProxyPack::from_addrs(
+        "socket".into(),
+        Listen::new("localhost", 2001).unwrap(),
+        Upstream::new("localhost", 2000).unwrap(),
+    );
+
+    assert_eq!("localhost:2001", proxy_pack.listen);
+    assert_eq!("localhost:2000", proxy_pack.upstream);
+}
+
+#[cfg(feature = "test_support")]
+#[test]
+fn test_echo_server_behind_proxy() {
+    use test_support::*;
+
+    let server = EchoServer::builder()
+        .respond_after(5)
+        .delay(Duration::from_millis(100))
+        .start()
+        .unwrap();
+    let proxies = TOXIPROXY
+        .populate(vec![ProxyPack::new_ephemeral(
+            "echo".into(),
+            server.addr().to_string(),
+        )])
+        .unwrap();
+
+    let mut stream = TcpStream::connect(proxies[0].listen_addr().unwrap()).unwrap();
+    stream.write_all(b"hel").unwrap();
+    stream.write_all(b"lo").unwrap();
+
+    let mut response = [0u8; 5];
+    stream.read_exact(&mut response).unwrap();
+    assert_eq!(b"hello", &response);
+
+    stream.write_all(b"again").unwrap();
+    stream.read_exact(&mut response).unwrap();
+    assert_eq!(b"again", &response);
+
+    assert!(proxies[0].delete().is_ok());
+}
+
+#[cfg(feature = "test_support")]
+#[test]
+fn test_echo_server_close_immediately() {
+    use test_support::*;
+
+    let server = EchoServer::builder()
+        .close_mode(CloseMode::Immediately)
+        .start()
+        .unwrap();
+
+    let mut stream = TcpStream::connect(server.addr()).unwrap();
+    let mut response = vec![];
+    let _ = stream.read_to_end(&mut response);
+    assert!(response.is_empty());
+}
+
+#[cfg(feature = "test_support")]
+#[test]
+fn test_proxy_probe() {
+    use test_support::*;
+
+    let server = EchoServer::builder().start().unwrap();
+    let proxy = TOXIPROXY
+        .proxy_for_upstream(&server.addr().to_string())
+        .unwrap()
+        .proxy;
+
+    assert!(proxy.probe(Duration::from_secs(1)).is_ok());
+
+    proxy
+        .with_down(|| {
+            assert!(proxy.probe(Duration::from_secs(1)).is_err());
+        })
+        .unwrap();
+
+    assert!(proxy.delete().is_ok());
+}
+
+#[cfg(feature = "test_support")]
+#[test]
+fn test_proxy_probe_with_echo_server() {
+    let proxy = TOXIPROXY
+        .populate(vec![ProxyPack::new_ephemeral(
+            "probe".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap()
+        .remove(0);
+
+    assert!(proxy.probe_with_echo_server(Duration::from_secs(1)).is_ok());
+
+    let mut proxy = proxy;
+    assert_eq!("localhost:2000", proxy.refresh().unwrap().upstream);
+    assert!(proxy.delete().is_ok());
+}
+
+#[cfg(feature = "steps")]
+#[test]
+fn test_steps() {
+    use steps::ToxiproxyWorld;
+
+    let proxy = TOXIPROXY
+        .populate(vec![ProxyPack::new_ephemeral(
+            "steps".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap()
+        .remove(0);
+    let mut world = ToxiproxyWorld::default();
+
+    world
+        .run_step("Given proxy \"steps\" has 2000ms latency downstream")
+        .unwrap();
+    world
+        .run_step("And proxy steps has 32KB/s bandwidth upstream")
+        .unwrap();
+    world
+        .run_step("When proxy steps times out after 100ms upstream")
+        .unwrap();
+    world.run_step("proxy steps is down").unwrap();
+
+    world
+        .run_step("Then proxy steps should have toxic bandwidth_upstream")
+        .unwrap();
+    world.run_step("Then proxy steps should be down").unwrap();
+    assert_eq!(3, proxy.toxics().unwrap().len());
+    assert!(world.run_step("Then proxy steps should be up").is_err());
+    assert!(world
+        .run_step("Given proxy steps has 2s latency downstream")
+        .is_err());
+    world
+        .run_step("Then proxy \"steps\" should have toxic \"latency_downstream\"")
+        .unwrap();
+    let err = world
+        .run_step("Then proxy \"missing steps\" should be up")
+        .unwrap_err();
+    assert!(!err.starts_with("unknown step"));
+    assert!(world
+        .run_step("Then proxy \"steps should be up")
+        .unwrap_err()
+        .starts_with("unterminated quote"));
+
+    world.reset().unwrap();
+    assert!(proxy.toxics().unwrap().is_empty());
+    assert!(proxy.is_enabled().unwrap());
+
+    world
+        .run_feature(
+            r#"
+Feature: Degraded upstream
+  Background:
+    Given proxy steps has 100ms latency downstream
+
+  Scenario: Down
+    When proxy steps is down
+    Then proxy steps should be down
+    And proxy steps should have toxic latency_downstream
+
+  Rule: Timeouts
+    Background:
+      Given proxy steps times out after 100ms upstream
+
+    Scenario: Timing out
+      Then proxy steps should have toxic timeout_upstream
+      And proxy steps should be up
+"#,
+        )
+        .unwrap();
+    assert!(proxy.toxics().unwrap().is_empty());
+    assert!(proxy.is_enabled().unwrap());
+
+    let err = world
+        .run_feature(
+            r#"
+Feature: Failing
+  Scenario: Wrong state
+    When proxy steps is down
+    Then proxy steps should be up
+"#,
+        )
+        .expect_err("scenario fails");
+    assert!(err.contains("Wrong state"));
+    assert!(proxy.is_enabled().unwrap());
+    assert!(world.run_feature("Scenario without a feature").is_err());
+
+    proxy.delete().unwrap();
+}
+
+#[cfg(feature = "proptest")]
+proptest::proptest! {
+    #[test]
+    fn test_strategies_generate_valid_configs(proxy_pack in strategies::proxy_pack()) {
+        assert!(proxy_pack.listen.parse::<Listen>().is_ok());
+        assert!(proxy_pack.upstream.parse::<Upstream>().is_ok());
+
+        let mut names = proxy_pack.toxics.iter().map(|toxic| &toxic.name).collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        assert_eq!(proxy_pack.toxics.len(), names.len());
+
+        for toxic in proxy_pack.toxics.iter().filter(|toxic| toxic.r#type == "slicer") {
+            assert!(toxic.attributes["size_variation"] < toxic.attributes["average_size"]);
+        }
+    }
+}
+
+#[cfg(feature = "criterion")]
+#[test]
+fn test_proxy_bench() {
+    use criterion::Criterion;
+    use std::cell::Cell;
+
+    let proxy = TOXIPROXY
+        .populate(vec![ProxyPack::new_ephemeral(
+            "bench".into(),
+            "localhost:2000".into(),
+        )])
+        .unwrap()
+        .remove(0);
+    let samples = Cell::new(0);
+
+    Criterion::default()
+        .sample_size(10)
+        .warm_up_time(Duration::from_millis(10))
+        .measurement_time(Duration::from_millis(100))
+        .without_plots()
+        .bench_function("bench_under_latency", |b| {
+            proxy.bench(
+                b,
+                |proxy| {
+                    samples.set(samples.get() + 1);
+                    proxy.with_latency("downstream".into(), 10, 0, 1.0);
+                },
+                || std::thread::sleep(Duration::from_micros(10)),
+            )
+        });
+
+    assert!(samples.get() >= 10);
+    assert!(proxy.toxics().unwrap().is_empty());
+
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        Criterion::default()
+            .sample_size(10)
+            .without_plots()
+            .bench_function("bench_panicking", |b| {
+                proxy.bench(
+                    b,
+                    |proxy| {
+                        proxy.with_latency("downstream".into(), 10, 0, 1.0);
+                    },
+                    || panic!("routine failed"),
+                )
+            });
+    }));
+    assert!(result.is_err());
+    assert!(proxy.toxics().unwrap().is_empty());
+
+    proxy.delete().unwrap();
+}
+
+/*
  * Support functions.
  */
 
@@ -193,10 +4461,10 @@ fn one_shot_client() -> Duration {
     let mut stream = TcpStream::connect("localhost:2001").expect("Failed to connect to server");
 
     stream
-        .write("hello".as_bytes())
+        .write_all("hello".as_bytes())
         .expect("Client failed sending request");
 
-    stream
+    let _ = stream
         .read(&mut [0u8; 1024])
         .expect("Client failed reading response");
 
@@ -211,12 +4479,12 @@ fn one_take_server() {
         .expect("Failed to listen for incoming")
         .expect("Request failes");
 
-    stream
+    let _ = stream
         .read(&mut [0u8; 1024])
         .expect("Server failed reading request");
 
     stream
-        .write("byebye".as_bytes())
+        .write_all("byebye".as_bytes())
         .expect("Server failed writing response");
 
     stream.flush().expect("Failed flushing connection");